@@ -16,6 +16,8 @@ const config = {
     process.env.CDK_DEFAULT_REGION ||
     '',
   feedUrl: process.env.FEED_URL || '',
+  feedUrls: process.env.FEED_URLS || '',
+  maxConcurrentFeeds: process.env.MAX_CONCURRENT_FEEDS || '',
   maxAgeHours: process.env.MAX_AGE_HOURS || '',
   enableAISummary: process.env.ENABLE_AI_SUMMARY?.toLowerCase() === 'true',
   aiModelId: process.env.AI_MODEL_ID || '',
@@ -23,12 +25,123 @@ const config = {
     process.env.AI_SUMMARY_MAX_GRAPHEMES || '100',
     10
   ),
+  includeKeywords: process.env.INCLUDE_KEYWORDS || '',
+  excludeKeywords: process.env.EXCLUDE_KEYWORDS || '',
+  includeCategories: process.env.INCLUDE_CATEGORIES || '',
+  excludeCategories: process.env.EXCLUDE_CATEGORIES || '',
+  creditAuthor: process.env.CREDIT_AUTHOR || '',
+  maxFeedSizeBytes: process.env.MAX_FEED_SIZE_BYTES || '',
+  fetchMaxRetries: process.env.FETCH_MAX_RETRIES || '',
+  fetchProxyUrl: process.env.FETCH_PROXY_URL || '',
+  fetchConnectTimeoutSecs: process.env.FETCH_CONNECT_TIMEOUT_SECS || '',
+  fetchReadTimeoutSecs: process.env.FETCH_READ_TIMEOUT_SECS || '',
+  maxFeedPages: process.env.MAX_FEED_PAGES || '',
+  itemOrder: process.env.ITEM_ORDER || '',
+  acceptUndatedItems: process.env.ACCEPT_UNDATED_ITEMS || '',
+  enableArticleExtraction: process.env.ENABLE_ARTICLE_EXTRACTION || '',
+  fetchMinHostIntervalMs: process.env.FETCH_MIN_HOST_INTERVAL_MS || '',
+  ignoreRobotsTxt: process.env.IGNORE_ROBOTS_TXT || '',
+  allowedLanguages: process.env.ALLOWED_LANGUAGES || '',
+  prefixSource: process.env.PREFIX_SOURCE || '',
+  minTitleLength: process.env.MIN_TITLE_LENGTH || '',
+  minDescriptionLength: process.env.MIN_DESCRIPTION_LENGTH || '',
+  eventHorizonDays: process.env.EVENT_HORIZON_DAYS || '',
+  releaseNotesLength: process.env.RELEASE_NOTES_LENGTH || '',
+  duplicateWindowHours: process.env.DUPLICATE_WINDOW_HOURS || '',
+  duplicateTitleSimilarity: process.env.DUPLICATE_TITLE_SIMILARITY || '',
+  disableDescriptionEnrichment: process.env.DISABLE_DESCRIPTION_ENRICHMENT || '',
+  windowOverlapMinutes: process.env.WINDOW_OVERLAP_MINUTES || '',
+  disableAiAltText: process.env.DISABLE_AI_ALT_TEXT || '',
+  altTextModelId: process.env.ALT_TEXT_MODEL_ID || '',
+  categoryHashtags: process.env.CATEGORY_HASHTAGS || '',
+  maxHashtags: process.env.MAX_HASHTAGS || '',
+  postHashtags: process.env.POST_HASHTAGS || '',
+  postTemplate: process.env.POST_TEMPLATE || '',
+  threadLongPosts: process.env.THREAD_LONG_POSTS || '',
+  maxThreadPosts: process.env.MAX_THREAD_POSTS || '',
+  postIntervalSeconds: parseInt(process.env.POST_INTERVAL_SECONDS || '0', 10),
+  dryRun: process.env.DRY_RUN || '',
+  updatedItems: process.env.UPDATED_ITEMS || '',
+  quoteRelatedPosts: process.env.QUOTE_RELATED_POSTS || '',
+  relatedTitleSimilarity: process.env.RELATED_TITLE_SIMILARITY || '',
+  authorHandles: process.env.AUTHOR_HANDLES || '',
+  cardDescriptionLength: process.env.CARD_DESCRIPTION_LENGTH || '',
+  mastodonSecretName: process.env.MASTODON_CREDENTIALS_SECRET_NAME || '',
+  mastodonPostTemplate: process.env.MASTODON_POST_TEMPLATE || '',
+  mastodonVisibility: process.env.MASTODON_VISIBILITY || '',
+  mastodonMaxChars: process.env.MASTODON_MAX_CHARS || '',
+  nostrSecretName: process.env.NOSTR_SECRET_NAME || '',
+  nostrRelays: process.env.NOSTR_RELAYS || '',
+  nostrPostTemplate: process.env.NOSTR_POST_TEMPLATE || '',
+  nostrMaxGraphemes: process.env.NOSTR_MAX_GRAPHEMES || '',
+  postingWindow: process.env.POSTING_WINDOW || '',
+  postingTimezone: process.env.POSTING_TIMEZONE || '',
+  maxPostsPerDay: process.env.MAX_POSTS_PER_DAY || '',
+  backdatePosts: process.env.BACKDATE_POSTS || '',
+  maxBackdateHours: process.env.MAX_BACKDATE_HOURS || '',
+  postTags: process.env.POST_TAGS || '',
+  categoryTags: process.env.CATEGORY_TAGS || '',
+  replyControl: process.env.REPLY_CONTROL || '',
+  digestMode: process.env.DIGEST_MODE?.toLowerCase() === 'true',
+  digestTitle: process.env.DIGEST_TITLE || '',
+  digestMaxPosts: process.env.DIGEST_MAX_POSTS || '',
+  digestHour: parseInt(process.env.DIGEST_HOUR || '18', 10),
+  weeklyRoundup: process.env.WEEKLY_ROUNDUP?.toLowerCase() === 'true',
+  roundupTitle: process.env.ROUNDUP_TITLE || '',
+  roundupMaxItems: process.env.ROUNDUP_MAX_ITEMS || '',
+  roundupMaxPosts: process.env.ROUNDUP_MAX_POSTS || '',
+  roundupRankByEngagement: process.env.ROUNDUP_RANK_BY_ENGAGEMENT || '',
+  roundupHour: parseInt(process.env.ROUNDUP_HOUR || '18', 10),
+  postRetentionDays: parseInt(process.env.POST_RETENTION_DAYS || '0', 10),
+  repromotePosts: process.env.REPROMOTE_POSTS || '',
+  repromoteHours: process.env.REPROMOTE_HOURS || '',
+  repromoteCount: process.env.REPROMOTE_COUNT || '',
+  repromoteMinEngagement: process.env.REPROMOTE_MIN_ENGAGEMENT || '',
+  repromoteMinAgeHours: process.env.REPROMOTE_MIN_AGE_HOURS || '',
+  postTemplateVariants: process.env.POST_TEMPLATE_VARIANTS || '',
+  syncProfile: process.env.SYNC_PROFILE?.toLowerCase() === 'true',
+  profileFeedUrl: process.env.PROFILE_FEED_URL || '',
+  profileSyncFields: process.env.PROFILE_SYNC_FIELDS || '',
+  collectEngagement: process.env.COLLECT_ENGAGEMENT?.toLowerCase() === 'true',
+  analyticsIntervalHours: parseInt(
+    process.env.ANALYTICS_INTERVAL_HOURS || '6',
+    10
+  ),
+  analyticsDays: process.env.ANALYTICS_DAYS || '',
+  analyticsRetentionDays: process.env.ANALYTICS_RETENTION_DAYS || '',
+  blobCacheDays: process.env.BLOB_CACHE_DAYS || '',
+  feedGenerator: process.env.FEED_GENERATOR?.toLowerCase() === 'true',
+  feedPublisherDid: process.env.FEED_PUBLISHER_DID || '',
+  feedRecordName: process.env.FEED_RECORD_NAME || '',
+  feedGeneratorHostname: process.env.FEED_GENERATOR_HOSTNAME || '',
+  feedGeneratorDid: process.env.FEED_GENERATOR_DID || '',
+  feedDays: process.env.FEED_DAYS || '',
+  contentWarnings: process.env.CONTENT_WARNINGS || '',
+  summaryProvider: process.env.SUMMARY_PROVIDER || '',
+  openaiBaseUrl: process.env.OPENAI_BASE_URL || '',
+  openaiModel: process.env.OPENAI_MODEL || '',
+  openaiApiKeySecretName: process.env.OPENAI_API_KEY_SECRET_NAME || '',
+  aiFallbackModelIds: process.env.AI_FALLBACK_MODEL_IDS || '',
+  targetLanguage: process.env.TARGET_LANGUAGE || '',
+  summaryCacheDays: process.env.SUMMARY_CACHE_DAYS || '',
+  aiUsageMetrics: process.env.AI_USAGE_METRICS || '',
+  aiInputTokenPrice: process.env.AI_INPUT_TOKEN_PRICE || '',
+  aiOutputTokenPrice: process.env.AI_OUTPUT_TOKEN_PRICE || '',
+  aiMaxTokens: process.env.AI_MAX_TOKENS || '',
+  aiTemperature: process.env.AI_TEMPERATURE || '',
+  aiTopP: process.env.AI_TOP_P || '',
+  disableArticleSummary: process.env.DISABLE_ARTICLE_SUMMARY || '',
+  aiMaxInputGraphemes: process.env.AI_MAX_INPUT_GRAPHEMES || '',
+  aiShortenAttempts: process.env.AI_SHORTEN_ATTEMPTS || '',
+  aiMaxChunks: process.env.AI_MAX_CHUNKS || '',
   logLevel: process.env.RUST_LOG || 'trace',
 };
 
 // Validation and assertions
-if (!config.feedUrl) {
-  throw new Error('FEED_URL must be set in .env.local');
+if (!config.feedUrl && !config.feedUrls) {
+  console.warn(
+    'Neither FEED_URL nor FEED_URLS is set in .env.local. Feeds must be registered in DynamoDB, e.g. with import-opml.'
+  );
 }
 
 if (!config.maxAgeHours) {
@@ -47,7 +160,11 @@ if (!config.region) {
   );
 }
 
-if (config.enableAISummary && !config.aiModelId) {
+if (
+  config.enableAISummary &&
+  config.summaryProvider.toLowerCase() !== 'openai' &&
+  !config.aiModelId
+) {
   throw new Error('AI summarization is enabled but AI_MODEL_ID is not set.');
 }
 
@@ -55,6 +172,49 @@ if (isNaN(config.aiSummaryMaxGraphemes) || config.aiSummaryMaxGraphemes <= 0) {
   throw new Error('AI_SUMMARY_MAX_GRAPHEMES must be a positive integer');
 }
 
+if (isNaN(config.postIntervalSeconds) || config.postIntervalSeconds < 0) {
+  throw new Error('POST_INTERVAL_SECONDS must be a non-negative integer');
+}
+
+if (isNaN(config.digestHour) || config.digestHour < 0 || config.digestHour > 23) {
+  throw new Error('DIGEST_HOUR must be an hour of the day from 0 to 23');
+}
+
+if (
+  isNaN(config.roundupHour) ||
+  config.roundupHour < 0 ||
+  config.roundupHour > 23
+) {
+  throw new Error('ROUNDUP_HOUR must be an hour of the day from 0 to 23');
+}
+
+if (isNaN(config.postRetentionDays) || config.postRetentionDays < 0) {
+  throw new Error('POST_RETENTION_DAYS must be a non-negative integer');
+}
+
+if (
+  config.repromoteHours &&
+  !config.repromoteHours
+    .split(',')
+    .every((hour) => /^\d{1,2}$/.test(hour.trim()) && Number(hour) <= 23)
+) {
+  throw new Error(
+    'REPROMOTE_HOURS must be a comma-separated list of hours of the day from 0 to 23'
+  );
+}
+
+if (
+  isNaN(config.analyticsIntervalHours) ||
+  config.analyticsIntervalHours < 1 ||
+  config.analyticsIntervalHours > 24
+) {
+  throw new Error('ANALYTICS_INTERVAL_HOURS must be from 1 to 24');
+}
+
+if (config.feedGenerator && !config.feedPublisherDid) {
+  throw new Error('FEED_PUBLISHER_DID is required when FEED_GENERATOR is set');
+}
+
 // Final validated config
 export const validatedConfig = {
   ...config,
@@ -2,9 +2,117 @@ import * as cdk from 'aws-cdk-lib';
 
 export interface RssBlueskyBridgeStackProps extends cdk.StackProps {
   feedUrl: string;
+  feedUrls: string;
+  maxConcurrentFeeds: string;
   maxAgeHours: string;
   enableAISummary: boolean;
   aiModelId: string;
   aiSummaryMaxGraphemes: number;
+  includeKeywords: string;
+  excludeKeywords: string;
+  includeCategories: string;
+  excludeCategories: string;
+  creditAuthor: string;
+  maxFeedSizeBytes: string;
+  fetchMaxRetries: string;
+  fetchProxyUrl: string;
+  fetchConnectTimeoutSecs: string;
+  fetchReadTimeoutSecs: string;
+  maxFeedPages: string;
+  itemOrder: string;
+  acceptUndatedItems: string;
+  enableArticleExtraction: string;
+  fetchMinHostIntervalMs: string;
+  ignoreRobotsTxt: string;
+  allowedLanguages: string;
+  prefixSource: string;
+  minTitleLength: string;
+  minDescriptionLength: string;
+  eventHorizonDays: string;
+  releaseNotesLength: string;
+  duplicateWindowHours: string;
+  duplicateTitleSimilarity: string;
+  disableDescriptionEnrichment: string;
+  windowOverlapMinutes: string;
+  disableAiAltText: string;
+  altTextModelId: string;
+  categoryHashtags: string;
+  maxHashtags: string;
+  postHashtags: string;
+  postTemplate: string;
+  threadLongPosts: string;
+  maxThreadPosts: string;
+  postIntervalSeconds: number;
+  dryRun: string;
+  updatedItems: string;
+  quoteRelatedPosts: string;
+  relatedTitleSimilarity: string;
+  authorHandles: string;
+  cardDescriptionLength: string;
+  mastodonSecretName: string;
+  mastodonPostTemplate: string;
+  mastodonVisibility: string;
+  mastodonMaxChars: string;
+  nostrSecretName: string;
+  nostrRelays: string;
+  nostrPostTemplate: string;
+  nostrMaxGraphemes: string;
+  postingWindow: string;
+  postingTimezone: string;
+  maxPostsPerDay: string;
+  backdatePosts: string;
+  maxBackdateHours: string;
+  postTags: string;
+  categoryTags: string;
+  replyControl: string;
+  digestMode: boolean;
+  digestTitle: string;
+  digestMaxPosts: string;
+  digestHour: number;
+  weeklyRoundup: boolean;
+  roundupTitle: string;
+  roundupMaxItems: string;
+  roundupMaxPosts: string;
+  roundupRankByEngagement: string;
+  roundupHour: number;
+  postRetentionDays: number;
+  repromotePosts: string;
+  repromoteHours: string;
+  repromoteCount: string;
+  repromoteMinEngagement: string;
+  repromoteMinAgeHours: string;
+  postTemplateVariants: string;
+  syncProfile: boolean;
+  profileFeedUrl: string;
+  profileSyncFields: string;
+  collectEngagement: boolean;
+  analyticsIntervalHours: number;
+  analyticsDays: string;
+  analyticsRetentionDays: string;
+  blobCacheDays: string;
+  feedGenerator: boolean;
+  feedPublisherDid: string;
+  feedRecordName: string;
+  feedGeneratorHostname: string;
+  feedGeneratorDid: string;
+  feedDays: string;
+  contentWarnings: string;
+  summaryProvider: string;
+  openaiBaseUrl: string;
+  openaiModel: string;
+  openaiApiKeySecretName: string;
+  aiFallbackModelIds: string;
+  targetLanguage: string;
+  summaryCacheDays: string;
+  aiUsageMetrics: string;
+  aiInputTokenPrice: string;
+  aiOutputTokenPrice: string;
+  aiMaxTokens: string;
+  aiTemperature: string;
+  aiTopP: string;
+  disableArticleSummary: string;
+  aiMaxInputGraphemes: string;
+  aiShortenAttempts: string;
+  aiMaxChunks: string;
   logLevel: string;
 }
@@ -9,7 +9,7 @@ import { RustFunction } from 'cargo-lambda-cdk';
 import { Construct } from 'constructs';
 import * as path from 'path';
 import * as iam from 'aws-cdk-lib/aws-iam';
-import { Architecture } from 'aws-cdk-lib/aws-lambda';
+import { Architecture, FunctionUrlAuthType } from 'aws-cdk-lib/aws-lambda';
 import { RssBlueskyBridgeStackProps } from './interfaces';
 
 export class RssBlueskyBridgeStack extends cdk.Stack {
@@ -19,14 +19,43 @@ export class RssBlueskyBridgeStack extends cdk.Stack {
     const blueskySecret = this.createBlueskySecret();
     const table = this.createDynamoDbTable();
 
-    const lambdas = {
+    const lambdas: Record<string, RustFunction> = {
       getRssItems: this.createLambdaFunction(
         'GetRssItemsLambda',
         'get-rss-items',
         {
           FEED_URL: props.feedUrl,
+          FEED_URLS: props.feedUrls,
+          MAX_CONCURRENT_FEEDS: props.maxConcurrentFeeds,
           DYNAMODB_TABLE_NAME: table.tableName,
           MAX_AGE_HOURS: props.maxAgeHours,
+          INCLUDE_KEYWORDS: props.includeKeywords,
+          EXCLUDE_KEYWORDS: props.excludeKeywords,
+          INCLUDE_CATEGORIES: props.includeCategories,
+          EXCLUDE_CATEGORIES: props.excludeCategories,
+          MAX_FEED_SIZE_BYTES: props.maxFeedSizeBytes,
+          FETCH_MAX_RETRIES: props.fetchMaxRetries,
+          FETCH_PROXY_URL: props.fetchProxyUrl,
+          FETCH_CONNECT_TIMEOUT_SECS: props.fetchConnectTimeoutSecs,
+          FETCH_READ_TIMEOUT_SECS: props.fetchReadTimeoutSecs,
+          MAX_FEED_PAGES: props.maxFeedPages,
+          ITEM_ORDER: props.itemOrder,
+          ACCEPT_UNDATED_ITEMS: props.acceptUndatedItems,
+          FETCH_MIN_HOST_INTERVAL_MS: props.fetchMinHostIntervalMs,
+          IGNORE_ROBOTS_TXT: props.ignoreRobotsTxt,
+          ALLOWED_LANGUAGES: props.allowedLanguages,
+          MIN_TITLE_LENGTH: props.minTitleLength,
+          MIN_DESCRIPTION_LENGTH: props.minDescriptionLength,
+          EVENT_HORIZON_DAYS: props.eventHorizonDays,
+          DUPLICATE_WINDOW_HOURS: props.duplicateWindowHours,
+          DUPLICATE_TITLE_SIMILARITY: props.duplicateTitleSimilarity,
+          WINDOW_OVERLAP_MINUTES: props.windowOverlapMinutes,
+          QUOTE_RELATED_POSTS: props.quoteRelatedPosts,
+          RELATED_TITLE_SIMILARITY: props.relatedTitleSimilarity,
+          POSTING_WINDOW: props.postingWindow,
+          POSTING_TIMEZONE: props.postingTimezone,
+          MAX_POSTS_PER_DAY: props.maxPostsPerDay,
+          DIGEST_MODE: String(props.digestMode),
           RUST_LOG: props.logLevel,
         }
       ),
@@ -35,6 +64,20 @@ export class RssBlueskyBridgeStack extends cdk.Stack {
         'check-dynamodb',
         {
           DYNAMODB_TABLE_NAME: table.tableName,
+          UPDATED_ITEMS: props.updatedItems,
+          RUST_LOG: props.logLevel,
+        }
+      ),
+      extractArticle: this.createLambdaFunction(
+        'ExtractArticleLambda',
+        'extract-article',
+        {
+          DYNAMODB_TABLE_NAME: table.tableName,
+          FETCH_PROXY_URL: props.fetchProxyUrl,
+          ENABLE_ARTICLE_EXTRACTION: props.enableArticleExtraction,
+          FETCH_MIN_HOST_INTERVAL_MS: props.fetchMinHostIntervalMs,
+          IGNORE_ROBOTS_TXT: props.ignoreRobotsTxt,
+          DISABLE_DESCRIPTION_ENRICHMENT: props.disableDescriptionEnrichment,
           RUST_LOG: props.logLevel,
         }
       ),
@@ -46,8 +89,27 @@ export class RssBlueskyBridgeStack extends cdk.Stack {
           ENABLE_AI_SUMMARY: String(props.enableAISummary),
           AI_MODEL_ID: props.aiModelId,
           AI_SUMMARY_MAX_GRAPHEMES: String(props.aiSummaryMaxGraphemes),
+          SUMMARY_PROVIDER: props.summaryProvider,
+          OPENAI_BASE_URL: props.openaiBaseUrl,
+          OPENAI_MODEL: props.openaiModel,
+          OPENAI_API_KEY_SECRET_NAME: props.openaiApiKeySecretName,
+          AI_FALLBACK_MODEL_IDS: props.aiFallbackModelIds,
+          TARGET_LANGUAGE: props.targetLanguage,
+          SUMMARY_CACHE_DAYS: props.summaryCacheDays,
+          AI_USAGE_METRICS: props.aiUsageMetrics,
+          AI_INPUT_TOKEN_PRICE: props.aiInputTokenPrice,
+          AI_OUTPUT_TOKEN_PRICE: props.aiOutputTokenPrice,
+          AI_MAX_TOKENS: props.aiMaxTokens,
+          AI_TEMPERATURE: props.aiTemperature,
+          AI_TOP_P: props.aiTopP,
+          DISABLE_ARTICLE_SUMMARY: props.disableArticleSummary,
+          AI_MAX_INPUT_GRAPHEMES: props.aiMaxInputGraphemes,
+          AI_SHORTEN_ATTEMPTS: props.aiShortenAttempts,
+          AI_MAX_CHUNKS: props.aiMaxChunks,
           RUST_LOG: props.logLevel,
-        }
+        },
+        // Leaves room for the retries of throttled requests, down the chain of models
+        { timeout: cdk.Duration.seconds(120) }
       ),
       postBluesky: this.createLambdaFunction(
         'PostBlueskyLambda',
@@ -55,8 +117,39 @@ export class RssBlueskyBridgeStack extends cdk.Stack {
         {
           BLUESKY_CREDENTIALS_SECRET_NAME: blueskySecret.secretName,
           DYNAMODB_TABLE_NAME: table.tableName,
+          CREDIT_AUTHOR: props.creditAuthor,
+          PREFIX_SOURCE: props.prefixSource,
+          RELEASE_NOTES_LENGTH: props.releaseNotesLength,
+          FETCH_PROXY_URL: props.fetchProxyUrl,
+          FETCH_MIN_HOST_INTERVAL_MS: props.fetchMinHostIntervalMs,
+          IGNORE_ROBOTS_TXT: props.ignoreRobotsTxt,
+          DISABLE_AI_ALT_TEXT: props.disableAiAltText,
+          ALT_TEXT_MODEL_ID: props.altTextModelId,
+          AI_MODEL_ID: props.aiModelId,
+          CATEGORY_HASHTAGS: props.categoryHashtags,
+          MAX_HASHTAGS: props.maxHashtags,
+          POST_HASHTAGS: props.postHashtags,
+          POST_TEMPLATE: props.postTemplate,
+          THREAD_LONG_POSTS: props.threadLongPosts,
+          MAX_THREAD_POSTS: props.maxThreadPosts,
+          DRY_RUN: props.dryRun,
+          UPDATED_ITEMS: props.updatedItems,
+          AUTHOR_HANDLES: props.authorHandles,
+          CARD_DESCRIPTION_LENGTH: props.cardDescriptionLength,
+          BACKDATE_POSTS: props.backdatePosts,
+          MAX_BACKDATE_HOURS: props.maxBackdateHours,
+          POST_TAGS: props.postTags,
+          CATEGORY_TAGS: props.categoryTags,
+          REPLY_CONTROL: props.replyControl,
+          POST_TEMPLATE_VARIANTS: props.postTemplateVariants,
+          BLOB_CACHE_DAYS: props.blobCacheDays,
+          CONTENT_WARNINGS: props.contentWarnings,
+          TARGET_LANGUAGE: props.targetLanguage,
           RUST_LOG: props.logLevel,
-        }
+        },
+        // Fetching, decoding and recompressing images, and passing videos of up to 50 MB through, needs more than
+        // the defaults
+        { memorySize: 1024, timeout: cdk.Duration.seconds(120) }
       ),
       updateDynamoDb: this.createLambdaFunction(
         'UpdateDynamoDbLambda',
@@ -67,26 +160,323 @@ export class RssBlueskyBridgeStack extends cdk.Stack {
         }
       ),
       errorCheck: this.createLambdaFunction('ErrorCheckLambda', 'error-check', {
+        DYNAMODB_TABLE_NAME: table.tableName,
+        RUST_LOG: props.logLevel,
+      }),
+      // Not part of the state machine, it's invoked by hand to take posts down
+      deletePost: this.createLambdaFunction('DeletePostLambda', 'delete-post', {
+        DYNAMODB_TABLE_NAME: table.tableName,
+        BLUESKY_CREDENTIALS_SECRET_NAME: blueskySecret.secretName,
         RUST_LOG: props.logLevel,
       }),
     };
 
     // Set up permissions
-    table.grantWriteData(lambdas.getRssItems);
-    table.grantReadData(lambdas.checkDynamoDb);
+    table.grantReadWriteData(lambdas.getRssItems);
+    table.grantReadWriteData(lambdas.checkDynamoDb);
+    table.grantReadWriteData(lambdas.extractArticle);
     table.grantReadWriteData(lambdas.summarizeBedrock);
+    // Write access, as OAuth sessions are stored back after every token refresh
     blueskySecret.grantRead(lambdas.postBluesky);
-    table.grantReadData(lambdas.postBluesky);
-    table.grantWriteData(lambdas.updateDynamoDb);
+    blueskySecret.grantWrite(lambdas.postBluesky);
+    // Write access for the blobs of posted images, cached for the next posts showing them
+    table.grantReadWriteData(lambdas.postBluesky);
+    table.grantReadWriteData(lambdas.updateDynamoDb);
+    table.grantReadWriteData(lambdas.errorCheck);
+    blueskySecret.grantRead(lambdas.deletePost);
+    blueskySecret.grantWrite(lambdas.deletePost);
+    table.grantReadWriteData(lambdas.deletePost);
+
+    // Credentials of authenticated feeds, referenced by the credentials_secret of their FeedConfig
+    lambdas.getRssItems.addToRolePolicy(
+      new iam.PolicyStatement({
+        actions: ['secretsmanager:GetSecretValue'],
+        resources: [
+          `arn:aws:secretsmanager:${this.region}:${this.account}:secret:rss-bluesky-bridge/*`,
+        ],
+      })
+    );
+
+    for (const fn of [lambdas.postBluesky, lambdas.deletePost]) {
+      this.grantAccountSecrets(fn);
+    }
+
+    // Cross-posting to Mastodon is turned on by naming the secret with the account's credentials
+    if (props.mastodonSecretName) {
+      lambdas.postMastodon = this.createLambdaFunction(
+        'PostMastodonLambda',
+        'post-mastodon',
+        {
+          MASTODON_CREDENTIALS_SECRET_NAME: props.mastodonSecretName,
+          DYNAMODB_TABLE_NAME: table.tableName,
+          CREDIT_AUTHOR: props.creditAuthor,
+          PREFIX_SOURCE: props.prefixSource,
+          RELEASE_NOTES_LENGTH: props.releaseNotesLength,
+          CATEGORY_HASHTAGS: props.categoryHashtags,
+          MAX_HASHTAGS: props.maxHashtags,
+          POST_HASHTAGS: props.postHashtags,
+          POST_TEMPLATE: props.postTemplate,
+          MASTODON_POST_TEMPLATE: props.mastodonPostTemplate,
+          MASTODON_VISIBILITY: props.mastodonVisibility,
+          MASTODON_MAX_CHARS: props.mastodonMaxChars,
+          DRY_RUN: props.dryRun,
+          RUST_LOG: props.logLevel,
+        }
+      );
+      table.grantReadData(lambdas.postMastodon);
+      secretsmanager.Secret.fromSecretNameV2(
+        this,
+        'MastodonCredentials',
+        props.mastodonSecretName
+      ).grantRead(lambdas.postMastodon);
+    }
+
+    // Likewise for Nostr, with the secret holding the key notes are signed with
+    if (props.nostrSecretName) {
+      lambdas.postNostr = this.createLambdaFunction(
+        'PostNostrLambda',
+        'post-nostr',
+        {
+          NOSTR_SECRET_NAME: props.nostrSecretName,
+          NOSTR_RELAYS: props.nostrRelays,
+          DYNAMODB_TABLE_NAME: table.tableName,
+          CREDIT_AUTHOR: props.creditAuthor,
+          PREFIX_SOURCE: props.prefixSource,
+          RELEASE_NOTES_LENGTH: props.releaseNotesLength,
+          CATEGORY_HASHTAGS: props.categoryHashtags,
+          MAX_HASHTAGS: props.maxHashtags,
+          POST_HASHTAGS: props.postHashtags,
+          POST_TEMPLATE: props.postTemplate,
+          NOSTR_POST_TEMPLATE: props.nostrPostTemplate,
+          NOSTR_MAX_GRAPHEMES: props.nostrMaxGraphemes,
+          DRY_RUN: props.dryRun,
+          RUST_LOG: props.logLevel,
+        },
+        // Relays that don't answer are given up on after 10 seconds each, in parallel
+        { timeout: cdk.Duration.seconds(30) }
+      );
+      table.grantReadData(lambdas.postNostr);
+      secretsmanager.Secret.fromSecretNameV2(
+        this,
+        'NostrKey',
+        props.nostrSecretName
+      ).grantRead(lambdas.postNostr);
+    }
+
+    // In digest mode the items are held by get-rss-items and posted in a daily digest instead
+    if (props.digestMode) {
+      const postDigest = this.createLambdaFunction(
+        'PostDigestLambda',
+        'post-digest',
+        {
+          DYNAMODB_TABLE_NAME: table.tableName,
+          BLUESKY_CREDENTIALS_SECRET_NAME: blueskySecret.secretName,
+          DIGEST_TITLE: props.digestTitle,
+          DIGEST_MAX_POSTS: props.digestMaxPosts,
+          DRY_RUN: props.dryRun,
+          RUST_LOG: props.logLevel,
+        },
+        { timeout: cdk.Duration.seconds(60) }
+      );
+      blueskySecret.grantRead(postDigest);
+      blueskySecret.grantWrite(postDigest);
+      table.grantReadWriteData(postDigest);
+      this.grantAccountSecrets(postDigest);
+      new events.Rule(this, 'DigestScheduleRule', {
+        schedule: events.Schedule.cron({
+          minute: '0',
+          hour: String(props.digestHour),
+        }),
+        targets: [new targets.LambdaFunction(postDigest)],
+      });
+    }
+
+    // The weekly roundup lists the week's top posts in a thread of its own, on Sundays
+    if (props.weeklyRoundup) {
+      const postRoundup = this.createLambdaFunction(
+        'PostRoundupLambda',
+        'post-roundup',
+        {
+          DYNAMODB_TABLE_NAME: table.tableName,
+          BLUESKY_CREDENTIALS_SECRET_NAME: blueskySecret.secretName,
+          ROUNDUP_TITLE: props.roundupTitle,
+          ROUNDUP_MAX_ITEMS: props.roundupMaxItems,
+          ROUNDUP_MAX_POSTS: props.roundupMaxPosts,
+          ROUNDUP_RANK_BY_ENGAGEMENT: props.roundupRankByEngagement,
+          DRY_RUN: props.dryRun,
+          RUST_LOG: props.logLevel,
+        },
+        { timeout: cdk.Duration.seconds(60) }
+      );
+      blueskySecret.grantRead(postRoundup);
+      blueskySecret.grantWrite(postRoundup);
+      table.grantReadData(postRoundup);
+      this.grantAccountSecrets(postRoundup);
+      new events.Rule(this, 'RoundupScheduleRule', {
+        schedule: events.Schedule.cron({
+          minute: '0',
+          hour: String(props.roundupHour),
+          weekDay: 'SUN',
+        }),
+        targets: [new targets.LambdaFunction(postRoundup)],
+      });
+    }
+
+    // Re-promotion reposts the best-performing recent posts at the given hours
+    if (props.repromoteHours) {
+      const repostTop = this.createLambdaFunction(
+        'RepostTopLambda',
+        'repost-top',
+        {
+          DYNAMODB_TABLE_NAME: table.tableName,
+          BLUESKY_CREDENTIALS_SECRET_NAME: blueskySecret.secretName,
+          REPROMOTE_POSTS: props.repromotePosts,
+          REPROMOTE_COUNT: props.repromoteCount,
+          REPROMOTE_MIN_ENGAGEMENT: props.repromoteMinEngagement,
+          REPROMOTE_MIN_AGE_HOURS: props.repromoteMinAgeHours,
+          DRY_RUN: props.dryRun,
+          RUST_LOG: props.logLevel,
+        },
+        { timeout: cdk.Duration.seconds(60) }
+      );
+      blueskySecret.grantRead(repostTop);
+      blueskySecret.grantWrite(repostTop);
+      table.grantReadData(repostTop);
+      this.grantAccountSecrets(repostTop);
+      new events.Rule(this, 'RepromoteScheduleRule', {
+        schedule: events.Schedule.cron({
+          minute: '0',
+          hour: props.repromoteHours.replace(/\s/g, ''),
+        }),
+        targets: [new targets.LambdaFunction(repostTop)],
+      });
+    }
 
+    // Profile sync keeps the accounts' avatars, descriptions and pinned posts in step with their feeds
+    if (props.syncProfile) {
+      const syncProfile = this.createLambdaFunction(
+        'SyncProfileLambda',
+        'sync-profile',
+        {
+          DYNAMODB_TABLE_NAME: table.tableName,
+          BLUESKY_CREDENTIALS_SECRET_NAME: blueskySecret.secretName,
+          FEED_URL: props.feedUrl,
+          PROFILE_FEED_URL: props.profileFeedUrl,
+          PROFILE_SYNC_FIELDS: props.profileSyncFields,
+          DRY_RUN: props.dryRun,
+          RUST_LOG: props.logLevel,
+        },
+        { timeout: cdk.Duration.seconds(60) }
+      );
+      blueskySecret.grantRead(syncProfile);
+      blueskySecret.grantWrite(syncProfile);
+      table.grantReadData(syncProfile);
+      this.grantAccountSecrets(syncProfile);
+      new events.Rule(this, 'ProfileSyncScheduleRule', {
+        schedule: events.Schedule.rate(cdk.Duration.hours(1)),
+        targets: [new targets.LambdaFunction(syncProfile)],
+      });
+    }
+
+    // Engagement analytics sample the likes, reposts, quotes and replies of the recent posts
+    if (props.collectEngagement) {
+      const collectEngagement = this.createLambdaFunction(
+        'CollectEngagementLambda',
+        'collect-engagement',
+        {
+          DYNAMODB_TABLE_NAME: table.tableName,
+          BLUESKY_CREDENTIALS_SECRET_NAME: blueskySecret.secretName,
+          ANALYTICS_DAYS: props.analyticsDays,
+          ANALYTICS_RETENTION_DAYS: props.analyticsRetentionDays,
+          DRY_RUN: props.dryRun,
+          RUST_LOG: props.logLevel,
+        },
+        { timeout: cdk.Duration.seconds(120) }
+      );
+      blueskySecret.grantRead(collectEngagement);
+      blueskySecret.grantWrite(collectEngagement);
+      table.grantReadWriteData(collectEngagement);
+      new events.Rule(this, 'EngagementScheduleRule', {
+        schedule: events.Schedule.rate(
+          cdk.Duration.hours(props.analyticsIntervalHours)
+        ),
+        targets: [new targets.LambdaFunction(collectEngagement)],
+      });
+    }
+
+    // The feed generator serves a custom feed of the bridge's posts to the Bluesky app, which calls it anonymously
+    if (props.feedGenerator) {
+      const feedGenerator = this.createLambdaFunction(
+        'FeedGeneratorLambda',
+        'feed-generator',
+        {
+          DYNAMODB_TABLE_NAME: table.tableName,
+          FEED_PUBLISHER_DID: props.feedPublisherDid,
+          FEED_RECORD_NAME: props.feedRecordName,
+          FEED_GENERATOR_HOSTNAME: props.feedGeneratorHostname,
+          FEED_GENERATOR_DID: props.feedGeneratorDid,
+          FEED_DAYS: props.feedDays,
+          RUST_LOG: props.logLevel,
+        },
+        { timeout: cdk.Duration.seconds(10) }
+      );
+      table.grantReadData(feedGenerator);
+      const feedGeneratorUrl = feedGenerator.addFunctionUrl({
+        authType: FunctionUrlAuthType.NONE,
+      });
+      new cdk.CfnOutput(this, 'FeedGeneratorUrl', {
+        value: feedGeneratorUrl.url,
+      });
+    }
+
+    // With a retention policy, delete-post takes down the posts older than it every day
+    if (props.postRetentionDays > 0) {
+      new events.Rule(this, 'RetentionScheduleRule', {
+        schedule: events.Schedule.cron({ minute: '30', hour: '3' }),
+        targets: [
+          new targets.LambdaFunction(lambdas.deletePost, {
+            event: events.RuleTargetInput.fromObject({
+              older_than_days: props.postRetentionDays,
+            }),
+          }),
+        ],
+      });
+    }
+
+    // Cross-region inference profiles route a request to a foundation model in any region of their geography
     lambdas.summarizeBedrock.addToRolePolicy(
+      new iam.PolicyStatement({
+        actions: ['bedrock:InvokeModel'],
+        resources: [
+          'arn:aws:bedrock:*::foundation-model/*',
+          `arn:aws:bedrock:${this.region}:${this.account}:inference-profile/*`,
+        ],
+      })
+    );
+
+    // The API key of an OpenAI-compatible summarizer, named like the other secrets of the bridge
+    if (props.openaiApiKeySecretName) {
+      lambdas.summarizeBedrock.addToRolePolicy(
+        new iam.PolicyStatement({
+          actions: ['secretsmanager:GetSecretValue'],
+          resources: [
+            `arn:aws:secretsmanager:${this.region}:${this.account}:secret:rss-bluesky-bridge/*`,
+          ],
+        })
+      );
+    }
+
+    lambdas.postBluesky.addToRolePolicy(
       new iam.PolicyStatement({
         actions: ['bedrock:InvokeModel'],
         resources: [`arn:aws:bedrock:${this.region}::foundation-model/*`],
       })
     );
 
-    const stateMachine = this.createStateMachine(lambdas);
+    const stateMachine = this.createStateMachine(
+      lambdas,
+      props.postIntervalSeconds
+    );
     this.createScheduleRule(stateMachine);
   }
 
@@ -116,18 +506,38 @@ export class RssBlueskyBridgeStack extends cdk.Stack {
   private createLambdaFunction(
     id: string,
     binaryName: string,
-    environment: Record<string, string>
+    environment: Record<string, string>,
+    sizing: { memorySize?: number; timeout?: cdk.Duration } = {}
   ): RustFunction {
     return new RustFunction(this, id, {
       manifestPath: path.join(__dirname, '../../lambda'),
       binaryName,
       environment,
       architecture: Architecture.ARM_64,
+      ...sizing,
     });
   }
 
+  // Bluesky accounts of feeds that post to their own, referenced by the bluesky_secret of their FeedConfig, which
+  // the lambda logs in to and stores refreshed sessions in, claiming their refresh by moving the AWSCURRENT label
+  private grantAccountSecrets(fn: RustFunction): void {
+    fn.addToRolePolicy(
+      new iam.PolicyStatement({
+        actions: [
+          'secretsmanager:GetSecretValue',
+          'secretsmanager:PutSecretValue',
+          'secretsmanager:UpdateSecretVersionStage',
+        ],
+        resources: [
+          `arn:aws:secretsmanager:${this.region}:${this.account}:secret:rss-bluesky-bridge/*`,
+        ],
+      })
+    );
+  }
+
   private createStateMachine(
-    lambdas: Record<string, RustFunction>
+    lambdas: Record<string, RustFunction>,
+    postIntervalSeconds: number
   ): sfn.StateMachine {
     const getRssItems = new tasks.LambdaInvoke(this, 'GetRSSFeedItems', {
       lambdaFunction: lambdas.getRssItems,
@@ -139,10 +549,23 @@ export class RssBlueskyBridgeStack extends cdk.Stack {
       payloadResponseOnly: true,
     });
 
+    const extractArticle = new tasks.LambdaInvoke(this, 'ExtractArticle', {
+      lambdaFunction: lambdas.extractArticle,
+      payloadResponseOnly: true,
+    });
+
     const summarizeBedrock = new tasks.LambdaInvoke(this, 'SummarizeBedrock', {
       lambdaFunction: lambdas.summarizeBedrock,
       payloadResponseOnly: true,
     });
+    // The lambda retries throttled requests itself, and fails with BedrockThrottled when Bedrock is still busy
+    summarizeBedrock.addRetry({
+      errors: ['BedrockThrottled'],
+      interval: cdk.Duration.seconds(30),
+      maxAttempts: 3,
+      backoffRate: 2,
+      jitterStrategy: sfn.JitterType.FULL,
+    });
 
     const postToBluesky = new tasks.LambdaInvoke(this, 'PostToBluesky', {
       lambdaFunction: lambdas.postBluesky,
@@ -154,11 +577,41 @@ export class RssBlueskyBridgeStack extends cdk.Stack {
       payloadResponseOnly: true,
     });
 
+    // Items are processed one at a time, so waiting after each post keeps a busy run from posting them all at
+    // once. Skipped items don't wait
+    let post = extractArticle
+      .next(summarizeBedrock)
+      .next(postToBluesky)
+      .next(updateDynamoDB);
+    // Cross-posted once the Bluesky post is recorded, so items are deduplicated for every network together
+    if (lambdas.postMastodon) {
+      post = post.next(
+        new tasks.LambdaInvoke(this, 'PostToMastodon', {
+          lambdaFunction: lambdas.postMastodon,
+          payloadResponseOnly: true,
+        })
+      );
+    }
+    if (lambdas.postNostr) {
+      post = post.next(
+        new tasks.LambdaInvoke(this, 'PostToNostr', {
+          lambdaFunction: lambdas.postNostr,
+          payloadResponseOnly: true,
+        })
+      );
+    }
+    if (postIntervalSeconds > 0) {
+      post.next(
+        new sfn.Wait(this, 'PacePosts', {
+          time: sfn.WaitTime.duration(
+            cdk.Duration.seconds(postIntervalSeconds)
+          ),
+        })
+      );
+    }
+
     const shouldProcess = new sfn.Choice(this, 'ShouldProcess')
-      .when(
-        sfn.Condition.booleanEquals('$.should_process', true),
-        summarizeBedrock.next(postToBluesky).next(updateDynamoDB)
-      )
+      .when(sfn.Condition.booleanEquals('$.should_process', true), post)
       .otherwise(new sfn.Pass(this, 'SkipProcessing'));
 
     const processItem = checkDynamoDB.next(shouldProcess);
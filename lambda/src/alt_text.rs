@@ -0,0 +1,118 @@
+use crate::text_utils::truncate_to_word;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use image::ImageFormat;
+use serde_json::Value;
+
+/// Bedrock model used for alt text when neither ALT_TEXT_MODEL_ID nor AI_MODEL_ID is set. It has to accept images.
+pub const DEFAULT_ALT_TEXT_MODEL_ID: &str = "anthropic.claude-3-haiku-20240307-v1:0";
+/// Generated alt text is cut to this length. Screen reader users shouldn't have to sit through an essay.
+const MAX_ALT_TEXT_GRAPHEMES: usize = 500;
+
+/// Returns the MIME type of an encoded image, if it's one of the formats multimodal models accept.
+pub fn image_media_type(image: &[u8]) -> Option<&'static str> {
+    match image::guess_format(image).ok()? {
+        ImageFormat::Jpeg => Some("image/jpeg"),
+        ImageFormat::Png => Some("image/png"),
+        ImageFormat::Gif => Some("image/gif"),
+        ImageFormat::WebP => Some("image/webp"),
+        _ => None,
+    }
+}
+
+/// Builds the body of a Bedrock request (Anthropic messages format) asking for the alt text of an image.
+///
+/// # Arguments
+///
+/// * `image` - The encoded image.
+/// * `media_type` - The MIME type of the image, see [`image_media_type`].
+/// * `title` - The title of the item the image belongs to, so the description can focus on what matters.
+///
+/// # Returns
+///
+/// The request body, to be serialized as JSON.
+pub fn alt_text_request(image: &[u8], media_type: &str, title: &str) -> Value {
+    let prompt = format!(
+        "This image accompanies a post about \"{}\". Write alt text for it: describe what the image shows in one \
+         or two plain sentences, so someone who can't see it knows what's there. Transcribe any important text in \
+         it. Don't start with \"Image of\" or similar. Reply with the alt text only.",
+        title
+    );
+
+    serde_json::json!({
+        "anthropic_version": "bedrock-2023-05-31",
+        "max_tokens": 300,
+        "messages": [
+            {
+                "role": "user",
+                "content": [
+                    {
+                        "type": "image",
+                        "source": {
+                            "type": "base64",
+                            "media_type": media_type,
+                            "data": STANDARD.encode(image)
+                        }
+                    },
+                    {
+                        "type": "text",
+                        "text": prompt
+                    }
+                ]
+            }
+        ],
+        "temperature": 0.0,
+    })
+}
+
+/// Reads the alt text from a Bedrock response to [`alt_text_request`].
+///
+/// # Returns
+///
+/// The alt text, without surrounding quotes and cut to a reasonable length, or None if the response has no text.
+pub fn parse_alt_text(response: &Value) -> Option<String> {
+    let text = response["content"][0]["text"].as_str()?.trim();
+    let text = text
+        .strip_prefix('"')
+        .and_then(|t| t.strip_suffix('"'))
+        .unwrap_or(text)
+        .trim();
+    (!text.is_empty()).then(|| truncate_to_word(text, MAX_ALT_TEXT_GRAPHEMES))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_alt_text_request() {
+        let png = b"\x89PNG\r\n\x1a\n\0\0\0\0";
+        assert_eq!(image_media_type(png), Some("image/png"));
+        assert_eq!(image_media_type(b"<html>"), None);
+
+        let request = alt_text_request(png, "image/png", "Launch day");
+        let content = &request["messages"][0]["content"];
+        assert_eq!(content[0]["source"]["media_type"], "image/png");
+        assert_eq!(content[0]["source"]["data"], STANDARD.encode(png));
+        assert!(content[1]["text"]
+            .as_str()
+            .unwrap()
+            .contains("\"Launch day\""));
+    }
+
+    #[test]
+    fn test_parse_alt_text() {
+        let response = serde_json::json!({
+            "content": [{"type": "text", "text": " \"A rocket lifting off at dawn.\" "}]
+        });
+        assert_eq!(
+            parse_alt_text(&response).as_deref(),
+            Some("A rocket lifting off at dawn.")
+        );
+        assert_eq!(
+            parse_alt_text(&serde_json::json!({"content": [{"type": "text", "text": "  "}]})),
+            None
+        );
+        assert_eq!(parse_alt_text(&serde_json::json!({})), None);
+    }
+}
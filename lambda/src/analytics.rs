@@ -0,0 +1,135 @@
+use crate::models::EngagementSample;
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// How the posts of a feed, or of a template variant, are doing.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct GroupEngagement {
+    /// The URL of the feed or the name of the variant. None for the posts of digests, which have no feed.
+    pub name: Option<String>,
+    pub posts: usize,
+    pub likes: i64,
+    pub reposts: i64,
+    pub quotes: i64,
+    pub replies: i64,
+    /// The likes, reposts, quotes and replies of a post together, on average.
+    pub average_engagement: f64,
+}
+
+/// How the posts of the last days are doing, by feed and by template variant, the best performing first.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EngagementReport {
+    pub by_feed: Vec<GroupEngagement>,
+    /// Only posts made during a template experiment count here.
+    pub by_variant: Vec<GroupEngagement>,
+}
+
+/// Sums up the engagement of posts.
+///
+/// # Arguments
+///
+/// * `samples` - The samples of the posts. Each post counts with its latest sample, as engagement only grows.
+///
+/// # Returns
+///
+/// The engagement of the posts by feed and by template variant, each sorted by the average engagement of their
+/// posts, highest first.
+pub fn engagement_report(samples: &[EngagementSample]) -> EngagementReport {
+    let mut latest: HashMap<&str, &EngagementSample> = HashMap::new();
+    for sample in samples {
+        let entry = latest.entry(&sample.guid).or_insert(sample);
+        if sample.sampled_at > entry.sampled_at {
+            *entry = sample;
+        }
+    }
+
+    let group = |key: fn(&EngagementSample) -> Option<&String>, skip_none: bool| {
+        let mut groups: HashMap<Option<&String>, Vec<&EngagementSample>> = HashMap::new();
+        for sample in latest.values() {
+            let name = key(sample);
+            if name.is_some() || !skip_none {
+                groups.entry(name).or_default().push(sample);
+            }
+        }
+        let mut groups: Vec<GroupEngagement> = groups
+            .into_iter()
+            .map(|(name, posts)| {
+                let sum =
+                    |count: fn(&EngagementSample) -> i64| posts.iter().map(|p| count(p)).sum();
+                let engagement: i64 = sum(EngagementSample::engagement);
+                GroupEngagement {
+                    name: name.cloned(),
+                    posts: posts.len(),
+                    likes: sum(|p| p.likes),
+                    reposts: sum(|p| p.reposts),
+                    quotes: sum(|p| p.quotes),
+                    replies: sum(|p| p.replies),
+                    average_engagement: engagement as f64 / posts.len() as f64,
+                }
+            })
+            .collect();
+        groups.sort_by(|a, b| {
+            b.average_engagement
+                .total_cmp(&a.average_engagement)
+                .then_with(|| a.name.cmp(&b.name))
+        });
+        groups
+    };
+
+    EngagementReport {
+        by_feed: group(|sample| sample.feed_url.as_ref(), false),
+        by_variant: group(|sample| sample.template_variant.as_ref(), true),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(
+        guid: &str,
+        feed_url: &str,
+        variant: Option<&str>,
+        sampled_at: i64,
+        likes: i64,
+    ) -> EngagementSample {
+        EngagementSample {
+            sampled_at,
+            guid: guid.to_string(),
+            uri: format!("at://did:plc:abc/app.bsky.feed.post/{}", guid),
+            feed_url: Some(feed_url.to_string()),
+            template_variant: variant.map(String::from),
+            likes,
+            replies: 1,
+            ..EngagementSample::default()
+        }
+    }
+
+    #[test]
+    fn test_engagement_report() {
+        let samples = vec![
+            sample("a", "https://one.example/feed", Some("short"), 1, 2),
+            // Only the latest sample of a post counts
+            sample("a", "https://one.example/feed", Some("short"), 2, 9),
+            sample("b", "https://one.example/feed", Some("long"), 2, 3),
+            sample("c", "https://two.example/feed", None, 2, 20),
+        ];
+        let report = engagement_report(&samples);
+
+        assert_eq!(report.by_feed.len(), 2);
+        assert_eq!(
+            report.by_feed[0].name.as_deref(),
+            Some("https://two.example/feed")
+        );
+        let one = &report.by_feed[1];
+        assert_eq!((one.posts, one.likes, one.replies), (2, 12, 2));
+        assert_eq!(one.average_engagement, 7.0);
+
+        let variants: Vec<_> = report
+            .by_variant
+            .iter()
+            .map(|group| (group.name.as_deref().unwrap(), group.average_engagement))
+            .collect();
+        assert_eq!(variants, vec![("short", 10.0), ("long", 4.0)]);
+    }
+}
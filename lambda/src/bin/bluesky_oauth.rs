@@ -0,0 +1,215 @@
+use anyhow::{anyhow, Context, Result};
+use rss_bluesky_bridge::bluesky::DEFAULT_SERVICE;
+use rss_bluesky_bridge::oauth::{
+    oauth_client, pkce_challenge, post_form, random_token, token_request, DpopKey, OAuthSession,
+    SCOPE,
+};
+use serde_json::{json, Value};
+use tracing_subscriber::EnvFilter;
+
+/// Authorizes the bridge to post to a Bluesky account with OAuth, and prints the secret to store in place of the
+/// app password.
+///
+/// This is a command line tool rather than a lambda, as the account's owner has to approve the request in their
+/// browser:
+///
+/// ```bash
+/// cargo run --bin bluesky-oauth -- example.bsky.social https://example.com/client-metadata.json https://example.com/callback
+/// ```
+///
+/// The client ID is the URL of the bridge's client metadata, which has to be hosted somewhere and list the redirect
+/// URI. Once approved, the browser is sent to the redirect URI; paste the full URL it ended up at. The secret JSON
+/// is printed to stdout, ready for `aws secretsmanager put-secret-value`.
+#[tokio::main]
+async fn main() -> Result<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::from_default_env())
+        .init();
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let [handle, client_id, redirect_uri] = args.as_slice() else {
+        return Err(anyhow!(
+            "Usage: bluesky-oauth <handle> <client-id> <redirect-uri>"
+        ));
+    };
+
+    let client = oauth_client()?;
+    let did = get_json(
+        &client,
+        &format!(
+            "{}/xrpc/com.atproto.identity.resolveHandle?handle={}",
+            DEFAULT_SERVICE, handle
+        ),
+    )
+    .await?["did"]
+        .as_str()
+        .map(String::from)
+        .with_context(|| format!("Failed to resolve handle {}", handle))?;
+    let pds_url = pds_url(&client, &did).await?;
+
+    let resource = get_json(
+        &client,
+        &format!("{}/.well-known/oauth-protected-resource", pds_url),
+    )
+    .await?;
+    let authorization_server = resource["authorization_servers"][0]
+        .as_str()
+        .context("The PDS doesn't name its authorization server")?;
+    let metadata = get_json(
+        &client,
+        &format!(
+            "{}/.well-known/oauth-authorization-server",
+            authorization_server.trim_end_matches('/')
+        ),
+    )
+    .await?;
+    let endpoint = |name: &str| {
+        metadata[name]
+            .as_str()
+            .map(String::from)
+            .with_context(|| format!("The authorization server has no {}", name))
+    };
+    let issuer = endpoint("issuer")?;
+    let par_endpoint = endpoint("pushed_authorization_request_endpoint")?;
+    let authorization_endpoint = endpoint("authorization_endpoint")?;
+    let token_endpoint = endpoint("token_endpoint")?;
+
+    let key = DpopKey::generate()?;
+    let verifier = random_token();
+    let state = random_token();
+    let par = post_form(
+        &client,
+        &par_endpoint,
+        &[
+            ("response_type", "code"),
+            ("client_id", client_id),
+            ("redirect_uri", redirect_uri),
+            ("scope", SCOPE),
+            ("state", &state),
+            ("code_challenge", &pkce_challenge(&verifier)),
+            ("code_challenge_method", "S256"),
+            ("login_hint", handle),
+        ],
+        &key,
+    )
+    .await
+    .context("The authorization request was refused")?;
+    let request_uri = par["request_uri"]
+        .as_str()
+        .context("The authorization server returned no request URI")?;
+
+    let mut authorize_url = url::Url::parse(&authorization_endpoint)?;
+    authorize_url
+        .query_pairs_mut()
+        .append_pair("client_id", client_id)
+        .append_pair("request_uri", request_uri);
+    eprintln!("Open this URL in a browser and approve the request:\n\n{authorize_url}\n");
+    eprintln!("Then paste the URL the browser was redirected to:");
+    let mut redirect = String::new();
+    std::io::stdin().read_line(&mut redirect)?;
+    let code = authorization_code(redirect.trim(), &state, &issuer)?;
+
+    let tokens = token_request(
+        &client,
+        &token_endpoint,
+        &[
+            ("grant_type", "authorization_code"),
+            ("code", &code),
+            ("redirect_uri", redirect_uri),
+            ("code_verifier", &verifier),
+            ("client_id", client_id),
+        ],
+        &key,
+    )
+    .await?;
+    if tokens.sub != did {
+        return Err(anyhow!(
+            "The tokens are for {} rather than {}",
+            tokens.sub,
+            did
+        ));
+    }
+    let refresh_token = tokens
+        .refresh_token
+        .context("The authorization server returned no refresh token")?;
+
+    let session = OAuthSession {
+        client_id: client_id.clone(),
+        token_endpoint,
+        pds_url,
+        did,
+        refresh_token,
+        access_token: Some(tokens.access_token),
+        expires_at: tokens
+            .expires_in
+            .map(|expires_in| chrono::Utc::now().timestamp() + expires_in),
+        dpop_key: key.to_pem()?,
+    };
+    println!(
+        "{}",
+        serde_json::to_string_pretty(&json!({"oauth": session}))?
+    );
+    Ok(())
+}
+
+async fn get_json(client: &reqwest::Client, url: &str) -> Result<Value> {
+    client
+        .get(url)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .with_context(|| format!("Failed to fetch {}", url))?
+        .json()
+        .await
+        .with_context(|| format!("Invalid JSON from {}", url))
+}
+
+/// Looks up the PDS of an account in its DID document.
+async fn pds_url(client: &reqwest::Client, did: &str) -> Result<String> {
+    let document_url = if let Some(host) = did.strip_prefix("did:web:") {
+        format!("https://{}/.well-known/did.json", host)
+    } else if did.starts_with("did:plc:") {
+        format!("https://plc.directory/{}", did)
+    } else {
+        return Err(anyhow!("Unsupported DID {}", did));
+    };
+    let document = get_json(client, &document_url).await?;
+    document["service"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .find(|service| {
+            service["id"]
+                .as_str()
+                .is_some_and(|id| id.ends_with("#atproto_pds"))
+        })
+        .and_then(|service| service["serviceEndpoint"].as_str())
+        .map(|endpoint| endpoint.trim_end_matches('/').to_string())
+        .with_context(|| format!("No PDS in the DID document of {}", did))
+}
+
+/// Reads the authorization code from the URL the browser was redirected to, after checking it's the answer to
+/// this request.
+fn authorization_code(redirect: &str, state: &str, issuer: &str) -> Result<String> {
+    let redirect = url::Url::parse(redirect).context("Invalid redirect URL")?;
+    let param = |name: &str| {
+        redirect
+            .query_pairs()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.into_owned())
+    };
+    if let Some(error) = param("error") {
+        return Err(anyhow!(
+            "The request was not approved: {} {}",
+            error,
+            param("error_description").unwrap_or_default()
+        ));
+    }
+    if param("state").as_deref() != Some(state) {
+        return Err(anyhow!("The redirect is for another authorization request"));
+    }
+    if param("iss").is_some_and(|iss| iss != issuer) {
+        return Err(anyhow!("The redirect is from another authorization server"));
+    }
+    param("code").context("No code in the redirect URL")
+}
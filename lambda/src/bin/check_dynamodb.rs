@@ -1,8 +1,13 @@
 use anyhow::Context;
 use aws_config::BehaviorVersion;
 use aws_sdk_dynamodb::Client;
+use chrono::Utc;
 use lambda_runtime::{run, service_fn, Error, LambdaEvent};
-use rss_bluesky_bridge::{models::ItemIdentifier, repository::DynamoRepository};
+use rss_bluesky_bridge::{
+    env,
+    models::{ItemIdentifier, UpdateMode},
+    repository::DynamoRepository,
+};
 use serde::{Deserialize, Serialize};
 use tracing::instrument;
 use tracing_subscriber::EnvFilter;
@@ -22,6 +27,7 @@ struct Output {
 
 struct Config {
     dynamodb_table_name: String,
+    updated_items: UpdateMode,
 }
 
 impl Config {
@@ -33,16 +39,24 @@ impl Config {
             return Err(Error::from("DYNAMODB_TABLE_NAME cannot be empty"));
         }
 
+        // Items are posted once by default, however often the feed changes them afterwards
+        let updated_items = env::optional("UPDATED_ITEMS")
+            .map(|mode| UpdateMode::parse(&mode))
+            .transpose()?
+            .unwrap_or_default();
+
         Ok(Self {
             dynamodb_table_name,
+            updated_items,
         })
     }
 }
 
-#[instrument(skip(event, repo))]
+#[instrument(skip(event, repo, config))]
 async fn check_dynamodb(
     event: LambdaEvent<Input>,
     repo: &DynamoRepository,
+    config: &Config,
 ) -> Result<Output, Error> {
     let guid = event.payload.item_identifier.guid.clone();
     tracing::info!("Checking DynamoDB for guid: {}", guid);
@@ -52,9 +66,37 @@ async fn check_dynamodb(
         .await
         .with_context(|| format!("Failed to check if guid exists in DynamoDB: {}", guid))?;
 
+    let posted_hash = if !guid_exists {
+        None
+    } else if config.updated_items == UpdateMode::Ignore {
+        return skip(event.payload.item_identifier);
+    } else {
+        match is_update(&event.payload.item_identifier, repo)
+            .await
+            .with_context(|| format!("Failed to check if guid was updated: {}", guid))?
+        {
+            Some(posted_hash) => Some(posted_hash),
+            None => return skip(event.payload.item_identifier),
+        }
+    };
+
+    // An overlapping execution may have seen the item as well, so it's only posted by the one that claims it
+    let should_process = repo
+        .claim_record_item(
+            &guid,
+            &event.payload.item_identifier.execution_id,
+            Utc::now().timestamp(),
+            posted_hash.as_deref(),
+        )
+        .await
+        .with_context(|| format!("Failed to claim guid: {}", guid))?;
+    if !should_process {
+        tracing::info!("Guid {} is claimed by another execution", guid);
+    }
+
     let output = Output {
         item_identifier: event.payload.item_identifier,
-        should_process: !guid_exists,
+        should_process,
     };
 
     tracing::info!(
@@ -66,6 +108,62 @@ async fn check_dynamodb(
     Ok(output)
 }
 
+/// The output for an item that isn't posted.
+fn skip(item_identifier: ItemIdentifier) -> Result<Output, Error> {
+    tracing::info!(
+        "Check result: should_process is false for guid {}",
+        item_identifier.guid
+    );
+    Ok(Output {
+        item_identifier,
+        should_process: false,
+    })
+}
+
+/// Checks whether a posted item came back with a changed title or description, and if so marks its ExecutionItem as
+/// an update of the earlier post.
+///
+/// Items recorded before content hashes were stored, and those whose post was deleted or isn't known, are never
+/// taken for updated.
+///
+/// # Returns
+///
+/// A Result containing the content hash of the posted version if the item is an update, None if it isn't.
+async fn is_update(
+    item_identifier: &ItemIdentifier,
+    repo: &DynamoRepository,
+) -> anyhow::Result<Option<String>> {
+    let record_item = repo.get_record_item(&item_identifier.guid).await?;
+    let item = repo
+        .get_execution_item(&item_identifier.execution_id, &item_identifier.guid)
+        .await?;
+
+    let (Some(posted_hash), Some(uri), Some(cid), None) = (
+        &record_item.content_hash,
+        &record_item.uri,
+        &record_item.cid,
+        record_item.deleted_at,
+    ) else {
+        return Ok(None);
+    };
+    if item
+        .content_hash
+        .as_ref()
+        .is_none_or(|hash| hash == posted_hash)
+    {
+        return Ok(None);
+    }
+
+    tracing::info!(
+        "Item {} changed since it was posted as {}, posting it again",
+        item.guid,
+        uri
+    );
+    repo.update_execution_item_update_of(&item.execution_id, &item.guid, uri, cid)
+        .await?;
+    Ok(Some(posted_hash.clone()))
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Error> {
     tracing_subscriber::fmt()
@@ -77,10 +175,10 @@ async fn main() -> Result<(), Error> {
     let aws_config = aws_config::load_defaults(BehaviorVersion::latest()).await;
     let dynamodb_client = Client::new(&aws_config);
 
-    let repo = DynamoRepository::new(dynamodb_client, config.dynamodb_table_name);
+    let repo = DynamoRepository::new(dynamodb_client, config.dynamodb_table_name.clone());
 
     run(service_fn(|event: LambdaEvent<Input>| {
-        check_dynamodb(event, &repo)
+        check_dynamodb(event, &repo, &config)
     }))
     .await
 }
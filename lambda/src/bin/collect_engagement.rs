@@ -0,0 +1,193 @@
+use ::tracing::instrument;
+use anyhow::Context;
+use aws_config::BehaviorVersion;
+use aws_sdk_dynamodb::Client as DynamoDbClient;
+use aws_sdk_secretsmanager::Client as SecretsManagerClient;
+use chrono::{Duration, Utc};
+use lambda_runtime::{run, service_fn, tracing, Error, LambdaEvent};
+use rss_bluesky_bridge::analytics::{engagement_report, EngagementReport};
+use rss_bluesky_bridge::bluesky;
+use rss_bluesky_bridge::env;
+use rss_bluesky_bridge::models::{EngagementSample, RecentPost};
+use rss_bluesky_bridge::repository::DynamoRepository;
+use serde::Serialize;
+use std::collections::HashMap;
+use tracing_subscriber::EnvFilter;
+
+const DEFAULT_ANALYTICS_DAYS: i64 = 7;
+const DEFAULT_ANALYTICS_RETENTION_DAYS: i64 = 90;
+
+#[derive(Serialize, Debug)]
+struct Output {
+    /// How many posts were sampled.
+    sampled: usize,
+    /// How the posts sampled in the last days are doing.
+    report: EngagementReport,
+    dry_run: bool,
+}
+
+struct Config {
+    dynamodb_table_name: String,
+    secret_name: String,
+    /// How many days after they were made posts are sampled, and how far back the report looks.
+    days: i64,
+    /// How many days samples are kept for.
+    retention_days: i64,
+    /// Logs the samples instead of storing them.
+    dry_run: bool,
+}
+
+impl Config {
+    fn from_env() -> Result<Self, Error> {
+        let dynamodb_table_name = std::env::var("DYNAMODB_TABLE_NAME")
+            .context("DYNAMODB_TABLE_NAME environment variable not set")?;
+
+        if dynamodb_table_name.trim().is_empty() {
+            return Err(Error::from("DYNAMODB_TABLE_NAME cannot be empty"));
+        }
+
+        let secret_name = std::env::var("BLUESKY_CREDENTIALS_SECRET_NAME")
+            .context("BLUESKY_CREDENTIALS_SECRET_NAME environment variable not set")?;
+
+        if secret_name.trim().is_empty() {
+            return Err(Error::from(
+                "BLUESKY_CREDENTIALS_SECRET_NAME cannot be empty",
+            ));
+        }
+
+        let days = env::parse_or("ANALYTICS_DAYS", DEFAULT_ANALYTICS_DAYS)?;
+        let retention_days =
+            env::parse_or("ANALYTICS_RETENTION_DAYS", DEFAULT_ANALYTICS_RETENTION_DAYS)?;
+        if days < 1 || retention_days < days {
+            return Err(Error::from(
+                "ANALYTICS_DAYS must be at least 1, and ANALYTICS_RETENTION_DAYS at least ANALYTICS_DAYS",
+            ));
+        }
+
+        Ok(Self {
+            dynamodb_table_name,
+            secret_name,
+            days,
+            retention_days,
+            dry_run: env::flag("DRY_RUN"),
+        })
+    }
+}
+
+/// Samples the likes, reposts, quotes and replies of the recent posts, and reports how the feeds and template
+/// variants are doing.
+///
+/// Every run stores a sample of each post made in the last days, so the samples of a post make up the time series
+/// of its engagement. The feed of a post comes from its RecentPost while there is one, and from its earlier samples
+/// after that.
+#[instrument(skip(repo, secrets_client, config))]
+async fn collect_engagement(
+    _event: LambdaEvent<serde_json::Value>,
+    repo: &DynamoRepository,
+    secrets_client: &SecretsManagerClient,
+    config: &Config,
+) -> Result<Output, Error> {
+    let now = Utc::now().timestamp();
+    let since = now - Duration::days(config.days).num_seconds();
+
+    let record_items: Vec<_> = repo
+        .list_record_items_posted_between(since, now)
+        .await
+        .context("Failed to list recent record items")?
+        .into_iter()
+        .filter(|record_item| record_item.deleted_at.is_none() && record_item.uri.is_some())
+        .collect();
+    let recent_posts: HashMap<String, RecentPost> = repo
+        .list_recent_posts(since)
+        .await
+        .context("Failed to list recent posts")?
+        .into_iter()
+        .map(|post| (post.guid.clone(), post))
+        .collect();
+    let mut samples = repo
+        .list_engagement_samples(since)
+        .await
+        .context("Failed to list engagement samples")?;
+    let earlier: HashMap<String, EngagementSample> = samples
+        .iter()
+        .map(|sample| (sample.guid.clone(), sample.clone()))
+        .collect();
+
+    // Counts are public, so the deployment's account looks up the posts of every account
+    let agent = bluesky::login(secrets_client, &config.secret_name).await?;
+    let uris: Vec<String> = record_items
+        .iter()
+        .filter_map(|record_item| record_item.uri.clone())
+        .collect();
+    let stats = bluesky::post_stats(&uris, &agent).await?;
+
+    let ttl = now + Duration::days(config.retention_days).num_seconds();
+    let new_samples: Vec<EngagementSample> = record_items
+        .into_iter()
+        .filter_map(|record_item| {
+            let uri = record_item.uri?;
+            let stats = stats.get(&uri)?;
+            let recent_post = recent_posts.get(&record_item.guid);
+            let earlier = earlier.get(&record_item.guid);
+            Some(EngagementSample {
+                sampled_at: now,
+                feed_url: recent_post
+                    .and_then(|post| post.feed_url.clone())
+                    .or_else(|| earlier.and_then(|sample| sample.feed_url.clone())),
+                account: match recent_post {
+                    Some(post) => post.account.clone(),
+                    None => earlier.and_then(|sample| sample.account.clone()),
+                },
+                template_variant: record_item.template_variant,
+                posted_at: record_item.posted_at.unwrap_or_default(),
+                likes: stats.likes,
+                reposts: stats.reposts,
+                quotes: stats.quotes,
+                replies: stats.replies,
+                ttl: Some(ttl),
+                guid: record_item.guid,
+                uri,
+            })
+        })
+        .collect();
+
+    if config.dry_run {
+        for sample in &new_samples {
+            tracing::info!("Dry run, not storing engagement sample: {:?}", sample);
+        }
+    } else {
+        repo.create_engagement_samples(&new_samples)
+            .await
+            .context("Failed to store engagement samples")?;
+    }
+
+    let sampled = new_samples.len();
+    samples.extend(new_samples);
+    let output = Output {
+        sampled,
+        report: engagement_report(&samples),
+        dry_run: config.dry_run,
+    };
+
+    tracing::info!("Engagement result: {:?}", output);
+    Ok(output)
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Error> {
+    tracing_subscriber::fmt()
+        .json()
+        .with_env_filter(EnvFilter::from_default_env())
+        .init();
+
+    let config = Config::from_env().expect("Failed to load configuration");
+    let aws_config = aws_config::load_defaults(BehaviorVersion::latest()).await;
+    let dynamodb_client = DynamoDbClient::new(&aws_config);
+    let secrets_client = SecretsManagerClient::new(&aws_config);
+    let repo = DynamoRepository::new(dynamodb_client, config.dynamodb_table_name.clone());
+
+    run(service_fn(|event: LambdaEvent<serde_json::Value>| {
+        collect_engagement(event, &repo, &secrets_client, &config)
+    }))
+    .await
+}
@@ -0,0 +1,298 @@
+use ::tracing::instrument;
+use anyhow::Context;
+use aws_config::BehaviorVersion;
+use aws_sdk_dynamodb::Client as DynamoDbClient;
+use aws_sdk_secretsmanager::Client as SecretsManagerClient;
+use bsky_sdk::BskyAgent;
+use chrono::Utc;
+use lambda_runtime::{run, service_fn, tracing, Error, LambdaEvent};
+use rss_bluesky_bridge::{
+    bluesky::{self, RetryingClient},
+    feed::parse_date,
+    models::RecordItem,
+    repository::DynamoRepository,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use tracing_subscriber::EnvFilter;
+
+/// Which posts to delete: the post of one item, all posts made within a time range, or all posts older than a
+/// number of days.
+///
+/// ```json
+/// {"guid": "https://example.com/retracted-article"}
+/// {"from": "2025-01-10T08:00:00Z", "to": "2025-01-10T09:00:00Z"}
+/// {"older_than_days": 30}
+/// ```
+///
+/// Feeds that post to their own account need its secret in `secret_name`. Posts of other accounts are skipped,
+/// except for `older_than_days`, which deletes the old posts of every account the bridge posts to.
+#[derive(Deserialize)]
+struct Input {
+    #[serde(default)]
+    guid: Option<String>,
+    /// Start of the time range, as an RFC 3339 or RFC 2822 date.
+    #[serde(default)]
+    from: Option<String>,
+    /// End of the time range, inclusive. Defaults to now.
+    #[serde(default)]
+    to: Option<String>,
+    #[serde(default)]
+    secret_name: Option<String>,
+    /// Deletes the posts made more than this many days ago, as the retention policy does every day.
+    #[serde(default)]
+    older_than_days: Option<u32>,
+}
+
+#[derive(Serialize, Debug)]
+struct Output {
+    /// The guids of the items whose posts were deleted.
+    deleted: Vec<String>,
+    /// The guids of the items whose posts were left alone, as they were deleted already, their URI isn't known or
+    /// they belong to another account.
+    skipped: Vec<String>,
+}
+
+struct Config {
+    dynamodb_table_name: String,
+    secret_name: String,
+}
+
+impl Config {
+    fn from_env() -> Result<Self, Error> {
+        let dynamodb_table_name = std::env::var("DYNAMODB_TABLE_NAME")
+            .context("DYNAMODB_TABLE_NAME environment variable not set")?;
+
+        if dynamodb_table_name.trim().is_empty() {
+            return Err(Error::from("DYNAMODB_TABLE_NAME cannot be empty"));
+        }
+
+        let secret_name = std::env::var("BLUESKY_CREDENTIALS_SECRET_NAME")
+            .context("BLUESKY_CREDENTIALS_SECRET_NAME environment variable not set")?;
+
+        if secret_name.trim().is_empty() {
+            return Err(Error::from(
+                "BLUESKY_CREDENTIALS_SECRET_NAME cannot be empty",
+            ));
+        }
+
+        Ok(Self {
+            dynamodb_table_name,
+            secret_name,
+        })
+    }
+}
+
+/// Deletes posts from Bluesky, for articles a feed retracted or posts that shouldn't have been made.
+///
+/// The RecordItems of the deleted posts are kept and marked as deleted, so the items aren't posted again. Replies
+/// continuing a thread are not deleted along with it.
+#[instrument(skip(event, repo, secrets_client, config))]
+async fn delete_post(
+    event: LambdaEvent<Input>,
+    repo: &DynamoRepository,
+    secrets_client: &SecretsManagerClient,
+    config: &Config,
+) -> Result<Output, Error> {
+    let input = event.payload;
+    if let Some(days) = input.older_than_days {
+        return expire_posts(days, repo, secrets_client, config).await;
+    }
+    let record_items = match (&input.guid, &input.from) {
+        (Some(guid), _) => vec![repo
+            .get_record_item(guid)
+            .await
+            .with_context(|| format!("No post recorded for guid {:?}", guid))?],
+        (None, Some(from)) => {
+            let from = parse_date(from)
+                .with_context(|| format!("Invalid from date {:?}", from))?
+                .timestamp();
+            let to = match &input.to {
+                Some(to) => parse_date(to)
+                    .with_context(|| format!("Invalid to date {:?}", to))?
+                    .timestamp(),
+                None => Utc::now().timestamp(),
+            };
+            repo.list_record_items_posted_between(from, to)
+                .await
+                .context("Failed to list posts")?
+        }
+        (None, None) => {
+            return Err(Error::from(
+                "Either guid, from or older_than_days must be given",
+            ))
+        }
+    };
+
+    let secret_name = input.secret_name.as_deref().unwrap_or(&config.secret_name);
+    let (agent, did) = login(secrets_client, secret_name).await?;
+
+    let mut output = Output {
+        deleted: Vec::new(),
+        skipped: Vec::new(),
+    };
+    delete_posts(record_items, &agent, &did, repo, &mut output).await?;
+
+    tracing::info!("Delete result: {:?}", output);
+    Ok(output)
+}
+
+/// Deletes the posts made more than `days` days ago, for accounts that only keep a rolling window of posts.
+///
+/// The posts of the deployment's account and of the accounts of feeds with a `bluesky_secret` are deleted. An
+/// account that can't be logged in to keeps its posts until the next time.
+async fn expire_posts(
+    days: u32,
+    repo: &DynamoRepository,
+    secrets_client: &SecretsManagerClient,
+    config: &Config,
+) -> Result<Output, Error> {
+    let before = Utc::now().timestamp() - i64::from(days) * 86_400;
+    let mut record_items: Vec<RecordItem> = repo
+        .list_record_items_posted_between(0, before)
+        .await
+        .context("Failed to list posts")?
+        .into_iter()
+        .filter(|record_item| record_item.deleted_at.is_none())
+        .collect();
+
+    let mut output = Output {
+        deleted: Vec::new(),
+        skipped: Vec::new(),
+    };
+    if record_items.is_empty() {
+        return Ok(output);
+    }
+
+    let mut secret_names = vec![config.secret_name.clone()];
+    for feed in repo
+        .list_feed_configs()
+        .await
+        .context("Failed to list feed configs")?
+    {
+        if let Some(secret_name) = feed.bluesky_secret {
+            if !secret_names.contains(&secret_name) {
+                secret_names.push(secret_name);
+            }
+        }
+    }
+
+    for secret_name in secret_names {
+        let (agent, did) = match login(secrets_client, &secret_name).await {
+            Ok(login) => login,
+            Err(e) => {
+                tracing::warn!("Not deleting the old posts of {}: {:?}", secret_name, e);
+                continue;
+            }
+        };
+        let prefix = format!("at://{}/", did);
+        let (own, others) = record_items.into_iter().partition(|record_item| {
+            record_item
+                .uri
+                .as_deref()
+                .is_some_and(|uri| uri.starts_with(&prefix))
+        });
+        record_items = others;
+        delete_posts(own, &agent, &did, repo, &mut output).await?;
+    }
+
+    // Posts without a URI, or of accounts the bridge no longer posts to
+    output
+        .skipped
+        .extend(record_items.into_iter().map(|record_item| record_item.guid));
+
+    tracing::info!("Expiry result: {:?}", output);
+    Ok(output)
+}
+
+/// Logs in to the Bluesky account of a secret.
+///
+/// # Returns
+///
+/// A Result containing the agent and the DID of the account, or an error if logging in failed.
+async fn login(
+    secrets_client: &SecretsManagerClient,
+    secret_name: &str,
+) -> anyhow::Result<(BskyAgent<RetryingClient>, String)> {
+    let agent = bluesky::login(secrets_client, secret_name).await?;
+    let did = agent
+        .get_session()
+        .await
+        .map(|session| session.did.as_str().to_string())
+        .context("Not logged in to Bluesky")?;
+    Ok((agent, did))
+}
+
+/// Deletes the posts of RecordItems that belong to the account `did`, and marks them as deleted.
+///
+/// The items a digest listed share its post, which is deleted once.
+async fn delete_posts(
+    record_items: Vec<RecordItem>,
+    agent: &BskyAgent<RetryingClient>,
+    did: &str,
+    repo: &DynamoRepository,
+    output: &mut Output,
+) -> anyhow::Result<()> {
+    let mut deleted_uris = HashSet::new();
+    for record_item in record_items {
+        let Some(uri) = deletable_uri(&record_item, did) else {
+            output.skipped.push(record_item.guid);
+            continue;
+        };
+
+        if deleted_uris.insert(uri.to_string()) {
+            agent
+                .delete_record(uri)
+                .await
+                .with_context(|| format!("Failed to delete post {}", uri))?;
+        }
+        repo.mark_record_item_deleted(&record_item.guid, Utc::now().timestamp())
+            .await
+            .context("Failed to mark record item as deleted")?;
+        tracing::info!("Deleted post {} of {:?}", uri, record_item.guid);
+        output.deleted.push(record_item.guid);
+    }
+    Ok(())
+}
+
+/// Returns the URI of a RecordItem's post if it can be deleted by the account `did`.
+fn deletable_uri<'a>(record_item: &'a RecordItem, did: &str) -> Option<&'a str> {
+    if record_item.deleted_at.is_some() {
+        tracing::info!("The post of {:?} is deleted already", record_item.guid);
+        return None;
+    }
+    let Some(uri) = record_item.uri.as_deref() else {
+        tracing::warn!(
+            "The URI of the post of {:?} isn't known, it has to be deleted by hand",
+            record_item.guid
+        );
+        return None;
+    };
+    if !uri.starts_with(&format!("at://{}/", did)) {
+        tracing::warn!(
+            "Post {} belongs to another account, pass its secret_name to delete it",
+            uri
+        );
+        return None;
+    }
+    Some(uri)
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Error> {
+    tracing_subscriber::fmt()
+        .json()
+        .with_env_filter(EnvFilter::from_default_env())
+        .init();
+
+    let config = Config::from_env().expect("Failed to load configuration");
+    let aws_config = aws_config::load_defaults(BehaviorVersion::latest()).await;
+    let dynamodb_client = DynamoDbClient::new(&aws_config);
+    let secrets_client = SecretsManagerClient::new(&aws_config);
+    let repo = DynamoRepository::new(dynamodb_client, config.dynamodb_table_name.clone());
+
+    run(service_fn(|event: LambdaEvent<Input>| {
+        delete_post(event, &repo, &secrets_client, &config)
+    }))
+    .await
+}
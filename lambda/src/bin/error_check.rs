@@ -1,5 +1,9 @@
 use ::tracing::instrument;
+use anyhow::Context;
+use aws_config::BehaviorVersion;
+use aws_sdk_dynamodb::Client;
 use lambda_runtime::{run, service_fn, tracing, Error, LambdaEvent};
+use rss_bluesky_bridge::repository::DynamoRepository;
 use serde::{Deserialize, Serialize};
 use tracing_subscriber::EnvFilter;
 
@@ -7,10 +11,35 @@ use tracing_subscriber::EnvFilter;
 struct ProcessedItem {
     #[serde(default)]
     error: Option<String>,
+    #[serde(default)]
+    dry_run: bool,
+}
+
+/// The output of get-rss-items, as wrapped by the LambdaInvoke task.
+#[derive(Deserialize)]
+struct Items {
+    #[serde(rename = "Payload")]
+    payload: ItemsPayload,
+}
+
+#[derive(Deserialize)]
+struct ItemsPayload {
+    #[serde(default)]
+    started_at: Option<i64>,
+    #[serde(default)]
+    changed_feeds: Vec<ChangedFeed>,
+}
+
+#[derive(Deserialize)]
+struct ChangedFeed {
+    feed_url: String,
+    content_hash: String,
 }
 
 #[derive(Deserialize)]
 struct Input {
+    #[serde(default)]
+    items: Option<Items>,
     processed_items: Vec<ProcessedItem>,
 }
 
@@ -21,8 +50,27 @@ struct Output {
     total_items: usize,
 }
 
-#[instrument(skip(event))]
-async fn error_check(event: LambdaEvent<Input>) -> Result<Output, Error> {
+struct Config {
+    dynamodb_table_name: String,
+}
+
+impl Config {
+    fn from_env() -> Result<Self, Error> {
+        let dynamodb_table_name = std::env::var("DYNAMODB_TABLE_NAME")
+            .context("DYNAMODB_TABLE_NAME environment variable not set")?;
+
+        if dynamodb_table_name.is_empty() {
+            return Err(Error::from("DYNAMODB_TABLE_NAME cannot be empty"));
+        }
+
+        Ok(Self {
+            dynamodb_table_name,
+        })
+    }
+}
+
+#[instrument(skip(event, repo))]
+async fn error_check(event: LambdaEvent<Input>, repo: &DynamoRepository) -> Result<Output, Error> {
     tracing::info!("Checking for errors in processed items");
 
     let total_items = event.payload.processed_items.len();
@@ -38,6 +86,35 @@ async fn error_check(event: LambdaEvent<Input>) -> Result<Output, Error> {
         error_count
     );
 
+    // The next run picks up items published since this one started. After a failure the window stays anchored to
+    // the last successful run, so the failed items are fetched again.
+    // A dry run leaves the window where it is as well, as its items were only pretended to be posted
+    let dry_run = event
+        .payload
+        .processed_items
+        .iter()
+        .any(|item| item.dry_run);
+    let payload = event
+        .payload
+        .items
+        .map(|items| items.payload)
+        .filter(|_| !dry_run);
+    if let (0, Some(payload)) = (error_count, payload) {
+        if let Some(started_at) = payload.started_at {
+            repo.advance_last_successful_run(started_at)
+                .await
+                .context("Failed to record successful run")?;
+        }
+        // The same goes for the hashes of changed feeds: once stored, an unchanged feed yields no items
+        for feed in payload.changed_feeds {
+            repo.update_feed_content_hash(&feed.feed_url, &feed.content_hash)
+                .await
+                .with_context(|| {
+                    format!("Failed to store content hash for feed {}", feed.feed_url)
+                })?;
+        }
+    }
+
     let output = Output {
         has_errors: error_count > 0,
         error_count,
@@ -54,5 +131,15 @@ async fn main() -> Result<(), Error> {
         .json()
         .with_env_filter(EnvFilter::from_default_env())
         .init();
-    run(service_fn(error_check)).await
+
+    let config = Config::from_env().expect("Failed to load configuration");
+    let aws_config = aws_config::load_defaults(BehaviorVersion::latest()).await;
+    let dynamodb_client = Client::new(&aws_config);
+
+    let repo = DynamoRepository::new(dynamodb_client, config.dynamodb_table_name);
+
+    run(service_fn(|event: LambdaEvent<Input>| {
+        error_check(event, &repo)
+    }))
+    .await
 }
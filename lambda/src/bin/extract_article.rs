@@ -0,0 +1,173 @@
+use ::tracing::instrument;
+use anyhow::Context;
+use aws_config::BehaviorVersion;
+use aws_sdk_dynamodb::Client as DynamoDbClient;
+use lambda_runtime::{run, service_fn, tracing, Error, LambdaEvent};
+use rss_bluesky_bridge::env;
+use rss_bluesky_bridge::html::{self, extract_article_text, page_metadata};
+use rss_bluesky_bridge::http::{HttpClient, HttpConfig, DEFAULT_MIN_HOST_INTERVAL};
+use rss_bluesky_bridge::text_utils::truncate_to_word;
+use rss_bluesky_bridge::{models::ItemIdentifier, repository::DynamoRepository};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tracing_subscriber::EnvFilter;
+
+/// Cap on the stored article text, keeping the ExecutionItem well below DynamoDB's 400 KB item limit.
+const MAX_ARTICLE_GRAPHEMES: usize = 30_000;
+
+#[derive(Deserialize)]
+struct Input {
+    #[serde(flatten)]
+    item_identifier: ItemIdentifier,
+}
+
+#[derive(Serialize)]
+struct Output {
+    #[serde(flatten)]
+    item_identifier: ItemIdentifier,
+}
+
+struct Config {
+    dynamodb_table_name: String,
+    enable_article_extraction: bool,
+    enrich_missing_descriptions: bool,
+    http: HttpConfig,
+}
+
+impl Config {
+    fn from_env() -> Result<Self, Error> {
+        let dynamodb_table_name = std::env::var("DYNAMODB_TABLE_NAME")
+            .context("DYNAMODB_TABLE_NAME environment variable not set")?;
+
+        if dynamodb_table_name.is_empty() {
+            return Err(Error::from("DYNAMODB_TABLE_NAME cannot be empty"));
+        }
+
+        let http = HttpConfig {
+            proxy_url: env::optional("FETCH_PROXY_URL"),
+            min_host_interval: Duration::from_millis(env::parse_or(
+                "FETCH_MIN_HOST_INTERVAL_MS",
+                DEFAULT_MIN_HOST_INTERVAL.as_millis() as u64,
+            )?),
+            respect_robots_txt: !env::flag("IGNORE_ROBOTS_TXT"),
+            ..HttpConfig::default()
+        };
+
+        Ok(Self {
+            dynamodb_table_name,
+            enable_article_extraction: env::flag("ENABLE_ARTICLE_EXTRACTION"),
+            // Items without a description can't be summarized or posted, so this is on unless turned off
+            enrich_missing_descriptions: !env::flag("DISABLE_DESCRIPTION_ENRICHMENT"),
+            http,
+        })
+    }
+}
+
+/// Fetches the web page an item links to and stores the main text of the article on the ExecutionItem. Items the
+/// feed gave no description get the page's Open Graph (or meta) description instead.
+///
+/// Both are best effort: when the page can't be fetched or doesn't contain a recognizable article, the item
+/// continues through the state machine as it came from the feed.
+#[instrument(skip(event, repo, http, config))]
+async fn extract_article(
+    event: LambdaEvent<Input>,
+    repo: &DynamoRepository,
+    http: &HttpClient,
+    config: &Config,
+) -> Result<Output, Error> {
+    let item_identifier = event.payload.item_identifier;
+    if !config.enable_article_extraction && !config.enrich_missing_descriptions {
+        return Ok(Output { item_identifier });
+    }
+
+    let item = repo
+        .get_execution_item(&item_identifier.execution_id, &item_identifier.guid)
+        .await
+        .with_context(|| {
+            format!(
+                "Failed to get item from DynamoDB for execution-id {:?} and guid {:?}",
+                item_identifier.execution_id, item_identifier.guid
+            )
+        })?;
+
+    let needs_description = config.enrich_missing_descriptions
+        && item
+            .description
+            .as_deref()
+            .is_none_or(|d| html::strip_tags(d).is_empty());
+    if !config.enable_article_extraction && !needs_description {
+        return Ok(Output { item_identifier });
+    }
+
+    let Some(link) = item.link else {
+        tracing::info!("Item {} has no link, skipping extraction", item.guid);
+        return Ok(Output { item_identifier });
+    };
+
+    let html = match http.get_page_text(&link).await {
+        Ok(html) => html,
+        Err(e) => {
+            tracing::warn!("Failed to fetch article {}: {:?}", link, e);
+            return Ok(Output { item_identifier });
+        }
+    };
+
+    if needs_description {
+        match page_metadata(&html, &link).description {
+            Some(description) => {
+                tracing::info!("Using the description of {} for item {}", link, item.guid);
+                repo.update_execution_item_description(
+                    &item_identifier.execution_id,
+                    &item_identifier.guid,
+                    &description,
+                )
+                .await
+                .context("Failed to update item in DynamoDB with page description")?;
+            }
+            None => tracing::info!("No description found at {}", link),
+        }
+    }
+
+    if !config.enable_article_extraction {
+        return Ok(Output { item_identifier });
+    }
+
+    let Some(article_text) = extract_article_text(&html) else {
+        tracing::info!("No article text found at {}", link);
+        return Ok(Output { item_identifier });
+    };
+
+    let article_text = truncate_to_word(&article_text, MAX_ARTICLE_GRAPHEMES);
+    tracing::info!(
+        "Extracted {} characters of article text from {}",
+        article_text.len(),
+        link
+    );
+
+    repo.update_execution_item_article_text(
+        &item_identifier.execution_id,
+        &item_identifier.guid,
+        &article_text,
+    )
+    .await
+    .context("Failed to update item in DynamoDB with article text")?;
+
+    Ok(Output { item_identifier })
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Error> {
+    tracing_subscriber::fmt()
+        .json()
+        .with_env_filter(EnvFilter::from_default_env())
+        .init();
+    let config = Config::from_env().expect("Failed to load configuration");
+    let aws_config = aws_config::load_defaults(BehaviorVersion::latest()).await;
+    let dynamodb_client = DynamoDbClient::new(&aws_config);
+    let repo = DynamoRepository::new(dynamodb_client, config.dynamodb_table_name.clone());
+    let http = HttpClient::new(config.http.clone())?;
+    run(service_fn(|event: LambdaEvent<Input>| {
+        extract_article(event, &repo, &http, &config)
+    }))
+    .await
+}
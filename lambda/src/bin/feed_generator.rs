@@ -0,0 +1,234 @@
+use ::tracing::instrument;
+use anyhow::Context;
+use aws_config::BehaviorVersion;
+use aws_lambda_events::event::lambda_function_urls::{
+    LambdaFunctionUrlRequest, LambdaFunctionUrlResponse,
+};
+use aws_lambda_events::http::header::CONTENT_TYPE;
+use aws_lambda_events::http::{HeaderMap, HeaderValue};
+use aws_sdk_dynamodb::Client as DynamoDbClient;
+use chrono::{Duration, Utc};
+use lambda_runtime::{run, service_fn, tracing, Error, LambdaEvent};
+use rss_bluesky_bridge::env;
+use rss_bluesky_bridge::feed_generator::{
+    did_document, feed_skeleton, FeedPost, DEFAULT_FEED_LIMIT, MAX_FEED_LIMIT,
+};
+use rss_bluesky_bridge::repository::DynamoRepository;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Instant;
+use tracing_subscriber::EnvFilter;
+
+const DEFAULT_FEED_RECORD_NAME: &str = "bridge";
+const DEFAULT_FEED_DAYS: i64 = 7;
+/// How long a warm lambda serves the posts it read, as reading them scans the RecordItems.
+const POSTS_CACHE_SECONDS: u64 = 60;
+
+struct Config {
+    dynamodb_table_name: String,
+    /// The DID of the account the feed is published by.
+    publisher_did: String,
+    /// The record key of the feed's app.bsky.feed.generator record.
+    record_name: String,
+    /// The host the feed generator is served from. None for the host of the function URL.
+    hostname: Option<String>,
+    /// The DID of the feed generator. None for the did:web of its host.
+    service_did: Option<String>,
+    /// How many days back the feed goes.
+    days: i64,
+}
+
+impl Config {
+    fn from_env() -> Result<Self, Error> {
+        let dynamodb_table_name = std::env::var("DYNAMODB_TABLE_NAME")
+            .context("DYNAMODB_TABLE_NAME environment variable not set")?;
+
+        if dynamodb_table_name.trim().is_empty() {
+            return Err(Error::from("DYNAMODB_TABLE_NAME cannot be empty"));
+        }
+
+        let publisher_did = env::optional("FEED_PUBLISHER_DID")
+            .ok_or_else(|| Error::from("FEED_PUBLISHER_DID environment variable not set"))?;
+        let days = env::parse_or("FEED_DAYS", DEFAULT_FEED_DAYS)?;
+        if days < 1 {
+            return Err(Error::from("FEED_DAYS must be at least 1"));
+        }
+
+        Ok(Self {
+            dynamodb_table_name,
+            publisher_did,
+            record_name: env::optional("FEED_RECORD_NAME")
+                .unwrap_or_else(|| DEFAULT_FEED_RECORD_NAME.to_string()),
+            hostname: env::optional("FEED_GENERATOR_HOSTNAME"),
+            service_did: env::optional("FEED_GENERATOR_DID"),
+            days,
+        })
+    }
+
+    /// The at:// URI of the feed, which the app asks for its skeleton by.
+    fn feed_uri(&self) -> String {
+        format!(
+            "at://{}/app.bsky.feed.generator/{}",
+            self.publisher_did, self.record_name
+        )
+    }
+}
+
+/// The posts read last, with when they were read.
+type PostsCache = Mutex<Option<(Instant, Arc<Vec<FeedPost>>)>>;
+
+/// Serves a custom feed of everything the bridge has posted, across accounts, the latest first.
+///
+/// The lambda is a feed generator behind a function URL: it serves app.bsky.feed.getFeedSkeleton and
+/// app.bsky.feed.describeFeedGenerator, and the DID document of its did:web. The posts are those of the RecordItems
+/// posted in the last days that weren't deleted since.
+#[instrument(skip(event, repo, config, cache))]
+async fn feed_generator(
+    event: LambdaEvent<LambdaFunctionUrlRequest>,
+    repo: &DynamoRepository,
+    config: &Config,
+    cache: &PostsCache,
+) -> Result<LambdaFunctionUrlResponse, Error> {
+    let request = event.payload;
+    let hostname = config
+        .hostname
+        .clone()
+        .or(request.request_context.domain_name.clone())
+        .unwrap_or_default();
+    let service_did = config
+        .service_did
+        .clone()
+        .unwrap_or_else(|| format!("did:web:{}", hostname));
+    let path = request
+        .raw_path
+        .as_deref()
+        .or(request.request_context.http.path.as_deref())
+        .unwrap_or("/");
+
+    let response = match path {
+        "/.well-known/did.json" if service_did.starts_with("did:web:") => {
+            json_response(200, &did_document(&service_did, &hostname))
+        }
+        "/xrpc/app.bsky.feed.describeFeedGenerator" => json_response(
+            200,
+            &json!({"did": service_did, "feeds": [{"uri": config.feed_uri()}]}),
+        ),
+        "/xrpc/app.bsky.feed.getFeedSkeleton" => {
+            get_feed_skeleton(&request.query_string_parameters, repo, config, cache).await?
+        }
+        _ => error_response(404, "MethodNotImplemented", "Method not implemented"),
+    };
+
+    tracing::info!("{} answered with {}", path, response.status_code);
+    Ok(response)
+}
+
+/// Answers app.bsky.feed.getFeedSkeleton with a page of the feed.
+async fn get_feed_skeleton(
+    query: &HashMap<String, String>,
+    repo: &DynamoRepository,
+    config: &Config,
+    cache: &PostsCache,
+) -> anyhow::Result<LambdaFunctionUrlResponse> {
+    if query.get("feed") != Some(&config.feed_uri()) {
+        return Ok(error_response(
+            400,
+            "UnsupportedAlgorithm",
+            "Unsupported feed",
+        ));
+    }
+    let limit = match query.get("limit").map(|limit| limit.parse::<usize>()) {
+        None => DEFAULT_FEED_LIMIT,
+        Some(Ok(limit)) if (1..=MAX_FEED_LIMIT).contains(&limit) => limit,
+        Some(_) => {
+            return Ok(error_response(
+                400,
+                "InvalidRequest",
+                &format!("limit must be between 1 and {}", MAX_FEED_LIMIT),
+            ))
+        }
+    };
+
+    let posts = feed_posts(repo, config, cache).await?;
+    Ok(
+        match feed_skeleton(&posts, query.get("cursor").map(String::as_str), limit) {
+            Ok(skeleton) => json_response(200, &json!(skeleton)),
+            Err(e) => error_response(400, "InvalidRequest", &e.to_string()),
+        },
+    )
+}
+
+/// Reads the posts of the feed, reusing those read in the last POSTS_CACHE_SECONDS.
+async fn feed_posts(
+    repo: &DynamoRepository,
+    config: &Config,
+    cache: &PostsCache,
+) -> anyhow::Result<Arc<Vec<FeedPost>>> {
+    if let Some((read_at, posts)) = cache
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .as_ref()
+    {
+        if read_at.elapsed().as_secs() < POSTS_CACHE_SECONDS {
+            return Ok(posts.clone());
+        }
+    }
+
+    let now = Utc::now().timestamp();
+    let since = now - Duration::days(config.days).num_seconds();
+    let posts: Vec<FeedPost> = repo
+        .list_record_items_posted_between(since, now)
+        .await
+        .context("Failed to list recent record items")?
+        .into_iter()
+        .filter(|record_item| record_item.deleted_at.is_none())
+        .filter_map(|record_item| {
+            Some(FeedPost {
+                posted_at: record_item.posted_at?,
+                uri: record_item.uri?,
+            })
+        })
+        .collect();
+    let posts = Arc::new(posts);
+    *cache.lock().unwrap_or_else(PoisonError::into_inner) = Some((Instant::now(), posts.clone()));
+    Ok(posts)
+}
+
+fn json_response(status_code: i64, body: &Value) -> LambdaFunctionUrlResponse {
+    let mut headers = HeaderMap::new();
+    headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+    LambdaFunctionUrlResponse {
+        status_code,
+        headers,
+        body: Some(body.to_string()),
+        is_base64_encoded: false,
+        cookies: Vec::new(),
+    }
+}
+
+/// Answers with an XRPC error.
+fn error_response(status_code: i64, error: &str, message: &str) -> LambdaFunctionUrlResponse {
+    json_response(status_code, &json!({"error": error, "message": message}))
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Error> {
+    tracing_subscriber::fmt()
+        .json()
+        .with_env_filter(EnvFilter::from_default_env())
+        .init();
+
+    let config = Config::from_env().expect("Failed to load configuration");
+    let aws_config = aws_config::load_defaults(BehaviorVersion::latest()).await;
+    let dynamodb_client = DynamoDbClient::new(&aws_config);
+    let repo = DynamoRepository::new(dynamodb_client, config.dynamodb_table_name.clone());
+    let cache = PostsCache::default();
+
+    run(service_fn(
+        |event: LambdaEvent<LambdaFunctionUrlRequest>| {
+            feed_generator(event, &repo, &config, &cache)
+        },
+    ))
+    .await
+}
@@ -3,27 +3,115 @@ use anyhow::Context;
 use aws_config::BehaviorVersion;
 use aws_lambda_events::event::cloudwatch_events::CloudWatchEvent;
 use aws_sdk_dynamodb::Client;
-use chrono::{DateTime, Duration, Utc};
+use aws_sdk_secretsmanager::Client as SecretsManagerClient;
+use chrono::{Duration, Utc};
+use chrono_tz::Tz;
+use futures::stream::{self, StreamExt};
 use lambda_runtime::{run, service_fn, tracing, Error, LambdaEvent};
-use reqwest;
 use rss::Channel;
 use rss_bluesky_bridge::{
-    models::{ExecutionItem, ItemIdentifier},
+    bluesky::MAX_VIDEO_BYTES,
+    env, feed,
+    filters::{parse_list, CategoryFilter, ContentLengthFilter, KeywordFilter, LanguageFilter},
+    html,
+    http::{
+        Credentials, HttpClient, HttpConfig, DEFAULT_CONNECT_TIMEOUT, DEFAULT_MAX_BODY_BYTES,
+        DEFAULT_MAX_RETRIES, DEFAULT_MIN_HOST_INTERVAL, DEFAULT_READ_TIMEOUT,
+    },
+    ical, json_api,
+    language::detect_language,
+    models::{ExecutionItem, FeedConfig, FeedType, HeldItem, ItemIdentifier},
+    preprocess::{ItemContent, Preprocessor},
     repository::DynamoRepository,
+    schedule::{apply_daily_cap, start_of_day, PostingWindow},
+    scrape,
+    similarity::{dedupe_by_guid, link_related_posts, suppress_near_duplicates},
+    sitemap::{self, SitemapUrl},
+    text_utils::{decode_html_entities, sha256_hex},
+    url_utils::{canonical_url, dedupe_key},
 };
 use serde::Serialize;
-use std::env;
+use std::collections::{HashMap, HashSet};
+use std::time::Duration as StdDuration;
 use tracing_subscriber::EnvFilter;
+use url::Url;
+
+const DEFAULT_MAX_CONCURRENT_FEEDS: usize = 4;
+/// Scheduled runs don't start at exactly the same second every time, so a feed is due slightly before its polling
+/// interval has fully passed. Otherwise an interval equal to the schedule's would skip every other run.
+const POLL_INTERVAL_SLACK_SECS: i64 = 120;
+const DEFAULT_MAX_FEED_PAGES: usize = 1;
+/// Child sitemaps of a sitemap index fetched per run, most recently modified first.
+const MAX_CHILD_SITEMAPS: usize = 10;
+/// Pages of a sitemap fetched per run, most recently modified first. Any others are picked up by later runs.
+const MAX_SITEMAP_PAGES: usize = 20;
+const DEFAULT_EVENT_HORIZON_DAYS: i64 = 7;
+const DEFAULT_DUPLICATE_WINDOW_HOURS: i64 = 48;
+/// update-dynamodb keeps RecentPost items for 7 days, so a longer window would find nothing more.
+const MAX_DUPLICATE_WINDOW_HOURS: i64 = 7 * 24;
+const DEFAULT_DUPLICATE_TITLE_SIMILARITY: f64 = 0.8;
+const DEFAULT_RELATED_TITLE_SIMILARITY: f64 = 0.5;
+const DEFAULT_WINDOW_OVERLAP_MINUTES: i64 = 30;
+/// How long ExecutionItems last, long enough for the execution to post them. Holding an item extends it.
+const EXECUTION_ITEM_TTL_HOURS: i64 = 24;
 
 #[derive(Serialize)]
 struct Output {
     item_identifiers: Vec<ItemIdentifier>,
+    /// When this run started, for error-check to record once every item went through. Not set when a feed failed,
+    /// so the next run looks back far enough to pick up what the failed feed published.
+    started_at: Option<i64>,
+    /// The feeds whose body changed, for error-check to store their new hashes once every item went through.
+    /// Storing them any earlier would skip the feeds on the next tick after a failed run, losing its items.
+    changed_feeds: Vec<ChangedFeed>,
+}
+
+/// A feed whose body differs from the one last fetched.
+#[derive(Serialize)]
+struct ChangedFeed {
+    feed_url: String,
+    content_hash: String,
+}
+
+/// Items fetched from a single feed.
+struct FeedItems {
+    feed_url: String,
+    items: Vec<ExecutionItem>,
+    /// The hash of the feed body, set only when it differs from the one stored in the FeedState.
+    changed_content_hash: Option<String>,
+    /// When the feed was fetched, set only for feeds with their own polling interval.
+    polled_at: Option<i64>,
 }
 
 struct Config {
     dynamodb_table_name: String,
     max_age_hours: i64,
-    feed_url: String,
+    window_overlap_minutes: i64,
+    feed_urls: Vec<String>,
+    max_concurrent_feeds: usize,
+    keyword_filter: KeywordFilter,
+    language_filter: LanguageFilter,
+    content_length_filter: ContentLengthFilter,
+    include_categories: Vec<String>,
+    exclude_categories: Vec<String>,
+    http: HttpConfig,
+    max_feed_pages: usize,
+    event_horizon_days: i64,
+    duplicate_window_hours: i64,
+    duplicate_title_similarity: f64,
+    /// How similar a title must be to that of a recent post for the item's post to quote it. None when related
+    /// posts aren't quoted.
+    related_title_similarity: Option<f64>,
+    oldest_first: bool,
+    accept_undated_items: bool,
+    /// The hours items are posted in. None when they are posted around the clock.
+    posting_window: Option<PostingWindow>,
+    /// The most items posted to an account a day. None when there's no cap.
+    max_posts_per_day: Option<usize>,
+    /// The timezone of the posting window, and of the days the cap counts posts in.
+    timezone: Tz,
+    /// Whether items are held for post-digest, which lists them in a daily digest, rather than posted one by one.
+    digest_mode: bool,
 }
 
 impl Config {
@@ -52,26 +140,174 @@ impl Config {
             max_age_hours
         };
 
-        let feed_url: String = env::var("FEED_URL")
-            .context("FEED_URL environment variable not set")
-            .map_err(Error::from)?;
+        // Items published shortly before the last successful run started may only have shown up in the feed after
+        // it was fetched, so the window reaches back a little further than that.
+        let window_overlap_minutes =
+            env::parse_or("WINDOW_OVERLAP_MINUTES", DEFAULT_WINDOW_OVERLAP_MINUTES)?.max(0);
 
-        if feed_url.trim().is_empty() {
-            return Err(Error::from("FEED_URL is not provided"));
+        // FEED_URLS takes a comma separated or JSON list of feeds. FEED_URL is still honoured for single feed
+        // deployments. Both may be empty when all feeds are registered as FeedConfig items in DynamoDB.
+        let mut feed_urls = match env::optional("FEED_URLS") {
+            Some(feed_urls) => parse_feed_urls(&feed_urls)?,
+            None => Vec::new(),
+        };
+
+        if let Some(feed_url) = env::optional("FEED_URL") {
+            if !feed_urls.contains(&feed_url) {
+                feed_urls.insert(0, feed_url);
+            }
         }
 
+        let max_concurrent_feeds =
+            env::parse_or("MAX_CONCURRENT_FEEDS", DEFAULT_MAX_CONCURRENT_FEEDS)?.max(1);
+
+        let keyword_filter = KeywordFilter::new(
+            parse_list(&env::optional("INCLUDE_KEYWORDS").unwrap_or_default()),
+            parse_list(&env::optional("EXCLUDE_KEYWORDS").unwrap_or_default()),
+        );
+
+        let language_filter = LanguageFilter::new(&parse_list(
+            &env::optional("ALLOWED_LANGUAGES").unwrap_or_default(),
+        ));
+
+        let content_length_filter = ContentLengthFilter::new(
+            env::parse_or("MIN_TITLE_LENGTH", 0)?,
+            env::parse_or("MIN_DESCRIPTION_LENGTH", 0)?,
+        );
+
+        // Default category lists for feeds that don't define their own in their FeedConfig
+        let include_categories =
+            parse_list(&env::optional("INCLUDE_CATEGORIES").unwrap_or_default());
+        let exclude_categories =
+            parse_list(&env::optional("EXCLUDE_CATEGORIES").unwrap_or_default());
+
+        let http = HttpConfig {
+            max_body_bytes: env::parse_or("MAX_FEED_SIZE_BYTES", DEFAULT_MAX_BODY_BYTES)?,
+            max_retries: env::parse_or("FETCH_MAX_RETRIES", DEFAULT_MAX_RETRIES)?,
+            proxy_url: env::optional("FETCH_PROXY_URL"),
+            connect_timeout: StdDuration::from_secs(env::parse_or(
+                "FETCH_CONNECT_TIMEOUT_SECS",
+                DEFAULT_CONNECT_TIMEOUT.as_secs(),
+            )?),
+            read_timeout: StdDuration::from_secs(env::parse_or(
+                "FETCH_READ_TIMEOUT_SECS",
+                DEFAULT_READ_TIMEOUT.as_secs(),
+            )?),
+            min_host_interval: StdDuration::from_millis(env::parse_or(
+                "FETCH_MIN_HOST_INTERVAL_MS",
+                DEFAULT_MIN_HOST_INTERVAL.as_millis() as u64,
+            )?),
+            respect_robots_txt: !env::flag("IGNORE_ROBOTS_TXT"),
+            ..Default::default()
+        };
+
+        // Paged feeds (RFC 5005) are only followed past the first page when this is raised above 1
+        let max_feed_pages = env::parse_or("MAX_FEED_PAGES", DEFAULT_MAX_FEED_PAGES)?.max(1);
+
+        // How many days ahead the events of ical feeds are announced
+        let event_horizon_days =
+            env::parse_or("EVENT_HORIZON_DAYS", DEFAULT_EVENT_HORIZON_DAYS)?.max(0);
+
+        // Items are posted oldest first so multi-item runs read chronologically on the timeline. ITEM_ORDER=feed
+        // keeps the order the feeds list them in instead.
+        let oldest_first = match env::optional("ITEM_ORDER").as_deref() {
+            None | Some("oldest_first") => true,
+            Some("feed") => false,
+            Some(other) => {
+                return Err(Error::from(format!(
+                    "ITEM_ORDER must be oldest_first or feed, got {}",
+                    other
+                )))
+            }
+        };
+
+        let accept_undated_items = env::flag("ACCEPT_UNDATED_ITEMS");
+
+        // Stories syndicated by several feeds are only posted once: items are compared by URL and title against
+        // what was posted in this many hours. 0 turns this off, as does a title similarity of 0 for titles alone.
+        let duplicate_window_hours =
+            env::parse_or("DUPLICATE_WINDOW_HOURS", DEFAULT_DUPLICATE_WINDOW_HOURS)?
+                .clamp(0, MAX_DUPLICATE_WINDOW_HOURS);
+        let duplicate_title_similarity = env::parse_or(
+            "DUPLICATE_TITLE_SIMILARITY",
+            DEFAULT_DUPLICATE_TITLE_SIMILARITY,
+        )?;
+        // Follow-ups to a story quote its earlier post, so readers see the story unfold. They are looked up among
+        // the same recent posts as duplicates, so this too is off when DUPLICATE_WINDOW_HOURS is 0
+        let related_title_similarity = if env::flag("QUOTE_RELATED_POSTS") {
+            Some(env::parse_or(
+                "RELATED_TITLE_SIMILARITY",
+                DEFAULT_RELATED_TITLE_SIMILARITY,
+            )?)
+        } else {
+            None
+        };
+
+        // Items fetched outside the posting window wait for the first run inside it, and those over an account's
+        // daily cap for the next day
+        let timezone_name = env::optional("POSTING_TIMEZONE").unwrap_or_else(|| "UTC".to_string());
+        let posting_window = env::optional("POSTING_WINDOW")
+            .map(|window| PostingWindow::parse(&window, &timezone_name))
+            .transpose()?;
+        let max_posts_per_day = Some(env::parse_or("MAX_POSTS_PER_DAY", 0)?).filter(|max| *max > 0);
+        let timezone = timezone_name
+            .parse::<Tz>()
+            .map_err(|_| Error::from(format!("Unknown timezone {:?}", timezone_name)))?;
+
         Ok(Self {
             dynamodb_table_name,
             max_age_hours,
-            feed_url,
+            window_overlap_minutes,
+            feed_urls,
+            max_concurrent_feeds,
+            keyword_filter,
+            language_filter,
+            content_length_filter,
+            include_categories,
+            exclude_categories,
+            http,
+            max_feed_pages,
+            event_horizon_days,
+            duplicate_window_hours,
+            duplicate_title_similarity,
+            related_title_similarity,
+            oldest_first,
+            accept_undated_items,
+            posting_window,
+            max_posts_per_day,
+            timezone,
+            digest_mode: env::flag("DIGEST_MODE"),
         })
     }
 }
 
-#[instrument(skip(event, repo, config))]
+/// Parses FEED_URLS, which is either a JSON array of strings or a comma separated list.
+fn parse_feed_urls(value: &str) -> Result<Vec<String>, Error> {
+    let value = value.trim();
+    let feed_urls: Vec<String> = if value.starts_with('[') {
+        serde_json::from_str(value)
+            .context("Failed to parse FEED_URLS as a JSON list")
+            .map_err(Error::from)?
+    } else {
+        value.split(',').map(String::from).collect()
+    };
+
+    let mut result: Vec<String> = Vec::new();
+    for feed_url in feed_urls {
+        let feed_url = feed_url.trim().to_string();
+        if !feed_url.is_empty() && !result.contains(&feed_url) {
+            result.push(feed_url);
+        }
+    }
+    Ok(result)
+}
+
+#[instrument(skip(event, repo, http, secrets_client, config))]
 async fn get_rss_items(
     event: LambdaEvent<CloudWatchEvent>,
     repo: &DynamoRepository,
+    http: &HttpClient,
+    secrets_client: &SecretsManagerClient,
     config: &Config,
 ) -> Result<Output, Error> {
     tracing::info!("Payload: {:?}", event.payload);
@@ -81,59 +317,865 @@ async fn get_rss_items(
         .ok_or_else(|| Error::from("Execution ID not provided in the event payload"))?;
     tracing::info!("Execution id: {:?}", execution_id);
 
-    let content = reqwest::get(&config.feed_url)
+    let started_at = Utc::now().timestamp();
+    let window_start = match repo
+        .get_last_successful_run()
+        .await
+        .context("Failed to get last successful run")?
+    {
+        Some(last_successful_run) => last_successful_run - config.window_overlap_minutes * 60,
+        None => started_at - config.max_age_hours * 3600,
+    };
+    tracing::info!("Selecting items published since {}", window_start);
+
+    let feeds = load_feeds(repo, config).await?;
+    if feeds.is_empty() {
+        return Err(Error::from(
+            "No feeds configured. Set FEED_URL / FEED_URLS or register FeedConfig items",
+        ));
+    }
+
+    let results: Vec<(String, anyhow::Result<FeedItems>)> = stream::iter(feeds.iter())
+        .map(|feed| {
+            let execution_id = execution_id.as_str();
+            async move {
+                let result = fetch_feed_items(
+                    feed,
+                    execution_id,
+                    window_start,
+                    repo,
+                    http,
+                    secrets_client,
+                    config,
+                )
+                .await;
+                (feed.feed_url.clone(), result)
+            }
+        })
+        .buffered(config.max_concurrent_feeds)
+        .collect()
+        .await;
+
+    // A single broken feed shouldn't stop the others from being bridged, so failures are only fatal when every
+    // feed failed.
+    let mut execution_items = Vec::new();
+    let mut changed_feeds = Vec::new();
+    let mut polled_feeds = Vec::new();
+    let mut failed_feeds = 0;
+    for (feed_url, result) in results {
+        match result {
+            Ok(feed_items) => {
+                tracing::info!(
+                    "Got {} items from feed {}",
+                    feed_items.items.len(),
+                    feed_url
+                );
+                execution_items.extend(feed_items.items);
+                if let Some(polled_at) = feed_items.polled_at {
+                    polled_feeds.push((feed_items.feed_url.clone(), polled_at));
+                }
+                if let Some(content_hash) = feed_items.changed_content_hash {
+                    changed_feeds.push(ChangedFeed {
+                        feed_url: feed_items.feed_url,
+                        content_hash,
+                    });
+                }
+            }
+            Err(e) => {
+                tracing::error!("Failed to process feed {}: {:?}", feed_url, e);
+                failed_feeds += 1;
+            }
+        }
+    }
+
+    if failed_feeds == feeds.len() {
+        return Err(Error::from("Failed to process all configured feeds"));
+    }
+
+    let mut execution_items = dedupe_by_guid(execution_items);
+
+    if config.duplicate_window_hours > 0 {
+        let since = Utc::now().timestamp() - config.duplicate_window_hours * 3600;
+        match repo.list_recent_posts(since).await {
+            Ok(recent_posts) => {
+                execution_items = suppress_near_duplicates(
+                    execution_items,
+                    &recent_posts,
+                    config.duplicate_title_similarity,
+                );
+                if let Some(threshold) = config.related_title_similarity {
+                    link_related_posts(&mut execution_items, &recent_posts, threshold);
+                }
+            }
+            // Without the recent posts only exact duplicates are caught, which is no reason to post nothing
+            Err(e) => tracing::warn!(
+                "Failed to list recent posts, not checking for near duplicates: {:?}",
+                e
+            ),
+        }
+    }
+
+    if config.oldest_first {
+        // Stable sort, so items with the same (or no) date keep their feed order. Undated items go last.
+        execution_items.sort_by_key(|item| {
+            let pub_date = item.pub_date.as_deref().and_then(feed::parse_date);
+            (pub_date.is_none(), pub_date)
+        });
+    }
+
+    // Store items in DynamoDB using bulk API
+    repo.create_execution_items(&execution_items).await?;
+
+    let now = Utc::now();
+    let items: Vec<HeldItem> = execution_items
+        .iter()
+        .map(|item| HeldItem {
+            execution_id: item.execution_id.clone(),
+            guid: item.guid.clone(),
+            account: item.bluesky_secret.clone(),
+            held_at: now.timestamp(),
+        })
+        .collect();
+
+    // The run still succeeds outside the posting window and in digest mode, as the items are safely held rather than
+    // lost
+    let items = match &config.posting_window {
+        _ if config.digest_mode => {
+            tracing::info!("Digest mode, holding {} items for the digest", items.len());
+            repo.hold_items(&items)
+                .await
+                .context("Failed to hold items")?;
+            Vec::new()
+        }
+        Some(window) if !window.contains(now) => {
+            tracing::info!("Outside the posting window, holding {} items", items.len());
+            repo.hold_items(&items)
+                .await
+                .context("Failed to hold items")?;
+            Vec::new()
+        }
+        _ => with_held_items(items, &execution_id, repo).await?,
+    };
+    let items = match config.max_posts_per_day {
+        Some(max_posts_per_day) if !items.is_empty() => {
+            cap_daily_posts(items, max_posts_per_day, config.timezone, repo).await?
+        }
+        _ => items,
+    };
+
+    let item_identifiers = items
+        .into_iter()
+        .map(|item| ItemIdentifier {
+            execution_id: item.execution_id,
+            guid: item.guid,
+        })
+        .collect();
+
+    for (feed_url, polled_at) in polled_feeds {
+        repo.update_feed_last_polled(&feed_url, polled_at)
+            .await
+            .with_context(|| format!("Failed to store poll time for feed {}", feed_url))?;
+    }
+
+    Ok(Output {
+        item_identifiers,
+        started_at: (failed_feeds == 0).then_some(started_at),
+        changed_feeds,
+    })
+}
+
+/// Combines the feeds configured in the environment with the FeedConfig items stored in DynamoDB.
+///
+/// A stored FeedConfig takes precedence over an environment feed with the same URL, since it may carry extra
+/// settings.
+async fn load_feeds(repo: &DynamoRepository, config: &Config) -> Result<Vec<FeedConfig>, Error> {
+    let stored_feeds = repo
+        .list_feed_configs()
+        .await
+        .context("Failed to list feed configs from DynamoDB")?;
+
+    let mut feeds = Vec::new();
+    for feed_url in &config.feed_urls {
+        if !stored_feeds.iter().any(|f| &f.feed_url == feed_url) {
+            feeds.push(FeedConfig::new(feed_url.clone())?);
+        }
+    }
+    feeds.extend(stored_feeds);
+
+    Ok(feeds)
+}
+
+/// Fetches and parses the feed document of a configured feed.
+///
+/// The configured URL may point to an HTML page instead of a feed, in which case the feed advertised by the page is
+/// discovered and its URL is cached in the FeedState so later runs can fetch it directly. Feeds that answer with a
+/// permanent redirect get the new location cached the same way, so moved feeds keep working.
+///
+/// # Returns
+///
+/// The raw feed body along with the parsed channel.
+async fn fetch_channel(
+    feed_url: &str,
+    resolved_url: Option<&str>,
+    credentials: Option<&Credentials>,
+    repo: &DynamoRepository,
+    http: &HttpClient,
+) -> anyhow::Result<(Vec<u8>, Channel)> {
+    // The credentials belong to the configured site, so a discovered or moved feed elsewhere doesn't get them
+    let credentials_for = |url: &str| credentials.filter(|_| same_origin(url, feed_url));
+
+    if let Some(resolved_url) = resolved_url {
+        let fetched = http
+            .fetch_with_credentials(resolved_url, credentials_for(resolved_url))
+            .await;
+        match fetched.and_then(|fetched| {
+            let channel = feed::read_channel(&fetched.body)?;
+            Ok((fetched, channel))
+        }) {
+            Ok((fetched, channel)) => {
+                if let Some(moved_to) = &fetched.permanent_redirect {
+                    remember_feed_location(feed_url, moved_to, repo).await?;
+                }
+                return Ok((fetched.body, channel));
+            }
+            Err(e) => tracing::warn!(
+                "Cached feed url {} for {} no longer works, rediscovering: {:?}",
+                resolved_url,
+                feed_url,
+                e
+            ),
+        }
+    }
+
+    let fetched = http.fetch_with_credentials(feed_url, credentials).await?;
+    let page_url = fetched.permanent_redirect.as_deref().unwrap_or(feed_url);
+    let error = match feed::read_channel(&fetched.body) {
+        Ok(channel) => {
+            match &fetched.permanent_redirect {
+                Some(moved_to) if resolved_url != Some(moved_to.as_str()) => {
+                    remember_feed_location(feed_url, moved_to, repo).await?
+                }
+                _ => {}
+            }
+            return Ok((fetched.body, channel));
+        }
+        Err(e) => e,
+    };
+
+    // The configured URL turned out to be a web page rather than a feed, so looking into it needs robots.txt's blessing
+    if !http.is_allowed(page_url).await {
+        tracing::warn!(
+            "Not looking for a feed link in {} as robots.txt disallows it",
+            page_url
+        );
+        return Err(error);
+    }
+
+    let Some(discovered_url) =
+        html::discover_feed_url(&String::from_utf8_lossy(&fetched.body), page_url)
+    else {
+        return Err(error);
+    };
+    tracing::info!("Discovered feed {} from page {}", discovered_url, page_url);
+
+    let fetched = http
+        .fetch_with_credentials(&discovered_url, credentials_for(&discovered_url))
+        .await?;
+    let channel = feed::read_channel(&fetched.body)?;
+
+    let discovered_url = fetched.permanent_redirect.unwrap_or(discovered_url);
+    if resolved_url != Some(discovered_url.as_str()) {
+        repo.update_feed_resolved_url(feed_url, &discovered_url)
+            .await
+            .with_context(|| format!("Failed to cache discovered feed url for {}", feed_url))?;
+    }
+
+    Ok((fetched.body, channel))
+}
+
+/// Reads the credentials of an authenticated feed from Secrets Manager.
+async fn load_credentials(
+    secrets_client: &SecretsManagerClient,
+    secret_name: &str,
+) -> anyhow::Result<Credentials> {
+    let secret = secrets_client
+        .get_secret_value()
+        .secret_id(secret_name)
+        .send()
+        .await
+        .with_context(|| format!("Failed to retrieve credentials secret {}", secret_name))?;
+
+    let secret_string = secret
+        .secret_string()
+        .with_context(|| format!("Credentials secret {} is empty", secret_name))?;
+    Credentials::from_secret_json(secret_string)
+}
+
+/// Checks whether two URLs share scheme, host and port.
+fn same_origin(a: &str, b: &str) -> bool {
+    match (Url::parse(a), Url::parse(b)) {
+        (Ok(a), Ok(b)) => a.origin() == b.origin(),
+        _ => false,
+    }
+}
+
+/// Caches the location a feed has permanently moved to, so later runs fetch it directly.
+async fn remember_feed_location(
+    feed_url: &str,
+    moved_to: &str,
+    repo: &DynamoRepository,
+) -> anyhow::Result<()> {
+    tracing::warn!(
+        "Feed {} has permanently moved to {}, fetching it from there from now on. Consider updating the configuration",
+        feed_url,
+        moved_to
+    );
+    repo.update_feed_resolved_url(feed_url, moved_to)
+        .await
+        .with_context(|| format!("Failed to cache new location of feed {}", feed_url))
+}
+
+/// Follows the `rel="next"` links of a paged feed, appending the items of up to `max_pages - 1` further pages to
+/// the channel. A page that fails to load ends the walk without failing the feed.
+async fn fetch_next_pages(
+    channel: &mut Channel,
+    page_url: &str,
+    http: &HttpClient,
+    max_pages: usize,
+) {
+    let mut visited = vec![page_url.to_string()];
+    let mut next_url = feed::next_page_url(channel, page_url);
+
+    while let Some(url) = next_url.take() {
+        if visited.len() >= max_pages || visited.contains(&url) {
+            break;
+        }
+
+        let page = match http.get_bytes(&url).await {
+            Ok(content) => feed::read_channel(&content),
+            Err(e) => Err(e),
+        };
+        match page {
+            Ok(mut page) => {
+                tracing::info!(
+                    "Fetched {} items from feed page {}",
+                    page.items().len(),
+                    url
+                );
+                next_url = feed::next_page_url(&page, &url);
+                channel.items.append(&mut page.items);
+            }
+            Err(e) => tracing::warn!("Failed to fetch feed page {}: {:?}", url, e),
+        }
+        visited.push(url);
+    }
+}
+
+/// Fetches a sitemap, along with the most recently modified child sitemaps if it is a sitemap index.
+///
+/// # Returns
+///
+/// The raw sitemap bodies, for change detection, along with the pages they list.
+async fn fetch_sitemap(
+    feed_url: &str,
+    credentials: Option<&Credentials>,
+    http: &HttpClient,
+) -> anyhow::Result<(Vec<u8>, Vec<SitemapUrl>)> {
+    let fetched = http.fetch_with_credentials(feed_url, credentials).await?;
+    let mut content = fetched.body;
+    let index = sitemap::parse_sitemap(&content)
+        .with_context(|| format!("Failed to parse sitemap {}", feed_url))?;
+
+    let mut urls = index.urls;
+    let mut children = index.sitemaps;
+    children.sort_by_key(|child| std::cmp::Reverse(child.lastmod));
+    for child in children.iter().take(MAX_CHILD_SITEMAPS) {
+        let credentials = credentials.filter(|_| same_origin(&child.loc, feed_url));
+        let child_sitemap = match http.fetch_with_credentials(&child.loc, credentials).await {
+            Ok(fetched) => {
+                let child_sitemap = sitemap::parse_sitemap(&fetched.body);
+                content.extend_from_slice(&fetched.body);
+                child_sitemap
+            }
+            Err(e) => Err(e),
+        };
+        match child_sitemap {
+            Ok(child_sitemap) => urls.extend(child_sitemap.urls),
+            Err(e) => tracing::warn!("Failed to fetch sitemap {}: {:?}", child.loc, e),
+        }
+    }
+
+    Ok((content, urls))
+}
+
+/// Fetches the pages of a sitemap modified since `cutoff` that haven't been posted yet, and turns them into items.
+///
+/// Pages that can't be fetched, or that robots.txt keeps the bridge out of, are skipped.
+async fn fetch_sitemap_pages(
+    mut urls: Vec<SitemapUrl>,
+    cutoff: i64,
+    repo: &DynamoRepository,
+    http: &HttpClient,
+    accept_undated_items: bool,
+) -> anyhow::Result<Vec<rss::Item>> {
+    urls.retain(|url| match url.lastmod {
+        Some(lastmod) => lastmod.timestamp() >= cutoff,
+        None => accept_undated_items,
+    });
+    urls.sort_by_key(|url| std::cmp::Reverse(url.lastmod));
+
+    let mut items = Vec::new();
+    let mut seen = Vec::new();
+    for url in urls {
+        if items.len() >= MAX_SITEMAP_PAGES {
+            break;
+        }
+        // Sitemap indexes may list a page in more than one child sitemap
+        if seen.contains(&url.loc) {
+            continue;
+        }
+        seen.push(url.loc.clone());
+        // The item's identity will be the page URL, normalized the same way feed::item_identity does
+        if repo.record_item_exists(&dedupe_key(&url.loc)).await? {
+            continue;
+        }
+        match http.get_page_text(&url.loc).await {
+            Ok(page) => items.push(sitemap::page_item(
+                &url,
+                html::page_metadata(&page, &url.loc),
+            )),
+            Err(e) => tracing::warn!("Failed to fetch sitemap page {}: {:?}", url.loc, e),
+        }
+    }
+
+    Ok(items)
+}
+
+/// Returns the TTL of the ExecutionItems stored now, as a Unix timestamp.
+fn execution_item_ttl() -> i64 {
+    (Utc::now() + Duration::hours(EXECUTION_ITEM_TTL_HOURS)).timestamp()
+}
+
+/// Puts the held items in front of the items of the run, oldest first, to be posted first.
+///
+/// Their ExecutionItems are copied into this execution, with a fresh TTL, which then claims them as its own like the
+/// rest. Held items
+/// that were posted already (by a run that failed before releasing them, say) are released instead. Those fetched
+/// again in this run keep their place, with the run's fresh copy.
+async fn with_held_items(
+    mut items: Vec<HeldItem>,
+    execution_id: &str,
+    repo: &DynamoRepository,
+) -> anyhow::Result<Vec<HeldItem>> {
+    let mut held_items = Vec::new();
+    let mut moved_items = Vec::new();
+    for mut held_item in repo
+        .list_held_items()
         .await
-        .with_context(|| format!("Failed to fetch RSS feed from {}", config.feed_url))
-        .map_err(Error::from)?
-        .text()
+        .context("Failed to list held items")?
+    {
+        if repo.record_item_exists(&held_item.guid).await? {
+            repo.release_held_item(&held_item.guid).await?;
+            continue;
+        }
+        if let Some(i) = items.iter().position(|item| item.guid == held_item.guid) {
+            items.remove(i);
+        } else if held_item.execution_id != execution_id {
+            match repo
+                .get_execution_item(&held_item.execution_id, &held_item.guid)
+                .await
+            {
+                Ok(mut execution_item) => {
+                    execution_item.execution_id = execution_id.to_string();
+                    execution_item.ttl = Some(execution_item_ttl());
+                    moved_items.push(execution_item);
+                }
+                Err(e) => {
+                    tracing::warn!("Dropping held item {}: {:?}", held_item.guid, e);
+                    repo.release_held_item(&held_item.guid).await?;
+                    continue;
+                }
+            }
+        }
+        held_item.execution_id = execution_id.to_string();
+        held_items.push(held_item);
+    }
+    repo.create_execution_items(&moved_items)
         .await
-        .context("Failed to read RSS feed content")
-        .map_err(Error::from)?;
+        .context("Failed to copy held items")?;
 
-    let channel = Channel::read_from(content.as_bytes())
-        .context("Failed to parse RSS feed")
-        .map_err(Error::from)?;
+    if !held_items.is_empty() {
+        tracing::info!("Posting {} held items", held_items.len());
+    }
+    held_items.extend(items);
+    Ok(held_items)
+}
 
-    let ttl = Utc::now() + Duration::hours(24);
-    let ttl_timestamp = ttl.timestamp();
+/// Holds the items over the daily post cap of their account until a later day, which posts them first.
+///
+/// Posts are counted from the RecentPosts of the day. Items posted already don't count towards the cap, whether
+/// check-dynamodb skips them or posts them again as updates.
+async fn cap_daily_posts(
+    items: Vec<HeldItem>,
+    max_posts_per_day: usize,
+    timezone: Tz,
+    repo: &DynamoRepository,
+) -> anyhow::Result<Vec<HeldItem>> {
+    let mut posted_today: HashMap<Option<String>, usize> = HashMap::new();
+    for post in repo
+        .list_recent_posts(start_of_day(Utc::now(), timezone))
+        .await
+        .context("Failed to list today's posts")?
+    {
+        *posted_today.entry(post.account).or_default() += 1;
+    }
 
-    let (execution_items, item_identifiers): (Vec<ExecutionItem>, Vec<ItemIdentifier>) = channel
+    let mut posted = HashSet::new();
+    for item in &items {
+        if repo.record_item_exists(&item.guid).await? {
+            posted.insert(item.guid.clone());
+        }
+    }
+
+    let (items, over_cap) = apply_daily_cap(items, &posted_today, max_posts_per_day, |item| {
+        !posted.contains(&item.guid)
+    });
+    if !over_cap.is_empty() {
+        tracing::info!(
+            "Daily post cap of {} reached, holding {} items for a later day",
+            max_posts_per_day,
+            over_cap.len()
+        );
+        repo.hold_items(&over_cap)
+            .await
+            .context("Failed to hold items")?;
+    }
+    Ok(items)
+}
+
+/// Fetches a single feed and converts its recent items into ExecutionItems.
+///
+/// Items are selected by the feed's high water mark once something from it has been posted, and otherwise by
+/// whether they were published after `window_start`.
+///
+/// Returns no items when the feed body is byte for byte identical to the previous fetch, as everything in it has
+/// already been through the pipeline.
+async fn fetch_feed_items(
+    feed: &FeedConfig,
+    execution_id: &str,
+    window_start: i64,
+    repo: &DynamoRepository,
+    http: &HttpClient,
+    secrets_client: &SecretsManagerClient,
+    config: &Config,
+) -> anyhow::Result<FeedItems> {
+    let feed_url = feed.feed_url.as_str();
+    let feed_state = repo
+        .get_feed_state(feed_url)
+        .await
+        .with_context(|| format!("Failed to get feed state for {}", feed_url))?
+        .unwrap_or_default();
+
+    let now = Utc::now().timestamp();
+    if let (Some(interval), Some(last_polled)) =
+        (feed.poll_interval_minutes, feed_state.last_polled)
+    {
+        let next_poll = last_polled + i64::from(interval) * 60 - POLL_INTERVAL_SLACK_SECS;
+        if now < next_poll {
+            tracing::info!(
+                "Feed {} is polled every {} minutes and isn't due for another {} seconds, skipping",
+                feed_url,
+                interval,
+                next_poll - now
+            );
+            return Ok(FeedItems {
+                feed_url: feed_url.to_string(),
+                items: Vec::new(),
+                changed_content_hash: None,
+                polled_at: None,
+            });
+        }
+    }
+    let polled_at = feed.poll_interval_minutes.map(|_| now);
+
+    let credentials = match &feed.credentials_secret {
+        Some(secret_name) => Some(load_credentials(secrets_client, secret_name).await?),
+        None => None,
+    };
+
+    let feed_type = feed.feed_type()?;
+    let (content, mut channel, sitemap_urls) = match feed_type {
+        FeedType::Rss => fetch_channel(
+            feed_url,
+            feed_state.resolved_url.as_deref(),
+            credentials.as_ref(),
+            repo,
+            http,
+        )
+        .await
+        .map(|(content, channel)| (content, channel, Vec::new()))?,
+        FeedType::Scrape => {
+            if !http.is_allowed(feed_url).await {
+                return Err(anyhow::anyhow!(
+                    "Scraping {} is disallowed by robots.txt",
+                    feed_url
+                ));
+            }
+            let fetched = http
+                .fetch_with_credentials(feed_url, credentials.as_ref())
+                .await?;
+            let channel = scrape::scrape_channel(
+                &String::from_utf8_lossy(&fetched.body),
+                feed_url,
+                &feed.selectors,
+            )
+            .with_context(|| format!("Failed to scrape {}", feed_url))?;
+            (fetched.body, channel, Vec::new())
+        }
+        FeedType::Json => {
+            let fetched = http
+                .fetch_with_credentials(feed_url, credentials.as_ref())
+                .await?;
+            let channel = json_api::json_channel(&fetched.body, feed_url, &feed.selectors)
+                .with_context(|| format!("Failed to read JSON API {}", feed_url))?;
+            (fetched.body, channel, Vec::new())
+        }
+        FeedType::Ical => {
+            let fetched = http
+                .fetch_with_credentials(feed_url, credentials.as_ref())
+                .await?;
+            let channel = ical::ical_channel(
+                &fetched.body,
+                feed.site_url.as_deref().unwrap_or(feed_url),
+                Utc::now(),
+                Duration::days(config.event_horizon_days),
+            )
+            .with_context(|| format!("Failed to read calendar {}", feed_url))?;
+            (fetched.body, channel, Vec::new())
+        }
+        FeedType::Sitemap => {
+            let (content, urls) = fetch_sitemap(feed_url, credentials.as_ref(), http).await?;
+            (content, Channel::default(), urls)
+        }
+    };
+
+    let content_hash = sha256_hex(&content);
+    if feed_state.content_hash.as_deref() == Some(content_hash.as_str()) {
+        tracing::info!(
+            "Feed {} is unchanged since the last fetch, skipping",
+            feed_url
+        );
+        return Ok(FeedItems {
+            feed_url: feed_url.to_string(),
+            items: Vec::new(),
+            changed_content_hash: None,
+            polled_at,
+        });
+    }
+
+    match feed_type {
+        FeedType::Rss => {
+            let page_url = feed_state.resolved_url.as_deref().unwrap_or(feed_url);
+            fetch_next_pages(&mut channel, page_url, http, config.max_feed_pages).await;
+        }
+        FeedType::Sitemap => {
+            let cutoff = feed_state
+                .high_water_mark
+                .map_or(window_start, |mark| mark.min(Utc::now().timestamp()));
+            channel.link = feed_url.to_string();
+            channel.items = fetch_sitemap_pages(
+                sitemap_urls,
+                cutoff,
+                repo,
+                http,
+                config.accept_undated_items,
+            )
+            .await?;
+        }
+        FeedType::Scrape | FeedType::Json | FeedType::Ical => {}
+    }
+
+    let category_filter =
+        if feed.include_categories.is_empty() && feed.exclude_categories.is_empty() {
+            CategoryFilter::new(&config.include_categories, &config.exclude_categories)
+        } else {
+            CategoryFilter::new(&feed.include_categories, &feed.exclude_categories)
+        };
+
+    let preprocessor = Preprocessor::for_feed(feed.preprocessor.as_deref(), feed_url);
+
+    let base_url = feed::base_url(&channel, feed_url);
+    let media_prefix = feed::media_prefix(&channel);
+    let atom_prefix = feed::atom_prefix(&channel);
+
+    // A title or site set on the FeedConfig (e.g. from OPML) is the subscriber's choice, so it wins over the feed's own
+    let feed_title = feed
+        .title
+        .clone()
+        .or_else(|| Some(decode_html_entities(channel.title().trim())).filter(|t| !t.is_empty()));
+    let site_url = feed.site_url.clone().or_else(|| {
+        Some(channel.link().trim())
+            .filter(|l| !l.is_empty())
+            .and_then(|l| html::resolve_url(feed_url, l))
+    });
+
+    let now = Utc::now().timestamp();
+    let ttl_timestamp = execution_item_ttl();
+
+    let execution_items = channel
         .items()
         .iter()
         .filter_map(|item| {
-            let pub_date = item.pub_date()?;
-            let pub_date = DateTime::parse_from_rfc2822(&pub_date).ok()?;
-            let age = Utc::now().signed_duration_since(pub_date);
-
-            if age.num_hours() <= config.max_age_hours {
-                let guid = item.guid()?.value().to_string();
-                Some((
-                    ExecutionItem {
-                        execution_id: execution_id.clone(),
-                        guid: guid.clone(),
-                        title: item.title().map(String::from),
-                        description: item.description().map(String::from),
-                        link: item.link().map(String::from),
-                        summary: None,
-                        ttl: Some(ttl_timestamp),
-                        _type: None,
-                        pub_date: Some(pub_date.to_rfc2822()),
-                    },
-                    ItemIdentifier {
-                        execution_id: execution_id.clone(),
-                        guid,
-                    },
-                ))
-            } else {
-                None
+            let pub_date = feed::item_pub_date(item, &atom_prefix);
+            match pub_date {
+                // Once something from the feed has been posted, everything published since is a candidate, however
+                // long ago the last run was. Items from the same second as the mark are left to the dedupe
+                Some(pub_date) => {
+                    if !feed::is_new_enough(
+                        pub_date.timestamp(),
+                        feed_state.high_water_mark,
+                        window_start,
+                        now,
+                    ) {
+                        return None;
+                    }
+                }
+                // Undated items can't be aged out, so accepting them relies on the RecordItem dedupe to post
+                // each one only once
+                None if config.accept_undated_items => {}
+                None => return None,
             }
-        })
-        .unzip();
 
-    // Store items in DynamoDB using bulk API
-    repo.create_execution_items(&execution_items).await?;
+            let link = feed::item_link(item, &base_url);
+            let mut content = match preprocessor {
+                Some(preprocessor) => {
+                    preprocessor.apply(item.title(), item.description(), link.as_deref())
+                }
+                None => ItemContent {
+                    description: item.description().map(String::from),
+                    link,
+                },
+            };
+            // Campaign parameters would end up in the link card and make the post look like an ad
+            content.link = content.link.as_deref().map(canonical_url);
+
+            if !config
+                .keyword_filter
+                .matches(item.title(), content.description.as_deref())
+            {
+                tracing::info!(
+                    "Skipping item {:?} as it doesn't match the keyword filter",
+                    item.title()
+                );
+                return None;
+            }
+
+            if !config.content_length_filter.is_empty() {
+                let description = content.description.as_deref().map(html::strip_tags);
+                if !config
+                    .content_length_filter
+                    .matches(item.title(), description.as_deref())
+                {
+                    tracing::info!(
+                        "Skipping item {:?} as its title or description is too short",
+                        item.title()
+                    );
+                    return None;
+                }
+            }
+
+            if !config.language_filter.is_empty() {
+                // The text decides; the channel's <language> is the fallback for items too short to tell
+                let text = format!(
+                    "{}\n{}",
+                    item.title().unwrap_or_default(),
+                    html::strip_tags(content.description.as_deref().unwrap_or_default())
+                );
+                let language = detect_language(&text).or(channel.language());
+                if !config.language_filter.matches(language) {
+                    tracing::info!(
+                        "Skipping item {:?} as its language {:?} isn't allowed",
+                        item.title(),
+                        language
+                    );
+                    return None;
+                }
+            }
+
+            let categories: Vec<String> = item
+                .categories()
+                .iter()
+                .map(|c| c.name().trim().to_string())
+                .filter(|c| !c.is_empty())
+                .collect();
+
+            if !category_filter.matches(&categories) {
+                tracing::info!(
+                    "Skipping item {:?} as its categories {:?} don't match the category filter",
+                    item.title(),
+                    categories
+                );
+                return None;
+            }
+
+            let guid = feed::item_identity(item)?;
+            let itunes = feed::item_itunes_metadata(&channel, item);
+            Some(ExecutionItem {
+                execution_id: execution_id.to_string(),
+                guid,
+                title: item.title().map(decode_html_entities),
+                description: content.description.as_deref().map(decode_html_entities),
+                link: content.link,
+                summary: None,
+                ttl: Some(ttl_timestamp),
+                _type: None,
+                pub_date: pub_date.map(|pub_date| pub_date.to_rfc2822()),
+                feed_url: Some(feed_url.to_string()),
+                categories,
+                image_url: feed::item_image_url(item, &media_prefix)
+                    .or(itunes.image)
+                    .and_then(|image_url| html::resolve_url(&base_url, &image_url)),
+                subtitle: itunes.subtitle,
+                episode: itunes.episode,
+                season: itunes.season,
+                author: feed::item_author(item, &atom_prefix).map(|a| decode_html_entities(&a)),
+                feed_title: feed_title.clone(),
+                site_url: site_url.clone(),
+                article_text: None,
+                image_urls: if feed.embed_images {
+                    feed::item_image_urls(item, &media_prefix)
+                        .into_iter()
+                        .filter_map(|image_url| html::resolve_url(&base_url, &image_url))
+                        .collect()
+                } else {
+                    Vec::new()
+                },
+                video_url: feed::item_video_url(item, MAX_VIDEO_BYTES as u64)
+                    .and_then(|video_url| html::resolve_url(&base_url, &video_url)),
+                text_only: feed.text_only,
+                prefix: feed.prefix.clone(),
+                hashtags: feed.hashtags.clone(),
+                labels: feed.labels.clone(),
+                language: feed.language.clone(),
+                bluesky_secret: feed.bluesky_secret.clone(),
+                reply_control: feed.reply_control.clone(),
+                ai_max_tokens: feed.ai_max_tokens,
+                ai_temperature: feed.ai_temperature,
+                ai_top_p: feed.ai_top_p,
+                content_hash: Some(feed::item_content_hash(item)),
+                update_of_uri: None,
+                update_of_cid: None,
+                quote_guid: None,
+            })
+        })
+        .collect();
 
-    Ok(Output { item_identifiers })
+    Ok(FeedItems {
+        feed_url: feed_url.to_string(),
+        items: execution_items,
+        changed_content_hash: Some(content_hash),
+        polled_at,
+    })
 }
 
 #[tokio::main]
@@ -147,9 +1189,11 @@ async fn main() -> Result<(), Error> {
     let aws_config = aws_config::load_defaults(BehaviorVersion::latest()).await;
     let dynamodb_client = Client::new(&aws_config);
     let repo = DynamoRepository::new(dynamodb_client, config.dynamodb_table_name.clone());
+    let http = HttpClient::new(config.http.clone()).expect("Failed to build HTTP client");
+    let secrets_client = SecretsManagerClient::new(&aws_config);
 
     run(service_fn(|event: LambdaEvent<CloudWatchEvent>| {
-        get_rss_items(event, &repo, &config)
+        get_rss_items(event, &repo, &http, &secrets_client, &config)
     }))
     .await
 }
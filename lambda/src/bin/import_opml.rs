@@ -0,0 +1,57 @@
+use anyhow::{anyhow, Context, Result};
+use aws_config::BehaviorVersion;
+use aws_sdk_dynamodb::Client;
+use rss_bluesky_bridge::{opml::parse_opml, repository::DynamoRepository};
+use tracing_subscriber::EnvFilter;
+
+/// Registers every feed in an OPML file as a FeedConfig in the DynamoDB table.
+///
+/// This is a command line tool rather than a lambda. Run it with the same AWS credentials used for deployment:
+///
+/// ```bash
+/// DYNAMODB_TABLE_NAME=<table> cargo run --bin import-opml -- subscriptions.opml
+/// ```
+#[tokio::main]
+async fn main() -> Result<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::from_default_env())
+        .init();
+
+    let path = std::env::args()
+        .nth(1)
+        .ok_or_else(|| anyhow!("Usage: import-opml <file.opml>"))?;
+
+    let dynamodb_table_name = std::env::var("DYNAMODB_TABLE_NAME")
+        .context("DYNAMODB_TABLE_NAME environment variable not set")?;
+
+    if dynamodb_table_name.trim().is_empty() {
+        return Err(anyhow!("DYNAMODB_TABLE_NAME cannot be empty"));
+    }
+
+    let content =
+        std::fs::read(&path).with_context(|| format!("Failed to read OPML file {}", path))?;
+    let feed_configs = parse_opml(&content)?;
+
+    if feed_configs.is_empty() {
+        tracing::warn!("No feeds found in {}", path);
+        return Ok(());
+    }
+
+    for feed_config in &feed_configs {
+        tracing::info!(
+            "Registering feed {} ({})",
+            feed_config.feed_url,
+            feed_config.title.as_deref().unwrap_or("untitled")
+        );
+    }
+
+    let aws_config = aws_config::load_defaults(BehaviorVersion::latest()).await;
+    let repo = DynamoRepository::new(Client::new(&aws_config), dynamodb_table_name);
+
+    repo.create_feed_configs(&feed_configs)
+        .await
+        .context("Failed to store feed configs in DynamoDB")?;
+
+    tracing::info!("Imported {} feeds from {}", feed_configs.len(), path);
+    Ok(())
+}
@@ -0,0 +1,38 @@
+use anyhow::{anyhow, Context, Result};
+use rss_bluesky_bridge::http::{HttpClient, HttpConfig};
+use rss_bluesky_bridge::lint::lint_feed;
+use tracing_subscriber::EnvFilter;
+
+/// Fetches a feed and prints a JSON report of the problems that affect how the bridge would process it.
+///
+/// This is a command line tool rather than a lambda, meant to be run before onboarding a new feed:
+///
+/// ```bash
+/// cargo run --bin lint-feed -- https://example.com/feed.xml
+/// ```
+///
+/// The exit code is 1 when the report contains errors.
+#[tokio::main]
+async fn main() -> Result<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::from_default_env())
+        .init();
+
+    let feed_url = std::env::args()
+        .nth(1)
+        .ok_or_else(|| anyhow!("Usage: lint-feed <feed-url>"))?;
+
+    let http = HttpClient::new(HttpConfig::default())?;
+    let content = http
+        .get_bytes(&feed_url)
+        .await
+        .with_context(|| format!("Failed to fetch feed {}", feed_url))?;
+
+    let report = lint_feed(&feed_url, &content);
+    println!("{}", serde_json::to_string_pretty(&report)?);
+
+    if report.has_errors() {
+        std::process::exit(1);
+    }
+    Ok(())
+}
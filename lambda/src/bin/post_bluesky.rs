@@ -1,25 +1,69 @@
 use ::tracing::instrument;
 use anyhow::Context;
+use atrium_api::app::bsky::embed::defs::{AspectRatio, AspectRatioData};
 use atrium_api::app::bsky::embed::external::External;
 use atrium_api::app::bsky::embed::external::ExternalData;
 use atrium_api::app::bsky::embed::external::Main;
 use atrium_api::app::bsky::embed::external::MainData;
-use atrium_api::app::bsky::feed::post::RecordEmbedRefs;
-use atrium_api::types::Union;
+use atrium_api::app::bsky::embed::{images, record, record_with_media, video};
+use atrium_api::app::bsky::feed::post::{self, RecordEmbedRefs, RecordLabelsRefs, ReplyRefData};
+use atrium_api::com::atproto::identity::resolve_handle;
+use atrium_api::com::atproto::label::defs::{SelfLabelData, SelfLabelsData};
+use atrium_api::com::atproto::repo::{get_record, strong_ref};
+use atrium_api::types::string::{Datetime, Handle, Language};
+use atrium_api::types::{BlobRef, TryFromUnknown, Union};
 use aws_config::BehaviorVersion;
+use aws_sdk_bedrockruntime::Client as BedrockClient;
 use aws_sdk_dynamodb::Client as DynamoDbClient;
 use aws_sdk_secretsmanager::Client as SecretsManagerClient;
+use bsky_sdk::record::Record;
 use bsky_sdk::rich_text::RichText;
 use bsky_sdk::BskyAgent;
+use chrono::Utc;
 use lambda_runtime::{run, service_fn, tracing, Error, LambdaEvent};
-use rss_bluesky_bridge::models::ItemIdentifier;
+use rss_bluesky_bridge::alt_text::{
+    alt_text_request, image_media_type, parse_alt_text, DEFAULT_ALT_TEXT_MODEL_ID,
+};
+use rss_bluesky_bridge::bluesky::{self, ReplyControl, RetryingClient, MAX_VIDEO_BYTES};
+use rss_bluesky_bridge::content_warnings::ContentWarningRules;
+use rss_bluesky_bridge::env;
+use rss_bluesky_bridge::filters::parse_list;
+use rss_bluesky_bridge::github;
+use rss_bluesky_bridge::html::{page_metadata, strip_tags};
+use rss_bluesky_bridge::http::{HttpClient, HttpConfig, DEFAULT_MIN_HOST_INTERVAL};
+use rss_bluesky_bridge::image_utils::{fit_image, image_dimensions, video_dimensions};
+use rss_bluesky_bridge::language::detect_language;
+use rss_bluesky_bridge::models::{
+    CachedBlob, ExecutionItem, ItemIdentifier, UpdateMode, UPDATE_PREFIX,
+};
+use rss_bluesky_bridge::poster::{PostResult, Poster};
 use rss_bluesky_bridge::repository::DynamoRepository;
-use rss_bluesky_bridge::text_utils::truncate_to_word;
+use rss_bluesky_bridge::text_utils::template::{
+    parse_variants, pick_variant, Field, PostTemplate, TemplateVariant,
+};
+use rss_bluesky_bridge::text_utils::{
+    append_hashtags, episode_label, hashtag, link_display_text, record_tags, sha256_hex,
+    split_into_posts, static_hashtags, truncate_to_word, MAX_POST_GRAPHEMES,
+};
 use serde::{Deserialize, Serialize};
-use std::env;
+use std::collections::HashMap;
+use std::sync::{Mutex, PoisonError};
+use std::time::Duration;
 use tracing_subscriber::EnvFilter;
+use unicode_segmentation::UnicodeSegmentation;
 
-const MAX_BSKY_GRAPHEMES: usize = 300; //accommodates the two new lines we add at end
+const DEFAULT_RELEASE_NOTES_GRAPHEMES: usize = 200;
+const DEFAULT_MAX_HASHTAGS: usize = 3;
+const DEFAULT_MAX_THREAD_POSTS: usize = 4;
+const DEFAULT_CARD_DESCRIPTION_GRAPHEMES: usize = 500;
+const DEFAULT_MAX_BACKDATE_HOURS: i64 = 24;
+const DEFAULT_BLOB_CACHE_DAYS: i64 = 7;
+/// Bluesky rejects images (and link card thumbnails) larger than this.
+const MAX_IMAGE_BYTES: usize = 1_000_000;
+/// The most images a post can hold.
+const MAX_IMAGES: usize = 4;
+/// Self-labels Bluesky acts on. Others are posted too, but no app will hide anything for them.
+const KNOWN_SELF_LABELS: [&str; 4] = ["sexual", "nudity", "porn", "graphic-media"];
 
 #[derive(Deserialize)]
 struct Input {
@@ -31,12 +75,59 @@ struct Input {
 struct Output {
     #[serde(flatten)]
     item_identifier: ItemIdentifier,
-    uri: String,
+    /// The URI of the post, None in a dry run.
+    uri: Option<String>,
+    /// The CID of the post, None in a dry run.
+    cid: Option<String>,
+    /// The template variant the post was laid out with, recorded by update-dynamodb.
+    template_variant: Option<String>,
+    /// Tells update-dynamodb and error-check that nothing was posted, so nothing is recorded as posted either.
+    dry_run: bool,
 }
 
 struct Config {
     dynamodb_table_name: String,
     secret_name: String,
+    /// Layout of posts with a link card.
+    template: PostTemplate,
+    /// Layout of posts with images or a video, and of text-only posts, which have no link card, so the link has to be
+    /// in the text.
+    image_template: PostTemplate,
+    /// Templates tried out against each other, each laying out a share of the posts in place of `template`. Empty
+    /// when there's no experiment.
+    template_variants: Vec<TemplateVariant>,
+    /// Hashtags added to every post, unless the item's feed has its own.
+    post_hashtags: Vec<String>,
+    category_hashtags: bool,
+    /// Tags added to the record of every post, where they don't show in the text.
+    post_tags: Vec<String>,
+    /// Whether the item categories go into the record's tags, as far as they aren't hashtags in the text already.
+    category_tags: bool,
+    max_hashtags: usize,
+    /// Bluesky handles of writers, by their lowercased author name.
+    author_handles: HashMap<String, String>,
+    /// Rules giving the posts of matching items self-labels or a warning in front of their text.
+    content_warnings: ContentWarningRules,
+    release_notes_graphemes: usize,
+    /// Length limit of the description shown in link cards.
+    card_description_graphemes: usize,
+    /// The most posts a long summary is split into. None when long summaries are truncated instead.
+    max_thread_posts: Option<usize>,
+    /// The language AI summaries are written in, which posts with one are in. None for the language of each item.
+    target_language: Option<String>,
+    http: HttpConfig,
+    /// How long the blobs of posted images are reused for posts showing the same image. None when they aren't.
+    blob_cache_ttl: Option<chrono::Duration>,
+    /// Bedrock model that writes the alt text of posted images. None when AI alt text is turned off.
+    alt_text_model_id: Option<String>,
+    /// How far back posts are dated to the publication of their item. None when they are dated when they are made.
+    max_backdate: Option<chrono::Duration>,
+    /// Logs the posts instead of creating them.
+    dry_run: bool,
+    /// How items that changed since they were posted are posted again. check-dynamodb decides whether they are.
+    updated_items: UpdateMode,
+    /// Who may reply to the posts, unless the item's feed says otherwise.
+    reply_control: ReplyControl,
 }
 
 impl Config {
@@ -48,7 +139,7 @@ impl Config {
             return Err(Error::from("DYNAMODB_TABLE_NAME cannot be empty"));
         }
 
-        let secret_name = env::var("BLUESKY_CREDENTIALS_SECRET_NAME")
+        let secret_name = std::env::var("BLUESKY_CREDENTIALS_SECRET_NAME")
             .context("BLUESKY_CREDENTIALS_SECRET_NAME environment variable not set")
             .map_err(Error::from)?;
 
@@ -58,18 +149,150 @@ impl Config {
             ));
         }
 
+        // Without a POST_TEMPLATE, posts are laid out as CREDIT_AUTHOR and PREFIX_SOURCE ask for
+        let template_text = env::optional("POST_TEMPLATE").unwrap_or_else(|| {
+            PostTemplate::default_text(env::flag("PREFIX_SOURCE"), env::flag("CREDIT_AUTHOR"))
+        });
+        let template = PostTemplate::parse(&template_text)?;
+        let image_template = template.with_link();
+        // An A/B experiment lays out the posts with the variants instead, and records which one each post used
+        let template_variants = match env::optional("POST_TEMPLATE_VARIANTS") {
+            Some(value) => parse_variants(&value)?,
+            None => Vec::new(),
+        };
+
+        // Tagged posts show up in Bluesky's tag feeds. Tags come after everything else, in whatever room is left
+        let post_hashtags = static_hashtags(&parse_list(
+            &env::optional("POST_HASHTAGS").unwrap_or_default(),
+        ));
+        let category_hashtags = env::flag("CATEGORY_HASHTAGS");
+        let max_hashtags = env::parse_or("MAX_HASHTAGS", DEFAULT_MAX_HASHTAGS)?;
+        // The record's tags find the posts the same way, without taking up room in the text. They are also where
+        // the hashtags that didn't fit go
+        let post_tags =
+            static_hashtags(&parse_list(&env::optional("POST_TAGS").unwrap_or_default()));
+        let category_tags = env::flag("CATEGORY_TAGS");
+
+        // Writers on Bluesky are mentioned in the posts of their items, e.g. {"Jane Doe": "jane.bsky.social"}
+        let author_handles = match env::optional("AUTHOR_HANDLES") {
+            Some(value) => parse_author_handles(&value)?,
+            None => HashMap::new(),
+        };
+
+        // Items about sensitive topics get a self-label or a warning, e.g. [{"keywords": ["gore"], "label": "graphic-media"}]
+        let content_warnings = match env::optional("CONTENT_WARNINGS") {
+            Some(value) => ContentWarningRules::parse(&value)?,
+            None => ContentWarningRules::default(),
+        };
+
+        // How much of the release notes posts announcing GitHub releases quote
+        let release_notes_graphemes =
+            env::parse_or("RELEASE_NOTES_LENGTH", DEFAULT_RELEASE_NOTES_GRAPHEMES)?;
+
+        // Link cards show a few lines of the description below the title, so they get more of it than fits a post
+        let card_description_graphemes = env::parse_or(
+            "CARD_DESCRIPTION_LENGTH",
+            DEFAULT_CARD_DESCRIPTION_GRAPHEMES,
+        )?;
+
+        // Long summaries are cut to fit a single post unless they may continue in a thread of replies
+        let max_thread_posts = if env::flag("THREAD_LONG_POSTS") {
+            Some(env::parse_or("MAX_THREAD_POSTS", DEFAULT_MAX_THREAD_POSTS)?.max(1))
+        } else {
+            None
+        };
+
+        // Summaries translated by summarize-bedrock make the post one in their language
+        let target_language = env::optional("TARGET_LANGUAGE");
+        if let Some(language) = &target_language {
+            Language::new(language.clone())
+                .map_err(|e| format!("TARGET_LANGUAGE {:?} is invalid: {}", language, e))?;
+        }
+
+        // Used to fetch the linked page for its thumbnail, so it goes through the same proxy and etiquette as
+        // article extraction
+        let http = HttpConfig {
+            proxy_url: env::optional("FETCH_PROXY_URL"),
+            min_host_interval: Duration::from_millis(env::parse_or(
+                "FETCH_MIN_HOST_INTERVAL_MS",
+                DEFAULT_MIN_HOST_INTERVAL.as_millis() as u64,
+            )?),
+            respect_robots_txt: !env::flag("IGNORE_ROBOTS_TXT"),
+            max_body_bytes: MAX_VIDEO_BYTES,
+            ..HttpConfig::default()
+        };
+
+        // Sites show the same logo or header on every link card, which then only needs uploading once
+        let blob_cache_ttl = Some(env::parse_or("BLOB_CACHE_DAYS", DEFAULT_BLOB_CACHE_DAYS)?)
+            .filter(|days| *days > 0)
+            .map(chrono::Duration::days);
+
+        // Posted images get AI written alt text unless this is turned off, so posts stay accessible by default
+        let alt_text_model_id = (!env::flag("DISABLE_AI_ALT_TEXT")).then(|| {
+            env::optional("ALT_TEXT_MODEL_ID")
+                .or_else(|| env::optional("AI_MODEL_ID"))
+                .unwrap_or_else(|| DEFAULT_ALT_TEXT_MODEL_ID.to_string())
+        });
+
+        // Posts of a run catching up after downtime can be dated to their items, so they land where they belong on
+        // the timeline
+        let max_backdate = if env::flag("BACKDATE_POSTS") {
+            Some(chrono::Duration::hours(
+                env::parse_or("MAX_BACKDATE_HOURS", DEFAULT_MAX_BACKDATE_HOURS)?.max(0),
+            ))
+        } else {
+            None
+        };
+
+        // Goes through everything but the posting itself, to try out feeds and templates against real items
+        let dry_run = env::flag("DRY_RUN");
+
+        let updated_items = env::optional("UPDATED_ITEMS")
+            .map(|mode| UpdateMode::parse(&mode))
+            .transpose()?
+            .unwrap_or_default();
+
+        // Bot accounts often want no replies, which nobody reads
+        let reply_control = env::optional("REPLY_CONTROL")
+            .map(|control| ReplyControl::parse(&control))
+            .transpose()?
+            .unwrap_or_default();
+
         Ok(Self {
             dynamodb_table_name,
             secret_name,
+            template,
+            image_template,
+            template_variants,
+            post_hashtags,
+            category_hashtags,
+            post_tags,
+            category_tags,
+            max_hashtags,
+            author_handles,
+            content_warnings,
+            release_notes_graphemes,
+            card_description_graphemes,
+            max_thread_posts,
+            target_language,
+            http,
+            blob_cache_ttl,
+            alt_text_model_id,
+            max_backdate,
+            dry_run,
+            updated_items,
+            reply_control,
         })
     }
 }
 
-#[instrument(skip(event, repo, secrets_client, config))]
+#[instrument(skip(event, repo, http, secrets_client, bedrock_client, config))]
 async fn post_bluesky(
     event: LambdaEvent<Input>,
     repo: &DynamoRepository,
+    http: &HttpClient,
     secrets_client: &SecretsManagerClient,
+    bedrock_client: &BedrockClient,
     config: &Config,
 ) -> Result<Output, Error> {
     tracing::info!(
@@ -92,99 +315,1101 @@ async fn post_bluesky(
         })
         .map_err(Error::from)?;
 
-    let title = item.title.context("Title not found in item")?;
-    let description = item.description.context("Description not found in item")?;
-    let link = item.link.context("Link not found in item")?;
+    // Get Bluesky credentials, of the feed's own account if it has one
+    let secret_name = item
+        .bluesky_secret
+        .as_deref()
+        .unwrap_or(&config.secret_name);
+    // Rate limited and failed requests are retried by the agent, rather than failing the item and posting it on a
+    // later run
+    let agent = bluesky::login(secrets_client, secret_name)
+        .await
+        .map_err(Error::from)?;
 
-    let summary = match item.summary {
-        Some(s) if !s.trim().is_empty() => s,
-        _ => {
-            tracing::info!("AI generated summary unavailable. Generating summary from description");
-            truncate_to_word(description.as_str(), MAX_BSKY_GRAPHEMES)
-        }
+    let poster = BlueskyPoster {
+        agent,
+        repo,
+        http,
+        bedrock_client,
+        config,
     };
-    tracing::info!("Using summary: {}", summary);
-
-    // Get Bluesky credentials
-    let secret = secrets_client
-        .get_secret_value()
-        .secret_id(&config.secret_name)
-        .send()
+    let result = poster
+        .post(&item, config.dry_run)
         .await
-        .context("Failed to retrieve secret")
         .map_err(Error::from)?;
 
-    let secret_string = secret
-        .secret_string()
-        .context("Secret string is empty")
-        .map_err(Error::from)?;
-    let credentials: serde_json::Value = serde_json::from_str(secret_string)
-        .context("Failed to parse secret JSON")
-        .map_err(Error::from)?;
+    Ok(Output {
+        item_identifier: event.payload.item_identifier,
+        uri: result.uri,
+        cid: result.cid,
+        template_variant: result.template_variant,
+        dry_run: result.dry_run,
+    })
+}
 
-    let username = credentials["username"]
-        .as_str()
-        .context("Username not found in secret")
-        .map_err(Error::from)?;
-    let password = credentials["password"]
-        .as_str()
-        .context("Password not found in secret")
-        .map_err(Error::from)?;
+/// Posts items to the Bluesky account the lambda logged in to.
+struct BlueskyPoster<'a> {
+    agent: BskyAgent<RetryingClient>,
+    repo: &'a DynamoRepository,
+    http: &'a HttpClient,
+    bedrock_client: &'a BedrockClient,
+    config: &'a Config,
+}
 
-    // Create Bluesky post
-    let rt = RichText::new_with_detect_facets(summary)
-        .await
-        .context("Failed to create RichText")
-        .map_err(Error::from)?;
+/// The post of an item, ready to be created.
+#[derive(Serialize)]
+struct PostDraft {
+    record: post::RecordData,
+    /// The texts of the replies continuing the post when its summary is split into a thread.
+    replies: Vec<String>,
+    /// Who may reply to the post, set by the threadgate created along with it.
+    reply_control: ReplyControl,
+    /// The template variant the post is laid out with.
+    template_variant: Option<String>,
+    /// The blobs uploaded for the post, cached once it's made.
+    #[serde(skip)]
+    cached_blobs: Vec<CachedBlob>,
+}
 
-    let record_data = atrium_api::app::bsky::feed::post::RecordData {
-        created_at: atrium_api::types::string::Datetime::now(),
-        embed: Some(Union::Refs(RecordEmbedRefs::AppBskyEmbedExternalMain(
-            Box::new(Main {
+impl Poster for BlueskyPoster<'_> {
+    type Draft = PostDraft;
+
+    fn network(&self) -> &'static str {
+        "Bluesky"
+    }
+
+    async fn prepare(&self, item: &ExecutionItem) -> anyhow::Result<PostDraft> {
+        let BlueskyPoster {
+            agent,
+            repo,
+            http,
+            bedrock_client,
+            config,
+        } = self;
+
+        let title = item.title.clone().context("Title not found in item")?;
+        let description = item
+            .description
+            .clone()
+            .context("Description not found in item")?;
+        let link = item.link.clone().context("Link not found in item")?;
+        let warning = config
+            .content_warnings
+            .check(Some(&title), Some(&description));
+
+        // Releases of GitHub projects are announced with their repository, version and notes rather than a summary
+        let release = item
+            .feed_url
+            .as_deref()
+            .filter(|feed_url| github::is_releases_feed(feed_url))
+            .and_then(|_| github::release_from_url(&link));
+
+        let ai_summary = release.is_none()
+            && item
+                .summary
+                .as_ref()
+                .is_some_and(|summary| !summary.trim().is_empty());
+        let summary = match (release, item.summary.clone()) {
+            (Some(release), _) => github::release_post_text(
+                &release,
+                // Releases without notes carry their title instead, which would only repeat the version
+                Some(description.as_str()).filter(|notes| *notes != title),
+                config.release_notes_graphemes,
+                MAX_POST_GRAPHEMES,
+            ),
+            (None, Some(s)) if !s.trim().is_empty() => s,
+            (None, _) if config.max_thread_posts.is_some() => {
+                tracing::info!("AI generated summary unavailable. Threading the description");
+                description.clone()
+            }
+            (None, _) => {
+                tracing::info!(
+                    "AI generated summary unavailable. Generating summary from description"
+                );
+                truncate_to_word(description.as_str(), MAX_POST_GRAPHEMES)
+            }
+        };
+
+        // Links in the text are shown shortened, leaving more of the post to the summary, and their facet has the URL
+        let link_text = link_display_text(&link);
+        let mut values = HashMap::from([
+            (Field::Title, title.clone()),
+            (Field::Summary, summary),
+            (Field::Link, link_text.clone()),
+        ]);
+        if let Some(label) = episode_label(item.season.as_deref(), item.episode.as_deref()) {
+            values.insert(Field::Episode, label);
+        }
+        if let Some(feed_title) = item.feed_title.clone() {
+            values.insert(Field::Source, feed_title);
+        }
+        if let Some(author) = item.author.clone() {
+            values.insert(Field::Author, author);
+        }
+        if let Some(prefix) = item.prefix.clone() {
+            values.insert(Field::Prefix, prefix);
+        }
+
+        // A handle that doesn't resolve (say, the writer renamed their account) would fail the post, so it's checked
+        // first and left out if need be
+        let mention = match item
+            .author
+            .as_deref()
+            .and_then(|author| config.author_handles.get(&author.trim().to_lowercase()))
+        {
+            Some(handle) => match check_handle(handle, agent).await {
+                Ok(()) => Some(format!("@{}", handle)),
+                Err(e) => {
+                    tracing::warn!("Not mentioning {}: {:?}", handle, e);
+                    None
+                }
+            },
+            None => None,
+        };
+
+        // Videos and the images of feeds that post them are shown instead of the link card, so the link goes into the
+        // text. A video that can't be posted leaves the item with its images or link card
+        let video = match &item.video_url {
+            Some(video_url) => match upload_item_video(video_url, &title, agent, http).await {
+                Ok(video) => video,
+                Err(e) => {
+                    tracing::warn!("Failed to attach video {}: {:?}", video_url, e);
+                    None
+                }
+            },
+            None => None,
+        };
+        let blob_cache = BlobCache::new(repo, agent, config.blob_cache_ttl).await;
+        let images = if video.is_some() {
+            Vec::new()
+        } else {
+            upload_item_images(
+                &item.image_urls,
+                &title,
+                agent,
+                http,
+                bedrock_client,
+                &blob_cache,
+                config,
+            )
+            .await
+        };
+        // Keyed by guid, so a retry lays the post out the same way
+        let variant = pick_variant(&config.template_variants, &item.guid);
+        let variant_templates = variant.map(|v| (v.template.clone(), v.template.with_link()));
+        let (link_template, image_template) = match &variant_templates {
+            Some((template, image_template)) => (template, image_template),
+            None => (&config.template, &config.image_template),
+        };
+        let (template, embed) = if let Some(video) = video {
+            (
+                image_template,
+                Some(RecordEmbedRefs::AppBskyEmbedVideoMain(Box::new(video))),
+            )
+        } else if images.is_empty() && item.text_only {
+            // The link in the text gets a link facet instead
+            (image_template, None)
+        } else if images.is_empty() {
+            // A card without a thumbnail is still a working link, so a missing or broken image doesn't stop the post
+            let thumb = match link_thumbnail(&link, agent, http, &blob_cache).await {
+                Ok(thumb) => thumb,
+                Err(e) => {
+                    tracing::warn!("Failed to attach a thumbnail for {}: {:?}", link, e);
+                    None
+                }
+            };
+            let embed = RecordEmbedRefs::AppBskyEmbedExternalMain(Box::new(Main {
                 data: MainData {
                     external: External {
                         data: ExternalData {
                             title: title.clone(),
-                            description: "".to_string(),
+                            description: card_description(
+                                &description,
+                                values.get(&Field::Summary),
+                                config.card_description_graphemes,
+                            ),
                             uri: link.clone(),
-                            thumb: None,
+                            thumb,
                         },
                         extra_data: ipld_core::ipld::Ipld::Null,
                     },
                 },
                 extra_data: ipld_core::ipld::Ipld::Null,
-            }),
-        ))),
+            }));
+            (link_template, Some(embed))
+        } else {
+            let embed = RecordEmbedRefs::AppBskyEmbedImagesMain(Box::new(images::Main {
+                data: images::MainData { images },
+                extra_data: ipld_core::ipld::Ipld::Null,
+            }));
+            (image_template, Some(embed))
+        };
+        // Content warnings lead the post, so they're read before the rest of it
+        let warned_template = (!warning.warnings.is_empty())
+            .then(|| template.with_warning(&warning.warnings.join("\n")));
+        let template = warned_template.as_ref().unwrap_or(template);
+        // The writer is mentioned in place of their name, or else along with the hashtags
+        let mut mention_tag = None;
+        let mentions: Vec<String> = mention.iter().cloned().collect();
+        if let Some(mention) = mention {
+            if template.has_field(Field::Author) {
+                values.insert(Field::Author, mention);
+            } else {
+                mention_tag = Some(mention);
+            }
+        }
+
+        // The marker goes in front of the summary (or the title, for templates without one), so it survives the
+        // truncation of the rest
+        if item.update_of_uri.is_some() {
+            let field = if template.has_field(Field::Summary) {
+                Field::Summary
+            } else {
+                Field::Title
+            };
+            if let Some(value) = values.get_mut(&field) {
+                value.insert_str(0, UPDATE_PREFIX);
+            }
+        }
+        let (text, replies) = post_texts(template, &values, config.max_thread_posts);
+
+        // A follow-up to a story quotes its earlier post. One that can't be quoted (deleted, or recorded before post
+        // URIs were stored) is simply left out
+        let embed = match &item.quote_guid {
+            Some(guid) => match quoted_post(guid, repo).await {
+                Ok(Some(quoted)) => Some(quote_embed(embed, quoted)),
+                Ok(None) => embed,
+                Err(e) => {
+                    tracing::warn!("Failed to quote the post of {:?}: {:?}", guid, e);
+                    embed
+                }
+            },
+            None => embed,
+        };
+
+        // The feed's own tags come first, as they are wanted on every post. Category tags fill up what's left
+        let mut hashtags = if item.hashtags.is_empty() {
+            config.post_hashtags.clone()
+        } else {
+            static_hashtags(&item.hashtags)
+        };
+        hashtags.splice(0..0, mention_tag);
+        if config.category_hashtags {
+            let mut category_hashtags: Vec<String> = Vec::new();
+            for tag in item.categories.iter().filter_map(|c| hashtag(c)) {
+                let is_new =
+                    |tags: &[String]| !tags.iter().any(|t| t.to_lowercase() == tag.to_lowercase());
+                if is_new(&hashtags) && is_new(&category_hashtags) {
+                    category_hashtags.push(tag);
+                }
+            }
+            category_hashtags.truncate(config.max_hashtags);
+            hashtags.extend(category_hashtags);
+        }
+        // RichText turns the tags into tag facets along with the links
+        let text = append_hashtags(&text, &hashtags, MAX_POST_GRAPHEMES);
+
+        let mut tag_candidates = config.post_tags.clone();
+        tag_candidates.extend(hashtags);
+        if config.category_tags {
+            tag_candidates.extend(item.categories.iter().filter_map(|c| hashtag(c)));
+        }
+        let tags = Some(record_tags(&tag_candidates, &text)).filter(|tags| !tags.is_empty());
+
+        tracing::info!("Using text: {}", text);
+
+        // Readers filter posts by language, so an unknown language is better than a wrong one
+        let language = config
+            .target_language
+            .clone()
+            .filter(|_| ai_summary)
+            .or_else(|| item.language.clone())
+            .or_else(|| {
+                let sample = format!("{}\n{}", title, values.get(&Field::Summary)?);
+                detect_language(&strip_tags(&sample)).map(String::from)
+            });
+        let langs =
+            language.and_then(
+                |language| match Language::new(language.trim().to_string()) {
+                    Ok(language) => Some(vec![language]),
+                    Err(e) => {
+                        tracing::warn!("Ignoring invalid language {:?}: {:?}", language, e);
+                        None
+                    }
+                },
+            );
+
+        let mut labels = item.labels.clone();
+        labels.extend(
+            warning
+                .labels
+                .into_iter()
+                .filter(|label| !item.labels.contains(label)),
+        );
+        let labels = self_labels(&labels);
+
+        let reply_control = match &item.reply_control {
+            Some(control) => ReplyControl::parse(control)
+                .with_context(|| format!("Invalid reply_control of feed {:?}", item.feed_url))?,
+            None => config.reply_control,
+        };
+
+        // An update that can't find its earlier post still goes out, as a post of its own
+        let reply = match (
+            config.updated_items,
+            &item.update_of_uri,
+            &item.update_of_cid,
+        ) {
+            (UpdateMode::Reply, Some(uri), Some(cid)) => match reply_ref(uri, cid, agent).await {
+                Ok(reply) => Some(reply),
+                Err(e) => {
+                    tracing::warn!("Failed to reply to the earlier post {}: {:?}", uri, e);
+                    None
+                }
+            },
+            _ => None,
+        };
+        // Replies follow the threadgate of the thread they are in
+        let reply_control = if reply.is_some() {
+            ReplyControl::Everyone
+        } else {
+            reply_control
+        };
+
+        let rt = RichText::new_with_detect_facets(text)
+            .await
+            .context("Failed to create RichText")?;
+
+        // Updates are dated when they are made, as they'd otherwise come before the post they follow up on
+        let created_at = match config.max_backdate {
+            Some(max_backdate) if item.update_of_uri.is_none() => Datetime::new(
+                bluesky::backdated_created_at(item.pub_date.as_deref(), Utc::now(), max_backdate),
+            ),
+            _ => Datetime::now(),
+        };
+
+        let record_data = atrium_api::app::bsky::feed::post::RecordData {
+            created_at,
+            embed: embed.map(Union::Refs),
+            entities: None,
+            facets: bluesky::link_facets(
+                bluesky::keep_mentions(rt.facets, &rt.text, &mentions),
+                &rt.text,
+                &[(link_text, link)],
+            ),
+            labels,
+            langs,
+            reply: reply.map(Into::into),
+            tags,
+            text: rt.text,
+        };
+
+        Ok(PostDraft {
+            record: record_data,
+            replies,
+            reply_control,
+            template_variant: variant.map(|v| v.name.clone()),
+            cached_blobs: blob_cache.into_uploaded(),
+        })
+    }
+
+    async fn publish(&self, draft: PostDraft) -> anyhow::Result<PostResult> {
+        let PostDraft {
+            record: record_data,
+            replies,
+            reply_control,
+            template_variant,
+            cached_blobs,
+        } = draft;
+        // The replies carry the languages and labels of the post, and belong to the thread it replies to, if any
+        let (langs, labels) = (record_data.langs.clone(), record_data.labels.clone());
+        let reply = record_data.reply.clone();
+        let agent = &self.agent;
+
+        let result = agent
+            .create_record(record_data)
+            .await
+            .context("Failed to create Bluesky post")?;
+
+        // A blob that isn't cached is only uploaded again for the next post showing it
+        for cached_blob in &cached_blobs {
+            if let Err(e) = self.repo.put_cached_blob(cached_blob).await {
+                tracing::warn!("Failed to cache an image blob: {:?}", e);
+            }
+        }
+
+        // Like the replies below, a threadgate that can't be created doesn't fail the item, whose post is out already
+        if let Some(threadgate) = reply_control.threadgate(&result.uri, Datetime::now()) {
+            let rkey = bluesky::parse_at_uri(&result.uri).map(|(_, _, rkey)| rkey.to_string());
+            match rkey {
+                Some(rkey) => {
+                    if let Err(e) = threadgate.put(agent, rkey).await {
+                        tracing::warn!("Failed to limit the replies to {}: {:?}", result.uri, e);
+                    }
+                }
+                None => tracing::warn!("Invalid post URI {}, replies aren't limited", result.uri),
+            }
+        }
+
+        // The root post is out at this point, and failing the item would post it again on retry, so a reply that
+        // can't be posted only leaves the thread short
+        let post = strong_ref::MainData {
+            cid: result.cid.clone(),
+            uri: result.uri.clone(),
+        };
+        let root = reply.map_or_else(|| post.clone(), |reply| reply.root.data.clone());
+        let mut parent = post;
+        for text in replies {
+            match post_reply(text, &root, &parent, langs.clone(), labels.clone(), agent).await {
+                Ok(reply) => parent = reply,
+                Err(e) => {
+                    tracing::warn!("Failed to continue the thread of {}: {:?}", result.uri, e);
+                    break;
+                }
+            }
+        }
+
+        Ok(PostResult {
+            uri: Some(result.uri.clone()),
+            cid: Some(result.cid.as_ref().to_string()),
+            template_variant,
+            ..PostResult::default()
+        })
+    }
+}
+
+/// Lays out the text of a post, along with that of the replies continuing it when the summary is too long.
+///
+/// # Arguments
+///
+/// * `template` - The layout of the post.
+/// * `values` - The values of the template fields.
+/// * `max_thread_posts` - The most posts a long summary may be split into, or None to truncate it.
+///
+/// # Returns
+///
+/// The text of the post and those of the replies, which are empty unless the summary is split into a thread.
+fn post_texts(
+    template: &PostTemplate,
+    values: &HashMap<Field, String>,
+    max_thread_posts: Option<usize>,
+) -> (String, Vec<String>) {
+    let single = || (template.render(values, MAX_POST_GRAPHEMES), Vec::new());
+    let (Some(max_posts), Some(summary)) = (
+        max_thread_posts.filter(|_| template.has_field(Field::Summary)),
+        values.get(&Field::Summary),
+    ) else {
+        return single();
+    };
+    if template.render(values, usize::MAX).graphemes(true).count() <= MAX_POST_GRAPHEMES {
+        return single();
+    }
+
+    // Every post ends with its place in the thread, e.g. " 2/4", so room is kept for the longest such number.
+    // The root post shares its room with the rest of the template, measured here with a one grapheme summary
+    let numbering = format!(" {0}/{0}", max_posts).len();
+    let mut placeholder = values.clone();
+    placeholder.insert(Field::Summary, "x".to_string());
+    let overhead = template
+        .render(&placeholder, usize::MAX)
+        .graphemes(true)
+        .count()
+        - 1;
+    let Some(first_max) = MAX_POST_GRAPHEMES
+        .checked_sub(overhead + numbering)
+        .filter(|max| *max > 0)
+    else {
+        return single();
+    };
+
+    let posts = split_into_posts(
+        summary,
+        first_max,
+        MAX_POST_GRAPHEMES - numbering,
+        max_posts,
+    );
+    if posts.len() < 2 {
+        return single();
+    }
+    let count = posts.len();
+    let mut posts = posts
+        .into_iter()
+        .enumerate()
+        .map(|(i, post)| format!("{} {}/{}", post, i + 1, count));
+
+    let mut root_values = values.clone();
+    root_values.insert(Field::Summary, posts.next().unwrap_or_default());
+    (
+        template.render(&root_values, MAX_POST_GRAPHEMES),
+        posts.collect(),
+    )
+}
+
+/// Writes the description of a link card: the item's description as plain text, or the summary for items without
+/// one, shortened at a word boundary. A limit of 0 leaves the card without a description.
+fn card_description(description: &str, summary: Option<&String>, max_graphemes: usize) -> String {
+    if max_graphemes == 0 {
+        return String::new();
+    }
+    let text = strip_tags(description);
+    let text = if text.is_empty() {
+        summary
+            .map(|summary| strip_tags(summary))
+            .unwrap_or_default()
+    } else {
+        text
+    };
+    truncate_to_word(&text, max_graphemes)
+}
+
+/// Looks up the post of an earlier item, for quoting it.
+///
+/// # Returns
+///
+/// A Result containing the reference to the post, or None if the post was deleted or its URI isn't known.
+async fn quoted_post(
+    guid: &str,
+    repo: &DynamoRepository,
+) -> anyhow::Result<Option<strong_ref::MainData>> {
+    let record_item = repo.get_record_item(guid).await?;
+    let (Some(uri), Some(cid), None) = (record_item.uri, record_item.cid, record_item.deleted_at)
+    else {
+        return Ok(None);
+    };
+    Ok(Some(strong_ref::MainData {
+        cid: cid.parse().context("Invalid CID")?,
+        uri,
+    }))
+}
+
+/// Turns the embed of a post into a quote of another post, keeping the link card, images or video alongside.
+fn quote_embed(embed: Option<RecordEmbedRefs>, quoted: strong_ref::MainData) -> RecordEmbedRefs {
+    let record = record::Main::from(record::MainData {
+        record: quoted.into(),
+    });
+    let Some(embed) = embed else {
+        return RecordEmbedRefs::AppBskyEmbedRecordMain(Box::new(record));
+    };
+    let media = match embed {
+        RecordEmbedRefs::AppBskyEmbedExternalMain(external) => {
+            record_with_media::MainMediaRefs::AppBskyEmbedExternalMain(external)
+        }
+        RecordEmbedRefs::AppBskyEmbedImagesMain(images) => {
+            record_with_media::MainMediaRefs::AppBskyEmbedImagesMain(images)
+        }
+        RecordEmbedRefs::AppBskyEmbedVideoMain(video) => {
+            record_with_media::MainMediaRefs::AppBskyEmbedVideoMain(video)
+        }
+        // Already a quote
+        embed => return embed,
+    };
+    RecordEmbedRefs::AppBskyEmbedRecordWithMediaMain(Box::new(
+        record_with_media::MainData {
+            media: Union::Refs(media),
+            record,
+        }
+        .into(),
+    ))
+}
+
+/// Builds the reference for replying to a post, which has to name the root of the thread as well.
+///
+/// # Arguments
+///
+/// * `uri` - The at:// URI of the post replied to.
+/// * `cid` - The CID of the post replied to.
+/// * `agent` - The logged in Bluesky agent.
+///
+/// # Returns
+///
+/// A Result containing the reference, or an error if the post can't be read.
+async fn reply_ref(
+    uri: &str,
+    cid: &str,
+    agent: &BskyAgent<RetryingClient>,
+) -> anyhow::Result<ReplyRefData> {
+    let parent = strong_ref::MainData {
+        cid: cid.parse().context("Invalid CID")?,
+        uri: uri.to_string(),
+    };
+    let (repo, collection, rkey) = bluesky::parse_at_uri(uri).context("Invalid post URI")?;
+    let output = agent
+        .api
+        .com
+        .atproto
+        .repo
+        .get_record(
+            get_record::ParametersData {
+                cid: None,
+                collection: collection.parse().map_err(anyhow::Error::msg)?,
+                repo: repo.parse().map_err(anyhow::Error::msg)?,
+                rkey: rkey.to_string(),
+            }
+            .into(),
+        )
+        .await
+        .context("Failed to get post")?;
+    let record =
+        post::Record::try_from_unknown(output.data.value).context("Failed to read post")?;
+
+    // A post that is a reply itself, such as an earlier update, belongs to its parent's thread
+    let root = match &record.reply {
+        Some(reply) => reply.root.data.clone(),
+        None => parent.clone(),
+    };
+    Ok(ReplyRefData {
+        parent: parent.into(),
+        root: root.into(),
+    })
+}
+
+/// Posts a reply in a thread.
+///
+/// # Arguments
+///
+/// * `text` - The text of the reply.
+/// * `root` - The post the thread starts with.
+/// * `parent` - The post replied to.
+/// * `langs` - The languages of the reply.
+/// * `labels` - The self-labels of the reply, the same as the post's.
+/// * `agent` - The logged in Bluesky agent.
+///
+/// # Returns
+///
+/// A Result containing the reference to the reply, which the next reply is posted under.
+async fn post_reply(
+    text: String,
+    root: &strong_ref::MainData,
+    parent: &strong_ref::MainData,
+    langs: Option<Vec<Language>>,
+    labels: Option<Union<RecordLabelsRefs>>,
+    agent: &BskyAgent<RetryingClient>,
+) -> anyhow::Result<strong_ref::MainData> {
+    let rt = RichText::new_with_detect_facets(text)
+        .await
+        .context("Failed to create RichText")?;
+
+    let record_data = atrium_api::app::bsky::feed::post::RecordData {
+        created_at: atrium_api::types::string::Datetime::now(),
+        embed: None,
         entities: None,
-        facets: rt.facets,
-        labels: None,
-        langs: None,
-        reply: None,
+        facets: bluesky::keep_mentions(rt.facets, &rt.text, &[]),
+        labels,
+        langs,
+        reply: Some(
+            ReplyRefData {
+                parent: parent.clone().into(),
+                root: root.clone().into(),
+            }
+            .into(),
+        ),
         tags: None,
         text: rt.text,
     };
 
-    let agent = BskyAgent::builder()
-        .build()
+    let result = agent
+        .create_record(record_data)
         .await
-        .context("Failed to build BskyAgent")
-        .map_err(Error::from)?;
+        .context("Failed to create Bluesky reply")?;
+    Ok(strong_ref::MainData {
+        cid: result.cid.clone(),
+        uri: result.uri.clone(),
+    })
+}
+
+/// Turns the labels of a feed into the self-labels of its posts.
+///
+/// # Returns
+///
+/// The self-labels, or None if the feed has none.
+fn self_labels(labels: &[String]) -> Option<Union<RecordLabelsRefs>> {
+    let values: Vec<_> = labels
+        .iter()
+        .map(|label| label.trim().to_lowercase())
+        .filter(|label| !label.is_empty())
+        .inspect(|label| {
+            if !KNOWN_SELF_LABELS.contains(&label.as_str()) {
+                tracing::warn!("Bluesky doesn't know the self-label {:?}", label);
+            }
+        })
+        .map(|val| SelfLabelData { val }.into())
+        .collect();
+    if values.is_empty() {
+        return None;
+    }
+    Some(Union::Refs(
+        RecordLabelsRefs::ComAtprotoLabelDefsSelfLabels(Box::new(SelfLabelsData { values }.into())),
+    ))
+}
+
+/// Parses AUTHOR_HANDLES, a JSON object of author names and the Bluesky handles of the writers.
+///
+/// Names are matched ignoring case, and handles may be given with or without their `@`.
+fn parse_author_handles(value: &str) -> Result<HashMap<String, String>, Error> {
+    let handles: HashMap<String, String> =
+        serde_json::from_str(value).context("AUTHOR_HANDLES must be a JSON object of strings")?;
+    handles
+        .into_iter()
+        .map(|(author, handle)| {
+            let handle = handle.trim().trim_start_matches('@').to_lowercase();
+            Handle::new(handle.clone()).map_err(|e| {
+                Error::from(format!(
+                    "Invalid handle {:?} in AUTHOR_HANDLES: {}",
+                    handle, e
+                ))
+            })?;
+            Ok((author.trim().to_lowercase(), handle))
+        })
+        .collect()
+}
+
+/// Checks that a handle belongs to an account, so a mention of it can be resolved.
+async fn check_handle(handle: &str, agent: &BskyAgent<RetryingClient>) -> anyhow::Result<()> {
     agent
-        .login(username, password)
+        .api
+        .com
+        .atproto
+        .identity
+        .resolve_handle(
+            resolve_handle::ParametersData {
+                handle: handle.parse().map_err(anyhow::Error::msg)?,
+            }
+            .into(),
+        )
         .await
-        .context("Failed to login to Bluesky")
-        .map_err(Error::from)?;
+        .context("Failed to resolve handle")?;
+    Ok(())
+}
 
-    let result = agent
-        .create_record(record_data)
+/// Uploads the images attached to an item for an images embed, up to the 4 a post can hold.
+///
+/// Images that can't be fetched or uploaded are left out, so this never fails. When none of them make it, the
+/// item is posted with a link card instead.
+///
+/// # Arguments
+///
+/// * `image_urls` - The URLs of the images.
+/// * `title` - The title of the item. It's the alt text of images the model couldn't describe.
+/// * `agent` - A logged in BskyAgent.
+/// * `http` - The HTTP client to fetch the images with.
+/// * `bedrock_client` - The client to generate alt text with.
+/// * `blob_cache` - The blobs of images posted before, which aren't uploaded again.
+/// * `config` - The configuration, for the alt text model.
+///
+/// # Returns
+///
+/// The uploaded images, in the order of `image_urls`.
+async fn upload_item_images(
+    image_urls: &[String],
+    title: &str,
+    agent: &BskyAgent<RetryingClient>,
+    http: &HttpClient,
+    bedrock_client: &BedrockClient,
+    blob_cache: &BlobCache<'_>,
+    config: &Config,
+) -> Vec<images::Image> {
+    let mut uploaded = Vec::new();
+    for image_url in image_urls {
+        if uploaded.len() == MAX_IMAGES {
+            break;
+        }
+        if let Some((blob, cached)) = blob_cache.get(image_url).await {
+            uploaded.push(images::Image {
+                data: images::ImageData {
+                    alt: cached.alt.unwrap_or_else(|| title.to_string()),
+                    aspect_ratio: cached.width.zip(cached.height).and_then(aspect_ratio),
+                    image: blob,
+                },
+                extra_data: ipld_core::ipld::Ipld::Null,
+            });
+            continue;
+        }
+        let image = match fetch_image(image_url, http).await {
+            Ok(Some(image)) => image,
+            Ok(None) => continue,
+            Err(e) => {
+                tracing::warn!("Failed to attach image {}: {:?}", image_url, e);
+                continue;
+            }
+        };
+
+        let alt = match &config.alt_text_model_id {
+            Some(model_id) => match describe_image(&image, title, bedrock_client, model_id).await {
+                Ok(alt) => alt,
+                Err(e) => {
+                    tracing::warn!("Failed to generate alt text for {}: {:?}", image_url, e);
+                    None
+                }
+            },
+            None => None,
+        };
+
+        // Without an aspect ratio, apps show the image in a square until it has loaded, then resize or crop it
+        let dimensions = image_dimensions(&image);
+        match upload_blob(image, agent).await {
+            Ok(blob) => {
+                blob_cache.add(image_url, &blob, dimensions, alt.as_deref());
+                uploaded.push(images::Image {
+                    data: images::ImageData {
+                        alt: alt.unwrap_or_else(|| title.to_string()),
+                        aspect_ratio: dimensions.and_then(aspect_ratio),
+                        image: blob,
+                    },
+                    extra_data: ipld_core::ipld::Ipld::Null,
+                })
+            }
+            Err(e) => tracing::warn!("Failed to attach image {}: {:?}", image_url, e),
+        }
+    }
+    uploaded
+}
+
+/// Uploads an item's video enclosure for a video embed.
+///
+/// # Arguments
+///
+/// * `video_url` - The URL of the MP4 video.
+/// * `title` - The title of the item, which is the alt text of the video.
+/// * `agent` - A logged in BskyAgent.
+/// * `http` - The HTTP client to fetch the video with. Its body limit keeps videos Bluesky rejects from being
+///   downloaded in full.
+///
+/// # Returns
+///
+/// A Result containing the video embed, None if the file isn't an MP4 video, or an error if fetching or uploading
+/// failed.
+async fn upload_item_video(
+    video_url: &str,
+    title: &str,
+    agent: &BskyAgent<RetryingClient>,
+    http: &HttpClient,
+) -> anyhow::Result<Option<video::Main>> {
+    let video = http
+        .get_bytes(video_url)
         .await
-        .context("Failed to create Bluesky post")
-        .map_err(Error::from)?;
+        .with_context(|| format!("Failed to fetch video {}", video_url))?;
+    // MP4 files start with an ftyp box; anything else, such as an HTML error page, is no video
+    if video.get(4..8) != Some(b"ftyp".as_slice()) {
+        tracing::warn!("{} is not an MP4 video, leaving it out", video_url);
+        return Ok(None);
+    }
 
-    Ok(Output {
-        item_identifier: event.payload.item_identifier,
-        uri: result.uri.clone(),
-    })
+    let aspect_ratio = video_dimensions(&video).and_then(aspect_ratio);
+    let blob = upload_blob(video, agent).await?;
+    Ok(Some(video::Main {
+        data: video::MainData {
+            alt: Some(title.to_string()),
+            aspect_ratio,
+            captions: None,
+            video: blob,
+        },
+        extra_data: ipld_core::ipld::Ipld::Null,
+    }))
+}
+
+/// Asks a multimodal Bedrock model to write the alt text of an image.
+///
+/// # Returns
+///
+/// A Result containing the alt text, None if the image isn't in a format the model accepts or the model gave no
+/// answer, or an error if the request failed.
+async fn describe_image(
+    image: &[u8],
+    title: &str,
+    bedrock_client: &BedrockClient,
+    model_id: &str,
+) -> anyhow::Result<Option<String>> {
+    let Some(media_type) = image_media_type(image) else {
+        return Ok(None);
+    };
+
+    let request_body = serde_json::to_vec(&alt_text_request(image, media_type, title))?;
+    let response = bedrock_client
+        .invoke_model()
+        .body(aws_sdk_bedrockruntime::primitives::Blob::new(request_body))
+        .model_id(model_id)
+        .content_type("application/json")
+        .accept("application/json")
+        .send()
+        .await
+        .context("Failed to invoke Bedrock model")?;
+
+    let response_body: serde_json::Value = serde_json::from_slice(response.body.as_ref())?;
+    let alt = parse_alt_text(&response_body);
+    tracing::info!("Generated alt text: {:?}", alt);
+    Ok(alt)
+}
+
+/// Uploads the image a page shares itself with (its og:image or twitter:image) as a blob for the link card.
+///
+/// # Arguments
+///
+/// * `link` - The URL of the page.
+/// * `agent` - A logged in BskyAgent.
+/// * `http` - The HTTP client to fetch the page and the image with.
+///
+/// # Returns
+///
+/// A Result containing the uploaded blob, None if the page has no usable image, or an error if fetching or
+/// uploading failed.
+async fn link_thumbnail(
+    link: &str,
+    agent: &BskyAgent<RetryingClient>,
+    http: &HttpClient,
+    blob_cache: &BlobCache<'_>,
+) -> anyhow::Result<Option<BlobRef>> {
+    let html = http.get_page_text(link).await?;
+    let Some(image_url) = page_metadata(&html, link).image_url else {
+        tracing::info!("{} has no og:image, posting without a thumbnail", link);
+        return Ok(None);
+    };
+    if let Some((blob, _)) = blob_cache.get(&image_url).await {
+        return Ok(Some(blob));
+    }
+    match fetch_image(&image_url, http).await? {
+        Some(image) => {
+            let dimensions = image_dimensions(&image);
+            let blob = upload_blob(image, agent).await?;
+            blob_cache.add(&image_url, &blob, dimensions, None);
+            Ok(Some(blob))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Fetches an image, scaling it down and recompressing it if it's larger than Bluesky accepts.
+///
+/// # Returns
+///
+/// A Result containing the image, None if it can't be made small enough, or an error if fetching failed.
+async fn fetch_image(image_url: &str, http: &HttpClient) -> anyhow::Result<Option<Vec<u8>>> {
+    let image = http
+        .get_bytes(image_url)
+        .await
+        .with_context(|| format!("Failed to fetch image {}", image_url))?;
+    match fit_image(&image, MAX_IMAGE_BYTES) {
+        Ok(image) => Ok(Some(image)),
+        Err(e) => {
+            tracing::warn!(
+                "Image {} is {} bytes and can't be made small enough for Bluesky, leaving it out: {:?}",
+                image_url,
+                image.len(),
+                e
+            );
+            Ok(None)
+        }
+    }
+}
+
+/// Makes the aspect ratio of an image for its embed, from its width and height.
+fn aspect_ratio((width, height): (u32, u32)) -> Option<AspectRatio> {
+    Some(
+        AspectRatioData {
+            height: u64::from(height).try_into().ok()?,
+            width: u64::from(width).try_into().ok()?,
+        }
+        .into(),
+    )
+}
+
+async fn upload_blob(image: Vec<u8>, agent: &BskyAgent<RetryingClient>) -> anyhow::Result<BlobRef> {
+    let output = agent
+        .api
+        .com
+        .atproto
+        .repo
+        .upload_blob(image)
+        .await
+        .context("Failed to upload blob")?;
+    Ok(output.data.blob)
+}
+
+/// The blobs of images posted before, by the hash of their URL, so recurring images such as site logos are fetched
+/// and uploaded once per account.
+struct BlobCache<'a> {
+    repo: &'a DynamoRepository,
+    /// The DID of the account, which owns the blobs. None when the cache is off.
+    did: Option<String>,
+    ttl: chrono::Duration,
+    /// The blobs uploaded for the post. They are cached once it's made, as the PDS deletes blobs no post refers to.
+    uploaded: Mutex<Vec<CachedBlob>>,
+}
+
+impl<'a> BlobCache<'a> {
+    async fn new(
+        repo: &'a DynamoRepository,
+        agent: &BskyAgent<RetryingClient>,
+        ttl: Option<chrono::Duration>,
+    ) -> Self {
+        let did = match ttl {
+            Some(_) => agent
+                .get_session()
+                .await
+                .map(|session| session.data.did.to_string()),
+            None => None,
+        };
+        Self {
+            repo,
+            did,
+            ttl: ttl.unwrap_or_default(),
+            uploaded: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Looks up the blob of an image. A cache that can't be read only means the image is uploaded again.
+    async fn get(&self, image_url: &str) -> Option<(BlobRef, CachedBlob)> {
+        let did = self.did.as_deref()?;
+        let url_hash = sha256_hex(image_url.as_bytes());
+        let cached = match self.repo.get_cached_blob(did, &url_hash).await {
+            Ok(cached) => cached?,
+            Err(e) => {
+                tracing::warn!("Failed to look up the blob of {}: {:?}", image_url, e);
+                return None;
+            }
+        };
+        match serde_json::from_str(&cached.blob) {
+            Ok(blob) => {
+                tracing::info!("Reusing the uploaded blob of {}", image_url);
+                Some((blob, cached))
+            }
+            Err(e) => {
+                tracing::warn!("Ignoring the invalid cached blob of {}: {:?}", image_url, e);
+                None
+            }
+        }
+    }
+
+    /// Adds the blob of an image uploaded for the post.
+    fn add(
+        &self,
+        image_url: &str,
+        blob: &BlobRef,
+        dimensions: Option<(u32, u32)>,
+        alt: Option<&str>,
+    ) {
+        let (Some(did), Ok(blob)) = (&self.did, serde_json::to_string(blob)) else {
+            return;
+        };
+        let (width, height) = dimensions.unzip();
+        self.uploaded
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(CachedBlob {
+                did: did.clone(),
+                url_hash: sha256_hex(image_url.as_bytes()),
+                blob,
+                width,
+                height,
+                alt: alt.map(String::from),
+                ttl: Some((Utc::now() + self.ttl).timestamp()),
+            });
+    }
+
+    fn into_uploaded(self) -> Vec<CachedBlob> {
+        self.uploaded
+            .into_inner()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
 }
 
 #[tokio::main]
@@ -199,8 +1424,17 @@ async fn main() -> Result<(), Error> {
     let dynamodb_client = DynamoDbClient::new(&aws_config);
     let secrets_client = SecretsManagerClient::new(&aws_config);
     let repo = DynamoRepository::new(dynamodb_client, config.dynamodb_table_name.clone());
+    let bedrock_client = BedrockClient::new(&aws_config);
+    let http = HttpClient::new(config.http.clone())?;
     run(service_fn(|event: LambdaEvent<Input>| {
-        post_bluesky(event, &repo, &secrets_client, &config)
+        post_bluesky(
+            event,
+            &repo,
+            &http,
+            &secrets_client,
+            &bedrock_client,
+            &config,
+        )
     }))
     .await
 }
@@ -0,0 +1,240 @@
+use ::tracing::instrument;
+use anyhow::Context;
+use atrium_api::com::atproto::repo::strong_ref;
+use aws_config::BehaviorVersion;
+use aws_sdk_dynamodb::Client as DynamoDbClient;
+use aws_sdk_secretsmanager::Client as SecretsManagerClient;
+use chrono::Utc;
+use lambda_runtime::{run, service_fn, tracing, Error, LambdaEvent};
+use rss_bluesky_bridge::bluesky;
+use rss_bluesky_bridge::digest::{compose_digest, split_listed, DigestEntry, MAX_LINK_GRAPHEMES};
+use rss_bluesky_bridge::env;
+use rss_bluesky_bridge::models::{HeldItem, RecordItem};
+use rss_bluesky_bridge::repository::DynamoRepository;
+use serde::Serialize;
+use tracing_subscriber::EnvFilter;
+use unicode_segmentation::UnicodeSegmentation;
+
+const DEFAULT_DIGEST_TITLE: &str = "Today's posts";
+const DEFAULT_DIGEST_MAX_POSTS: usize = 4;
+
+#[derive(Serialize, Debug)]
+struct Output {
+    /// The URIs of the first posts of the digests, one per account. Empty in a dry run.
+    uris: Vec<String>,
+    /// How many items the digests listed.
+    items: usize,
+    dry_run: bool,
+}
+
+struct Config {
+    dynamodb_table_name: String,
+    secret_name: String,
+    /// The text a digest starts with.
+    title: String,
+    /// The most posts a digest is made of. Items that don't fit are listed in the next one.
+    max_posts: usize,
+    /// Logs the digests instead of posting them.
+    dry_run: bool,
+}
+
+impl Config {
+    fn from_env() -> Result<Self, Error> {
+        let dynamodb_table_name = std::env::var("DYNAMODB_TABLE_NAME")
+            .context("DYNAMODB_TABLE_NAME environment variable not set")?;
+
+        if dynamodb_table_name.trim().is_empty() {
+            return Err(Error::from("DYNAMODB_TABLE_NAME cannot be empty"));
+        }
+
+        let secret_name = std::env::var("BLUESKY_CREDENTIALS_SECRET_NAME")
+            .context("BLUESKY_CREDENTIALS_SECRET_NAME environment variable not set")?;
+
+        if secret_name.trim().is_empty() {
+            return Err(Error::from(
+                "BLUESKY_CREDENTIALS_SECRET_NAME cannot be empty",
+            ));
+        }
+
+        let title =
+            env::optional("DIGEST_TITLE").unwrap_or_else(|| DEFAULT_DIGEST_TITLE.to_string());
+        let max_posts = env::parse_or("DIGEST_MAX_POSTS", DEFAULT_DIGEST_MAX_POSTS)?;
+        if max_posts == 0 {
+            return Err(Error::from("DIGEST_MAX_POSTS must be at least 1"));
+        }
+
+        Ok(Self {
+            dynamodb_table_name,
+            secret_name,
+            title,
+            max_posts,
+            dry_run: env::flag("DRY_RUN"),
+        })
+    }
+}
+
+/// Posts the daily digest of every account: a post, or a short thread, listing the titles and links of the items
+/// held for it since the last digest.
+///
+/// In digest mode get-rss-items holds the items it fetched instead of posting them one by one. Listed items are
+/// recorded as posted, with the post listing them, and released; those that didn't fit stay held for the next
+/// digest.
+#[instrument(skip(repo, secrets_client, config))]
+async fn post_digest(
+    _event: LambdaEvent<serde_json::Value>,
+    repo: &DynamoRepository,
+    secrets_client: &SecretsManagerClient,
+    config: &Config,
+) -> Result<Output, Error> {
+    let mut output = Output {
+        uris: Vec::new(),
+        items: 0,
+        dry_run: config.dry_run,
+    };
+
+    // Held items are listed by the time they were held, which the digests keep
+    let mut accounts: Vec<(Option<String>, Vec<HeldItem>)> = Vec::new();
+    for held_item in repo
+        .list_held_items()
+        .await
+        .context("Failed to list held items")?
+    {
+        match accounts
+            .iter_mut()
+            .find(|(account, _)| *account == held_item.account)
+        {
+            Some((_, items)) => items.push(held_item),
+            None => accounts.push((held_item.account.clone(), vec![held_item])),
+        }
+    }
+
+    for (account, held_items) in accounts {
+        let mut listed = Vec::new();
+        let mut entries = Vec::new();
+        for held_item in held_items {
+            match digest_entry(&held_item, repo).await? {
+                Some(entry) => {
+                    listed.push(held_item);
+                    entries.push(entry);
+                }
+                None => repo.release_held_item(&held_item.guid).await?,
+            }
+        }
+
+        let posts = compose_digest(&config.title, &entries, config.max_posts);
+        if posts.is_empty() {
+            continue;
+        }
+        if config.dry_run {
+            for post in &posts {
+                tracing::info!("Dry run, not posting digest post: {}", post.text);
+            }
+            output.items += posts.iter().map(|post| post.entries).sum::<usize>();
+            continue;
+        }
+
+        let secret_name = account.as_deref().unwrap_or(&config.secret_name);
+        let agent = bluesky::login(secrets_client, secret_name).await?;
+        let posted = bluesky::post_thread(posts.iter().map(|post| post.text.clone()), &agent).await;
+        let Some(root) = posted.first() else {
+            return Err(Error::from(format!(
+                "Failed to post the digest of {}",
+                secret_name
+            )));
+        };
+        output.uris.push(root.uri.clone());
+
+        // Items listed by replies that failed to post stay held, like those that didn't fit
+        let (listed, left) = split_listed(listed, &posts, posted.len());
+        for (held_items, posted) in listed.into_iter().zip(&posted) {
+            for held_item in held_items {
+                record_listed_item(&held_item, posted, repo).await?;
+                output.items += 1;
+            }
+        }
+        // Holding them again keeps their ExecutionItems until the next digest, a day away
+        if !left.is_empty() {
+            tracing::info!("{} items wait for the next digest", left.len());
+            repo.hold_items(&left)
+                .await
+                .context("Failed to hold items for the next digest")?;
+        }
+    }
+
+    tracing::info!("Digest result: {:?}", output);
+    Ok(output)
+}
+
+/// Reads the title and link of a held item for its digest entry.
+///
+/// # Returns
+///
+/// A Result containing the entry, or None for items to drop: those posted already, gone from the table or without
+/// a link a digest can list.
+async fn digest_entry(
+    held_item: &HeldItem,
+    repo: &DynamoRepository,
+) -> anyhow::Result<Option<DigestEntry>> {
+    if repo.record_item_exists(&held_item.guid).await? {
+        return Ok(None);
+    }
+    let item = match repo
+        .get_execution_item(&held_item.execution_id, &held_item.guid)
+        .await
+    {
+        Ok(item) => item,
+        Err(e) => {
+            tracing::warn!("Dropping held item {}: {:?}", held_item.guid, e);
+            return Ok(None);
+        }
+    };
+    match item.link {
+        Some(link) if link.graphemes(true).count() <= MAX_LINK_GRAPHEMES => Ok(Some(DigestEntry {
+            title: item.title.unwrap_or_default(),
+            link,
+        })),
+        _ => {
+            tracing::warn!(
+                "Leaving {} out of the digest, its link is missing or too long",
+                held_item.guid
+            );
+            Ok(None)
+        }
+    }
+}
+
+/// Records a listed item as posted, with the post that lists it, and releases it.
+async fn record_listed_item(
+    held_item: &HeldItem,
+    post: &strong_ref::MainData,
+    repo: &DynamoRepository,
+) -> anyhow::Result<()> {
+    let mut record_item =
+        RecordItem::new(held_item.guid.clone()).context("Failed to create RecordItem")?;
+    record_item.uri = Some(post.uri.clone());
+    record_item.cid = Some(post.cid.as_ref().to_string());
+    record_item.posted_at = Some(Utc::now().timestamp());
+    repo.create_record_item(&record_item)
+        .await
+        .context("Failed to create record item in DynamoDB")?;
+    repo.release_held_item(&held_item.guid).await
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Error> {
+    tracing_subscriber::fmt()
+        .json()
+        .with_env_filter(EnvFilter::from_default_env())
+        .init();
+
+    let config = Config::from_env().expect("Failed to load configuration");
+    let aws_config = aws_config::load_defaults(BehaviorVersion::latest()).await;
+    let dynamodb_client = DynamoDbClient::new(&aws_config);
+    let secrets_client = SecretsManagerClient::new(&aws_config);
+    let repo = DynamoRepository::new(dynamodb_client, config.dynamodb_table_name.clone());
+
+    run(service_fn(|event: LambdaEvent<serde_json::Value>| {
+        post_digest(event, &repo, &secrets_client, &config)
+    }))
+    .await
+}
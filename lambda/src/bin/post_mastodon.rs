@@ -0,0 +1,136 @@
+use ::tracing::instrument;
+use anyhow::Context;
+use aws_config::BehaviorVersion;
+use aws_sdk_dynamodb::Client as DynamoDbClient;
+use aws_sdk_secretsmanager::Client as SecretsManagerClient;
+use lambda_runtime::{run, service_fn, tracing, Error, LambdaEvent};
+use rss_bluesky_bridge::crosspost::TextSettings;
+use rss_bluesky_bridge::env;
+use rss_bluesky_bridge::mastodon::{MastodonClient, MastodonPoster, DEFAULT_MAX_STATUS_CHARS};
+use rss_bluesky_bridge::models::ItemIdentifier;
+use rss_bluesky_bridge::poster::Poster;
+use rss_bluesky_bridge::repository::DynamoRepository;
+use serde::{Deserialize, Serialize};
+use tracing_subscriber::EnvFilter;
+
+/// The output of update-dynamodb, as the item is cross-posted once its Bluesky post is recorded.
+#[derive(Deserialize)]
+struct Input {
+    #[serde(flatten)]
+    item_identifier: ItemIdentifier,
+    #[serde(default)]
+    dry_run: bool,
+}
+
+#[derive(Serialize)]
+struct Output {
+    #[serde(flatten)]
+    item_identifier: ItemIdentifier,
+    /// The web address of the status, None in a dry run.
+    url: Option<String>,
+    /// Passed on for error-check, which keeps the window of a dry run where it is.
+    dry_run: bool,
+}
+
+struct Config {
+    dynamodb_table_name: String,
+    secret_name: String,
+    /// Logs the statuses instead of posting them.
+    dry_run: bool,
+}
+
+impl Config {
+    fn from_env() -> Result<Self, Error> {
+        let dynamodb_table_name = std::env::var("DYNAMODB_TABLE_NAME")
+            .context("DYNAMODB_TABLE_NAME environment variable not set")?;
+
+        if dynamodb_table_name.trim().is_empty() {
+            return Err(Error::from("DYNAMODB_TABLE_NAME cannot be empty"));
+        }
+
+        let secret_name = std::env::var("MASTODON_CREDENTIALS_SECRET_NAME")
+            .context("MASTODON_CREDENTIALS_SECRET_NAME environment variable not set")?;
+
+        if secret_name.trim().is_empty() {
+            return Err(Error::from(
+                "MASTODON_CREDENTIALS_SECRET_NAME cannot be empty",
+            ));
+        }
+
+        let dry_run = env::flag("DRY_RUN");
+
+        Ok(Self {
+            dynamodb_table_name,
+            secret_name,
+            dry_run,
+        })
+    }
+}
+
+/// Sets up the poster of the account, laid out like the Bluesky posts unless statuses have a template of their own.
+fn poster_from_env(client: MastodonClient) -> Result<MastodonPoster, Error> {
+    Ok(MastodonPoster {
+        client,
+        text: TextSettings::from_env("MASTODON_POST_TEMPLATE")?,
+        max_status_chars: env::parse_or("MASTODON_MAX_CHARS", DEFAULT_MAX_STATUS_CHARS)?,
+        visibility: env::optional("MASTODON_VISIBILITY"),
+    })
+}
+
+/// Cross-posts an item to Mastodon, laid out like its Bluesky post.
+///
+/// It runs after update-dynamodb, so the item is deduplicated along with its Bluesky post: an item that failed
+/// here is recorded as posted already and isn't posted to Mastodon on a later run.
+#[instrument(skip(event, repo, poster, config))]
+async fn post_mastodon(
+    event: LambdaEvent<Input>,
+    repo: &DynamoRepository,
+    poster: &MastodonPoster,
+    config: &Config,
+) -> Result<Output, Error> {
+    let item_identifier = event.payload.item_identifier;
+    tracing::info!("Posting to Mastodon for item: {:?}", item_identifier);
+
+    let item = repo
+        .get_execution_item(&item_identifier.execution_id, &item_identifier.guid)
+        .await
+        .with_context(|| {
+            format!(
+                "Failed to get item from DynamoDB for execution-id {:?} and guid {:?}",
+                item_identifier.execution_id, item_identifier.guid
+            )
+        })?;
+
+    let result = poster
+        .post(&item, config.dry_run || event.payload.dry_run)
+        .await?;
+
+    Ok(Output {
+        item_identifier,
+        url: result.url,
+        dry_run: result.dry_run,
+    })
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Error> {
+    tracing_subscriber::fmt()
+        .json()
+        .with_env_filter(EnvFilter::from_default_env())
+        .init();
+
+    let config = Config::from_env().expect("Failed to load configuration");
+    let aws_config = aws_config::load_defaults(BehaviorVersion::latest()).await;
+    let dynamodb_client = DynamoDbClient::new(&aws_config);
+    let secrets_client = SecretsManagerClient::new(&aws_config);
+    let repo = DynamoRepository::new(dynamodb_client, config.dynamodb_table_name.clone());
+    let client = MastodonClient::from_secret(&secrets_client, &config.secret_name)
+        .await
+        .expect("Failed to load Mastodon credentials");
+    let poster = poster_from_env(client).expect("Failed to load configuration");
+
+    run(service_fn(|event: LambdaEvent<Input>| {
+        post_mastodon(event, &repo, &poster, &config)
+    }))
+    .await
+}
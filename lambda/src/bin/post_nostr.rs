@@ -0,0 +1,161 @@
+use ::tracing::instrument;
+use anyhow::Context;
+use aws_config::BehaviorVersion;
+use aws_sdk_dynamodb::Client as DynamoDbClient;
+use aws_sdk_secretsmanager::Client as SecretsManagerClient;
+use lambda_runtime::{run, service_fn, tracing, Error, LambdaEvent};
+use rss_bluesky_bridge::crosspost::TextSettings;
+use rss_bluesky_bridge::env;
+use rss_bluesky_bridge::filters::parse_list;
+use rss_bluesky_bridge::models::ItemIdentifier;
+use rss_bluesky_bridge::nostr::{NostrKeys, NostrPoster, DEFAULT_MAX_NOTE_GRAPHEMES};
+use rss_bluesky_bridge::poster::Poster;
+use rss_bluesky_bridge::repository::DynamoRepository;
+use serde::{Deserialize, Serialize};
+use tracing_subscriber::EnvFilter;
+
+/// The output of update-dynamodb (or post-mastodon), as the item is cross-posted once its Bluesky post is recorded.
+#[derive(Deserialize)]
+struct Input {
+    #[serde(flatten)]
+    item_identifier: ItemIdentifier,
+    #[serde(default)]
+    dry_run: bool,
+}
+
+#[derive(Serialize)]
+struct Output {
+    #[serde(flatten)]
+    item_identifier: ItemIdentifier,
+    /// The id of the note, None in a dry run.
+    event_id: Option<String>,
+    /// Passed on for error-check, which keeps the window of a dry run where it is.
+    dry_run: bool,
+}
+
+struct Config {
+    dynamodb_table_name: String,
+    secret_name: String,
+    /// Logs the notes instead of publishing them.
+    dry_run: bool,
+}
+
+impl Config {
+    fn from_env() -> Result<Self, Error> {
+        let dynamodb_table_name = std::env::var("DYNAMODB_TABLE_NAME")
+            .context("DYNAMODB_TABLE_NAME environment variable not set")?;
+
+        if dynamodb_table_name.trim().is_empty() {
+            return Err(Error::from("DYNAMODB_TABLE_NAME cannot be empty"));
+        }
+
+        let secret_name = std::env::var("NOSTR_SECRET_NAME")
+            .context("NOSTR_SECRET_NAME environment variable not set")?;
+
+        if secret_name.trim().is_empty() {
+            return Err(Error::from("NOSTR_SECRET_NAME cannot be empty"));
+        }
+
+        let dry_run = env::flag("DRY_RUN");
+
+        Ok(Self {
+            dynamodb_table_name,
+            secret_name,
+            dry_run,
+        })
+    }
+}
+
+/// Sets up the poster of the account, laid out like the Bluesky posts unless notes have a template of their own.
+fn poster_from_env(keys: NostrKeys) -> Result<NostrPoster, Error> {
+    let relays = parse_list(&env::optional("NOSTR_RELAYS").unwrap_or_default());
+    if relays.is_empty() {
+        return Err(Error::from("NOSTR_RELAYS must name at least one relay"));
+    }
+
+    Ok(NostrPoster {
+        keys,
+        relays,
+        text: TextSettings::from_env("NOSTR_POST_TEMPLATE")?,
+        max_note_graphemes: env::parse_or("NOSTR_MAX_GRAPHEMES", DEFAULT_MAX_NOTE_GRAPHEMES)?,
+    })
+}
+
+/// Cross-posts an item to Nostr as a text note, laid out like its Bluesky post, and publishes it to the relays.
+///
+/// The item fails only when no relay takes the note. Like post-mastodon it runs after update-dynamodb, so a failed
+/// item isn't published on a later run.
+#[instrument(skip(event, repo, poster, config))]
+async fn post_nostr(
+    event: LambdaEvent<Input>,
+    repo: &DynamoRepository,
+    poster: &NostrPoster,
+    config: &Config,
+) -> Result<Output, Error> {
+    let item_identifier = event.payload.item_identifier;
+    tracing::info!("Posting to Nostr for item: {:?}", item_identifier);
+
+    let item = repo
+        .get_execution_item(&item_identifier.execution_id, &item_identifier.guid)
+        .await
+        .with_context(|| {
+            format!(
+                "Failed to get item from DynamoDB for execution-id {:?} and guid {:?}",
+                item_identifier.execution_id, item_identifier.guid
+            )
+        })?;
+
+    let result = poster
+        .post(&item, config.dry_run || event.payload.dry_run)
+        .await?;
+
+    Ok(Output {
+        item_identifier,
+        event_id: result.id,
+        dry_run: result.dry_run,
+    })
+}
+
+/// Reads the secret key of the Nostr account from a Secrets Manager secret holding `{"secret_key": "nsec1..."}`.
+async fn load_keys(
+    secrets_client: &SecretsManagerClient,
+    secret_name: &str,
+) -> anyhow::Result<NostrKeys> {
+    let secret = secrets_client
+        .get_secret_value()
+        .secret_id(secret_name)
+        .send()
+        .await
+        .context("Failed to retrieve secret")?;
+
+    let secret_string = secret.secret_string().context("Secret string is empty")?;
+    let credentials: serde_json::Value =
+        serde_json::from_str(secret_string).context("Failed to parse secret JSON")?;
+    let secret_key = credentials["secret_key"]
+        .as_str()
+        .context("Secret key not found in secret")?;
+    NostrKeys::parse(secret_key)
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Error> {
+    tracing_subscriber::fmt()
+        .json()
+        .with_env_filter(EnvFilter::from_default_env())
+        .init();
+
+    let config = Config::from_env().expect("Failed to load configuration");
+    let aws_config = aws_config::load_defaults(BehaviorVersion::latest()).await;
+    let dynamodb_client = DynamoDbClient::new(&aws_config);
+    let secrets_client = SecretsManagerClient::new(&aws_config);
+    let repo = DynamoRepository::new(dynamodb_client, config.dynamodb_table_name.clone());
+    let keys = load_keys(&secrets_client, &config.secret_name)
+        .await
+        .expect("Failed to load Nostr key");
+    let poster = poster_from_env(keys).expect("Failed to load configuration");
+
+    run(service_fn(|event: LambdaEvent<Input>| {
+        post_nostr(event, &repo, &poster, &config)
+    }))
+    .await
+}
@@ -0,0 +1,224 @@
+use ::tracing::instrument;
+use anyhow::Context;
+use aws_config::BehaviorVersion;
+use aws_sdk_dynamodb::Client as DynamoDbClient;
+use aws_sdk_secretsmanager::Client as SecretsManagerClient;
+use chrono::{Duration, Utc};
+use lambda_runtime::{run, service_fn, tracing, Error, LambdaEvent};
+use rss_bluesky_bridge::bluesky;
+use rss_bluesky_bridge::digest::{compose_digest, top_posts, DigestEntry, MAX_LINK_GRAPHEMES};
+use rss_bluesky_bridge::env;
+use rss_bluesky_bridge::models::RecentPost;
+use rss_bluesky_bridge::repository::DynamoRepository;
+use serde::Serialize;
+use std::collections::HashMap;
+use tracing_subscriber::EnvFilter;
+use unicode_segmentation::UnicodeSegmentation;
+
+const DEFAULT_ROUNDUP_TITLE: &str = "This week:";
+const DEFAULT_ROUNDUP_MAX_ITEMS: usize = 5;
+const DEFAULT_ROUNDUP_MAX_POSTS: usize = 3;
+/// The days a roundup looks back on. RecentPosts, which hold the titles and links, aren't kept any longer.
+const ROUNDUP_DAYS: i64 = 7;
+
+#[derive(Serialize, Debug)]
+struct Output {
+    /// The URIs of the first posts of the roundups, one per account. Empty in a dry run.
+    uris: Vec<String>,
+    /// How many items the roundups listed.
+    items: usize,
+    dry_run: bool,
+}
+
+struct Config {
+    dynamodb_table_name: String,
+    secret_name: String,
+    /// The text a roundup starts with.
+    title: String,
+    /// The most items a roundup lists.
+    max_items: usize,
+    /// The most posts a roundup is made of.
+    max_posts: usize,
+    /// Whether the items with the most likes, reposts, quotes and replies are listed, rather than the latest.
+    rank_by_engagement: bool,
+    /// Logs the roundups instead of posting them.
+    dry_run: bool,
+}
+
+impl Config {
+    fn from_env() -> Result<Self, Error> {
+        let dynamodb_table_name = std::env::var("DYNAMODB_TABLE_NAME")
+            .context("DYNAMODB_TABLE_NAME environment variable not set")?;
+
+        if dynamodb_table_name.trim().is_empty() {
+            return Err(Error::from("DYNAMODB_TABLE_NAME cannot be empty"));
+        }
+
+        let secret_name = std::env::var("BLUESKY_CREDENTIALS_SECRET_NAME")
+            .context("BLUESKY_CREDENTIALS_SECRET_NAME environment variable not set")?;
+
+        if secret_name.trim().is_empty() {
+            return Err(Error::from(
+                "BLUESKY_CREDENTIALS_SECRET_NAME cannot be empty",
+            ));
+        }
+
+        let title =
+            env::optional("ROUNDUP_TITLE").unwrap_or_else(|| DEFAULT_ROUNDUP_TITLE.to_string());
+        let max_items = env::parse_or("ROUNDUP_MAX_ITEMS", DEFAULT_ROUNDUP_MAX_ITEMS)?;
+        let max_posts = env::parse_or("ROUNDUP_MAX_POSTS", DEFAULT_ROUNDUP_MAX_POSTS)?;
+        if max_items == 0 || max_posts == 0 {
+            return Err(Error::from(
+                "ROUNDUP_MAX_ITEMS and ROUNDUP_MAX_POSTS must be at least 1",
+            ));
+        }
+
+        Ok(Self {
+            dynamodb_table_name,
+            secret_name,
+            title,
+            max_items,
+            max_posts,
+            rank_by_engagement: env::flag("ROUNDUP_RANK_BY_ENGAGEMENT"),
+            dry_run: env::flag("DRY_RUN"),
+        })
+    }
+}
+
+/// Posts the weekly roundup of every account: a thread listing the titles and links of the week's top posts.
+///
+/// The posts are those of the RecordItems posted in the last week that weren't deleted since, with the titles and
+/// links of their RecentPosts. Items posted in a digest have no RecentPost, and aren't listed.
+#[instrument(skip(repo, secrets_client, config))]
+async fn post_roundup(
+    _event: LambdaEvent<serde_json::Value>,
+    repo: &DynamoRepository,
+    secrets_client: &SecretsManagerClient,
+    config: &Config,
+) -> Result<Output, Error> {
+    let now = Utc::now().timestamp();
+    let since = now - Duration::days(ROUNDUP_DAYS).num_seconds();
+
+    let uris: HashMap<String, String> = repo
+        .list_record_items_posted_between(since, now)
+        .await
+        .context("Failed to list the week's record items")?
+        .into_iter()
+        .filter(|record_item| record_item.deleted_at.is_none())
+        .filter_map(|record_item| Some((record_item.guid, record_item.uri?)))
+        .collect();
+
+    // Every post of an account goes into its roundup, in the order they were posted
+    let mut accounts: Vec<(Option<String>, Vec<RecentPost>)> = Vec::new();
+    for post in repo
+        .list_recent_posts(since)
+        .await
+        .context("Failed to list the week's posts")?
+    {
+        let listable = post.title.is_some()
+            && post
+                .link
+                .as_ref()
+                .is_some_and(|link| link.graphemes(true).count() <= MAX_LINK_GRAPHEMES);
+        if !listable || !uris.contains_key(&post.guid) {
+            continue;
+        }
+        match accounts
+            .iter_mut()
+            .find(|(account, _)| *account == post.account)
+        {
+            Some((_, posts)) => posts.push(post),
+            None => accounts.push((post.account.clone(), vec![post])),
+        }
+    }
+
+    let mut output = Output {
+        uris: Vec::new(),
+        items: 0,
+        dry_run: config.dry_run,
+    };
+    for (account, posts) in accounts {
+        let secret_name = account.as_deref().unwrap_or(&config.secret_name);
+        let agent = bluesky::login(secrets_client, secret_name).await?;
+
+        // Without engagement the roundup still goes out, with the latest posts
+        let engagement = if config.rank_by_engagement {
+            let post_uris: Vec<String> = posts
+                .iter()
+                .filter_map(|post| uris.get(&post.guid).cloned())
+                .collect();
+            match bluesky::post_stats(&post_uris, &agent).await {
+                Ok(by_uri) => Some(
+                    posts
+                        .iter()
+                        .filter_map(|post| {
+                            let stats = by_uri.get(uris.get(&post.guid)?)?;
+                            Some((post.guid.clone(), stats.engagement))
+                        })
+                        .collect::<HashMap<_, _>>(),
+                ),
+                Err(e) => {
+                    tracing::warn!("Failed to get the engagement of the posts: {:?}", e);
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
+        let entries: Vec<DigestEntry> = top_posts(posts, engagement.as_ref(), config.max_items)
+            .into_iter()
+            .filter_map(|post| {
+                Some(DigestEntry {
+                    title: post.title?,
+                    link: post.link?,
+                })
+            })
+            .collect();
+        let roundup = compose_digest(&config.title, &entries, config.max_posts);
+        if roundup.is_empty() {
+            continue;
+        }
+        let items = roundup.iter().map(|post| post.entries).sum::<usize>();
+
+        if config.dry_run {
+            for post in &roundup {
+                tracing::info!("Dry run, not posting roundup post: {}", post.text);
+            }
+            output.items += items;
+            continue;
+        }
+
+        let posted = bluesky::post_thread(roundup.into_iter().map(|post| post.text), &agent).await;
+        let Some(root) = posted.first() else {
+            return Err(Error::from(format!(
+                "Failed to post the roundup of {}",
+                secret_name
+            )));
+        };
+        output.uris.push(root.uri.clone());
+        output.items += items;
+    }
+
+    tracing::info!("Roundup result: {:?}", output);
+    Ok(output)
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Error> {
+    tracing_subscriber::fmt()
+        .json()
+        .with_env_filter(EnvFilter::from_default_env())
+        .init();
+
+    let config = Config::from_env().expect("Failed to load configuration");
+    let aws_config = aws_config::load_defaults(BehaviorVersion::latest()).await;
+    let dynamodb_client = DynamoDbClient::new(&aws_config);
+    let secrets_client = SecretsManagerClient::new(&aws_config);
+    let repo = DynamoRepository::new(dynamodb_client, config.dynamodb_table_name.clone());
+
+    run(service_fn(|event: LambdaEvent<serde_json::Value>| {
+        post_roundup(event, &repo, &secrets_client, &config)
+    }))
+    .await
+}
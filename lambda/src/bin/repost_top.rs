@@ -0,0 +1,222 @@
+use ::tracing::instrument;
+use anyhow::Context;
+use atrium_api::app::bsky::feed::repost;
+use atrium_api::com::atproto::repo::strong_ref;
+use atrium_api::types::string::Datetime;
+use aws_config::BehaviorVersion;
+use aws_sdk_dynamodb::Client as DynamoDbClient;
+use aws_sdk_secretsmanager::Client as SecretsManagerClient;
+use chrono::{Duration, Utc};
+use lambda_runtime::{run, service_fn, tracing, Error, LambdaEvent};
+use rss_bluesky_bridge::bluesky;
+use rss_bluesky_bridge::digest::top_posts;
+use rss_bluesky_bridge::env;
+use rss_bluesky_bridge::models::RecentPost;
+use rss_bluesky_bridge::repository::DynamoRepository;
+use serde::Serialize;
+use std::collections::HashMap;
+use tracing_subscriber::EnvFilter;
+
+const DEFAULT_REPROMOTE_COUNT: usize = 1;
+const DEFAULT_REPROMOTE_MIN_ENGAGEMENT: i64 = 5;
+const DEFAULT_REPROMOTE_MIN_AGE_HOURS: i64 = 12;
+/// How far back posts are reposted. RecentPosts, which tell the feed of a post, aren't kept any longer.
+const REPROMOTE_DAYS: i64 = 7;
+
+#[derive(Serialize, Debug)]
+struct Output {
+    /// The URIs of the reposted posts, or of those that would have been in a dry run.
+    reposted: Vec<String>,
+    dry_run: bool,
+}
+
+struct Config {
+    dynamodb_table_name: String,
+    secret_name: String,
+    /// Whether the posts of feeds that don't say otherwise are reposted.
+    repromote_posts: bool,
+    /// The most posts reposted to an account a run.
+    count: usize,
+    /// The fewest likes, reposts, quotes and replies together a post needs to be reposted.
+    min_engagement: i64,
+    /// How old a post has to be to be reposted, so it's had time to be seen.
+    min_age: Duration,
+    /// Logs the reposts instead of making them.
+    dry_run: bool,
+}
+
+impl Config {
+    fn from_env() -> Result<Self, Error> {
+        let dynamodb_table_name = std::env::var("DYNAMODB_TABLE_NAME")
+            .context("DYNAMODB_TABLE_NAME environment variable not set")?;
+
+        if dynamodb_table_name.trim().is_empty() {
+            return Err(Error::from("DYNAMODB_TABLE_NAME cannot be empty"));
+        }
+
+        let secret_name = std::env::var("BLUESKY_CREDENTIALS_SECRET_NAME")
+            .context("BLUESKY_CREDENTIALS_SECRET_NAME environment variable not set")?;
+
+        if secret_name.trim().is_empty() {
+            return Err(Error::from(
+                "BLUESKY_CREDENTIALS_SECRET_NAME cannot be empty",
+            ));
+        }
+
+        Ok(Self {
+            dynamodb_table_name,
+            secret_name,
+            repromote_posts: env::flag("REPROMOTE_POSTS"),
+            count: env::parse_or("REPROMOTE_COUNT", DEFAULT_REPROMOTE_COUNT)?,
+            min_engagement: env::parse_or(
+                "REPROMOTE_MIN_ENGAGEMENT",
+                DEFAULT_REPROMOTE_MIN_ENGAGEMENT,
+            )?,
+            min_age: Duration::hours(env::parse_or(
+                "REPROMOTE_MIN_AGE_HOURS",
+                DEFAULT_REPROMOTE_MIN_AGE_HOURS,
+            )?),
+            dry_run: env::flag("DRY_RUN"),
+        })
+    }
+}
+
+/// Reposts the best-performing posts of the last week, run at the hours the accounts' readers are around.
+///
+/// The posts with the most likes, reposts, quotes and replies that reach the minimum are reposted, each at most once:
+/// posts the account reposted already are passed over. Feeds take part as their `repromote` setting, or
+/// REPROMOTE_POSTS for those without one, says.
+#[instrument(skip(repo, secrets_client, config))]
+async fn repost_top(
+    _event: LambdaEvent<serde_json::Value>,
+    repo: &DynamoRepository,
+    secrets_client: &SecretsManagerClient,
+    config: &Config,
+) -> Result<Output, Error> {
+    let now = Utc::now();
+    let since = (now - Duration::days(REPROMOTE_DAYS)).timestamp();
+    let until = (now - config.min_age).timestamp();
+
+    let uris: HashMap<String, String> = repo
+        .list_record_items_posted_between(since, until)
+        .await
+        .context("Failed to list recent record items")?
+        .into_iter()
+        .filter(|record_item| record_item.deleted_at.is_none())
+        .filter_map(|record_item| Some((record_item.guid, record_item.uri?)))
+        .collect();
+    let repromoted_feeds: HashMap<String, bool> = repo
+        .list_feed_configs()
+        .await
+        .context("Failed to list feed configs")?
+        .into_iter()
+        .filter_map(|feed| Some((feed.feed_url, feed.repromote?)))
+        .collect();
+
+    let mut accounts: Vec<(Option<String>, Vec<RecentPost>)> = Vec::new();
+    for post in repo
+        .list_recent_posts(since)
+        .await
+        .context("Failed to list recent posts")?
+    {
+        let repromoted = post
+            .feed_url
+            .as_ref()
+            .and_then(|feed_url| repromoted_feeds.get(feed_url))
+            .copied()
+            .unwrap_or(config.repromote_posts);
+        if !repromoted || !uris.contains_key(&post.guid) {
+            continue;
+        }
+        match accounts
+            .iter_mut()
+            .find(|(account, _)| *account == post.account)
+        {
+            Some((_, posts)) => posts.push(post),
+            None => accounts.push((post.account.clone(), vec![post])),
+        }
+    }
+
+    let mut output = Output {
+        reposted: Vec::new(),
+        dry_run: config.dry_run,
+    };
+    for (account, posts) in accounts {
+        let secret_name = account.as_deref().unwrap_or(&config.secret_name);
+        let agent = bluesky::login(secrets_client, secret_name).await?;
+
+        let post_uris: Vec<String> = posts
+            .iter()
+            .filter_map(|post| uris.get(&post.guid).cloned())
+            .collect();
+        let stats = bluesky::post_stats(&post_uris, &agent).await?;
+        let engagement: HashMap<String, i64> = posts
+            .iter()
+            .filter_map(|post| {
+                let stats = stats.get(uris.get(&post.guid)?)?;
+                (!stats.reposted && stats.engagement >= config.min_engagement)
+                    .then(|| (post.guid.clone(), stats.engagement))
+            })
+            .collect();
+        let candidates = posts
+            .into_iter()
+            .filter(|post| engagement.contains_key(&post.guid))
+            .collect();
+
+        for post in top_posts(candidates, Some(&engagement), config.count) {
+            let Some((uri, stats)) = uris
+                .get(&post.guid)
+                .and_then(|uri| Some((uri, stats.get(uri)?)))
+            else {
+                continue;
+            };
+            if config.dry_run {
+                tracing::info!(
+                    "Dry run, not reposting {} with an engagement of {}",
+                    uri,
+                    stats.engagement
+                );
+            } else {
+                agent
+                    .create_record(repost::RecordData {
+                        created_at: Datetime::now(),
+                        subject: strong_ref::MainData {
+                            cid: stats.cid.clone(),
+                            uri: uri.clone(),
+                        }
+                        .into(),
+                    })
+                    .await
+                    .with_context(|| format!("Failed to repost {}", uri))?;
+                tracing::info!(
+                    "Reposted {} with an engagement of {}",
+                    uri,
+                    stats.engagement
+                );
+            }
+            output.reposted.push(uri.clone());
+        }
+    }
+
+    tracing::info!("Repost result: {:?}", output);
+    Ok(output)
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Error> {
+    tracing_subscriber::fmt()
+        .json()
+        .with_env_filter(EnvFilter::from_default_env())
+        .init();
+
+    let config = Config::from_env().expect("Failed to load configuration");
+    let aws_config = aws_config::load_defaults(BehaviorVersion::latest()).await;
+    let dynamodb_client = DynamoDbClient::new(&aws_config);
+    let secrets_client = SecretsManagerClient::new(&aws_config);
+    let repo = DynamoRepository::new(dynamodb_client, config.dynamodb_table_name.clone());
+
+    run(service_fn(|event: LambdaEvent<serde_json::Value>| {
+        repost_top(event, &repo, &secrets_client, &config)
+    }))
+    .await
+}
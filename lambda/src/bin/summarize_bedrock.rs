@@ -3,15 +3,41 @@ use anyhow::Context;
 use aws_config::BehaviorVersion;
 use aws_sdk_bedrockruntime::Client as BedrockClient;
 use aws_sdk_dynamodb::Client as DynamoDbClient;
-use lambda_runtime::{run, service_fn, tracing, Error, LambdaEvent};
-use rss_bluesky_bridge::text_utils::truncate_to_word;
-use rss_bluesky_bridge::{models::ItemIdentifier, repository::DynamoRepository};
+use aws_sdk_secretsmanager::Client as SecretsManagerClient;
+use lambda_runtime::{run, service_fn, tracing, Diagnostic, Error, LambdaEvent};
+use rss_bluesky_bridge::env as bridge_env;
+use rss_bluesky_bridge::filters::parse_list;
+use rss_bluesky_bridge::metrics::{emf_record, Metric};
+use rss_bluesky_bridge::models::{CachedSummary, ItemIdentifier};
+use rss_bluesky_bridge::openai::{OpenAiClient, DEFAULT_OPENAI_BASE_URL};
+use rss_bluesky_bridge::repository::DynamoRepository;
+use rss_bluesky_bridge::retry::backoff_delay;
+use rss_bluesky_bridge::summarize::{
+    chunk_prompt, parse_summary, parse_usage, shorten_prompt, split_into_chunks, summary_cache_key,
+    summary_prompt, summary_request, summary_source, Completion, InferenceParams, ModelFamily,
+    TokenUsage, DEFAULT_MAX_INPUT_GRAPHEMES,
+};
+use rss_bluesky_bridge::text_utils::{truncate_to_word, MAX_POST_GRAPHEMES};
 use serde::{Deserialize, Serialize};
 use std::env;
+use std::fmt;
+use std::time::Duration;
 use tracing_subscriber::EnvFilter;
 use unicode_segmentation::UnicodeSegmentation;
 
-const MAX_BSKY_GRAPHEMES: usize = 290;
+/// Summary length asked for when AI_SUMMARY_MAX_GRAPHEMES isn't usable, leaving room for a short prefix or title.
+const DEFAULT_AI_SUMMARY_GRAPHEMES: i64 = 280;
+/// How many times a throttled model is asked again before the next one of the chain takes over.
+const MAX_THROTTLING_RETRIES: u32 = 3;
+/// Delay ceiling for the first retry of a throttled request.
+const THROTTLING_BASE_DELAY: Duration = Duration::from_secs(1);
+/// The error type of failures the state machine retries, as Bedrock may have capacity again a little later.
+const THROTTLED_ERROR_TYPE: &str = "BedrockThrottled";
+const DEFAULT_SUMMARY_CACHE_DAYS: i64 = 30;
+const DEFAULT_SHORTEN_ATTEMPTS: u32 = 2;
+const DEFAULT_MAX_CHUNKS: usize = 4;
+/// The length the chunks of a long text are summarized in, before their summaries are summarized together.
+const CHUNK_SUMMARY_GRAPHEMES: usize = 800;
 
 #[derive(Deserialize)]
 struct Input {
@@ -25,11 +51,90 @@ struct Output {
     item_identifier: ItemIdentifier,
 }
 
+/// Bedrock kept throttling a model after the retries.
+#[derive(Debug)]
+struct Throttled;
+
+impl fmt::Display for Throttled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Bedrock throttled the request")
+    }
+}
+
+impl std::error::Error for Throttled {}
+
+/// The error the lambda fails with, typed so the state machine can tell throttling, which it retries, from failures
+/// retrying won't fix.
+#[derive(Debug)]
+enum SummarizeError {
+    Throttled(anyhow::Error),
+    Failed(anyhow::Error),
+}
+
+impl From<anyhow::Error> for SummarizeError {
+    fn from(e: anyhow::Error) -> Self {
+        if e.downcast_ref::<Throttled>().is_some() {
+            SummarizeError::Throttled(e)
+        } else {
+            SummarizeError::Failed(e)
+        }
+    }
+}
+
+impl From<SummarizeError> for Diagnostic {
+    fn from(e: SummarizeError) -> Self {
+        let (error_type, e) = match e {
+            SummarizeError::Throttled(e) => (THROTTLED_ERROR_TYPE, e),
+            SummarizeError::Failed(e) => ("SummarizeFailed", e),
+        };
+        Diagnostic {
+            error_type: error_type.to_string(),
+            error_message: format!("{:#}", e),
+        }
+    }
+}
+
+/// Where an OpenAI-compatible API that writes the summaries instead of Bedrock is.
+struct OpenAiSettings {
+    base_url: String,
+    model: String,
+    /// The Secrets Manager secret holding the API key.
+    secret_name: String,
+}
+
 struct Config {
     dynamodb_table_name: String,
     enable_ai_summary: bool,
-    ai_model_id: String,
+    /// The Bedrock models or inference profiles summaries are written with, in the order they're tried, with the
+    /// families that set the format of their requests and responses.
+    models: Vec<(String, ModelFamily)>,
     ai_summary_max_graphemes: i64,
+    /// How many times a summary longer than AI_SUMMARY_MAX_GRAPHEMES is sent back to be shortened before it's
+    /// truncated instead.
+    shorten_attempts: u32,
+    /// Whether items are summarized from the text of their article, when it was extracted and says more than their
+    /// description.
+    summarize_article: bool,
+    /// The most of the text of an item a summary is written from at once. Longer texts are split into chunks.
+    max_input_graphemes: usize,
+    /// The most chunks a long text is split into, each summarized on its own before their summaries are
+    /// summarized together. Text past the last chunk is cut. 1 cuts long texts to a single chunk.
+    max_chunks: usize,
+    /// The settings summaries are written with, unless the FeedConfig of an item's feed has its own.
+    inference_params: InferenceParams,
+    /// BCP 47 tag of the language summaries are written in, translating items in other languages. None for the
+    /// language of each item.
+    target_language: Option<String>,
+    /// How long summaries are reused for items with the same prompt. None when they aren't.
+    summary_cache_ttl: Option<chrono::Duration>,
+    /// Whether the tokens of each request are published as CloudWatch metrics per feed.
+    usage_metrics: bool,
+    /// Prices of 1000 input and output tokens, which the estimated cost metric is worked out with. Zero for no cost
+    /// metric.
+    input_token_price: f64,
+    output_token_price: f64,
+    /// The OpenAI-compatible API summaries are written with. None for Bedrock.
+    openai: Option<OpenAiSettings>,
 }
 
 impl Config {
@@ -52,44 +157,491 @@ impl Config {
             .context("Failed to parse AI_SUMMARY_MAX_GRAPHEMES as an integer")
             .map_err(Error::from)?;
 
-        let ai_model_id: String = env::var("AI_MODEL_ID")
-            .context("AI_MODEL_ID environment variable not set")
-            .map_err(Error::from)?;
+        // Deployments without Bedrock in their region summarize with an OpenAI-compatible API
+        let openai = match env::var("SUMMARY_PROVIDER")
+            .unwrap_or_default()
+            .trim()
+            .to_lowercase()
+            .as_str()
+        {
+            "" | "bedrock" => None,
+            "openai" => Some(OpenAiSettings {
+                base_url: bridge_env::optional("OPENAI_BASE_URL")
+                    .unwrap_or_else(|| DEFAULT_OPENAI_BASE_URL.to_string()),
+                model: bridge_env::optional("OPENAI_MODEL").ok_or_else(|| {
+                    Error::from("SUMMARY_PROVIDER is openai, but OPENAI_MODEL is not set")
+                })?,
+                secret_name: bridge_env::optional("OPENAI_API_KEY_SECRET_NAME").ok_or_else(
+                    || {
+                        Error::from(
+                            "SUMMARY_PROVIDER is openai, but OPENAI_API_KEY_SECRET_NAME is not set",
+                        )
+                    },
+                )?,
+            }),
+            other => {
+                return Err(Error::from(format!(
+                    "SUMMARY_PROVIDER must be bedrock or openai, not {:?}",
+                    other
+                )))
+            }
+        };
 
-        if enable_ai_summary && ai_model_id.trim().is_empty() {
+        let ai_model_id = env::var("AI_MODEL_ID").unwrap_or_default();
+        let uses_bedrock = enable_ai_summary && openai.is_none();
+        if uses_bedrock && ai_model_id.trim().is_empty() {
             return Err(Error::from(
                 "AI Summary is enabled, but AI_MODEL_ID env variable is missing",
             ));
         }
 
+        // Models that take over when the one before them fails, e.g. another model or a cross-region inference
+        // profile of the same one. The families only matter when Bedrock writes the summaries
+        let models = if uses_bedrock {
+            let mut model_ids = vec![ai_model_id.trim().to_string()];
+            model_ids.extend(parse_list(
+                &bridge_env::optional("AI_FALLBACK_MODEL_IDS").unwrap_or_default(),
+            ));
+            model_ids
+                .into_iter()
+                .map(|model_id| Ok((model_id.clone(), ModelFamily::from_model_id(&model_id)?)))
+                .collect::<Result<Vec<_>, Error>>()?
+        } else {
+            Vec::new()
+        };
+
         let ai_summary_max_graphemes = if ai_summary_max_graphemes <= 0 {
             if enable_ai_summary {
                 tracing::warn!(
-                    "AI Summary is enabled, but AI_SUMMARY_MAX_GRAPHEMES is invalid , defaulting to {}. Orignal value = {}", MAX_BSKY_GRAPHEMES,
+                    "AI Summary is enabled, but AI_SUMMARY_MAX_GRAPHEMES is invalid , defaulting to {}. Orignal value = {}", DEFAULT_AI_SUMMARY_GRAPHEMES,
                     ai_summary_max_graphemes
                 );
             }
-            280
+            DEFAULT_AI_SUMMARY_GRAPHEMES
         } else {
             ai_summary_max_graphemes
         };
 
+        // Re-runs, retries and items posted by several feeds would otherwise pay for the same summary again
+        let summary_cache_ttl = Some(bridge_env::parse_or(
+            "SUMMARY_CACHE_DAYS",
+            DEFAULT_SUMMARY_CACHE_DAYS,
+        )?)
+        .filter(|days| *days > 0)
+        .map(chrono::Duration::days);
+
+        let max_input_graphemes =
+            bridge_env::parse_or("AI_MAX_INPUT_GRAPHEMES", DEFAULT_MAX_INPUT_GRAPHEMES)?;
+        if max_input_graphemes == 0 {
+            return Err(Error::from("AI_MAX_INPUT_GRAPHEMES must be at least 1"));
+        }
+        let max_chunks = bridge_env::parse_or("AI_MAX_CHUNKS", DEFAULT_MAX_CHUNKS)?;
+        if max_chunks == 0 {
+            return Err(Error::from("AI_MAX_CHUNKS must be at least 1"));
+        }
+
+        // Temperature and top_p of zero keep summaries close to the text and the same from one run to the next
+        let defaults = InferenceParams::default();
+        let inference_params = InferenceParams {
+            max_tokens: bridge_env::parse_or("AI_MAX_TOKENS", defaults.max_tokens)?,
+            temperature: bridge_env::parse_or("AI_TEMPERATURE", defaults.temperature)?,
+            top_p: bridge_env::parse_or("AI_TOP_P", defaults.top_p)?,
+        };
+        inference_params
+            .validate()
+            .context("Invalid AI_MAX_TOKENS, AI_TEMPERATURE or AI_TOP_P")?;
+
+        let input_token_price: f64 = bridge_env::parse_or("AI_INPUT_TOKEN_PRICE", 0.0)?;
+        let output_token_price: f64 = bridge_env::parse_or("AI_OUTPUT_TOKEN_PRICE", 0.0)?;
+        if input_token_price < 0.0 || output_token_price < 0.0 {
+            return Err(Error::from(
+                "AI_INPUT_TOKEN_PRICE and AI_OUTPUT_TOKEN_PRICE cannot be negative",
+            ));
+        }
+
         Ok(Self {
             dynamodb_table_name,
             enable_ai_summary,
-            ai_model_id,
+            models,
             ai_summary_max_graphemes,
+            shorten_attempts: bridge_env::parse_or(
+                "AI_SHORTEN_ATTEMPTS",
+                DEFAULT_SHORTEN_ATTEMPTS,
+            )?,
+            summarize_article: !bridge_env::flag("DISABLE_ARTICLE_SUMMARY"),
+            max_input_graphemes,
+            max_chunks,
+            inference_params,
+            target_language: bridge_env::optional("TARGET_LANGUAGE"),
+            summary_cache_ttl,
+            usage_metrics: bridge_env::flag("AI_USAGE_METRICS"),
+            input_token_price,
+            output_token_price,
+            openai,
         })
     }
+
+    /// The models summaries are written with, in the order they're tried.
+    fn summary_models(&self) -> Vec<String> {
+        match &self.openai {
+            Some(openai) => vec![format!("{} {}", openai.base_url, openai.model)],
+            None => self.models.iter().map(|(model, _)| model.clone()).collect(),
+        }
+    }
+}
+
+/// The service that writes the summaries.
+enum Summarizer {
+    Bedrock(BedrockClient),
+    OpenAi(OpenAiClient),
+}
+
+impl Summarizer {
+    /// Asks the model to complete a prompt. Bedrock tries the models of the chain in order until one writes a summary.
+    ///
+    /// # Arguments
+    ///
+    /// * `prompt` - The prompt.
+    /// * `params` - The settings the model generates with.
+    /// * `feed` - The feed of the item, which the token usage is recorded for.
+    /// * `config` - The configuration.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the text the model wrote, None if it wrote none, or an error if the request failed.
+    async fn complete(
+        &self,
+        prompt: &str,
+        params: &InferenceParams,
+        feed: &str,
+        config: &Config,
+    ) -> anyhow::Result<Option<String>> {
+        match self {
+            Summarizer::Bedrock(bedrock_client) => {
+                // A model that errors or throttles hands the summary on to the next one in the chain
+                let mut last_error = None;
+                for (model_id, family) in &config.models {
+                    match invoke_bedrock(bedrock_client, model_id, *family, prompt, params).await {
+                        Ok(completion) => {
+                            record_usage(completion.usage, feed, model_id, config);
+                            match completion.text {
+                                Some(summary) => return Ok(Some(summary)),
+                                None => tracing::warn!("{} wrote no summary", model_id),
+                            }
+                        }
+                        Err(e) => {
+                            tracing::warn!("Failed to summarize with {}: {:?}", model_id, e);
+                            // A throttled model may have capacity again later, so its error is the one reported
+                            let throttled = last_error.as_ref().is_some_and(|e: &anyhow::Error| {
+                                e.downcast_ref::<Throttled>().is_some()
+                            });
+                            if !throttled {
+                                last_error = Some(e);
+                            }
+                        }
+                    }
+                }
+                match last_error {
+                    Some(e) => Err(e.context("No model of the chain could summarize")),
+                    None => Ok(None),
+                }
+            }
+            Summarizer::OpenAi(client) => {
+                let completion = client.complete(prompt, params).await?;
+                let model = config.openai.as_ref().map(|openai| openai.model.as_str());
+                record_usage(completion.usage, feed, model.unwrap_or_default(), config);
+                Ok(completion.text)
+            }
+        }
+    }
+}
+
+/// Asks a Bedrock model to complete a prompt.
+///
+/// Throttled requests are retried with exponential backoff and jitter, up to MAX_THROTTLING_RETRIES times.
+///
+/// # Arguments
+///
+/// * `bedrock_client` - The client to invoke the model with.
+/// * `model_id` - The ID of the model or inference profile.
+/// * `family` - The family of the model, which sets the format of the request and response.
+/// * `prompt` - The prompt.
+/// * `params` - The settings the model generates with.
+///
+/// # Returns
+///
+/// A Result containing what the model wrote, or an error if the request failed, which is [`Throttled`] if Bedrock
+/// still throttled it after the retries.
+async fn invoke_bedrock(
+    bedrock_client: &BedrockClient,
+    model_id: &str,
+    family: ModelFamily,
+    prompt: &str,
+    params: &InferenceParams,
+) -> anyhow::Result<Completion> {
+    // Prepare the request body, in the format of the model's family
+    let request_body = summary_request(family, prompt, params);
+
+    // Convert the request body to bytes
+    let request_body_bytes = serde_json::to_vec(&request_body)?;
+
+    // Make the API call to Bedrock
+    let mut attempt = 0;
+    let response = loop {
+        let result = bedrock_client
+            .invoke_model()
+            .body(aws_sdk_bedrockruntime::primitives::Blob::new(
+                request_body_bytes.clone(),
+            ))
+            .model_id(model_id)
+            .content_type("application/json")
+            .accept("application/json")
+            .send()
+            .await;
+        let error = match result {
+            Ok(response) => break response,
+            Err(e) => e,
+        };
+        let throttled = error
+            .as_service_error()
+            .is_some_and(|e| e.is_throttling_exception());
+        if !throttled {
+            return Err(anyhow::Error::new(error).context("Failed to invoke the Bedrock model"));
+        }
+        if attempt >= MAX_THROTTLING_RETRIES {
+            return Err(anyhow::Error::new(Throttled).context(format!(
+                "{} is still throttled after {} retries",
+                model_id, MAX_THROTTLING_RETRIES
+            )));
+        }
+
+        let delay = backoff_delay(THROTTLING_BASE_DELAY, attempt);
+        tracing::warn!(
+            "Bedrock throttled {}, retrying in {:?} (attempt {} of {})",
+            model_id,
+            delay,
+            attempt + 1,
+            MAX_THROTTLING_RETRIES
+        );
+        tokio::time::sleep(delay).await;
+        attempt += 1;
+    };
+
+    tracing::info!("Response received: {:?}", response);
+
+    // Parse the response
+    let response_body: serde_json::Value = serde_json::from_slice(response.body.as_ref())?;
+    tracing::info!("Parsed response body: {:?}", response_body);
+    Ok(Completion {
+        text: parse_summary(family, &response_body),
+        usage: parse_usage(family, &response_body),
+    })
+}
+
+/// Publishes the tokens a request used, and what they cost, as CloudWatch metrics of the item's feed.
+///
+/// The metrics are logged in the embedded metric format, so the lambda needs no permission to publish them.
+fn record_usage(usage: Option<TokenUsage>, feed: &str, model: &str, config: &Config) {
+    if !config.usage_metrics {
+        return;
+    }
+    let Some(usage) = usage else {
+        tracing::info!("{} didn't tell the tokens it used", model);
+        return;
+    };
+    let mut metrics = vec![
+        Metric {
+            name: "InputTokens",
+            value: usage.input_tokens as f64,
+            unit: "Count",
+        },
+        Metric {
+            name: "OutputTokens",
+            value: usage.output_tokens as f64,
+            unit: "Count",
+        },
+    ];
+    if config.input_token_price > 0.0 || config.output_token_price > 0.0 {
+        metrics.push(Metric {
+            name: "EstimatedCost",
+            value: usage.cost(config.input_token_price, config.output_token_price),
+            unit: "None",
+        });
+    }
+    // Printed rather than traced, as CloudWatch only reads records that are log lines of their own
+    let record = emf_record(
+        &[("Feed", feed)],
+        &[("Model", model)],
+        &metrics,
+        chrono::Utc::now().timestamp_millis(),
+    );
+    println!("{}", record);
+}
+
+/// Asks the model for a summary, and to shorten it when it's longer than asked for, as truncating it afterwards
+/// tends to cut it off mid-thought.
+///
+/// # Arguments
+///
+/// * `summarizer` - The service that writes the summary.
+/// * `text` - The text to summarize.
+/// * `prompt` - The prompt asking for the summary of the text, see [`summary_prompt`].
+/// * `params` - The settings the model generates with.
+/// * `feed` - The feed of the item, which the token usage is recorded for.
+/// * `config` - The configuration.
+///
+/// # Returns
+///
+/// A Result containing the summary, None if the model wrote none, or an error if asking for it failed.
+async fn summarize(
+    summarizer: &Summarizer,
+    text: &str,
+    prompt: &str,
+    params: &InferenceParams,
+    feed: &str,
+    config: &Config,
+) -> anyhow::Result<Option<String>> {
+    // Texts too long for one request are summarized chunk by chunk, so the end of an article isn't lost
+    let mut chunks = split_into_chunks(text, config.max_input_graphemes);
+    // Chunks end at paragraphs, so they may not fill up and a text cut to fit max_chunks of them still needs more
+    chunks.truncate(config.max_chunks);
+    let summary = if chunks.len() > 1 {
+        map_reduce(summarizer, &chunks, params, feed, config).await?
+    } else {
+        summarizer.complete(prompt, params, feed, config).await?
+    };
+    let Some(mut summary) = summary else {
+        return Ok(None);
+    };
+
+    let max_graphemes = config.ai_summary_max_graphemes as usize;
+    for attempt in 1..=config.shorten_attempts {
+        let length = summary.graphemes(true).count();
+        if length <= max_graphemes {
+            break;
+        }
+        tracing::info!(
+            "Summary is {} graphemes long, asking to shorten it to {} (attempt {} of {})",
+            length,
+            max_graphemes,
+            attempt,
+            config.shorten_attempts
+        );
+        // The summary already written is the fallback, so failing to shorten it doesn't fail the item
+        let shortened = summarizer
+            .complete(
+                &shorten_prompt(&summary, config.ai_summary_max_graphemes),
+                params,
+                feed,
+                config,
+            )
+            .await;
+        match shortened {
+            Ok(Some(shortened)) if shortened.graphemes(true).count() < length => {
+                summary = shortened
+            }
+            Ok(_) => {
+                tracing::warn!("The model didn't shorten the summary");
+                break;
+            }
+            Err(e) => {
+                tracing::warn!("Failed to shorten the summary: {:?}", e);
+                break;
+            }
+        }
+    }
+    Ok(Some(summary))
+}
+
+/// Summarizes a text too long for one request: each of its chunks on its own, then the summaries of the chunks
+/// together.
+///
+/// # Returns
+///
+/// A Result containing the summary, None if the model wrote none, or an error if asking for it failed.
+async fn map_reduce(
+    summarizer: &Summarizer,
+    chunks: &[String],
+    params: &InferenceParams,
+    feed: &str,
+    config: &Config,
+) -> anyhow::Result<Option<String>> {
+    tracing::info!("Summarizing the text in {} chunks", chunks.len());
+    let mut summaries = Vec::new();
+    for (i, chunk) in chunks.iter().enumerate() {
+        let prompt = chunk_prompt(chunk, i + 1, chunks.len(), CHUNK_SUMMARY_GRAPHEMES);
+        match summarizer.complete(&prompt, params, feed, config).await? {
+            Some(summary) => summaries.push(summary),
+            None => tracing::warn!(
+                "The model wrote no summary of chunk {} of {}",
+                i + 1,
+                chunks.len()
+            ),
+        }
+    }
+    if summaries.is_empty() {
+        return Ok(None);
+    }
+
+    let prompt = summary_prompt(
+        &summaries.join("\n\n"),
+        config.ai_summary_max_graphemes,
+        config.target_language.as_deref(),
+    );
+    summarizer.complete(&prompt, params, feed, config).await
+}
+
+/// Looks up the summary written for the same prompt, with the same models and settings, before.
+///
+/// # Arguments
+///
+/// * `cache_key` - The key of the summary, see [`summary_cache_key`].
+///
+/// # Returns
+///
+/// The cached summary, or None if there is none, the cache is off or looking it up failed.
+async fn cached_summary(
+    repo: &DynamoRepository,
+    cache_key: &str,
+    config: &Config,
+) -> Option<String> {
+    config.summary_cache_ttl?;
+    match repo.get_cached_summary(cache_key).await {
+        Ok(cached) => {
+            let cached = cached?;
+            tracing::info!("Reusing the summary cached under {}", cache_key);
+            Some(cached.summary)
+        }
+        Err(e) => {
+            tracing::warn!("Failed to look up the cached summary: {:?}", e);
+            None
+        }
+    }
+}
+
+/// Caches a summary for the next item with the same prompt, models and settings. Failing to only costs a summary
+/// later, so it's logged.
+async fn cache_summary(repo: &DynamoRepository, cache_key: &str, summary: &str, config: &Config) {
+    let Some(ttl) = config.summary_cache_ttl else {
+        return;
+    };
+    let item = CachedSummary {
+        prompt_hash: cache_key.to_string(),
+        summary: summary.to_string(),
+        ttl: Some((chrono::Utc::now() + ttl).timestamp()),
+    };
+    if let Err(e) = repo.put_cached_summary(&item).await {
+        tracing::warn!("Failed to cache the summary: {:?}", e);
+    }
 }
 
-#[instrument(skip(event, repo, bedrock_client, config))]
+#[instrument(skip(event, repo, summarizer, config))]
 async fn summarize_bedrock(
     event: LambdaEvent<Input>,
     repo: &DynamoRepository,
-    bedrock_client: &BedrockClient,
+    summarizer: &Summarizer,
     config: &Config,
-) -> Result<Output, Error> {
+) -> Result<Output, SummarizeError> {
     if !config.enable_ai_summary {
         return Ok(Output {
             item_identifier: event.payload.item_identifier,
@@ -111,59 +663,57 @@ async fn summarize_bedrock(
         })?;
 
     let description = item.description.context("Description not found in item")?;
-    //get the summary from description
+    // Teaser-only feeds say more in the article extracted from their page than in their description
+    let text = summary_source(
+        &description,
+        item.article_text
+            .as_deref()
+            .filter(|_| config.summarize_article),
+        config.max_input_graphemes.saturating_mul(config.max_chunks),
+    );
     // Prepare the prompt
-    let prompt = format!(
-    "\n\nHuman: Remove all html tags and summarize the following text in {} graphemes or less:\n\n{}\n\nAssistant:",
-    config.ai_summary_max_graphemes, description
-);
+    let prompt = summary_prompt(
+        &text,
+        config.ai_summary_max_graphemes,
+        config.target_language.as_deref(),
+    );
     tracing::info!("Prompt: {:?}", prompt);
 
-    // Prepare the request body
-    let request_body = serde_json::json!({
-        "anthropic_version": "bedrock-2023-05-31",
-        "max_tokens": 300,
-        "messages": [
-            {
-                "role": "user",
-                "content": [
-                    {
-                        "type": "text",
-                        "text": prompt
-                    }
-                ]
-            }
-        ],
-        "temperature": 0.0,
-        "top_p": 0,
-    });
-
-    // Convert the request body to bytes
-    let request_body_bytes = serde_json::to_vec(&request_body)?;
-
-    // Make the API call to Bedrock
-    let response = bedrock_client
-        .invoke_model()
-        .body(aws_sdk_bedrockruntime::primitives::Blob::new(
-            request_body_bytes,
-        ))
-        .model_id(&config.ai_model_id)
-        .content_type("application/json")
-        .accept("application/json")
-        .send()
-        .await?;
-
-    tracing::info!("Response received: {:?}", response);
+    // A feed's own settings that aren't valid are passed over rather than failing its items
+    let params = config.inference_params.with_overrides(
+        item.ai_max_tokens,
+        item.ai_temperature,
+        item.ai_top_p,
+    );
+    let params = match params.validate() {
+        Ok(()) => params,
+        Err(e) => {
+            tracing::warn!(
+                "Ignoring the invalid inference settings of the item's feed: {:?}",
+                e
+            );
+            config.inference_params
+        }
+    };
 
-    // Parse the response
-    let response_body: serde_json::Value = serde_json::from_slice(response.body.as_ref())?;
-    tracing::info!("Parsed response body: {:?}", response_body);
-    let summary = response_body["content"][0]["text"]
-        .as_str()
-        .unwrap_or(&description);
+    let cache_key = summary_cache_key(&prompt, &config.summary_models(), &params);
+    let summary = match cached_summary(repo, &cache_key, config).await {
+        Some(summary) => Some(summary),
+        None => {
+            let feed = item.feed_url.as_deref().unwrap_or("unknown");
+            let summary = summarize(summarizer, &text, &prompt, &params, feed, config).await?;
+            if let Some(summary) = &summary {
+                cache_summary(repo, &cache_key, summary, config).await;
+            }
+            summary
+        }
+    }
+    .unwrap_or_else(|| description.clone());
 
     tracing::info!("Summary before trimming:\n{}", summary);
-    let summary = truncate_to_word(summary, MAX_BSKY_GRAPHEMES);
+    // Models don't count graphemes exactly. A summary that is still too long next to the rest of the post is
+    // shortened again when the post is laid out
+    let summary = truncate_to_word(&summary, MAX_POST_GRAPHEMES);
 
     tracing::info!("Summary after trimming:\n{}", summary);
     let num_graphemes = summary.graphemes(true).count();
@@ -195,10 +745,25 @@ async fn main() -> Result<(), Error> {
     let config = Config::from_env().expect("Failed to load configuration");
     let aws_config = aws_config::load_defaults(BehaviorVersion::latest()).await;
     let dynamodb_client = DynamoDbClient::new(&aws_config);
-    let bedrock_client = BedrockClient::new(&aws_config);
     let repo = DynamoRepository::new(dynamodb_client, config.dynamodb_table_name.clone());
+    let summarizer = match &config.openai {
+        Some(openai) => {
+            let secrets_client = SecretsManagerClient::new(&aws_config);
+            Summarizer::OpenAi(
+                OpenAiClient::from_secret(
+                    &secrets_client,
+                    &openai.secret_name,
+                    &openai.base_url,
+                    &openai.model,
+                )
+                .await
+                .expect("Failed to set up the OpenAI-compatible client"),
+            )
+        }
+        None => Summarizer::Bedrock(BedrockClient::new(&aws_config)),
+    };
     run(service_fn(|event: LambdaEvent<Input>| {
-        summarize_bedrock(event, &repo, &bedrock_client, &config)
+        summarize_bedrock(event, &repo, &summarizer, &config)
     }))
     .await
 }
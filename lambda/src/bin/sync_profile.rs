@@ -0,0 +1,375 @@
+use ::tracing::instrument;
+use anyhow::Context;
+use atrium_api::app::bsky::actor::profile;
+use atrium_api::com::atproto::repo::{get_record, strong_ref};
+use atrium_api::types::{BlobRef, TryFromUnknown};
+use atrium_api::xrpc::error::{XrpcError, XrpcErrorKind};
+use aws_config::BehaviorVersion;
+use aws_sdk_dynamodb::Client as DynamoDbClient;
+use aws_sdk_secretsmanager::Client as SecretsManagerClient;
+use bsky_sdk::record::Record;
+use bsky_sdk::BskyAgent;
+use chrono::{Duration, Utc};
+use lambda_runtime::{run, service_fn, tracing, Error, LambdaEvent};
+use rss_bluesky_bridge::bluesky::{self, RetryingClient};
+use rss_bluesky_bridge::env;
+use rss_bluesky_bridge::feed::{base_url, channel_image_url, read_channel};
+use rss_bluesky_bridge::filters::parse_list;
+use rss_bluesky_bridge::html::strip_tags;
+use rss_bluesky_bridge::http::{HttpClient, HttpConfig};
+use rss_bluesky_bridge::image_utils::fit_image;
+use rss_bluesky_bridge::models::FeedType;
+use rss_bluesky_bridge::repository::DynamoRepository;
+use rss_bluesky_bridge::text_utils::truncate_to_word;
+use serde::Serialize;
+use tracing_subscriber::EnvFilter;
+
+/// Bluesky rejects profile descriptions longer than this.
+const MAX_DESCRIPTION_GRAPHEMES: usize = 256;
+/// Bluesky rejects avatars larger than this.
+const MAX_AVATAR_BYTES: usize = 1_000_000;
+/// How far back the latest post is looked for. RecentPosts, which tell the account of a post, aren't kept any longer.
+const PINNED_POST_DAYS: i64 = 7;
+/// The record key of an account's profile, which has only the one.
+const PROFILE_RKEY: &str = "self";
+
+#[derive(Serialize, Debug)]
+struct Output {
+    /// The secrets of the accounts whose profiles changed, or would have in a dry run.
+    updated: Vec<String>,
+    dry_run: bool,
+}
+
+struct Config {
+    dynamodb_table_name: String,
+    secret_name: String,
+    /// The feed the profile of the deployment's account follows. None to take the first feed of the table posting
+    /// to it.
+    feed_url: Option<String>,
+    /// Which fields of the profiles are kept in sync.
+    sync_avatar: bool,
+    sync_description: bool,
+    sync_pinned_post: bool,
+    /// Logs the changes instead of making them.
+    dry_run: bool,
+}
+
+impl Config {
+    fn from_env() -> Result<Self, Error> {
+        let dynamodb_table_name = std::env::var("DYNAMODB_TABLE_NAME")
+            .context("DYNAMODB_TABLE_NAME environment variable not set")?;
+
+        if dynamodb_table_name.trim().is_empty() {
+            return Err(Error::from("DYNAMODB_TABLE_NAME cannot be empty"));
+        }
+
+        let secret_name = std::env::var("BLUESKY_CREDENTIALS_SECRET_NAME")
+            .context("BLUESKY_CREDENTIALS_SECRET_NAME environment variable not set")?;
+
+        if secret_name.trim().is_empty() {
+            return Err(Error::from(
+                "BLUESKY_CREDENTIALS_SECRET_NAME cannot be empty",
+            ));
+        }
+
+        // Everything is synced unless PROFILE_SYNC_FIELDS names only some of it
+        let fields = parse_list(&env::optional("PROFILE_SYNC_FIELDS").unwrap_or_default());
+        if let Some(field) = fields
+            .iter()
+            .find(|field| !matches!(field.as_str(), "avatar" | "description" | "pinned_post"))
+        {
+            return Err(Error::from(format!(
+                "Unknown PROFILE_SYNC_FIELDS field {:?}, expected avatar, description or pinned_post",
+                field
+            )));
+        }
+        let syncs = |field: &str| fields.is_empty() || fields.iter().any(|f| f == field);
+
+        Ok(Self {
+            dynamodb_table_name,
+            secret_name,
+            feed_url: env::optional("PROFILE_FEED_URL").or_else(|| env::optional("FEED_URL")),
+            sync_avatar: syncs("avatar"),
+            sync_description: syncs("description"),
+            sync_pinned_post: syncs("pinned_post"),
+            dry_run: env::flag("DRY_RUN"),
+        })
+    }
+}
+
+/// Keeps the profiles of the accounts in step with the feeds they post: the avatar is the channel image, the
+/// description the channel description, and the pinned post the account's latest post.
+///
+/// Each account follows one feed, PROFILE_FEED_URL for the deployment's account and the first feed of the table
+/// posting to it for the others. Only RSS and Atom feeds that need no credentials have a channel to follow. What
+/// the feed doesn't have, such as an image, is left as it is, and profiles that are in step already aren't written.
+#[instrument(skip(repo, http, secrets_client, config))]
+async fn sync_profile(
+    _event: LambdaEvent<serde_json::Value>,
+    repo: &DynamoRepository,
+    http: &HttpClient,
+    secrets_client: &SecretsManagerClient,
+    config: &Config,
+) -> Result<Output, Error> {
+    let mut accounts: Vec<(Option<String>, String)> = Vec::new();
+    if let Some(feed_url) = &config.feed_url {
+        accounts.push((None, feed_url.clone()));
+    }
+    for feed in repo
+        .list_feed_configs()
+        .await
+        .context("Failed to list feed configs")?
+    {
+        let is_rss = matches!(feed.feed_type(), Ok(FeedType::Rss));
+        let known = accounts
+            .iter()
+            .any(|(account, _)| *account == feed.bluesky_secret);
+        if is_rss && feed.credentials_secret.is_none() && !known {
+            accounts.push((feed.bluesky_secret, feed.feed_url));
+        }
+    }
+
+    let mut output = Output {
+        updated: Vec::new(),
+        dry_run: config.dry_run,
+    };
+    let mut failed = 0;
+    for (account, feed_url) in accounts {
+        let secret_name = account.as_deref().unwrap_or(&config.secret_name);
+        // An account that can't be synced doesn't hold up the others
+        match sync_account(
+            account.as_deref(),
+            secret_name,
+            &feed_url,
+            repo,
+            http,
+            secrets_client,
+            config,
+        )
+        .await
+        {
+            Ok(true) => output.updated.push(secret_name.to_string()),
+            Ok(false) => {}
+            Err(e) => {
+                tracing::error!("Failed to sync the profile of {}: {:?}", secret_name, e);
+                failed += 1;
+            }
+        }
+    }
+    if failed > 0 {
+        return Err(Error::from(format!("Failed to sync {} profile(s)", failed)));
+    }
+
+    tracing::info!("Profile sync result: {:?}", output);
+    Ok(output)
+}
+
+/// Brings the profile of an account in step with its feed.
+///
+/// # Arguments
+///
+/// * `account` - The `bluesky_secret` the account's posts are recorded with, None for the deployment's account.
+/// * `secret_name` - The secret to log in with.
+/// * `feed_url` - The feed the profile follows.
+///
+/// # Returns
+///
+/// A Result telling whether the profile changed, or an error if the feed or the profile couldn't be read or the
+/// profile couldn't be written.
+async fn sync_account(
+    account: Option<&str>,
+    secret_name: &str,
+    feed_url: &str,
+    repo: &DynamoRepository,
+    http: &HttpClient,
+    secrets_client: &SecretsManagerClient,
+    config: &Config,
+) -> anyhow::Result<bool> {
+    let content = http
+        .get_bytes(feed_url)
+        .await
+        .with_context(|| format!("Failed to fetch feed {}", feed_url))?;
+    let channel = read_channel(&content)?;
+
+    let agent = bluesky::login(secrets_client, secret_name).await?;
+    let mut profile = get_profile(&agent).await?;
+    let original = profile.clone();
+
+    if config.sync_description {
+        let description = truncate_to_word(
+            &strip_tags(channel.description()),
+            MAX_DESCRIPTION_GRAPHEMES,
+        );
+        if !description.is_empty() {
+            profile.description = Some(description);
+        }
+    }
+
+    if config.sync_pinned_post {
+        if let Some(pinned_post) = latest_post(account, repo).await? {
+            profile.pinned_post = Some(pinned_post.into());
+        }
+    }
+
+    // The same image uploads to the same blob, so an avatar that didn't change leaves the profile as it is
+    let image_url = channel_image_url(&channel, &base_url(&channel, feed_url));
+    if let (true, Some(image_url)) = (config.sync_avatar, image_url) {
+        if config.dry_run {
+            tracing::info!(
+                "Dry run, not uploading avatar {} of {}",
+                image_url,
+                secret_name
+            );
+        } else {
+            match upload_avatar(&image_url, &agent, http).await {
+                Ok(avatar) => profile.avatar = Some(avatar),
+                Err(e) => tracing::warn!("Failed to sync avatar {}: {:?}", image_url, e),
+            }
+        }
+    }
+
+    if profile == original {
+        tracing::info!(
+            "The profile of {} is in step with {}",
+            secret_name,
+            feed_url
+        );
+        return Ok(false);
+    }
+    if config.dry_run {
+        tracing::info!(
+            "Dry run, not updating the profile of {}: {}",
+            secret_name,
+            serde_json::to_string(&profile)?
+        );
+        return Ok(true);
+    }
+    profile
+        .put(&agent, PROFILE_RKEY.to_string())
+        .await
+        .context("Failed to update profile")?;
+    tracing::info!("Updated the profile of {} from {}", secret_name, feed_url);
+    Ok(true)
+}
+
+/// Reads the profile record of the logged in account, keeping the fields that aren't synced, such as the display
+/// name and the banner.
+///
+/// # Returns
+///
+/// A Result containing the profile, blank for an account that never set one up, or an error if it couldn't be read.
+async fn get_profile(agent: &BskyAgent<RetryingClient>) -> anyhow::Result<profile::RecordData> {
+    let session = agent.get_session().await.context("Not logged in")?;
+    let result = agent
+        .api
+        .com
+        .atproto
+        .repo
+        .get_record(
+            get_record::ParametersData {
+                cid: None,
+                collection: "app.bsky.actor.profile"
+                    .parse()
+                    .map_err(anyhow::Error::msg)?,
+                repo: session.data.did.clone().into(),
+                rkey: PROFILE_RKEY.to_string(),
+            }
+            .into(),
+        )
+        .await;
+    match result {
+        Ok(output) => Ok(profile::Record::try_from_unknown(output.data.value)
+            .context("Failed to read profile")?
+            .data),
+        // Overwriting a profile that failed to load for another reason would lose its display name and banner
+        Err(atrium_api::xrpc::Error::XrpcResponse(XrpcError {
+            error: Some(XrpcErrorKind::Custom(get_record::Error::RecordNotFound(_))),
+            ..
+        })) => Ok(profile::RecordData {
+            avatar: None,
+            banner: None,
+            created_at: None,
+            description: None,
+            display_name: None,
+            joined_via_starter_pack: None,
+            labels: None,
+            pinned_post: None,
+        }),
+        Err(e) => Err(e).context("Failed to get profile"),
+    }
+}
+
+/// Finds the latest post of an account that is still up.
+///
+/// # Returns
+///
+/// A Result containing a reference to the post, or None if the account posted nothing lately (or only before the
+/// bridge stored post URIs).
+async fn latest_post(
+    account: Option<&str>,
+    repo: &DynamoRepository,
+) -> anyhow::Result<Option<strong_ref::MainData>> {
+    let since = (Utc::now() - Duration::days(PINNED_POST_DAYS)).timestamp();
+    let mut posts = repo
+        .list_recent_posts(since)
+        .await
+        .context("Failed to list recent posts")?;
+    posts.retain(|post| post.account.as_deref() == account);
+    posts.sort_by_key(|post| std::cmp::Reverse(post.posted_at));
+
+    for post in posts {
+        let record_item = repo.get_record_item(&post.guid).await?;
+        if record_item.deleted_at.is_some() {
+            continue;
+        }
+        if let (Some(uri), Some(cid)) = (record_item.uri, record_item.cid) {
+            return Ok(Some(strong_ref::MainData {
+                cid: cid.parse().context("Invalid CID")?,
+                uri,
+            }));
+        }
+    }
+    Ok(None)
+}
+
+/// Fetches an image and uploads it as an avatar, scaled down if it's too large.
+async fn upload_avatar(
+    image_url: &str,
+    agent: &BskyAgent<RetryingClient>,
+    http: &HttpClient,
+) -> anyhow::Result<BlobRef> {
+    let image = http
+        .get_bytes(image_url)
+        .await
+        .with_context(|| format!("Failed to fetch image {}", image_url))?;
+    let image = fit_image(&image, MAX_AVATAR_BYTES)?;
+    let output = agent
+        .api
+        .com
+        .atproto
+        .repo
+        .upload_blob(image)
+        .await
+        .context("Failed to upload blob")?;
+    Ok(output.data.blob)
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Error> {
+    tracing_subscriber::fmt()
+        .json()
+        .with_env_filter(EnvFilter::from_default_env())
+        .init();
+
+    let config = Config::from_env().expect("Failed to load configuration");
+    let aws_config = aws_config::load_defaults(BehaviorVersion::latest()).await;
+    let dynamodb_client = DynamoDbClient::new(&aws_config);
+    let secrets_client = SecretsManagerClient::new(&aws_config);
+    let repo = DynamoRepository::new(dynamodb_client, config.dynamodb_table_name.clone());
+    let http = HttpClient::new(HttpConfig::default())?;
+
+    run(service_fn(|event: LambdaEvent<serde_json::Value>| {
+        sync_profile(event, &repo, &http, &secrets_client, &config)
+    }))
+    .await
+}
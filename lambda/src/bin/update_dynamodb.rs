@@ -2,24 +2,42 @@ use ::tracing::instrument;
 use anyhow::Context;
 use aws_config::BehaviorVersion;
 use aws_sdk_dynamodb::Client;
+use chrono::{Duration, Utc};
 use lambda_runtime::{run, service_fn, tracing, Error, LambdaEvent};
 use rss_bluesky_bridge::{
-    models::{ItemIdentifier, RecordItem},
+    feed::{high_water_mark, parse_date},
+    models::{ExecutionItem, ItemIdentifier, RecentPost, RecordItem},
     repository::DynamoRepository,
+    url_utils::dedupe_key,
 };
 use serde::{Deserialize, Serialize};
 use tracing_subscriber::EnvFilter;
 
+/// How long posted items are remembered for recognising the same story from another feed.
+const RECENT_POST_RETENTION_DAYS: i64 = 7;
+
 #[derive(Deserialize)]
 struct Input {
     #[serde(flatten)]
     item_identifier: ItemIdentifier,
+    /// The URI and CID of the post, as returned by post-bluesky.
+    #[serde(default)]
+    uri: Option<String>,
+    #[serde(default)]
+    cid: Option<String>,
+    /// The template variant post-bluesky laid the post out with, during a template experiment.
+    #[serde(default)]
+    template_variant: Option<String>,
+    /// Set by post-bluesky when it only logged the post.
+    #[serde(default)]
+    dry_run: bool,
 }
 
 #[derive(Serialize, Debug)]
 struct Output {
     #[serde(flatten)]
     item_identifier: ItemIdentifier,
+    dry_run: bool,
 }
 
 struct Config {
@@ -45,21 +63,112 @@ async fn update_dynamodb(
     event: LambdaEvent<Input>,
     repo: &DynamoRepository,
 ) -> Result<Output, Error> {
-    let record_item = RecordItem::new(event.payload.item_identifier.guid.clone())
+    // An item that wasn't posted stays unrecorded, so it's posted once the dry run is over
+    if event.payload.dry_run {
+        tracing::info!(
+            "Dry run, not recording {:?} as posted",
+            event.payload.item_identifier
+        );
+        repo.release_record_claim(
+            &event.payload.item_identifier.guid,
+            &event.payload.item_identifier.execution_id,
+        )
+        .await
+        .context("Failed to release the claim on the item")?;
+        return Ok(Output {
+            item_identifier: event.payload.item_identifier,
+            dry_run: true,
+        });
+    }
+
+    let item = repo
+        .get_execution_item(
+            &event.payload.item_identifier.execution_id,
+            &event.payload.item_identifier.guid,
+        )
+        .await;
+
+    // Replaces the claim on the item, along with the RecordItem of an earlier version, so a later update follows up
+    // on this post
+    let mut record_item = RecordItem::new(event.payload.item_identifier.guid.clone())
         .context("Failed to create RecordItem")?;
+    record_item.uri = event.payload.uri.clone();
+    record_item.cid = event.payload.cid.clone();
+    record_item.template_variant = event.payload.template_variant.clone();
+    record_item.posted_at = Some(Utc::now().timestamp());
+    record_item.content_hash = item
+        .as_ref()
+        .ok()
+        .and_then(|item| item.content_hash.clone());
 
     repo.create_record_item(&record_item)
         .await
         .context("Failed to create record item in DynamoDB")?;
 
+    // The item is posted at this point, so failing to update the bookkeeping below only means it may be seen (and
+    // skipped as a duplicate) again, or a copy of it from another feed may not be recognised
+    match item {
+        Ok(item) => {
+            if let Err(e) = advance_high_water_mark(&item, repo).await {
+                tracing::warn!("Failed to advance the feed high-water mark: {:?}", e);
+            }
+            if let Err(e) = remember_recent_post(&item, repo).await {
+                tracing::warn!("Failed to remember the recent post: {:?}", e);
+            }
+        }
+        Err(e) => tracing::warn!("Failed to get the posted item: {:?}", e),
+    }
+    if let Err(e) = repo
+        .release_held_item(&event.payload.item_identifier.guid)
+        .await
+    {
+        tracing::warn!("Failed to release the held item: {:?}", e);
+    }
+
     let output = Output {
         item_identifier: event.payload.item_identifier,
+        dry_run: false,
     };
 
     tracing::info!("Update result: {:?}", output);
     Ok(output)
 }
 
+/// Moves the high-water mark of the item's feed forward to the item's publication date, or to now for an item
+/// dated in the future.
+async fn advance_high_water_mark(
+    item: &ExecutionItem,
+    repo: &DynamoRepository,
+) -> anyhow::Result<()> {
+    let (Some(feed_url), Some(pub_date)) = (&item.feed_url, &item.pub_date) else {
+        return Ok(());
+    };
+    let Some(pub_date) = parse_date(pub_date) else {
+        return Ok(());
+    };
+
+    repo.advance_feed_high_water_mark(
+        feed_url,
+        high_water_mark(pub_date.timestamp(), Utc::now().timestamp()),
+    )
+    .await
+}
+
+/// Stores the posted item as a RecentPost, so get_rss_items can suppress copies of it from other feeds.
+async fn remember_recent_post(item: &ExecutionItem, repo: &DynamoRepository) -> anyhow::Result<()> {
+    let now = Utc::now();
+    repo.create_recent_post(&RecentPost {
+        posted_at: now.timestamp(),
+        guid: item.guid.clone(),
+        title: item.title.clone(),
+        link: item.link.as_deref().map(dedupe_key),
+        feed_url: item.feed_url.clone(),
+        account: item.bluesky_secret.clone(),
+        ttl: Some((now + Duration::days(RECENT_POST_RETENTION_DAYS)).timestamp()),
+    })
+    .await
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Error> {
     tracing_subscriber::fmt()
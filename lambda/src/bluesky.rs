@@ -0,0 +1,1008 @@
+use crate::feed::parse_date;
+use crate::http::USER_AGENT;
+use crate::oauth::{DpopAuth, DpopKey, OAuthSession};
+use crate::retry::backoff_delay;
+use anyhow::{anyhow, Context, Result};
+use atrium_api::app::bsky::feed::get_posts;
+use atrium_api::app::bsky::feed::post::{self, ReplyRefData};
+use atrium_api::app::bsky::feed::threadgate::{
+    self, FollowingRuleData, MentionRuleData, RecordAllowItem,
+};
+use atrium_api::app::bsky::richtext::facet::{self, MainFeaturesItem};
+use atrium_api::com::atproto::repo::strong_ref;
+use atrium_api::com::atproto::server::create_session::OutputData as SessionData;
+use atrium_api::types::string::{Cid, Datetime};
+use atrium_api::types::Union;
+use atrium_api::xrpc::http::{HeaderMap, Method, Request, Response, StatusCode};
+use atrium_api::xrpc::{HttpClient, XrpcClient};
+use aws_sdk_secretsmanager::Client as SecretsManagerClient;
+use bsky_sdk::rich_text::RichText;
+use bsky_sdk::BskyAgent;
+use chrono::{DateTime, FixedOffset, Utc};
+use reqwest::Client;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// The most posts `app.bsky.feed.getPosts` returns at a time.
+const GET_POSTS_BATCH: usize = 25;
+
+/// The PDS the agent talks to until it's logged in and knows the account's own.
+pub const DEFAULT_SERVICE: &str = "https://bsky.social";
+/// Default number of times a failed request to Bluesky is retried.
+pub const DEFAULT_MAX_RETRIES: u32 = 3;
+/// Delay ceiling for the first retry when the response doesn't say when to come back.
+const RETRY_BASE_DELAY: Duration = Duration::from_secs(1);
+/// Longest wait for a rate limit to reset. A limit that resets later (the daily one, say) fails the request, as the
+/// Lambda would time out waiting for it.
+pub const MAX_RATE_LIMIT_WAIT: Duration = Duration::from_secs(30);
+/// Bluesky rejects videos larger than this.
+pub const MAX_VIDEO_BYTES: usize = 50_000_000;
+/// How long an invocation that claimed the refresh of an OAuth session has to store the refreshed one, before the
+/// others take it that the refresh failed and claim it themselves.
+const OAUTH_REFRESH_CLAIM_SECONDS: i64 = 30;
+/// The field of the secret holding until when, as a Unix timestamp, an invocation has claimed the refresh.
+const OAUTH_REFRESH_CLAIM_FIELD: &str = "refreshing_until";
+/// The staging label of the versions of a secret that claim the refresh of its OAuth session.
+const OAUTH_REFRESH_CLAIM_STAGE: &str = "OAUTH_REFRESH_CLAIM";
+/// How long apart an invocation waiting for another to refresh the OAuth session reads the secret again.
+const OAUTH_REFRESH_POLL_INTERVAL: Duration = Duration::from_secs(2);
+/// How often the secret is read while waiting, enough to outlast a claim of one that failed.
+const OAUTH_REFRESH_POLLS: u32 = 20;
+
+/// An XRPC client for the Bluesky agent that retries rate limited (429) and failed (5xx) requests.
+///
+/// Rate limited requests are retried once the limit resets, as told by the `ratelimit-reset` header (or
+/// `retry-after`), other failures after an exponential backoff. Server errors and connection errors are only retried
+/// for reads, as a write such as a new post may have gone through before a proxy answered with a 502 or 504. Failed
+/// writes are left to the retries of the state machine.
+///
+/// For OAuth sessions, it also turns the agent's bearer tokens into DPoP tokens (see [`DpopAuth`]).
+#[derive(Clone)]
+pub struct RetryingClient {
+    client: Client,
+    base_uri: String,
+    max_retries: u32,
+    dpop: Option<Arc<DpopAuth>>,
+}
+
+impl RetryingClient {
+    /// Creates a client.
+    ///
+    /// # Arguments
+    ///
+    /// * `base_uri` - The URL of the PDS, usually [`DEFAULT_SERVICE`].
+    /// * `max_retries` - How many times a failed request is retried.
+    pub fn new(base_uri: &str, max_retries: u32) -> Self {
+        let client = Client::builder()
+            .user_agent(USER_AGENT)
+            .build()
+            .unwrap_or_default();
+        Self {
+            client,
+            base_uri: base_uri.to_string(),
+            max_retries,
+            dpop: None,
+        }
+    }
+
+    /// Signs the authorized requests with the DPoP key of an OAuth session.
+    pub fn with_dpop(mut self, dpop: DpopAuth) -> Self {
+        self.dpop = Some(Arc::new(dpop));
+        self
+    }
+
+    /// Sends a request, with a DPoP proof if the client has a key. A request the PDS refuses for want of a new
+    /// nonce is sent once more with it.
+    async fn send_authorized(
+        &self,
+        request: &Request<Vec<u8>>,
+    ) -> Result<Response<Vec<u8>>, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let Some(dpop) = &self.dpop else {
+            return self.send_once(copy_request(request)).await;
+        };
+        let mut attempt = copy_request(request);
+        dpop.authorize(&mut attempt)?;
+        let response = self.send_once(attempt).await?;
+        if !dpop.update_nonce(&response) {
+            return Ok(response);
+        }
+
+        let mut attempt = copy_request(request);
+        dpop.authorize(&mut attempt)?;
+        let response = self.send_once(attempt).await?;
+        dpop.update_nonce(&response);
+        Ok(response)
+    }
+
+    async fn send_once(
+        &self,
+        request: Request<Vec<u8>>,
+    ) -> Result<Response<Vec<u8>>, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let response = self.client.execute(request.try_into()?).await?;
+        let mut builder = Response::builder().status(response.status());
+        for (name, value) in response.headers() {
+            builder = builder.header(name, value);
+        }
+        Ok(builder.body(response.bytes().await?.to_vec())?)
+    }
+}
+
+impl HttpClient for RetryingClient {
+    async fn send_http(
+        &self,
+        request: Request<Vec<u8>>,
+    ) -> Result<Response<Vec<u8>>, Box<dyn std::error::Error + Send + Sync + 'static>> {
+        let mut attempt = 0;
+        loop {
+            let result = self.send_authorized(&request).await;
+            if attempt >= self.max_retries {
+                return result;
+            }
+
+            let delay = match &result {
+                Ok(response) if response.status() == StatusCode::TOO_MANY_REQUESTS => {
+                    let now = chrono::Utc::now().timestamp();
+                    match rate_limit_delay(response.headers(), now) {
+                        Some(delay) if delay > MAX_RATE_LIMIT_WAIT => return result,
+                        Some(delay) => delay,
+                        None => backoff_delay(RETRY_BASE_DELAY, attempt),
+                    }
+                }
+                Ok(response)
+                    if response.status().is_server_error() && request.method() == Method::GET =>
+                {
+                    backoff_delay(RETRY_BASE_DELAY, attempt)
+                }
+                Err(_) if request.method() == Method::GET => {
+                    backoff_delay(RETRY_BASE_DELAY, attempt)
+                }
+                _ => return result,
+            };
+
+            tracing::warn!(
+                "Bluesky request to {} failed ({}), retrying in {:?} (attempt {} of {})",
+                request.uri().path(),
+                match &result {
+                    Ok(response) => response.status().to_string(),
+                    Err(e) => e.to_string(),
+                },
+                delay,
+                attempt + 1,
+                self.max_retries
+            );
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+}
+
+impl XrpcClient for RetryingClient {
+    fn base_uri(&self) -> String {
+        self.base_uri.clone()
+    }
+}
+
+/// Logs in to the Bluesky account whose credentials are stored in a Secrets Manager secret.
+///
+/// # Arguments
+///
+/// * `secrets_client` - The client to read the secret with.
+/// * `secret_name` - Name or ARN of the secret, holding `{"username": "...", "password": "..."}` for an app password
+///   or `{"oauth": {...}}` for an OAuth session (see [`OAuthSession`]).
+///
+/// # Returns
+///
+/// A Result containing the logged in agent, which retries rate limited and failed requests, or an error if the
+/// secret can't be read or the login failed.
+pub async fn login(
+    secrets_client: &SecretsManagerClient,
+    secret_name: &str,
+) -> Result<BskyAgent<RetryingClient>> {
+    let secret = read_secret(secrets_client, secret_name).await?;
+    if secret.credentials.get("oauth").is_some() {
+        return oauth_login(secrets_client, secret_name, secret).await;
+    }
+    let credentials = secret.credentials;
+
+    let username = credentials["username"]
+        .as_str()
+        .context("Username not found in secret")?;
+    let password = credentials["password"]
+        .as_str()
+        .context("Password not found in secret")?;
+
+    let agent = BskyAgent::builder()
+        .client(RetryingClient::new(DEFAULT_SERVICE, DEFAULT_MAX_RETRIES))
+        .build()
+        .await
+        .context("Failed to build BskyAgent")?;
+    agent
+        .login(username, password)
+        .await
+        .context("Failed to login to Bluesky")?;
+    Ok(agent)
+}
+
+/// The current version of a secret holding Bluesky credentials.
+struct StoredSecret {
+    credentials: serde_json::Value,
+    version_id: String,
+}
+
+async fn read_secret(
+    secrets_client: &SecretsManagerClient,
+    secret_name: &str,
+) -> Result<StoredSecret> {
+    let secret = secrets_client
+        .get_secret_value()
+        .secret_id(secret_name)
+        .send()
+        .await
+        .context("Failed to retrieve secret")?;
+
+    let secret_string = secret.secret_string().context("Secret string is empty")?;
+    Ok(StoredSecret {
+        credentials: serde_json::from_str(secret_string).context("Failed to parse secret JSON")?,
+        version_id: secret
+            .version_id()
+            .context("Secret has no version ID")?
+            .to_string(),
+    })
+}
+
+fn parse_oauth_session(credentials: &serde_json::Value) -> Result<OAuthSession> {
+    serde_json::from_value(credentials["oauth"].clone())
+        .context("Failed to parse OAuth session in secret")
+}
+
+/// Whether another invocation has claimed the refresh of the OAuth session in a secret, and may still store it.
+fn refresh_claimed(credentials: &serde_json::Value, now: i64) -> bool {
+    credentials[OAUTH_REFRESH_CLAIM_FIELD]
+        .as_i64()
+        .is_some_and(|until| until > now)
+}
+
+/// Resumes the OAuth session stored in a secret, refreshing its tokens first unless the access token will last
+/// the run.
+///
+/// Refresh tokens can only be used once, so the refreshes of concurrent invocations are serialized: one claims
+/// the refresh by moving the secret's `AWSCURRENT` label from the version it read to one marking the claim, which
+/// fails if another moved it first, and the others wait for it to store the refreshed session. The agent can't
+/// refresh an OAuth session itself: a run that outlasts the access token fails, and the next one refreshes it.
+async fn oauth_login(
+    secrets_client: &SecretsManagerClient,
+    secret_name: &str,
+    secret: StoredSecret,
+) -> Result<BskyAgent<RetryingClient>> {
+    let session = fresh_oauth_session(secrets_client, secret_name, secret).await?;
+    let access_token = session
+        .access_token
+        .clone()
+        .ok_or_else(|| anyhow!("OAuth session has no access token"))?;
+
+    let client = RetryingClient::new(&session.pds_url, DEFAULT_MAX_RETRIES)
+        .with_dpop(DpopAuth::new(DpopKey::from_pem(&session.dpop_key)?));
+    let agent = BskyAgent::builder()
+        .client(client)
+        .build()
+        .await
+        .context("Failed to build BskyAgent")?;
+    agent
+        .resume_session(
+            SessionData {
+                access_jwt: access_token.clone(),
+                active: None,
+                did: session
+                    .did
+                    .parse()
+                    .map_err(|e| anyhow!("Invalid DID {}: {}", session.did, e))?,
+                did_doc: None,
+                email: None,
+                email_auth_factor: None,
+                email_confirmed: None,
+                // Filled in by the PDS as the session is resumed
+                handle: "handle.invalid"
+                    .parse()
+                    .map_err(|e| anyhow!("Invalid handle: {}", e))?,
+                // The agent would refresh with this, which fails, rather than with the OAuth refresh token
+                refresh_jwt: access_token,
+                status: None,
+            }
+            .into(),
+        )
+        .await
+        .context("Failed to resume the OAuth session")?;
+    Ok(agent)
+}
+
+/// Returns the OAuth session in a secret once it has a fresh access token, refreshing it, or waiting for another
+/// invocation to, if need be.
+async fn fresh_oauth_session(
+    secrets_client: &SecretsManagerClient,
+    secret_name: &str,
+    mut secret: StoredSecret,
+) -> Result<OAuthSession> {
+    for _ in 0..OAUTH_REFRESH_POLLS {
+        let session = parse_oauth_session(&secret.credentials)?;
+        let now = Utc::now().timestamp();
+        if session.has_fresh_access_token(now) {
+            return Ok(session);
+        }
+        if refresh_claimed(&secret.credentials, now) {
+            tracing::info!(
+                "Waiting for another invocation to refresh the OAuth session of {}",
+                session.did
+            );
+            tokio::time::sleep(OAUTH_REFRESH_POLL_INTERVAL).await;
+        } else if claim_refresh(secrets_client, secret_name, &secret, now).await? {
+            return refresh_oauth_session(secrets_client, secret_name, secret, session, now).await;
+        }
+        secret = read_secret(secrets_client, secret_name).await?;
+    }
+    Err(anyhow!(
+        "Timed out waiting for another invocation to refresh the OAuth session"
+    ))
+}
+
+/// Claims the refresh of the OAuth session in a secret for this invocation.
+///
+/// # Returns
+///
+/// A Result containing whether the claim succeeded, false if another invocation changed the secret since it was
+/// read, or an error if the secret can't be written.
+async fn claim_refresh(
+    secrets_client: &SecretsManagerClient,
+    secret_name: &str,
+    secret: &StoredSecret,
+    now: i64,
+) -> Result<bool> {
+    let mut claim = secret.credentials.clone();
+    claim[OAUTH_REFRESH_CLAIM_FIELD] = serde_json::json!(now + OAUTH_REFRESH_CLAIM_SECONDS);
+    let output = secrets_client
+        .put_secret_value()
+        .secret_id(secret_name)
+        .secret_string(claim.to_string())
+        .version_stages(OAUTH_REFRESH_CLAIM_STAGE)
+        .send()
+        .await
+        .context("Failed to claim the refresh of the OAuth session")?;
+    let claim_version = output
+        .version_id()
+        .context("Claim of the refresh has no version ID")?;
+
+    // Fails unless AWSCURRENT is still on the version that was read
+    match secrets_client
+        .update_secret_version_stage()
+        .secret_id(secret_name)
+        .version_stage("AWSCURRENT")
+        .move_to_version_id(claim_version)
+        .remove_from_version_id(&secret.version_id)
+        .send()
+        .await
+    {
+        Ok(_) => Ok(true),
+        Err(e)
+            if e.as_service_error()
+                .is_some_and(|e| e.is_invalid_parameter_exception()) =>
+        {
+            tracing::info!("Another invocation changed the OAuth session first");
+            Ok(false)
+        }
+        Err(e) => Err(e).context("Failed to claim the refresh of the OAuth session"),
+    }
+}
+
+/// Refreshes the OAuth session in a secret, whose refresh this invocation claimed, and stores the refreshed one,
+/// retrying failed writes as its new refresh token is lost with them.
+async fn refresh_oauth_session(
+    secrets_client: &SecretsManagerClient,
+    secret_name: &str,
+    secret: StoredSecret,
+    mut session: OAuthSession,
+    now: i64,
+) -> Result<OAuthSession> {
+    if let Err(e) = session.refresh(&crate::oauth::oauth_client()?, now).await {
+        // An invocation that took the claim for a failed one may have refreshed the session after all
+        let current =
+            parse_oauth_session(&read_secret(secrets_client, secret_name).await?.credentials)?;
+        if current.has_fresh_access_token(Utc::now().timestamp()) {
+            return Ok(current);
+        }
+        return Err(e);
+    }
+
+    let mut credentials = secret.credentials;
+    credentials["oauth"] = serde_json::to_value(&session)?;
+    if let Some(fields) = credentials.as_object_mut() {
+        fields.remove(OAUTH_REFRESH_CLAIM_FIELD);
+    }
+    let mut attempt = 0;
+    loop {
+        match secrets_client
+            .put_secret_value()
+            .secret_id(secret_name)
+            .secret_string(credentials.to_string())
+            .send()
+            .await
+        {
+            Ok(_) => {
+                tracing::info!("Refreshed the OAuth session of {}", session.did);
+                return Ok(session);
+            }
+            Err(e) if attempt < DEFAULT_MAX_RETRIES => {
+                let delay = backoff_delay(RETRY_BASE_DELAY, attempt);
+                tracing::warn!(
+                    "Failed to store the refreshed OAuth session: {}, retrying in {:?} (attempt {}/{})",
+                    e,
+                    delay,
+                    attempt + 1,
+                    DEFAULT_MAX_RETRIES
+                );
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+            Err(e) => {
+                return Err(e).context(
+                    "Failed to store the refreshed OAuth session, whose new refresh token is lost",
+                )
+            }
+        }
+    }
+}
+
+/// Posts texts as a thread of plain posts, each replying to the one before, with their links and tags as facets.
+///
+/// # Arguments
+///
+/// * `texts` - The texts of the posts, in order.
+/// * `agent` - A logged in BskyAgent.
+///
+/// # Returns
+///
+/// References to the posts that were made, in order. A post that fails ends the thread there, so none are made if
+/// the first one fails.
+pub async fn post_thread(
+    texts: impl IntoIterator<Item = String>,
+    agent: &BskyAgent<RetryingClient>,
+) -> Vec<strong_ref::MainData> {
+    let mut posted: Vec<strong_ref::MainData> = Vec::new();
+    for text in texts {
+        let reply = posted.first().zip(posted.last()).map(|(root, parent)| {
+            ReplyRefData {
+                parent: parent.clone().into(),
+                root: root.clone().into(),
+            }
+            .into()
+        });
+        let result = async {
+            let rt = RichText::new_with_detect_facets(text)
+                .await
+                .context("Failed to create RichText")?;
+            let record_data = post::RecordData {
+                created_at: Datetime::now(),
+                embed: None,
+                entities: None,
+                facets: keep_mentions(rt.facets, &rt.text, &[]),
+                labels: None,
+                langs: None,
+                reply,
+                tags: None,
+                text: rt.text,
+            };
+            agent
+                .create_record(record_data)
+                .await
+                .context("Failed to create Bluesky post")
+        }
+        .await;
+        match result {
+            Ok(result) => posted.push(strong_ref::MainData {
+                cid: result.cid.clone(),
+                uri: result.uri.clone(),
+            }),
+            Err(e) => {
+                tracing::warn!("Failed to post the thread: {:?}", e);
+                break;
+            }
+        }
+    }
+    posted
+}
+
+/// How a post is doing, as the account that made it sees it.
+#[derive(Debug, Clone)]
+pub struct PostStats {
+    /// The CID of the post, for strong references to it.
+    pub cid: Cid,
+    pub likes: i64,
+    pub reposts: i64,
+    pub quotes: i64,
+    pub replies: i64,
+    /// The likes, reposts, quotes and replies of the post together.
+    pub engagement: i64,
+    /// Whether the account reposted the post.
+    pub reposted: bool,
+}
+
+/// Looks up how posts are doing.
+///
+/// # Arguments
+///
+/// * `uris` - The at:// URIs of the posts.
+/// * `agent` - A BskyAgent logged in to the account that made the posts.
+///
+/// # Returns
+///
+/// A Result containing the stats by post URI, without the posts Bluesky no longer has, or an error if they couldn't
+/// be fetched.
+pub async fn post_stats(
+    uris: &[String],
+    agent: &BskyAgent<RetryingClient>,
+) -> Result<HashMap<String, PostStats>> {
+    let mut stats = HashMap::new();
+    for batch in uris.chunks(GET_POSTS_BATCH) {
+        let output = agent
+            .api
+            .app
+            .bsky
+            .feed
+            .get_posts(
+                get_posts::ParametersData {
+                    uris: batch.to_vec(),
+                }
+                .into(),
+            )
+            .await
+            .context("Failed to get posts")?;
+        for post in output.data.posts {
+            let likes = post.like_count.unwrap_or_default();
+            let reposts = post.repost_count.unwrap_or_default();
+            let quotes = post.quote_count.unwrap_or_default();
+            let replies = post.reply_count.unwrap_or_default();
+            let reposted = post
+                .viewer
+                .as_ref()
+                .is_some_and(|viewer| viewer.repost.is_some());
+            stats.insert(
+                post.data.uri,
+                PostStats {
+                    cid: post.data.cid,
+                    likes,
+                    reposts,
+                    quotes,
+                    replies,
+                    engagement: likes + reposts + quotes + replies,
+                    reposted,
+                },
+            );
+        }
+    }
+    Ok(stats)
+}
+
+/// Drops the mention facets RichText detected in a post other than those of the mentions it makes on purpose. The
+/// texts of feed items have handle-like words, such as the domain of an email address, which would otherwise link to,
+/// and notify, whoever has that handle.
+///
+/// # Arguments
+///
+/// * `facets` - The facets of the post.
+/// * `text` - The text of the post, which the facets index.
+/// * `mentions` - The mentions the post makes on purpose, including their `@`.
+///
+/// # Returns
+///
+/// The facets without the other mentions, None if none are left.
+pub fn keep_mentions(
+    facets: Option<Vec<facet::Main>>,
+    text: &str,
+    mentions: &[String],
+) -> Option<Vec<facet::Main>> {
+    let facets: Vec<facet::Main> = facets?
+        .into_iter()
+        .filter_map(|mut facet| {
+            let span = text.get(facet.index.byte_start..facet.index.byte_end);
+            let wanted = span.is_some_and(|span| {
+                mentions
+                    .iter()
+                    .any(|mention| mention.eq_ignore_ascii_case(span))
+            });
+            facet.features.retain(|feature| {
+                wanted || !matches!(feature, Union::Refs(MainFeaturesItem::Mention(_)))
+            });
+            (!facet.features.is_empty()).then_some(facet)
+        })
+        .collect();
+    (!facets.is_empty()).then_some(facets)
+}
+
+/// Points the links a post shows by their display text at their full URLs. RichText only detects links from the
+/// text, which has them shortened, so the facets it made for them are replaced.
+///
+/// # Arguments
+///
+/// * `facets` - The facets of the post.
+/// * `text` - The text of the post, which the facets index.
+/// * `links` - The display texts of the links, with their URLs.
+///
+/// # Returns
+///
+/// The facets with those of the links, in the order of the text. None if there are none.
+pub fn link_facets(
+    facets: Option<Vec<facet::Main>>,
+    text: &str,
+    links: &[(String, String)],
+) -> Option<Vec<facet::Main>> {
+    let mut facets = facets.unwrap_or_default();
+    for (display_text, url) in links.iter().filter(|(display, _)| !display.is_empty()) {
+        for (start, _) in text.match_indices(display_text.as_str()) {
+            let end = start + display_text.len();
+            facets.retain(|facet| facet.index.byte_end <= start || facet.index.byte_start >= end);
+            facets.push(
+                facet::MainData {
+                    features: vec![Union::Refs(MainFeaturesItem::Link(Box::new(
+                        facet::LinkData { uri: url.clone() }.into(),
+                    )))],
+                    index: facet::ByteSliceData {
+                        byte_end: end,
+                        byte_start: start,
+                    }
+                    .into(),
+                }
+                .into(),
+            );
+        }
+    }
+    facets.sort_by_key(|facet| facet.index.byte_start);
+    (!facets.is_empty()).then_some(facets)
+}
+
+/// Splits an at:// URI into the repo, collection and record key of the record it points to.
+///
+/// # Returns
+///
+/// The three parts, or None if the URI doesn't point to a record.
+pub fn parse_at_uri(uri: &str) -> Option<(&str, &str, &str)> {
+    let mut parts = uri.strip_prefix("at://")?.split('/');
+    match (parts.next(), parts.next(), parts.next(), parts.next()) {
+        (Some(repo), Some(collection), Some(rkey), None)
+            if !repo.is_empty() && !collection.is_empty() && !rkey.is_empty() =>
+        {
+            Some((repo, collection, rkey))
+        }
+        _ => None,
+    }
+}
+
+/// Works out the date of a post backdated to the publication of its item.
+///
+/// # Arguments
+///
+/// * `pub_date` - The publication date of the item, as the feed gave it.
+/// * `now` - The current time.
+/// * `max_age` - How far back a post may be dated. Apps sort posts dated much earlier far down the timeline, or
+///   flag them as backdated.
+///
+/// # Returns
+///
+/// The publication date, or `now` if the item has no valid date, or one in the future or further back than
+/// `max_age`.
+pub fn backdated_created_at(
+    pub_date: Option<&str>,
+    now: DateTime<Utc>,
+    max_age: chrono::Duration,
+) -> DateTime<FixedOffset> {
+    pub_date
+        .and_then(parse_date)
+        .filter(|pub_date| *pub_date <= now && now - pub_date.to_utc() <= max_age)
+        .unwrap_or_else(|| now.fixed_offset())
+}
+
+/// Who may reply to a post.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReplyControl {
+    /// Anyone, the way posts are without a threadgate.
+    #[default]
+    Everyone,
+    /// No one but the posting account.
+    Nobody,
+    /// Accounts following the posting account.
+    Followers,
+    /// Accounts the posting account follows.
+    Following,
+    /// Accounts mentioned in the post.
+    Mentioned,
+}
+
+impl ReplyControl {
+    /// Parses a reply control: `everyone`, `nobody`, `followers`, `following` or `mentioned`, in any case.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the ReplyControl, or an error if it's unknown.
+    pub fn parse(control: &str) -> Result<Self> {
+        match control.trim().to_lowercase().as_str() {
+            "" | "everyone" => Ok(ReplyControl::Everyone),
+            "nobody" => Ok(ReplyControl::Nobody),
+            "followers" => Ok(ReplyControl::Followers),
+            "following" => Ok(ReplyControl::Following),
+            "mentioned" => Ok(ReplyControl::Mentioned),
+            other => Err(anyhow!("Unknown reply control {:?}", other)),
+        }
+    }
+
+    /// Writes the threadgate record that limits the replies to a post.
+    ///
+    /// # Arguments
+    ///
+    /// * `post_uri` - The at:// URI of the post. The threadgate has to be stored under the same record key.
+    /// * `created_at` - The date of the threadgate.
+    ///
+    /// # Returns
+    ///
+    /// The threadgate, or None if everyone may reply, which takes no threadgate.
+    pub fn threadgate(
+        &self,
+        post_uri: &str,
+        created_at: Datetime,
+    ) -> Option<threadgate::RecordData> {
+        let allow = match self {
+            ReplyControl::Everyone => return None,
+            // An empty list of rules leaves no one allowed to reply
+            ReplyControl::Nobody => Vec::new(),
+            // The version of the lexicon the bridge is built with predates the follower rule, so it goes out as an
+            // object of an unknown type, which the PDS stores as it is
+            ReplyControl::Followers => vec![Union::Unknown(
+                serde_json::from_value(serde_json::json!({
+                    "$type": "app.bsky.feed.threadgate#followerRule"
+                }))
+                .ok()?,
+            )],
+            ReplyControl::Following => vec![Union::Refs(RecordAllowItem::FollowingRule(Box::new(
+                FollowingRuleData {}.into(),
+            )))],
+            ReplyControl::Mentioned => vec![Union::Refs(RecordAllowItem::MentionRule(Box::new(
+                MentionRuleData {}.into(),
+            )))],
+        };
+        Some(threadgate::RecordData {
+            allow: Some(allow),
+            created_at,
+            hidden_replies: None,
+            post: post_uri.to_string(),
+        })
+    }
+}
+
+/// Requests aren't Clone, so every attempt sends a copy.
+fn copy_request(request: &Request<Vec<u8>>) -> Request<Vec<u8>> {
+    let mut copy = Request::new(request.body().clone());
+    *copy.method_mut() = request.method().clone();
+    *copy.uri_mut() = request.uri().clone();
+    *copy.version_mut() = request.version();
+    *copy.headers_mut() = request.headers().clone();
+    copy
+}
+
+/// Reads how long a rate limited request has to wait from the headers of the 429 response.
+///
+/// # Arguments
+///
+/// * `headers` - The response headers. Bluesky sends `ratelimit-reset`, the Unix time the limit resets at; other
+///   servers may send `retry-after` in seconds.
+/// * `now` - The current Unix time.
+///
+/// # Returns
+///
+/// The time to wait, or None if the headers don't say.
+pub fn rate_limit_delay(headers: &HeaderMap, now: i64) -> Option<Duration> {
+    let header = |name: &str| {
+        headers
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.trim().parse::<i64>().ok())
+    };
+    let seconds = header("ratelimit-reset")
+        .map(|reset| reset - now)
+        .or_else(|| header("retry-after"))?;
+    Some(Duration::from_secs(seconds.max(0) as u64))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use atrium_api::xrpc::http::HeaderValue;
+
+    #[test]
+    fn test_refresh_claimed() {
+        assert!(!refresh_claimed(&serde_json::json!({"oauth": {}}), 1_000));
+        assert!(refresh_claimed(
+            &serde_json::json!({"oauth": {}, "refreshing_until": 1_030}),
+            1_000
+        ));
+        // A claim that ran out was given up
+        assert!(!refresh_claimed(
+            &serde_json::json!({"oauth": {}, "refreshing_until": 1_000}),
+            1_000
+        ));
+    }
+
+    #[test]
+    fn test_rate_limit_delay() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(rate_limit_delay(&headers, 1_000), None);
+
+        headers.insert("retry-after", HeaderValue::from_static("5"));
+        assert_eq!(
+            rate_limit_delay(&headers, 1_000),
+            Some(Duration::from_secs(5))
+        );
+
+        // The reset time wins, and one that has passed already means retrying right away
+        headers.insert("ratelimit-reset", HeaderValue::from_static("1012"));
+        assert_eq!(
+            rate_limit_delay(&headers, 1_000),
+            Some(Duration::from_secs(12))
+        );
+        assert_eq!(rate_limit_delay(&headers, 2_000), Some(Duration::ZERO));
+
+        headers.insert("ratelimit-reset", HeaderValue::from_static("soon"));
+        assert_eq!(
+            rate_limit_delay(&headers, 1_000),
+            Some(Duration::from_secs(5))
+        );
+    }
+
+    #[test]
+    fn test_backdated_created_at() {
+        let now = DateTime::parse_from_rfc3339("2024-07-01T12:00:00Z")
+            .unwrap()
+            .to_utc();
+        let max_age = chrono::Duration::hours(24);
+
+        let created_at =
+            backdated_created_at(Some("Mon, 01 Jul 2024 09:30:00 +0200"), now, max_age);
+        assert_eq!(created_at.to_rfc3339(), "2024-07-01T09:30:00+02:00");
+
+        // Dates in the future, too old or missing don't backdate the post
+        for pub_date in [
+            Some("2024-07-01T13:00:00Z"),
+            Some("2024-06-29T12:00:00Z"),
+            Some("yesterday"),
+            None,
+        ] {
+            assert_eq!(backdated_created_at(pub_date, now, max_age), now);
+        }
+    }
+
+    #[test]
+    fn test_reply_control() {
+        assert_eq!(ReplyControl::parse("").unwrap(), ReplyControl::Everyone);
+        assert_eq!(
+            ReplyControl::parse(" Nobody ").unwrap(),
+            ReplyControl::Nobody
+        );
+        assert!(ReplyControl::parse("friends").is_err());
+
+        let uri = "at://did:plc:abc/app.bsky.feed.post/3kx";
+        let allow = |control: ReplyControl| {
+            let threadgate = control.threadgate(uri, Datetime::now())?;
+            assert_eq!(threadgate.post, uri);
+            Some(serde_json::to_value(threadgate.allow).unwrap())
+        };
+        assert_eq!(allow(ReplyControl::Everyone), None);
+        assert_eq!(allow(ReplyControl::Nobody), Some(serde_json::json!([])));
+        assert_eq!(
+            allow(ReplyControl::Followers),
+            Some(serde_json::json!([{"$type": "app.bsky.feed.threadgate#followerRule"}]))
+        );
+        assert_eq!(
+            allow(ReplyControl::Following),
+            Some(serde_json::json!([{"$type": "app.bsky.feed.threadgate#followingRule"}]))
+        );
+    }
+
+    #[test]
+    fn test_keep_mentions() {
+        let text = "Ask @jane.bsky.social, or write to info @example.com at https://example.com";
+        let facet = |start: usize, end: usize, feature: serde_json::Value| -> facet::Main {
+            serde_json::from_value(serde_json::json!({
+                "index": {"byteStart": start, "byteEnd": end},
+                "features": [feature],
+            }))
+            .unwrap()
+        };
+        let mention = |did: &str| -> serde_json::Value {
+            serde_json::json!({"$type": "app.bsky.richtext.facet#mention", "did": did})
+        };
+        let link = facet(
+            56,
+            75,
+            serde_json::json!({"$type": "app.bsky.richtext.facet#link", "uri": "https://example.com"}),
+        );
+        let facets = vec![
+            facet(4, 21, mention("did:plc:jane")),
+            facet(40, 52, mention("did:plc:example")),
+            link.clone(),
+        ];
+
+        let kept = keep_mentions(
+            Some(facets.clone()),
+            text,
+            &["@Jane.bsky.social".to_string()],
+        );
+        assert_eq!(kept, Some(vec![facets[0].clone(), link.clone()]));
+        assert_eq!(
+            keep_mentions(Some(facets.clone()), text, &[]),
+            Some(vec![link])
+        );
+        assert_eq!(keep_mentions(Some(facets[1..2].to_vec()), text, &[]), None);
+        assert_eq!(keep_mentions(None, text, &[]), None);
+    }
+
+    #[test]
+    fn test_link_facets() {
+        let text = "Read more: example.com/articles/2024/a-v… #news";
+        let url = "https://example.com/articles/2024/a-very-long-slug";
+        let detected: facet::Main = serde_json::from_value(serde_json::json!({
+            "index": {"byteStart": 11, "byteEnd": 40},
+            "features": [{"$type": "app.bsky.richtext.facet#link", "uri": "https://example.com/articles/2024/a-v"}],
+        }))
+        .unwrap();
+        let tag: facet::Main = serde_json::from_value(serde_json::json!({
+            "index": {"byteStart": 44, "byteEnd": 49},
+            "features": [{"$type": "app.bsky.richtext.facet#tag", "tag": "news"}],
+        }))
+        .unwrap();
+        let links = [(
+            "example.com/articles/2024/a-v…".to_string(),
+            url.to_string(),
+        )];
+
+        let facets = link_facets(Some(vec![detected, tag.clone()]), text, &links).unwrap();
+        assert_eq!(facets.len(), 2);
+        assert_eq!(
+            (facets[0].index.byte_start, facets[0].index.byte_end),
+            (11, 43)
+        );
+        assert_eq!(
+            serde_json::to_value(&facets[0].features).unwrap(),
+            serde_json::json!([{"$type": "app.bsky.richtext.facet#link", "uri": url}])
+        );
+        assert_eq!(facets[1], tag);
+
+        assert_eq!(link_facets(None, "No links", &links), None);
+    }
+
+    #[test]
+    fn test_parse_at_uri() {
+        assert_eq!(
+            parse_at_uri("at://did:plc:abc/app.bsky.feed.post/3kx"),
+            Some(("did:plc:abc", "app.bsky.feed.post", "3kx"))
+        );
+        assert_eq!(parse_at_uri("at://did:plc:abc/app.bsky.feed.post"), None);
+        assert_eq!(
+            parse_at_uri("at://did:plc:abc/app.bsky.feed.post/3kx/x"),
+            None
+        );
+        assert_eq!(parse_at_uri("https://bsky.app/profile/abc/post/3kx"), None);
+    }
+
+    #[test]
+    fn test_copy_request() {
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri("https://bsky.social/xrpc/com.atproto.repo.createRecord")
+            .header("content-type", "application/json")
+            .body(b"{}".to_vec())
+            .unwrap();
+        let copy = copy_request(&request);
+        assert_eq!(copy.method(), Method::POST);
+        assert_eq!(copy.uri(), request.uri());
+        assert_eq!(copy.headers(), request.headers());
+        assert_eq!(copy.body(), request.body());
+    }
+}
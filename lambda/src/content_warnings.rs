@@ -0,0 +1,197 @@
+use anyhow::{anyhow, Context, Result};
+use regex::{Regex, RegexBuilder};
+use serde::Deserialize;
+
+/// A rule giving the posts of matching items a self-label, a warning in front of their text, or both.
+#[derive(Debug, Clone)]
+struct Rule {
+    /// Lowercased keywords, matched as substrings.
+    keywords: Vec<String>,
+    /// Matched ignoring case.
+    regex: Option<Regex>,
+    label: Option<String>,
+    warning: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct RuleSpec {
+    #[serde(default)]
+    keywords: Vec<String>,
+    regex: Option<String>,
+    label: Option<String>,
+    warning: Option<String>,
+}
+
+/// The content warnings of an item.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ContentWarning {
+    /// The self-labels the post gets, e.g. `graphic-media`.
+    pub labels: Vec<String>,
+    /// The warnings the text of the post leads with, e.g. `CW: death`.
+    pub warnings: Vec<String>,
+}
+
+/// Rules that find the items whose posts need a content warning by keywords or regular expressions in their title
+/// or description.
+#[derive(Debug, Clone, Default)]
+pub struct ContentWarningRules {
+    rules: Vec<Rule>,
+}
+
+impl ContentWarningRules {
+    /// Parses the rules, a JSON array such as
+    /// `[{"keywords": ["graphic", "gore"], "label": "graphic-media"}, {"regex": "\\bdeaths?\\b", "warning": "CW: death"}]`.
+    ///
+    /// A rule matches when its title or description contains one of its `keywords`, or matches its `regex`, both
+    /// ignoring case. It gives the post its `label`, its `warning`, or both.
+    ///
+    /// # Arguments
+    ///
+    /// * `json` - The JSON array of rules.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the rules, or an error if a rule has an invalid regex, nothing to match or nothing to
+    /// give.
+    pub fn parse(json: &str) -> Result<Self> {
+        let specs: Vec<RuleSpec> =
+            serde_json::from_str(json).context("Content warnings must be a JSON array of rules")?;
+        let rules = specs
+            .into_iter()
+            .enumerate()
+            .map(|(i, spec)| {
+                let keywords: Vec<String> = spec
+                    .keywords
+                    .iter()
+                    .map(|keyword| keyword.trim().to_lowercase())
+                    .filter(|keyword| !keyword.is_empty())
+                    .collect();
+                let regex = spec
+                    .regex
+                    .filter(|regex| !regex.trim().is_empty())
+                    .map(|regex| {
+                        RegexBuilder::new(&regex)
+                            .case_insensitive(true)
+                            .build()
+                            .with_context(|| {
+                                format!("Invalid regex {:?} in content warning", regex)
+                            })
+                    })
+                    .transpose()?;
+                let label = spec
+                    .label
+                    .map(|label| label.trim().to_lowercase())
+                    .filter(|label| !label.is_empty());
+                let warning = spec
+                    .warning
+                    .map(|warning| warning.trim().to_string())
+                    .filter(|warning| !warning.is_empty());
+                if keywords.is_empty() && regex.is_none() {
+                    return Err(anyhow!(
+                        "Content warning {} has no keywords or regex",
+                        i + 1
+                    ));
+                }
+                if label.is_none() && warning.is_none() {
+                    return Err(anyhow!("Content warning {} has no label or warning", i + 1));
+                }
+                Ok(Rule {
+                    keywords,
+                    regex,
+                    label,
+                    warning,
+                })
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self { rules })
+    }
+
+    /// Returns true if there are no rules.
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Finds the content warnings of an item.
+    ///
+    /// # Returns
+    ///
+    /// The labels and warnings of the rules the item matches, each once, in the order of the rules.
+    pub fn check(&self, title: Option<&str>, description: Option<&str>) -> ContentWarning {
+        let text = format!(
+            "{}\n{}",
+            title.unwrap_or_default(),
+            description.unwrap_or_default()
+        );
+        let lowercase = text.to_lowercase();
+
+        let mut warning = ContentWarning::default();
+        for rule in &self.rules {
+            let matches = rule
+                .keywords
+                .iter()
+                .any(|keyword| lowercase.contains(keyword.as_str()))
+                || rule
+                    .regex
+                    .as_ref()
+                    .is_some_and(|regex| regex.is_match(&text));
+            if !matches {
+                continue;
+            }
+            for (value, values) in [
+                (&rule.label, &mut warning.labels),
+                (&rule.warning, &mut warning.warnings),
+            ] {
+                if let Some(value) = value.as_ref().filter(|value| !values.contains(value)) {
+                    values.push(value.clone());
+                }
+            }
+        }
+        warning
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_content_warnings() {
+        let rules = ContentWarningRules::parse(
+            r#"[
+                {"keywords": ["Graphic", "gore"], "label": "graphic-media"},
+                {"regex": "\\bdeaths?\\b", "warning": "CW: death"},
+                {"keywords": ["war"], "label": "graphic-media", "warning": "CW: war"}
+            ]"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            rules.check(Some("GRAPHIC footage"), Some("Two deaths reported")),
+            ContentWarning {
+                labels: vec!["graphic-media".to_string()],
+                warnings: vec!["CW: death".to_string()],
+            }
+        );
+        assert_eq!(
+            rules.check(Some("Gore and war"), None),
+            ContentWarning {
+                labels: vec!["graphic-media".to_string()],
+                warnings: vec!["CW: war".to_string()],
+            }
+        );
+        // The regex only matches whole words
+        assert_eq!(
+            rules.check(Some("Deathloop review"), None),
+            ContentWarning::default()
+        );
+    }
+
+    #[test]
+    fn test_parse_invalid_rules() {
+        assert!(ContentWarningRules::parse("{}").is_err());
+        assert!(ContentWarningRules::parse(r#"[{"regex": "(", "label": "x"}]"#).is_err());
+        assert!(ContentWarningRules::parse(r#"[{"label": "graphic-media"}]"#).is_err());
+        assert!(ContentWarningRules::parse(r#"[{"keywords": ["gore"]}]"#).is_err());
+        assert!(ContentWarningRules::parse("[]").unwrap().is_empty());
+    }
+}
@@ -0,0 +1,229 @@
+use crate::env;
+use crate::filters::parse_list;
+use crate::github;
+use crate::html::strip_tags;
+use crate::language::detect_language;
+use crate::models::{ExecutionItem, UPDATE_PREFIX};
+use crate::text_utils::template::{Field, PostTemplate};
+use crate::text_utils::{
+    append_hashtags, episode_label, hashtag, static_hashtags, truncate_to_word,
+};
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+
+const DEFAULT_RELEASE_NOTES_GRAPHEMES: usize = 200;
+const DEFAULT_MAX_HASHTAGS: usize = 3;
+
+/// How the items cross-posted to other networks are written, from the same settings as their Bluesky posts.
+pub struct TextSettings {
+    /// Layout of the posts, which carry the link in their text as there are no link cards.
+    pub template: PostTemplate,
+    pub post_hashtags: Vec<String>,
+    pub category_hashtags: bool,
+    pub max_hashtags: usize,
+    pub release_notes_graphemes: usize,
+}
+
+impl TextSettings {
+    /// Reads the settings from the environment.
+    ///
+    /// # Arguments
+    ///
+    /// * `template_var` - The variable holding the network's own template, e.g. `MASTODON_POST_TEMPLATE`.
+    ///   POST_TEMPLATE, and then the default layout, are used without it.
+    pub fn from_env(template_var: &str) -> Result<Self> {
+        let template_text = env::optional(template_var)
+            .or_else(|| env::optional("POST_TEMPLATE"))
+            .unwrap_or_else(|| {
+                PostTemplate::default_text(env::flag("PREFIX_SOURCE"), env::flag("CREDIT_AUTHOR"))
+            });
+        Ok(Self {
+            template: PostTemplate::parse(&template_text)?.with_link(),
+            post_hashtags: static_hashtags(&parse_list(
+                &env::optional("POST_HASHTAGS").unwrap_or_default(),
+            )),
+            category_hashtags: env::flag("CATEGORY_HASHTAGS"),
+            max_hashtags: env::parse_or("MAX_HASHTAGS", DEFAULT_MAX_HASHTAGS)?,
+            release_notes_graphemes: env::parse_or(
+                "RELEASE_NOTES_LENGTH",
+                DEFAULT_RELEASE_NOTES_GRAPHEMES,
+            )?,
+        })
+    }
+}
+
+/// The text of a cross-posted item.
+#[derive(Debug, PartialEq)]
+pub struct ItemText {
+    /// The text, hashtags included.
+    pub text: String,
+    /// The hashtags that made it into the text.
+    pub hashtags: Vec<String>,
+    /// The language of the item, if it's known or can be told.
+    pub language: Option<String>,
+}
+
+/// Writes the text of an item for another network, with the summary, template and hashtags of its Bluesky post.
+///
+/// # Arguments
+///
+/// * `item` - The item.
+/// * `settings` - The text settings.
+/// * `max_graphemes` - The length limit of the text.
+///
+/// # Returns
+///
+/// A Result containing the text, or an error if the item has no title, description or link.
+pub fn item_text(
+    item: &ExecutionItem,
+    settings: &TextSettings,
+    max_graphemes: usize,
+) -> Result<ItemText> {
+    let title = item.title.clone().context("Title not found in item")?;
+    let description = item
+        .description
+        .clone()
+        .context("Description not found in item")?;
+    let link = item.link.clone().context("Link not found in item")?;
+
+    let release = item
+        .feed_url
+        .as_deref()
+        .filter(|feed_url| github::is_releases_feed(feed_url))
+        .and_then(|_| github::release_from_url(&link));
+    let summary = match (release, &item.summary) {
+        (Some(release), _) => github::release_post_text(
+            &release,
+            Some(description.as_str()).filter(|notes| *notes != title),
+            settings.release_notes_graphemes,
+            max_graphemes,
+        ),
+        (None, Some(s)) if !s.trim().is_empty() => s.clone(),
+        (None, _) => truncate_to_word(&strip_tags(&description), max_graphemes),
+    };
+
+    let mut values = HashMap::from([
+        (Field::Title, title.clone()),
+        (Field::Summary, summary),
+        (Field::Link, link),
+    ]);
+    if let Some(label) = episode_label(item.season.as_deref(), item.episode.as_deref()) {
+        values.insert(Field::Episode, label);
+    }
+    if let Some(feed_title) = item.feed_title.clone() {
+        values.insert(Field::Source, feed_title);
+    }
+    if let Some(author) = item.author.clone() {
+        values.insert(Field::Author, author);
+    }
+    if let Some(prefix) = item.prefix.clone() {
+        values.insert(Field::Prefix, prefix);
+    }
+    if item.update_of_uri.is_some() {
+        let field = if settings.template.has_field(Field::Summary) {
+            Field::Summary
+        } else {
+            Field::Title
+        };
+        if let Some(value) = values.get_mut(&field) {
+            value.insert_str(0, UPDATE_PREFIX);
+        }
+    }
+    let text = settings.template.render(&values, max_graphemes);
+
+    let mut hashtags = if item.hashtags.is_empty() {
+        settings.post_hashtags.clone()
+    } else {
+        static_hashtags(&item.hashtags)
+    };
+    if settings.category_hashtags {
+        let mut category_hashtags: Vec<String> = Vec::new();
+        for tag in item.categories.iter().filter_map(|c| hashtag(c)) {
+            let is_new =
+                |tags: &[String]| !tags.iter().any(|t| t.to_lowercase() == tag.to_lowercase());
+            if is_new(&hashtags) && is_new(&category_hashtags) {
+                category_hashtags.push(tag);
+            }
+        }
+        category_hashtags.truncate(settings.max_hashtags);
+        hashtags.extend(category_hashtags);
+    }
+    let with_hashtags = append_hashtags(&text, &hashtags, max_graphemes);
+    let tag_line = with_hashtags[text.len()..].to_lowercase();
+    hashtags.retain(|tag| tag_line.split_whitespace().any(|t| t == tag.to_lowercase()));
+
+    let language = item
+        .language
+        .clone()
+        .or_else(|| {
+            let sample = format!("{}\n{}", title, values.get(&Field::Summary)?);
+            detect_language(&strip_tags(&sample)).map(String::from)
+        })
+        .map(|language| language.trim().to_string());
+
+    Ok(ItemText {
+        text: with_hashtags,
+        hashtags,
+        language,
+    })
+}
+
+/// Returns the self-labels of an item's feed, trimmed and lowercased, for the content warnings of networks without
+/// self-labels.
+pub fn content_labels(item: &ExecutionItem) -> Vec<String> {
+    item.labels
+        .iter()
+        .map(|label| label.trim().to_lowercase())
+        .filter(|label| !label.is_empty())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(template: &str) -> TextSettings {
+        TextSettings {
+            template: PostTemplate::parse(template).unwrap().with_link(),
+            post_hashtags: vec!["#news".to_string()],
+            category_hashtags: true,
+            max_hashtags: 3,
+            release_notes_graphemes: 200,
+        }
+    }
+
+    #[test]
+    fn test_item_text() {
+        let mut item = ExecutionItem::new(
+            "execution".to_string(),
+            "guid".to_string(),
+            Some("Rust 2.0 released".to_string()),
+            Some("<p>The Rust team announced Rust 2.0 today.</p>".to_string()),
+            Some("https://example.com/rust".to_string()),
+            None,
+        )
+        .unwrap();
+        item.categories = vec!["Rust".to_string(), "news".to_string()];
+        item.language = Some("en".to_string());
+
+        let text = item_text(&item, &settings("{title}: {summary}"), 500).unwrap();
+        assert_eq!(
+            text.text,
+            "Rust 2.0 released: The Rust team announced Rust 2.0 today.\n\nhttps://example.com/rust\n\n#news #Rust"
+        );
+        assert_eq!(text.hashtags, vec!["#news", "#Rust"]);
+        assert_eq!(text.language.as_deref(), Some("en"));
+
+        // Updates are marked, and tags that don't fit are left out
+        item.update_of_uri = Some("at://did:plc:abc/app.bsky.feed.post/3kx".to_string());
+        let text = item_text(&item, &settings("{title}"), 55).unwrap();
+        assert_eq!(
+            text.text,
+            "Updated: Rust 2.0 released\n\nhttps://example.com/rust"
+        );
+        assert!(text.hashtags.is_empty());
+
+        item.link = None;
+        assert!(item_text(&item, &settings("{title}"), 500).is_err());
+    }
+}
@@ -0,0 +1,248 @@
+use crate::models::RecentPost;
+use crate::text_utils::{truncate_to_word, MAX_POST_GRAPHEMES};
+use std::collections::HashMap;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Digests leave out items with longer links, which would leave no room for their title.
+pub const MAX_LINK_GRAPHEMES: usize = 200;
+
+/// An item listed in a digest.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DigestEntry {
+    pub title: String,
+    pub link: String,
+}
+
+/// A post of a digest, the first one of its thread or a reply continuing it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DigestPost {
+    pub text: String,
+    /// How many entries the post lists, following those listed by the posts before it.
+    pub entries: usize,
+}
+
+/// Lays out a digest listing items with their titles and links, as a thread when they don't fit in one post.
+///
+/// # Arguments
+///
+/// * `header` - The text the first post starts with, e.g. `Today's posts`. Empty for none.
+/// * `entries` - The items to list, in order.
+/// * `max_posts` - The most posts the digest is made of. The entries that don't fit are left for the next digest.
+///
+/// # Returns
+///
+/// The posts of the digest, none if there are no entries.
+pub fn compose_digest(header: &str, entries: &[DigestEntry], max_posts: usize) -> Vec<DigestPost> {
+    let mut posts = Vec::new();
+    let mut text = header.trim().to_string();
+    let mut count = 0;
+    for entry in entries {
+        if count > 0 {
+            let joined = format!("{}\n\n{}", text, entry_text(entry, MAX_POST_GRAPHEMES));
+            if joined.graphemes(true).count() <= MAX_POST_GRAPHEMES {
+                text = joined;
+                count += 1;
+                continue;
+            }
+            posts.push(DigestPost {
+                text: std::mem::take(&mut text),
+                entries: count,
+            });
+            if posts.len() >= max_posts.max(1) {
+                return posts;
+            }
+        }
+
+        // The first entry of a post always goes in, with its title shortened to the room the header leaves
+        text = if text.is_empty() {
+            entry_text(entry, MAX_POST_GRAPHEMES)
+        } else {
+            let room = MAX_POST_GRAPHEMES.saturating_sub(text.graphemes(true).count() + 2);
+            format!("{}\n\n{}", text, entry_text(entry, room))
+        };
+        count = 1;
+    }
+    if count > 0 {
+        posts.push(DigestPost {
+            text,
+            entries: count,
+        });
+    }
+    posts
+}
+
+/// Splits the items of a digest into those listed by the posts that were posted and those that stay held for the
+/// next digest: the entries that didn't fit, and those of replies that failed to post.
+///
+/// # Arguments
+///
+/// * `items` - The items, in the order of their entries.
+/// * `posts` - The posts of the digest, see [`compose_digest`].
+/// * `posted` - How many of the posts were posted, from the first.
+///
+/// # Returns
+///
+/// The items listed by each of the posted posts, and those left over, in order.
+pub fn split_listed<T>(
+    items: Vec<T>,
+    posts: &[DigestPost],
+    posted: usize,
+) -> (Vec<Vec<T>>, Vec<T>) {
+    let mut items = items.into_iter();
+    let listed = posts
+        .iter()
+        .take(posted)
+        .map(|post| items.by_ref().take(post.entries).collect())
+        .collect();
+    (listed, items.collect())
+}
+
+/// Picks the posts a roundup lists.
+///
+/// # Arguments
+///
+/// * `posts` - The posts the roundup covers.
+/// * `engagement` - The likes, reposts, quotes and replies of the posts, by guid, to list those with the most. None
+///   lists the latest posts.
+/// * `max_items` - The most posts listed.
+///
+/// # Returns
+///
+/// The posts to list, in the order they were posted.
+pub fn top_posts(
+    mut posts: Vec<RecentPost>,
+    engagement: Option<&HashMap<String, i64>>,
+    max_items: usize,
+) -> Vec<RecentPost> {
+    // Ties, and posts without engagement, go to the latest
+    posts.sort_by_key(|post| {
+        let engagement = engagement.and_then(|engagement| engagement.get(&post.guid));
+        std::cmp::Reverse((engagement.copied().unwrap_or_default(), post.posted_at))
+    });
+    posts.truncate(max_items);
+    posts.sort_by_key(|post| post.posted_at);
+    posts
+}
+
+/// Writes the title of an entry with its link on the next line, shortening the title to fit in `max_graphemes`.
+/// Without room for a title there's just the link.
+fn entry_text(entry: &DigestEntry, max_graphemes: usize) -> String {
+    let room = max_graphemes.saturating_sub(entry.link.graphemes(true).count() + 1);
+    let title = if room > 0 {
+        truncate_to_word(&entry.title, room)
+    } else {
+        String::new()
+    };
+    if title.is_empty() {
+        entry.link.clone()
+    } else {
+        format!("{}\n{}", title, entry.link)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(title: &str, link: &str) -> DigestEntry {
+        DigestEntry {
+            title: title.to_string(),
+            link: link.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_compose_digest() {
+        let entries = vec![
+            entry("First story", "https://example.com/1"),
+            entry("Second story", "https://example.com/2"),
+        ];
+        assert_eq!(
+            compose_digest("Today's posts", &entries, 4),
+            vec![DigestPost {
+                text: "Today's posts\n\nFirst story\nhttps://example.com/1\n\nSecond story\nhttps://example.com/2"
+                    .to_string(),
+                entries: 2,
+            }]
+        );
+        assert!(compose_digest("Today's posts", &[], 4).is_empty());
+    }
+
+    #[test]
+    fn test_compose_digest_thread() {
+        let title = "A story with a title long enough that only a few fit in a post";
+        let entries: Vec<_> = (0..10)
+            .map(|i| entry(title, &format!("https://example.com/{}", i)))
+            .collect();
+
+        let posts = compose_digest("Today's posts", &entries, 4);
+        assert!(posts.len() > 1);
+        assert!(posts[0].text.starts_with("Today's posts\n\n"));
+        assert!(!posts[1].text.starts_with("Today's posts"));
+        for post in &posts {
+            assert!(post.text.graphemes(true).count() <= MAX_POST_GRAPHEMES);
+        }
+        assert_eq!(posts.iter().map(|post| post.entries).sum::<usize>(), 10);
+
+        // The rest waits for the next digest
+        let posts = compose_digest("Today's posts", &entries, 1);
+        assert_eq!(posts.len(), 1);
+        assert!(posts[0].entries < 10);
+    }
+
+    #[test]
+    fn test_split_listed() {
+        let title = "A story with a title long enough that only a few fit in a post";
+        let entries: Vec<_> = (0..10)
+            .map(|i| entry(title, &format!("https://example.com/{}", i)))
+            .collect();
+        let posts = compose_digest("Today's posts", &entries, 2);
+        let fitted: usize = posts.iter().map(|post| post.entries).sum();
+        assert!(fitted < 10);
+
+        let (listed, left) = split_listed((0..10).collect(), &posts, posts.len());
+        assert_eq!(listed.len(), posts.len());
+        assert_eq!(listed[0], (0..posts[0].entries).collect::<Vec<_>>());
+        // The entries that didn't fit stay held for the next digest
+        assert_eq!(left, (fitted..10).collect::<Vec<_>>());
+
+        // So do those of a reply that failed to post
+        let (listed, left) = split_listed((0..10).collect(), &posts, 1);
+        assert_eq!(listed.len(), 1);
+        assert_eq!(left, (posts[0].entries..10).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_top_posts() {
+        let post = |guid: &str, posted_at: i64| RecentPost {
+            posted_at,
+            guid: guid.to_string(),
+            title: None,
+            link: None,
+            feed_url: None,
+            account: None,
+            ttl: None,
+        };
+        let posts = vec![post("a", 1), post("b", 2), post("c", 3), post("d", 4)];
+        let guids = |posts: Vec<RecentPost>| posts.into_iter().map(|p| p.guid).collect::<Vec<_>>();
+
+        assert_eq!(guids(top_posts(posts.clone(), None, 2)), vec!["c", "d"]);
+
+        let engagement = HashMap::from([("a".to_string(), 10), ("c".to_string(), 3)]);
+        assert_eq!(
+            guids(top_posts(posts.clone(), Some(&engagement), 3)),
+            vec!["a", "c", "d"]
+        );
+        assert_eq!(guids(top_posts(posts, Some(&engagement), 10)).len(), 4);
+    }
+
+    #[test]
+    fn test_entry_text() {
+        let long = entry(&"word ".repeat(100), "https://example.com/1");
+        let text = entry_text(&long, MAX_POST_GRAPHEMES);
+        assert!(text.graphemes(true).count() <= MAX_POST_GRAPHEMES);
+        assert!(text.ends_with("\nhttps://example.com/1"));
+
+        assert_eq!(entry_text(&long, 10), "https://example.com/1");
+    }
+}
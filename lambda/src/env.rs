@@ -0,0 +1,33 @@
+use anyhow::{Context, Result};
+use std::str::FromStr;
+
+/// Reads an optional environment variable. Unset and blank values are both treated as missing.
+pub fn optional(name: &str) -> Option<String> {
+    std::env::var(name)
+        .ok()
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+}
+
+/// Reads and parses an optional environment variable, falling back to `default` when it is unset or blank.
+///
+/// # Returns
+///
+/// A Result containing the parsed value, or an error if the variable is set but can't be parsed.
+pub fn parse_or<T>(name: &str, default: T) -> Result<T>
+where
+    T: FromStr,
+    T::Err: std::error::Error + Send + Sync + 'static,
+{
+    match optional(name) {
+        Some(value) => value
+            .parse()
+            .with_context(|| format!("Failed to parse {} value {:?}", name, value)),
+        None => Ok(default),
+    }
+}
+
+/// Reads a boolean flag. Only "true" (in any case) enables it.
+pub fn flag(name: &str) -> bool {
+    optional(name).is_some_and(|value| value.eq_ignore_ascii_case("true"))
+}
@@ -0,0 +1,1053 @@
+use crate::html::resolve_url;
+use crate::text_utils::sha256_hex;
+use crate::url_utils::dedupe_key;
+use anyhow::{Context, Result};
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveTime};
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use rss::extension::Extension;
+use rss::{Channel, Guid, Item};
+
+/// Namespace URI of the Media RSS extension.
+const MEDIA_NAMESPACE: &str = "http://search.yahoo.com/mrss/";
+/// Namespace URI of Atom elements embedded in RSS.
+const ATOM_NAMESPACE: &str = "http://www.w3.org/2005/Atom";
+
+/// Parses a feed document into an RSS channel.
+///
+/// RSS 2.0 documents are returned as parsed. RDF based RSS 1.0 (and 0.9) documents are normalized so that
+/// the rest of the pipeline can treat them like RSS 2.0: the `rdf:about` attribute of each item becomes its
+/// guid and `dc:date` becomes its publication date. Atom documents are converted, see [`read_atom`].
+///
+/// # Arguments
+///
+/// * `content` - The raw bytes of the feed document.
+///
+/// # Returns
+///
+/// A Result containing the normalized Channel, or an error if the document is not a valid feed.
+pub fn read_channel(content: &[u8]) -> Result<Channel> {
+    match root_element(content)?.as_deref() {
+        Some(b"feed") => read_atom(content).context("Failed to parse Atom feed"),
+        root => {
+            let mut channel = Channel::read_from(content).context("Failed to parse RSS feed")?;
+            if root == Some(b"RDF".as_slice()) {
+                normalize_rdf(&mut channel, content)?;
+            }
+            Ok(channel)
+        }
+    }
+}
+
+/// Parses a publication date in either RFC 2822 (RSS 2.0 `pubDate`) or RFC 3339 / W3CDTF (`dc:date`) format.
+///
+/// Dates without a time, such as `2024-11-20` (common in sitemaps and on web pages), are taken as midnight UTC.
+pub fn parse_date(value: &str) -> Option<DateTime<FixedOffset>> {
+    let value = value.trim();
+    DateTime::parse_from_rfc2822(value)
+        .or_else(|_| DateTime::parse_from_rfc3339(value))
+        .ok()
+        .or_else(|| {
+            let date = NaiveDate::parse_from_str(value, "%Y-%m-%d").ok()?;
+            Some(date.and_time(NaiveTime::MIN).and_utc().fixed_offset())
+        })
+}
+
+/// Returns the identity used to dedupe an item.
+///
+/// This is the item's guid when it has one. Feeds that omit guids get a stable fallback derived from the link, or
+/// from the title and description when there is no link either, so the same item gets the same identity on every
+/// fetch. Guids and links that are URLs are normalized with [`dedupe_key`] first, so an article doesn't count as new
+/// because its URL gained a campaign parameter or lost a trailing slash. Returns None if the item has nothing to
+/// identify it by.
+pub fn item_identity(item: &Item) -> Option<String> {
+    if let Some(guid) = non_empty(item.guid().map(|g| g.value())) {
+        return Some(dedupe_key(guid));
+    }
+
+    if let Some(link) = non_empty(item.link()) {
+        return Some(format!("link:{}", sha256_hex(dedupe_key(link).as_bytes())));
+    }
+
+    let title = non_empty(item.title());
+    let description = non_empty(item.description());
+    if title.is_none() && description.is_none() {
+        return None;
+    }
+
+    let content = format!(
+        "{}\n{}",
+        title.unwrap_or_default(),
+        description.unwrap_or_default()
+    );
+    Some(format!("content:{}", sha256_hex(content.as_bytes())))
+}
+
+/// Returns the hash of an item's title and description, which changes when the feed updates the item.
+///
+/// Whitespace at either end is ignored, as feeds tend to rewrap their items without changing them.
+pub fn item_content_hash(item: &Item) -> String {
+    let content = format!(
+        "{}\n{}",
+        item.title().unwrap_or_default().trim(),
+        item.description().unwrap_or_default().trim()
+    );
+    sha256_hex(content.as_bytes())
+}
+
+/// Returns the base URL relative links in a feed are resolved against.
+///
+/// This is the channel's `<link>` (itself resolved against the feed URL, as it may be relative too), or the feed
+/// URL when the channel has no link.
+pub fn base_url(channel: &Channel, feed_url: &str) -> String {
+    non_empty(Some(channel.link()))
+        .and_then(|link| resolve_url(feed_url, link))
+        .unwrap_or_else(|| feed_url.to_string())
+}
+
+/// Returns the URL of the channel's image, such as the logo of the site, as an absolute URL.
+///
+/// This is the RSS `<image>`, or else the `itunes:image` of a podcast.
+pub fn channel_image_url(channel: &Channel, base_url: &str) -> Option<String> {
+    let url = non_empty(channel.image().map(|image| image.url()))
+        .or_else(|| non_empty(channel.itunes_ext().and_then(|itunes| itunes.image())))?;
+    resolve_url(base_url, url)
+}
+
+/// Returns the item's link as an absolute URL, resolving relative links against `base_url`.
+pub fn item_link(item: &Item, base_url: &str) -> Option<String> {
+    let link = non_empty(item.link())?;
+    resolve_url(base_url, link).or_else(|| {
+        tracing::warn!("Unable to resolve item link {} against {}", link, base_url);
+        Some(link.to_string())
+    })
+}
+
+/// Returns the URL of the next (older) page of a paged or archived feed, as advertised by an RFC 5005
+/// `<atom:link rel="next">` element in the channel.
+///
+/// # Arguments
+///
+/// * `channel` - The channel of the current page.
+/// * `page_url` - The URL the current page was fetched from, used to resolve relative links.
+pub fn next_page_url(channel: &Channel, page_url: &str) -> Option<String> {
+    let links = channel
+        .extensions()
+        .get(&atom_prefix(channel))?
+        .get("link")?;
+    links
+        .iter()
+        .filter(|link| link.attrs().get("rel").map(String::as_str) == Some("next"))
+        .find_map(|link| non_empty(link.attrs().get("href").map(String::as_str)))
+        .and_then(|href| resolve_url(page_url, href))
+}
+
+/// Returns the prefix the channel uses for the Media RSS namespace, defaulting to the conventional `media`.
+pub fn media_prefix(channel: &Channel) -> String {
+    namespace_prefix(channel, MEDIA_NAMESPACE).unwrap_or_else(|| "media".to_string())
+}
+
+/// Returns the prefix the channel uses for the Atom namespace, defaulting to the conventional `atom`.
+pub fn atom_prefix(channel: &Channel) -> String {
+    namespace_prefix(channel, ATOM_NAMESPACE).unwrap_or_else(|| "atom".to_string())
+}
+
+fn namespace_prefix(channel: &Channel, namespace: &str) -> Option<String> {
+    channel
+        .namespaces()
+        .iter()
+        .find(|(_, uri)| uri.as_str() == namespace)
+        .map(|(prefix, _)| prefix.clone())
+}
+
+/// Extracts the URL of the item's main image.
+///
+/// Looks at `media:content` elements that are images (also inside `media:group`), then `media:thumbnail`, and
+/// finally an image `<enclosure>`.
+///
+/// # Arguments
+///
+/// * `item` - The RSS item.
+/// * `media_prefix` - The prefix the feed uses for the Media RSS namespace, see [`media_prefix`].
+pub fn item_image_url(item: &Item, media_prefix: &str) -> Option<String> {
+    let media = item.extensions().get(media_prefix);
+    let elements = |name: &str| -> Vec<&Extension> {
+        let Some(media) = media else {
+            return Vec::new();
+        };
+        let mut elements: Vec<&Extension> = media.get(name).into_iter().flatten().collect();
+        for group in media.get("group").into_iter().flatten() {
+            elements.extend(group.children().get(name).into_iter().flatten());
+        }
+        elements
+    };
+
+    let url = |e: &Extension| non_empty(e.attrs().get("url").map(String::as_str)).map(String::from);
+
+    elements("content")
+        .into_iter()
+        .filter(|e| is_image_content(e))
+        .find_map(url)
+        .or_else(|| elements("thumbnail").into_iter().find_map(url))
+        .or_else(|| {
+            item.enclosure()
+                .filter(|e| e.mime_type().starts_with("image/"))
+                .and_then(|e| non_empty(Some(e.url())).map(String::from))
+        })
+}
+
+/// Extracts the URLs of all images attached to the item, for posting them rather than just linking the item.
+///
+/// These are the `media:content` elements that are images (also inside `media:group`) and an image `<enclosure>`, in
+/// document order and without duplicates. Thumbnails are left out, as they are smaller copies of the same images.
+///
+/// # Arguments
+///
+/// * `item` - The RSS item.
+/// * `media_prefix` - The prefix the feed uses for the Media RSS namespace, see [`media_prefix`].
+pub fn item_image_urls(item: &Item, media_prefix: &str) -> Vec<String> {
+    let mut contents: Vec<&Extension> = Vec::new();
+    if let Some(media) = item.extensions().get(media_prefix) {
+        contents.extend(media.get("content").into_iter().flatten());
+        for group in media.get("group").into_iter().flatten() {
+            contents.extend(group.children().get("content").into_iter().flatten());
+        }
+    }
+
+    let enclosure = item
+        .enclosure()
+        .filter(|e| e.mime_type().starts_with("image/"))
+        .map(|e| e.url());
+    let mut urls: Vec<String> = Vec::new();
+    for url in contents
+        .into_iter()
+        .filter(|e| is_image_content(e))
+        .filter_map(|e| e.attrs().get("url").map(String::as_str))
+        .chain(enclosure)
+    {
+        if let Some(url) = non_empty(Some(url)) {
+            if !urls.iter().any(|u| u == url) {
+                urls.push(url.to_string());
+            }
+        }
+    }
+    urls
+}
+
+/// Returns the URL of the item's MP4 video enclosure, for posting the video rather than just linking the item.
+///
+/// # Arguments
+///
+/// * `item` - The RSS item.
+/// * `max_bytes` - The largest video accepted. Enclosures whose `length` says they're larger are left out; those
+///   without a length are only checked once downloaded.
+pub fn item_video_url(item: &Item, max_bytes: u64) -> Option<String> {
+    item.enclosure()
+        .filter(|e| e.mime_type() == "video/mp4")
+        .filter(|e| {
+            e.length()
+                .trim()
+                .parse::<u64>()
+                .map_or(true, |length| length <= max_bytes)
+        })
+        .and_then(|e| non_empty(Some(e.url())).map(String::from))
+}
+
+/// Checks whether a `media:content` element is an image, by its `medium` or MIME type.
+fn is_image_content(e: &Extension) -> bool {
+    e.attrs().get("medium").map(String::as_str) == Some("image")
+        || e.attrs()
+            .get("type")
+            .is_some_and(|t| t.starts_with("image/"))
+}
+
+/// Returns the item's publication date.
+///
+/// Uses `<pubDate>` and falls back to `dc:date`, `atom:updated` and `atom:published`, in that order. Returns None
+/// when none of them holds a parseable date.
+///
+/// # Arguments
+///
+/// * `item` - The RSS item.
+/// * `atom_prefix` - The prefix the feed uses for the Atom namespace, see [`atom_prefix`].
+pub fn item_pub_date(item: &Item, atom_prefix: &str) -> Option<DateTime<FixedOffset>> {
+    if let Some(date) = item.pub_date().and_then(parse_date) {
+        return Some(date);
+    }
+
+    if let Some(date) = item
+        .dublin_core_ext()
+        .and_then(|dc| dc.dates().iter().find_map(|d| parse_date(d)))
+    {
+        return Some(date);
+    }
+
+    let atom = item.extensions().get(atom_prefix)?;
+    ["updated", "published"].iter().find_map(|element| {
+        atom.get(*element)?
+            .iter()
+            .find_map(|e| e.value().and_then(parse_date))
+    })
+}
+
+/// Computes the high-water mark a posted item moves its feed's to: its publication date, unless that lies in the
+/// future, as it would hide every item published before it from the runs until then.
+///
+/// # Arguments
+///
+/// * `pub_date` - Unix timestamp of the item's publication date.
+/// * `now` - The current Unix timestamp.
+pub fn high_water_mark(pub_date: i64, now: i64) -> i64 {
+    pub_date.min(now)
+}
+
+/// Checks whether an item is new enough to be posted: published no earlier than the feed's high-water mark, or,
+/// before anything from the feed has been posted, than the start of the window.
+///
+/// A mark in the future, stored before marks were capped at the time of posting, counts as now.
+///
+/// # Arguments
+///
+/// * `pub_date` - Unix timestamp of the item's publication date.
+/// * `high_water_mark` - The high-water mark of the feed, if it has one.
+/// * `window_start` - Unix timestamp of the start of the window.
+/// * `now` - The current Unix timestamp.
+pub fn is_new_enough(
+    pub_date: i64,
+    high_water_mark: Option<i64>,
+    window_start: i64,
+    now: i64,
+) -> bool {
+    match high_water_mark {
+        Some(mark) => pub_date >= mark.min(now),
+        None => pub_date >= window_start,
+    }
+}
+
+/// Extracts the name of the item's author.
+///
+/// Uses `dc:creator` first, then the RSS `<author>` element (which is usually an e-mail address, optionally
+/// followed by the name in parentheses), and finally `atom:author` / `atom:contributor`.
+///
+/// # Arguments
+///
+/// * `item` - The RSS item.
+/// * `atom_prefix` - The prefix the feed uses for the Atom namespace, see [`atom_prefix`].
+pub fn item_author(item: &Item, atom_prefix: &str) -> Option<String> {
+    if let Some(creator) = item
+        .dublin_core_ext()
+        .and_then(|dc| dc.creators().iter().find_map(|c| non_empty(Some(c))))
+    {
+        return Some(creator.to_string());
+    }
+
+    if let Some(author) = non_empty(item.author()) {
+        // "jane@example.com (Jane Doe)" -> "Jane Doe"
+        let name = match (author.find('('), author.rfind(')')) {
+            (Some(start), Some(end)) if start < end => non_empty(Some(&author[start + 1..end])),
+            _ => None,
+        };
+        return Some(name.unwrap_or(author).to_string());
+    }
+
+    let atom = item.extensions().get(atom_prefix)?;
+    ["author", "contributor"].iter().find_map(|element| {
+        atom.get(*element)?.iter().find_map(|person| {
+            person
+                .children()
+                .get("name")?
+                .iter()
+                .find_map(|name| non_empty(name.value()))
+                .map(String::from)
+        })
+    })
+}
+
+/// Podcast metadata from the iTunes namespace.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ItunesMetadata {
+    pub subtitle: Option<String>,
+    pub episode: Option<String>,
+    pub season: Option<String>,
+    /// Episode artwork, falling back to the show artwork of the channel.
+    pub image: Option<String>,
+}
+
+/// Extracts `itunes:subtitle`, `itunes:episode`, `itunes:season` and `itunes:image` from a podcast item.
+pub fn item_itunes_metadata(channel: &Channel, item: &Item) -> ItunesMetadata {
+    let ext = item.itunes_ext();
+    let get = |f: fn(&rss::extension::itunes::ITunesItemExtension) -> Option<&str>| {
+        ext.and_then(|ext| non_empty(f(ext))).map(String::from)
+    };
+
+    ItunesMetadata {
+        subtitle: get(|e| e.subtitle()),
+        episode: get(|e| e.episode()),
+        season: get(|e| e.season()),
+        image: get(|e| e.image()).or_else(|| {
+            channel
+                .itunes_ext()
+                .and_then(|ext| non_empty(ext.image()))
+                .map(String::from)
+        }),
+    }
+}
+
+/// Trims a value, treating blank values as missing.
+fn non_empty(value: Option<&str>) -> Option<&str> {
+    value.map(str::trim).filter(|v| !v.is_empty())
+}
+
+/// Returns the local name of the document's root element, or None for an empty document.
+fn root_element(content: &[u8]) -> Result<Option<Vec<u8>>> {
+    let mut reader = Reader::from_reader(content);
+    let mut buf = Vec::new();
+
+    loop {
+        match reader
+            .read_event_into(&mut buf)
+            .context("Failed to read feed root element")?
+        {
+            Event::Start(element) => return Ok(Some(element.local_name().as_ref().to_vec())),
+            Event::Eof => return Ok(None),
+            _ => buf.clear(),
+        }
+    }
+}
+
+fn atom_image(url: String) -> rss::Image {
+    rss::Image {
+        url,
+        ..rss::Image::default()
+    }
+}
+
+/// The fields of an Atom `<entry>` that make up an RSS item.
+#[derive(Default)]
+struct AtomEntry {
+    id: Option<String>,
+    title: Option<String>,
+    link: Option<String>,
+    published: Option<String>,
+    updated: Option<String>,
+    summary: Option<String>,
+    content: Option<String>,
+    author: Option<String>,
+}
+
+/// Converts an Atom 1.0 document into an RSS channel.
+///
+/// Each entry becomes an item: its `id` is the guid, the `alternate` link the link, `published` (or else `updated`)
+/// the publication date, `content` (or else `summary`) the description, and the first author's name the author.
+/// The feed's `icon` (or else its `logo`) becomes the channel image.
+fn read_atom(content: &[u8]) -> Result<Channel> {
+    let mut reader = Reader::from_reader(content);
+    let mut buf = Vec::new();
+
+    let mut channel = Channel::default();
+    let mut entries: Vec<AtomEntry> = Vec::new();
+    let mut entry: Option<AtomEntry> = None;
+    // The local names of the open elements, and the text of the innermost field element being read
+    let mut path: Vec<Vec<u8>> = Vec::new();
+    let mut text = String::new();
+
+    // Fields are the children of <feed> and <entry>, plus the author's <name>. Anything below one, such as the
+    // markup of type="xhtml" content, only contributes its text
+    let field_depth = |path: &[Vec<u8>]| match path {
+        [_, entry, author, _] if entry == b"entry" && author == b"author" => Some(4),
+        [_, entry, _, ..] if entry == b"entry" => Some(3),
+        [_, _, ..] => Some(2),
+        _ => None,
+    };
+
+    loop {
+        let event = reader.read_event_into(&mut buf)?;
+        match &event {
+            Event::Start(element) | Event::Empty(element) => {
+                let name = element.local_name().as_ref().to_vec();
+                if name == b"entry" && path.len() == 1 {
+                    entry = Some(AtomEntry::default());
+                }
+                if name == b"link" {
+                    let attribute = |key: &[u8]| {
+                        element
+                            .attributes()
+                            .flatten()
+                            .find(|attr| attr.key.as_ref() == key)
+                            .and_then(|attr| attr.decode_and_unescape_value(reader.decoder()).ok())
+                            .map(|value| value.trim().to_string())
+                    };
+                    let is_alternate =
+                        matches!(attribute(b"rel").as_deref(), None | Some("alternate"));
+                    if let (true, Some(href)) = (is_alternate, attribute(b"href")) {
+                        match entry.as_mut() {
+                            Some(entry) if path.len() == 2 => {
+                                entry.link.get_or_insert(href);
+                            }
+                            None if path.len() == 1 && channel.link.is_empty() => {
+                                channel.link = href
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+                if matches!(event, Event::Start(_)) {
+                    path.push(name);
+                    if field_depth(&path) == Some(path.len()) {
+                        text.clear();
+                    } else {
+                        text.push(' ');
+                    }
+                }
+            }
+            Event::Text(value) => text.push_str(&value.unescape()?),
+            Event::CData(value) => text.push_str(&String::from_utf8_lossy(value)),
+            Event::End(_) => {
+                let value = Some(text.trim().to_string()).filter(|t| !t.is_empty());
+                let names: Vec<&[u8]> = path.iter().map(Vec::as_slice).collect();
+                match (names.as_slice(), entry.as_mut()) {
+                    ([_, b"title"], None) => channel.title = value.unwrap_or_default(),
+                    ([_, b"subtitle"], None) => channel.description = value.unwrap_or_default(),
+                    // The square icon makes a better avatar than the wide logo
+                    ([_, b"icon"], None) => channel.image = value.map(atom_image),
+                    ([_, b"logo"], None) if channel.image.is_none() => {
+                        channel.image = value.map(atom_image)
+                    }
+                    ([_, b"entry"], Some(_)) => entries.extend(entry.take()),
+                    ([_, b"entry", field], Some(entry)) => match *field {
+                        b"id" => entry.id = value,
+                        b"title" => entry.title = value,
+                        b"published" => entry.published = value,
+                        b"updated" => entry.updated = value,
+                        b"summary" => entry.summary = value,
+                        b"content" => entry.content = value,
+                        _ => {}
+                    },
+                    ([_, b"entry", b"author", b"name"], Some(entry)) => {
+                        if entry.author.is_none() {
+                            entry.author = value;
+                        }
+                    }
+                    _ => text.push(' '),
+                }
+                path.pop();
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+
+    channel.items = entries
+        .into_iter()
+        .map(|entry| {
+            let mut item = Item::default();
+            item.set_guid(entry.id.map(|id| Guid {
+                value: id,
+                permalink: false,
+            }));
+            item.set_link(entry.link);
+            item.set_title(entry.title);
+            item.set_pub_date(
+                entry
+                    .published
+                    .or(entry.updated)
+                    .and_then(|date| parse_date(&date))
+                    .map(|date| date.to_rfc2822()),
+            );
+            item.set_description(entry.content.or(entry.summary));
+            item.set_author(entry.author);
+            item
+        })
+        .collect();
+
+    Ok(channel)
+}
+
+fn normalize_rdf(channel: &mut Channel, content: &[u8]) -> Result<()> {
+    let abouts = read_rdf_item_abouts(content)?;
+
+    if abouts.len() != channel.items().len() {
+        tracing::warn!(
+            "RDF feed has {} items but {} rdf:about attributes, guids may be missing",
+            channel.items().len(),
+            abouts.len()
+        );
+    }
+
+    for (item, about) in channel.items_mut().iter_mut().zip(abouts) {
+        if item.guid().is_none() {
+            if let Some(about) = about.or_else(|| item.link().map(String::from)) {
+                item.set_guid(Guid {
+                    value: about,
+                    permalink: true,
+                });
+            }
+        }
+
+        if item.pub_date().is_none() {
+            let dc_date = item
+                .dublin_core_ext()
+                .and_then(|dc| dc.dates().first())
+                .and_then(|date| parse_date(date));
+            if let Some(dc_date) = dc_date {
+                item.set_pub_date(dc_date.to_rfc2822());
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Collects the `rdf:about` attribute of every `item` element, in document order.
+fn read_rdf_item_abouts(content: &[u8]) -> Result<Vec<Option<String>>> {
+    let mut reader = Reader::from_reader(content);
+    let mut buf = Vec::new();
+    let mut abouts = Vec::new();
+
+    loop {
+        match reader
+            .read_event_into(&mut buf)
+            .context("Failed to read RDF feed items")?
+        {
+            Event::Start(element) | Event::Empty(element)
+                if element.local_name().as_ref() == b"item" =>
+            {
+                let about = element
+                    .attributes()
+                    .flatten()
+                    .find(|attr| attr.key.local_name().as_ref() == b"about")
+                    .and_then(|attr| attr.decode_and_unescape_value(reader.decoder()).ok())
+                    .map(|value| value.trim().to_string())
+                    .filter(|value| !value.is_empty());
+                abouts.push(about);
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+
+    Ok(abouts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_high_water_mark() {
+        assert_eq!(high_water_mark(1_000, 2_000), 1_000);
+        // A future-dated item doesn't move the mark past now
+        assert_eq!(high_water_mark(9_000, 2_000), 2_000);
+    }
+
+    #[test]
+    fn test_is_new_enough() {
+        assert!(is_new_enough(1_500, None, 1_000, 2_000));
+        assert!(!is_new_enough(500, None, 1_000, 2_000));
+        // The mark wins over the window, and items from its second are left to the dedupe
+        assert!(is_new_enough(500, Some(500), 1_000, 2_000));
+        assert!(!is_new_enough(1_500, Some(1_600), 1_000, 2_000));
+        // A mark left in the future by a future-dated item counts as now
+        assert!(is_new_enough(2_000, Some(9_000), 1_000, 2_000));
+        assert!(!is_new_enough(1_999, Some(9_000), 1_000, 2_000));
+    }
+
+    #[test]
+    fn test_read_channel_rdf() {
+        let content = r#"<?xml version="1.0" encoding="UTF-8"?>
+<rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#"
+    xmlns="http://purl.org/rss/1.0/"
+    xmlns:dc="http://purl.org/dc/elements/1.1/">
+  <channel rdf:about="https://example.com/">
+    <title>Example</title>
+    <link>https://example.com/</link>
+    <description>Example feed</description>
+    <items>
+      <rdf:Seq>
+        <rdf:li rdf:resource="https://example.com/1" />
+        <rdf:li rdf:resource="https://example.com/2" />
+      </rdf:Seq>
+    </items>
+  </channel>
+  <item rdf:about="https://example.com/1">
+    <title>First</title>
+    <link>https://example.com/1</link>
+    <description>First item</description>
+    <dc:date>2024-11-20T10:00:00+00:00</dc:date>
+  </item>
+  <item rdf:about="https://example.com/2">
+    <title>Second</title>
+    <link>https://example.com/2</link>
+  </item>
+</rdf:RDF>"#;
+
+        let channel = read_channel(content.as_bytes()).unwrap();
+        let items = channel.items();
+        assert_eq!(items.len(), 2);
+
+        assert_eq!(items[0].guid().unwrap().value(), "https://example.com/1");
+        assert_eq!(
+            parse_date(items[0].pub_date().unwrap()),
+            parse_date("2024-11-20T10:00:00Z")
+        );
+
+        assert_eq!(items[1].guid().unwrap().value(), "https://example.com/2");
+        assert_eq!(items[1].pub_date(), None);
+
+        // Plain RSS 2.0 passes through untouched
+        let content = r#"<rss version="2.0"><channel><title>t</title><link>l</link><description>d</description>
+<item><guid>abc</guid><pubDate>Wed, 20 Nov 2024 10:00:00 +0000</pubDate></item></channel></rss>"#;
+        let channel = read_channel(content.as_bytes()).unwrap();
+        assert_eq!(channel.items()[0].guid().unwrap().value(), "abc");
+    }
+
+    #[test]
+    fn test_read_channel_atom() {
+        let content = r#"<?xml version="1.0" encoding="UTF-8"?>
+<feed xmlns="http://www.w3.org/2005/Atom">
+  <id>tag:github.com,2008:https://github.com/rust-lang/rust/releases</id>
+  <link type="text/html" rel="alternate" href="https://github.com/rust-lang/rust/releases"/>
+  <link type="application/atom+xml" rel="self" href="https://github.com/rust-lang/rust/releases.atom"/>
+  <title>Release notes from rust</title>
+  <updated>2024-11-28T15:00:00Z</updated>
+  <icon>https://github.com/fluidicon.png</icon>
+  <logo>https://github.com/logo.png</logo>
+  <entry>
+    <id>tag:github.com,2008:Repository/724712/1.83.0</id>
+    <updated>2024-11-28T15:00:00Z</updated>
+    <link rel="alternate" type="text/html" href="https://github.com/rust-lang/rust/releases/tag/1.83.0"/>
+    <title>Rust 1.83.0</title>
+    <content type="html">&lt;h2&gt;Language&lt;/h2&gt;&lt;p&gt;Stabilize &amp;amp;mut in const&lt;/p&gt;</content>
+    <author><name>rustbot</name><uri>https://github.com/rustbot</uri></author>
+  </entry>
+  <entry>
+    <id>urn:uuid:2</id>
+    <title type="xhtml"><div xmlns="http://www.w3.org/1999/xhtml">An <b>xhtml</b> title</div></title>
+    <published>2024-11-20T10:00:00+01:00</published>
+    <updated>2024-11-21T10:00:00Z</updated>
+    <summary>Just a summary</summary>
+    <link href="/posts/2"/>
+  </entry>
+</feed>"#;
+
+        let channel = read_channel(content.as_bytes()).unwrap();
+        assert_eq!(channel.title(), "Release notes from rust");
+        assert_eq!(channel.link(), "https://github.com/rust-lang/rust/releases");
+        assert_eq!(
+            channel.image().map(|image| image.url()),
+            Some("https://github.com/fluidicon.png")
+        );
+        assert_eq!(channel.items().len(), 2);
+
+        let item = &channel.items()[0];
+        assert_eq!(
+            item.guid().map(|g| g.value()),
+            Some("tag:github.com,2008:Repository/724712/1.83.0")
+        );
+        assert_eq!(
+            item.link(),
+            Some("https://github.com/rust-lang/rust/releases/tag/1.83.0")
+        );
+        assert_eq!(item.title(), Some("Rust 1.83.0"));
+        assert_eq!(
+            item.description(),
+            Some("<h2>Language</h2><p>Stabilize &amp;mut in const</p>")
+        );
+        assert_eq!(item.author(), Some("rustbot"));
+        assert_eq!(
+            item.pub_date().and_then(parse_date),
+            parse_date("2024-11-28T15:00:00Z")
+        );
+
+        let item = &channel.items()[1];
+        assert_eq!(
+            item.title()
+                .map(|t| t.split_whitespace().collect::<Vec<_>>().join(" ")),
+            Some("An xhtml title".to_string())
+        );
+        assert_eq!(item.link(), Some("/posts/2"));
+        assert_eq!(item.description(), Some("Just a summary"));
+        assert_eq!(
+            item.pub_date().and_then(parse_date),
+            parse_date("2024-11-20T10:00:00+01:00")
+        );
+    }
+
+    #[test]
+    fn test_item_content_hash() {
+        let mut item = Item::default();
+        item.set_title("Title".to_string());
+        item.set_description("Description".to_string());
+        let hash = item_content_hash(&item);
+
+        item.set_description(" Description\n".to_string());
+        assert_eq!(item_content_hash(&item), hash);
+
+        item.set_description("Corrected description".to_string());
+        assert_ne!(item_content_hash(&item), hash);
+
+        // The title and description can't be swapped to the same hash
+        let mut swapped = Item::default();
+        swapped.set_title("Description".to_string());
+        swapped.set_description("Title".to_string());
+        assert_ne!(item_content_hash(&swapped), hash);
+    }
+
+    #[test]
+    fn test_item_identity() {
+        let mut item = Item::default();
+        assert_eq!(item_identity(&item), None);
+
+        item.set_title("Title".to_string());
+        let by_content = item_identity(&item).unwrap();
+        assert!(by_content.starts_with("content:"));
+
+        item.set_link("https://example.com/1".to_string());
+        let by_link = item_identity(&item).unwrap();
+        assert!(by_link.starts_with("link:"));
+        assert_ne!(by_link, by_content);
+
+        // The fallback only depends on the link once there is one
+        item.set_title("Changed title".to_string());
+        assert_eq!(item_identity(&item).unwrap(), by_link);
+
+        item.set_guid(Guid {
+            value: " guid-1 ".to_string(),
+            permalink: false,
+        });
+        assert_eq!(item_identity(&item).as_deref(), Some("guid-1"));
+
+        item.set_guid(Guid {
+            value: "http://example.com/1/?utm_source=rss".to_string(),
+            permalink: true,
+        });
+        assert_eq!(
+            item_identity(&item).as_deref(),
+            Some("https://example.com/1")
+        );
+
+        let mut tagged = Item::default();
+        tagged.set_link("https://example.com/1/?utm_medium=feed".to_string());
+        assert_eq!(item_identity(&tagged).unwrap(), by_link);
+    }
+
+    #[test]
+    fn test_item_image_url() {
+        let content = r#"<rss version="2.0" xmlns:m="http://search.yahoo.com/mrss/"><channel><title>t</title><link>l</link><description>d</description>
+<item><guid>1</guid>
+  <m:content url="https://example.com/video.mp4" type="video/mp4" />
+  <m:content url="https://example.com/image.jpg" medium="image" />
+  <m:thumbnail url="https://example.com/thumb.jpg" />
+</item>
+<item><guid>2</guid>
+  <m:group><m:content url="https://example.com/grouped.png" type="image/png" /></m:group>
+</item>
+<item><guid>3</guid><m:thumbnail url="https://example.com/thumb.jpg" /></item>
+<item><guid>4</guid><enclosure url="https://example.com/enclosure.jpg" length="1" type="image/jpeg" /></item>
+<item><guid>5</guid><enclosure url="https://example.com/episode.mp3" length="1" type="audio/mpeg" /></item>
+</channel></rss>"#;
+
+        let channel = read_channel(content.as_bytes()).unwrap();
+        let prefix = media_prefix(&channel);
+        assert_eq!(prefix, "m");
+
+        let urls: Vec<Option<String>> = channel
+            .items()
+            .iter()
+            .map(|item| item_image_url(item, &prefix))
+            .collect();
+        assert_eq!(
+            urls,
+            vec![
+                Some("https://example.com/image.jpg".to_string()),
+                Some("https://example.com/grouped.png".to_string()),
+                Some("https://example.com/thumb.jpg".to_string()),
+                Some("https://example.com/enclosure.jpg".to_string()),
+                None,
+            ]
+        );
+
+        let urls: Vec<Vec<String>> = channel
+            .items()
+            .iter()
+            .map(|item| item_image_urls(item, &prefix))
+            .collect();
+        assert_eq!(
+            urls,
+            vec![
+                vec!["https://example.com/image.jpg".to_string()],
+                vec!["https://example.com/grouped.png".to_string()],
+                vec![],
+                vec!["https://example.com/enclosure.jpg".to_string()],
+                vec![],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_item_video_url() {
+        let content = r#"<rss version="2.0"><channel><title>t</title><link>l</link><description>d</description>
+<item><guid>1</guid><enclosure url="https://example.com/short.mp4" length="1000" type="video/mp4" /></item>
+<item><guid>2</guid><enclosure url="https://example.com/unknown.mp4" length="" type="video/mp4" /></item>
+<item><guid>3</guid><enclosure url="https://example.com/episode.mp4" length="900000000" type="video/mp4" /></item>
+<item><guid>4</guid><enclosure url="https://example.com/episode.mp3" length="1000" type="audio/mpeg" /></item>
+</channel></rss>"#;
+
+        let channel = read_channel(content.as_bytes()).unwrap();
+        let urls: Vec<Option<String>> = channel
+            .items()
+            .iter()
+            .map(|item| item_video_url(item, 50_000_000))
+            .collect();
+        assert_eq!(
+            urls,
+            vec![
+                Some("https://example.com/short.mp4".to_string()),
+                Some("https://example.com/unknown.mp4".to_string()),
+                None,
+                None,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_item_author() {
+        let content = r#"<rss version="2.0" xmlns:dc="http://purl.org/dc/elements/1.1/" xmlns:a="http://www.w3.org/2005/Atom"><channel><title>t</title><link>l</link><description>d</description>
+<item><guid>1</guid><dc:creator>Jane Doe</dc:creator><author>other@example.com (Other)</author></item>
+<item><guid>2</guid><author>jane@example.com (Jane Doe)</author></item>
+<item><guid>3</guid><author>jane@example.com</author></item>
+<item><guid>4</guid><a:contributor><a:name>Jane Doe</a:name></a:contributor></item>
+<item><guid>5</guid></item>
+</channel></rss>"#;
+
+        let channel = read_channel(content.as_bytes()).unwrap();
+        let prefix = atom_prefix(&channel);
+        let authors: Vec<Option<String>> = channel
+            .items()
+            .iter()
+            .map(|item| item_author(item, &prefix))
+            .collect();
+        assert_eq!(
+            authors,
+            vec![
+                Some("Jane Doe".to_string()),
+                Some("Jane Doe".to_string()),
+                Some("jane@example.com".to_string()),
+                Some("Jane Doe".to_string()),
+                None,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_channel_image_url() {
+        let content = r#"<?xml version="1.0"?>
+<rss version="2.0" xmlns:itunes="http://www.itunes.com/dtds/podcast-1.0.dtd">
+  <channel>
+    <title>Podcast</title>
+    <link>https://example.com/</link>
+    <itunes:image href="/cover.jpg"/>
+  </channel>
+</rss>"#;
+        let mut channel = read_channel(content.as_bytes()).unwrap();
+        assert_eq!(
+            channel_image_url(&channel, "https://example.com/"),
+            Some("https://example.com/cover.jpg".to_string())
+        );
+
+        channel.set_image(rss::Image {
+            url: "https://example.com/logo.png".to_string(),
+            ..rss::Image::default()
+        });
+        assert_eq!(
+            channel_image_url(&channel, "https://example.com/"),
+            Some("https://example.com/logo.png".to_string())
+        );
+
+        assert_eq!(
+            channel_image_url(&Channel::default(), "https://example.com/"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_item_link() {
+        let content = r#"<rss version="2.0"><channel><title>t</title><link>/blog/</link><description>d</description>
+<item><guid>1</guid><link>posts/1.html</link></item>
+<item><guid>2</guid><link>/about</link></item>
+<item><guid>3</guid><link>https://other.example.com/3</link></item>
+<item><guid>4</guid></item>
+</channel></rss>"#;
+
+        let channel = read_channel(content.as_bytes()).unwrap();
+        let base = base_url(&channel, "https://example.com/feeds/rss.xml");
+        assert_eq!(base, "https://example.com/blog/");
+
+        let links: Vec<Option<String>> = channel
+            .items()
+            .iter()
+            .map(|item| item_link(item, &base))
+            .collect();
+        assert_eq!(
+            links,
+            vec![
+                Some("https://example.com/blog/posts/1.html".to_string()),
+                Some("https://example.com/about".to_string()),
+                Some("https://other.example.com/3".to_string()),
+                None,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_next_page_url() {
+        let content = r#"<rss version="2.0" xmlns:atom="http://www.w3.org/2005/Atom"><channel><title>t</title><link>l</link><description>d</description>
+<atom:link rel="self" href="https://example.com/feed?page=1" />
+<atom:link rel="next" href="/feed?page=2" />
+</channel></rss>"#;
+        let channel = read_channel(content.as_bytes()).unwrap();
+        assert_eq!(
+            next_page_url(&channel, "https://example.com/feed").as_deref(),
+            Some("https://example.com/feed?page=2")
+        );
+
+        let content = r#"<rss version="2.0"><channel><title>t</title><link>l</link><description>d</description></channel></rss>"#;
+        let channel = read_channel(content.as_bytes()).unwrap();
+        assert_eq!(next_page_url(&channel, "https://example.com/feed"), None);
+    }
+
+    #[test]
+    fn test_parse_date() {
+        let expected = DateTime::parse_from_rfc3339("2024-11-20T10:00:00Z").ok();
+        assert_eq!(parse_date("Wed, 20 Nov 2024 10:00:00 +0000"), expected);
+        assert_eq!(parse_date(" 2024-11-20T11:00:00+01:00 "), expected);
+        assert_eq!(
+            parse_date("2024-11-20"),
+            DateTime::parse_from_rfc3339("2024-11-20T00:00:00Z").ok()
+        );
+        assert_eq!(parse_date("20 November"), None);
+    }
+
+    #[test]
+    fn test_item_pub_date() {
+        let content = r#"<rss version="2.0" xmlns:dc="http://purl.org/dc/elements/1.1/" xmlns:atom="http://www.w3.org/2005/Atom"><channel><title>t</title><link>l</link><description>d</description>
+<item><guid>1</guid><pubDate>Wed, 20 Nov 2024 10:00:00 +0000</pubDate><dc:date>2020-01-01T00:00:00Z</dc:date></item>
+<item><guid>2</guid><pubDate>not a date</pubDate><dc:date>2024-11-20T10:00:00Z</dc:date></item>
+<item><guid>3</guid><atom:published>2020-01-01T00:00:00Z</atom:published><atom:updated>2024-11-20T10:00:00Z</atom:updated></item>
+<item><guid>4</guid><atom:published>2024-11-20T10:00:00+00:00</atom:published></item>
+<item><guid>5</guid></item>
+</channel></rss>"#;
+
+        let channel = read_channel(content.as_bytes()).unwrap();
+        let prefix = atom_prefix(&channel);
+        let expected = parse_date("2024-11-20T10:00:00Z");
+        let dates: Vec<_> = channel
+            .items()
+            .iter()
+            .map(|item| item_pub_date(item, &prefix))
+            .collect();
+        assert_eq!(dates, vec![expected, expected, expected, expected, None]);
+    }
+}
@@ -0,0 +1,153 @@
+use anyhow::{anyhow, Context, Result};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::HashSet;
+
+/// The posts a page of the feed has unless the app asks for another number.
+pub const DEFAULT_FEED_LIMIT: usize = 50;
+/// The most posts a page of the feed has, as the getFeedSkeleton lexicon allows.
+pub const MAX_FEED_LIMIT: usize = 100;
+
+/// A post of the bridge, as the feed lists it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FeedPost {
+    /// Unix timestamp of when it was posted.
+    pub posted_at: i64,
+    pub uri: String,
+}
+
+/// A post of a feed skeleton, which the app looks up by its URI.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SkeletonPost {
+    pub post: String,
+}
+
+/// A page of the feed, the output of app.bsky.feed.getFeedSkeleton.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FeedSkeleton {
+    /// Where the next page starts. None on the last page.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cursor: Option<String>,
+    pub feed: Vec<SkeletonPost>,
+}
+
+/// Makes a page of the feed, the latest posts first.
+///
+/// # Arguments
+///
+/// * `posts` - The posts of the feed, in any order. Posts sharing a URI, like the items of a digest, are listed once.
+/// * `cursor` - Where the page starts, from the page before. None for the first page.
+/// * `limit` - The most posts on the page.
+///
+/// # Returns
+///
+/// A Result containing the page, or an error if the cursor isn't one the feed handed out.
+pub fn feed_skeleton(
+    posts: &[FeedPost],
+    cursor: Option<&str>,
+    limit: usize,
+) -> Result<FeedSkeleton> {
+    // The cursor is the last post of the page before, which is stable as posts come in
+    let after = match cursor {
+        Some(cursor) => {
+            let (posted_at, uri) = cursor
+                .split_once("::")
+                .ok_or_else(|| anyhow!("Invalid cursor {:?}", cursor))?;
+            let posted_at: i64 = posted_at
+                .parse()
+                .with_context(|| format!("Invalid cursor {:?}", cursor))?;
+            Some((posted_at, uri))
+        }
+        None => None,
+    };
+
+    let mut posts: Vec<&FeedPost> = posts
+        .iter()
+        .filter(|post| after.is_none_or(|after| (post.posted_at, post.uri.as_str()) < after))
+        .collect();
+    posts.sort_by(|a, b| (b.posted_at, &b.uri).cmp(&(a.posted_at, &a.uri)));
+    let mut seen = HashSet::new();
+    posts.retain(|post| seen.insert(&post.uri));
+
+    let more = posts.len() > limit;
+    posts.truncate(limit);
+    Ok(FeedSkeleton {
+        cursor: posts
+            .last()
+            .filter(|_| more)
+            .map(|post| format!("{}::{}", post.posted_at, post.uri)),
+        feed: posts
+            .into_iter()
+            .map(|post| SkeletonPost {
+                post: post.uri.clone(),
+            })
+            .collect(),
+    })
+}
+
+/// Writes the DID document of a did:web feed generator, which tells the app where to get the feed.
+///
+/// # Arguments
+///
+/// * `did` - The DID of the feed generator.
+/// * `hostname` - The host the feed generator is served from.
+pub fn did_document(did: &str, hostname: &str) -> Value {
+    json!({
+        "@context": ["https://www.w3.org/ns/did/v1"],
+        "id": did,
+        "service": [{
+            "id": "#bsky_fg",
+            "type": "BskyFeedGenerator",
+            "serviceEndpoint": format!("https://{}", hostname),
+        }],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn post(posted_at: i64, rkey: &str) -> FeedPost {
+        FeedPost {
+            posted_at,
+            uri: format!("at://did:plc:abc/app.bsky.feed.post/{}", rkey),
+        }
+    }
+
+    #[test]
+    fn test_feed_skeleton() {
+        let posts = vec![post(1, "a"), post(3, "c"), post(2, "b"), post(3, "c")];
+        let uris = |skeleton: &FeedSkeleton| {
+            skeleton
+                .feed
+                .iter()
+                .map(|post| post.post.rsplit('/').next().unwrap().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        let first = feed_skeleton(&posts, None, 2).unwrap();
+        assert_eq!(uris(&first), vec!["c", "b"]);
+        let cursor = first.cursor.clone().unwrap();
+        assert_eq!(cursor, "2::at://did:plc:abc/app.bsky.feed.post/b");
+
+        let last = feed_skeleton(&posts, Some(&cursor), 2).unwrap();
+        assert_eq!(uris(&last), vec!["a"]);
+        assert_eq!(last.cursor, None);
+
+        assert!(feed_skeleton(&posts, Some("yesterday"), 2).is_err());
+        assert_eq!(
+            serde_json::to_value(feed_skeleton(&[], None, 2).unwrap()).unwrap(),
+            json!({"feed": []})
+        );
+    }
+
+    #[test]
+    fn test_did_document() {
+        let document = did_document("did:web:feed.example.com", "feed.example.com");
+        assert_eq!(document["id"], "did:web:feed.example.com");
+        assert_eq!(
+            document["service"][0]["serviceEndpoint"],
+            "https://feed.example.com"
+        );
+    }
+}
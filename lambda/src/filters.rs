@@ -0,0 +1,282 @@
+use crate::language::primary_language;
+
+/// Filters feed items by keywords found in their title or description.
+///
+/// Matching is case insensitive and on substrings. An item is kept when it contains at least one include keyword
+/// (or there are none) and none of the exclude keywords. Exclusion wins over inclusion.
+#[derive(Debug, Clone, Default)]
+pub struct KeywordFilter {
+    include: Vec<String>,
+    exclude: Vec<String>,
+}
+
+impl KeywordFilter {
+    /// Creates a new KeywordFilter.
+    ///
+    /// # Arguments
+    ///
+    /// * `include` - Keywords of which at least one must be present. Empty means everything is included.
+    /// * `exclude` - Keywords of which none may be present.
+    ///
+    /// # Returns
+    ///
+    /// A new instance of KeywordFilter.
+    pub fn new(include: Vec<String>, exclude: Vec<String>) -> Self {
+        let normalize = |keywords: Vec<String>| {
+            keywords
+                .into_iter()
+                .map(|k| k.trim().to_lowercase())
+                .filter(|k| !k.is_empty())
+                .collect()
+        };
+        Self {
+            include: normalize(include),
+            exclude: normalize(exclude),
+        }
+    }
+
+    /// Returns true if the filter doesn't remove anything.
+    pub fn is_empty(&self) -> bool {
+        self.include.is_empty() && self.exclude.is_empty()
+    }
+
+    /// Checks whether an item with the given title and description passes the filter.
+    pub fn matches(&self, title: Option<&str>, description: Option<&str>) -> bool {
+        if self.is_empty() {
+            return true;
+        }
+
+        let text = format!(
+            "{}\n{}",
+            title.unwrap_or_default(),
+            description.unwrap_or_default()
+        )
+        .to_lowercase();
+
+        if self.exclude.iter().any(|k| text.contains(k.as_str())) {
+            return false;
+        }
+
+        self.include.is_empty() || self.include.iter().any(|k| text.contains(k.as_str()))
+    }
+}
+
+/// Filters feed items by their `<category>` values.
+///
+/// Categories are compared case insensitively and must match exactly. An item is kept when it has at least one
+/// allowed category (or the allow list is empty) and no denied category.
+#[derive(Debug, Clone, Default)]
+pub struct CategoryFilter {
+    allow: Vec<String>,
+    deny: Vec<String>,
+}
+
+impl CategoryFilter {
+    /// Creates a new CategoryFilter.
+    ///
+    /// # Arguments
+    ///
+    /// * `allow` - Categories of which at least one must be present. Empty means everything is allowed.
+    /// * `deny` - Categories of which none may be present.
+    ///
+    /// # Returns
+    ///
+    /// A new instance of CategoryFilter.
+    pub fn new(allow: &[String], deny: &[String]) -> Self {
+        let normalize = |categories: &[String]| {
+            categories
+                .iter()
+                .map(|c| c.trim().to_lowercase())
+                .filter(|c| !c.is_empty())
+                .collect()
+        };
+        Self {
+            allow: normalize(allow),
+            deny: normalize(deny),
+        }
+    }
+
+    /// Checks whether an item with the given categories passes the filter.
+    pub fn matches(&self, categories: &[String]) -> bool {
+        let categories: Vec<String> = categories.iter().map(|c| c.trim().to_lowercase()).collect();
+
+        if self.deny.iter().any(|c| categories.contains(c)) {
+            return false;
+        }
+
+        self.allow.is_empty() || self.allow.iter().any(|c| categories.contains(c))
+    }
+}
+
+/// Filters feed items by language.
+///
+/// Languages are ISO 639-1 codes such as `en`. An item is kept when its language is allowed (or the allow list is
+/// empty). Items whose language can't be determined are kept too, as dropping every short post would be worse.
+#[derive(Debug, Clone, Default)]
+pub struct LanguageFilter {
+    allow: Vec<String>,
+}
+
+impl LanguageFilter {
+    /// Creates a new LanguageFilter.
+    ///
+    /// # Arguments
+    ///
+    /// * `allow` - Languages items may be in. Region subtags are ignored, so `en-GB` allows all English items.
+    ///
+    /// # Returns
+    ///
+    /// A new instance of LanguageFilter.
+    pub fn new(allow: &[String]) -> Self {
+        Self {
+            allow: allow.iter().filter_map(|l| primary_language(l)).collect(),
+        }
+    }
+
+    /// Returns true if the filter doesn't remove anything.
+    pub fn is_empty(&self) -> bool {
+        self.allow.is_empty()
+    }
+
+    /// Checks whether an item in the given language passes the filter.
+    ///
+    /// # Arguments
+    ///
+    /// * `language` - The language of the item, e.g. detected from its text or declared by the feed.
+    pub fn matches(&self, language: Option<&str>) -> bool {
+        match language.and_then(primary_language) {
+            Some(language) => self.allow.is_empty() || self.allow.contains(&language),
+            None => true,
+        }
+    }
+}
+
+/// Titles feed generators put in place of a missing one. They count as no title at all.
+const PLACEHOLDER_TITLES: [&str; 3] = ["(no title)", "no title", "untitled"];
+
+/// Filters out feed items with too little text to be worth posting.
+///
+/// Lengths are counted in characters after trimming. A minimum of 0 disables the check for that field.
+#[derive(Debug, Clone, Default)]
+pub struct ContentLengthFilter {
+    min_title_chars: usize,
+    min_description_chars: usize,
+}
+
+impl ContentLengthFilter {
+    /// Creates a new ContentLengthFilter.
+    ///
+    /// # Arguments
+    ///
+    /// * `min_title_chars` - Minimum length of the title.
+    /// * `min_description_chars` - Minimum length of the description, as plain text.
+    ///
+    /// # Returns
+    ///
+    /// A new instance of ContentLengthFilter.
+    pub fn new(min_title_chars: usize, min_description_chars: usize) -> Self {
+        Self {
+            min_title_chars,
+            min_description_chars,
+        }
+    }
+
+    /// Returns true if the filter doesn't remove anything.
+    pub fn is_empty(&self) -> bool {
+        self.min_title_chars == 0 && self.min_description_chars == 0
+    }
+
+    /// Checks whether an item with the given title and plain text description passes the filter.
+    pub fn matches(&self, title: Option<&str>, description: Option<&str>) -> bool {
+        let title = title
+            .map(str::trim)
+            .filter(|t| !PLACEHOLDER_TITLES.contains(&t.to_lowercase().as_str()))
+            .unwrap_or_default();
+        let description = description.map(str::trim).unwrap_or_default();
+
+        title.chars().count() >= self.min_title_chars
+            && description.chars().count() >= self.min_description_chars
+    }
+}
+
+/// Splits a comma separated configuration value into its trimmed, non-empty entries.
+#[must_use]
+pub fn parse_list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keyword_filter() {
+        let filter = KeywordFilter::default();
+        assert!(filter.matches(None, None));
+        assert!(filter.matches(Some("Anything"), Some("goes")));
+
+        let filter = KeywordFilter::new(parse_list("rust, Lambda"), parse_list("sponsored"));
+        assert!(filter.matches(Some("Rust 1.80 released"), None));
+        assert!(filter.matches(Some("New"), Some("AWS LAMBDA now supports")));
+        assert!(!filter.matches(Some("Go 1.23 released"), Some("Nothing here")));
+        assert!(!filter.matches(Some("Rust tips"), Some("Sponsored post")));
+        assert!(!filter.matches(None, None));
+
+        let filter = KeywordFilter::new(Vec::new(), parse_list("sponsored,,  "));
+        assert!(filter.matches(Some("Anything"), None));
+        assert!(!filter.matches(Some("[Sponsored] Anything"), None));
+
+        assert_eq!(parse_list(" a, b ,,c "), vec!["a", "b", "c"]);
+        assert!(parse_list("").is_empty());
+    }
+
+    #[test]
+    fn test_category_filter() {
+        let categories = |c: &str| parse_list(c);
+
+        let filter = CategoryFilter::default();
+        assert!(filter.matches(&[]));
+        assert!(filter.matches(&categories("News")));
+
+        let filter = CategoryFilter::new(&categories("Rust, AWS"), &categories("Sponsored"));
+        assert!(filter.matches(&categories("rust")));
+        assert!(filter.matches(&categories("Compute, aws")));
+        assert!(!filter.matches(&categories("Rustacean")));
+        assert!(!filter.matches(&categories("Rust, sponsored")));
+        assert!(!filter.matches(&[]));
+    }
+
+    #[test]
+    fn test_language_filter() {
+        let filter = LanguageFilter::default();
+        assert!(filter.matches(Some("fr")));
+
+        let filter = LanguageFilter::new(&parse_list("en-GB, DE"));
+        assert!(filter.matches(Some("en")));
+        assert!(filter.matches(Some("en-US")));
+        assert!(filter.matches(Some("de")));
+        assert!(!filter.matches(Some("fr")));
+        assert!(filter.matches(None));
+    }
+
+    #[test]
+    fn test_content_length_filter() {
+        let filter = ContentLengthFilter::default();
+        assert!(filter.is_empty());
+        assert!(filter.matches(None, None));
+
+        let filter = ContentLengthFilter::new(5, 10);
+        assert!(filter.matches(Some("A title"), Some("Long enough text")));
+        assert!(!filter.matches(Some("Hi"), Some("Long enough text")));
+        assert!(!filter.matches(Some("A title"), Some("  Short   ")));
+        assert!(!filter.matches(Some("(No Title)"), Some("Long enough text")));
+        assert!(!filter.matches(None, Some("Long enough text")));
+
+        let filter = ContentLengthFilter::new(0, 10);
+        assert!(filter.matches(None, Some("Long enough text")));
+    }
+}
@@ -0,0 +1,126 @@
+use crate::text_utils::{remaining_graphemes, truncate_to_word};
+use url::Url;
+
+/// A release of a GitHub repository.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Release {
+    /// The repository, as `owner/name`.
+    pub repository: String,
+    /// The tag of the release, usually its version.
+    pub tag: String,
+}
+
+/// Checks whether a feed is the releases feed of a GitHub repository, i.e.
+/// `https://github.com/<owner>/<name>/releases.atom`.
+pub fn is_releases_feed(feed_url: &str) -> bool {
+    github_path(feed_url).is_some_and(
+        |segments| matches!(segments.as_slice(), [_, _, feed] if feed == "releases.atom"),
+    )
+}
+
+/// Reads the repository and tag from the URL of a release page, i.e.
+/// `https://github.com/<owner>/<name>/releases/tag/<tag>`.
+///
+/// # Returns
+///
+/// The Release, or None if the URL is not a GitHub release page.
+pub fn release_from_url(url: &str) -> Option<Release> {
+    match github_path(url)?.as_slice() {
+        [owner, name, releases, tag_segment, tag]
+            if releases == "releases" && tag_segment == "tag" && !tag.is_empty() =>
+        {
+            Some(Release {
+                repository: format!("{}/{}", owner, name),
+                tag: tag.clone(),
+            })
+        }
+        _ => None,
+    }
+}
+
+/// Composes the post announcing a release: the repository and tag, followed by the start of the release notes.
+///
+/// # Arguments
+///
+/// * `release` - The release.
+/// * `notes` - The release notes as plain text, if there are any.
+/// * `max_notes_graphemes` - How much of the notes to include.
+/// * `max_graphemes` - The length limit of the whole post.
+///
+/// # Returns
+///
+/// The text of the post, e.g. `rust-lang/rust 1.83.0 released` followed by a blank line and the notes.
+pub fn release_post_text(
+    release: &Release,
+    notes: Option<&str>,
+    max_notes_graphemes: usize,
+    max_graphemes: usize,
+) -> String {
+    let heading = format!("{} {} released", release.repository, release.tag);
+    let budget = remaining_graphemes(max_graphemes, &[&heading, "\n\n"]).min(max_notes_graphemes);
+    match notes.map(str::trim).filter(|n| !n.is_empty()) {
+        Some(notes) if budget > 0 => format!("{}\n\n{}", heading, truncate_to_word(notes, budget)),
+        _ => truncate_to_word(&heading, max_graphemes),
+    }
+}
+
+/// Returns the path segments of a github.com URL.
+fn github_path(url: &str) -> Option<Vec<String>> {
+    let url = Url::parse(url).ok()?;
+    let host = url.host_str()?.to_lowercase();
+    if host != "github.com" && host != "www.github.com" {
+        return None;
+    }
+    let segments = url
+        .path_segments()?
+        .filter(|s| !s.is_empty())
+        .map(String::from)
+        .collect();
+    Some(segments)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_releases() {
+        assert!(is_releases_feed(
+            "https://github.com/rust-lang/rust/releases.atom"
+        ));
+        assert!(!is_releases_feed(
+            "https://github.com/rust-lang/rust/tags.atom"
+        ));
+        assert!(!is_releases_feed(
+            "https://example.com/rust-lang/rust/releases.atom"
+        ));
+
+        let release =
+            release_from_url("https://github.com/rust-lang/rust/releases/tag/1.83.0").unwrap();
+        assert_eq!(release.repository, "rust-lang/rust");
+        assert_eq!(release.tag, "1.83.0");
+        assert_eq!(
+            release_from_url("https://github.com/tokio-rs/tokio/releases/tag/tokio-1.41.1")
+                .map(|r| r.tag),
+            Some("tokio-1.41.1".to_string())
+        );
+        assert_eq!(
+            release_from_url("https://github.com/rust-lang/rust/releases"),
+            None
+        );
+
+        assert_eq!(
+            release_post_text(
+                &release,
+                Some("Stabilized async closures and more"),
+                20,
+                300
+            ),
+            "rust-lang/rust 1.83.0 released\n\nStabilized async…"
+        );
+        assert_eq!(
+            release_post_text(&release, Some("  "), 20, 300),
+            "rust-lang/rust 1.83.0 released"
+        );
+    }
+}
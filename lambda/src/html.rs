@@ -0,0 +1,305 @@
+use scraper::{ElementRef, Html, Selector};
+use url::Url;
+
+/// Feed types that can be discovered, in order of preference.
+const FEED_TYPES: [&str; 3] = [
+    "application/rss+xml",
+    "application/rdf+xml",
+    "application/atom+xml",
+];
+
+/// Finds the feed advertised by an HTML page through `<link rel="alternate" type="application/rss+xml">`.
+///
+/// # Arguments
+///
+/// * `html` - The HTML of the page.
+/// * `page_url` - The URL the page was fetched from, used to resolve relative links.
+///
+/// # Returns
+///
+/// The absolute URL of the feed, or None if the page doesn't advertise one.
+pub fn discover_feed_url(html: &str, page_url: &str) -> Option<String> {
+    let document = Html::parse_document(html);
+    let selector = Selector::parse(r#"link[rel~="alternate"][href]"#).ok()?;
+
+    let links: Vec<(String, &str)> = document
+        .select(&selector)
+        .filter_map(|link| {
+            let link_type = link.value().attr("type")?.trim().to_lowercase();
+            let href = link.value().attr("href")?.trim();
+            (!href.is_empty()).then_some((link_type, href))
+        })
+        .collect();
+
+    let href = FEED_TYPES.iter().find_map(|feed_type| {
+        links
+            .iter()
+            .find(|(link_type, _)| link_type == feed_type)
+            .map(|(_, href)| *href)
+    })?;
+
+    resolve_url(page_url, href)
+}
+
+/// What a web page says about itself in its `<head>`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PageMetadata {
+    /// `og:title`, or else the `<title>`.
+    pub title: Option<String>,
+    /// `og:description`, or else the `description` meta tag.
+    pub description: Option<String>,
+    /// `og:image`, or else `twitter:image`, resolved against the page URL.
+    pub image_url: Option<String>,
+}
+
+/// Reads the Open Graph metadata of a web page, falling back to the plain HTML title and description.
+///
+/// # Arguments
+///
+/// * `html` - The HTML of the page.
+/// * `page_url` - The URL the page was fetched from, used to resolve a relative image URL.
+///
+/// # Returns
+///
+/// The PageMetadata, with fields the page doesn't have set to None.
+pub fn page_metadata(html: &str, page_url: &str) -> PageMetadata {
+    let document = Html::parse_document(html);
+    let meta = |selector: &str| {
+        let selector = Selector::parse(selector).ok()?;
+        document
+            .select(&selector)
+            .filter_map(|e| e.value().attr("content"))
+            .map(normalize_whitespace)
+            .find(|content| !content.is_empty())
+    };
+    let title = || {
+        let selector = Selector::parse("title").ok()?;
+        document
+            .select(&selector)
+            .map(|e| normalize_whitespace(&e.text().collect::<String>()))
+            .find(|title| !title.is_empty())
+    };
+
+    PageMetadata {
+        title: meta(r#"meta[property="og:title"]"#).or_else(title),
+        description: meta(r#"meta[property="og:description"]"#)
+            .or_else(|| meta(r#"meta[name="description"]"#)),
+        image_url: meta(r#"meta[property="og:image"], meta[property="og:image:url"]"#)
+            .or_else(|| meta(r#"meta[name="twitter:image"], meta[name="twitter:image:src"]"#))
+            .and_then(|url| resolve_url(page_url, &url)),
+    }
+}
+
+/// Articles with less text than this are assumed to be extraction failures (paywalls, cookie walls, index pages).
+const MIN_ARTICLE_CHARS: usize = 200;
+
+/// Extracts the main text of an article page, readability style.
+///
+/// The content container is the page's `<article>` or `<main>` element when there is one. Otherwise it's the
+/// element whose direct `<p>` children hold the most text, which skips navigation, sidebars and footers. The text of
+/// the container's paragraphs and headings is returned, one block per line.
+///
+/// # Arguments
+///
+/// * `html` - The HTML of the article page.
+///
+/// # Returns
+///
+/// The article text, or None if no meaningful amount of text was found.
+pub fn extract_article_text(html: &str) -> Option<String> {
+    let document = Html::parse_document(html);
+    let selector = |s: &str| Selector::parse(s).ok();
+
+    let container = ["article", "main", "[role=main]"]
+        .iter()
+        .filter_map(|s| selector(s))
+        .find_map(|s| document.select(&s).max_by_key(|e| paragraph_text_len(e)))
+        .filter(|e| paragraph_text_len(e) >= MIN_ARTICLE_CHARS)
+        .or_else(|| {
+            let candidates = selector("div, section, td")?;
+            document
+                .select(&candidates)
+                .max_by_key(|e| direct_paragraph_text_len(e))
+        })?;
+
+    let text = block_text(&container);
+    (text.chars().count() >= MIN_ARTICLE_CHARS).then_some(text)
+}
+
+/// Returns the text of the paragraphs, headings, list items and other blocks below `element`, one block per line.
+///
+/// Whitespace within a block is collapsed, and inline markup such as links and emphasis is reduced to its text.
+pub fn block_text(element: &ElementRef) -> String {
+    let Ok(blocks) = Selector::parse("p, h1, h2, h3, h4, li, blockquote, pre") else {
+        return String::new();
+    };
+    element
+        .select(&blocks)
+        // Nested blocks (e.g. a <p> inside a <li>) would otherwise be repeated
+        .filter(|block| {
+            !block
+                .ancestors()
+                .filter_map(ElementRef::wrap)
+                .take_while(|ancestor| ancestor.id() != element.id())
+                .any(|ancestor| blocks.matches(&ancestor))
+        })
+        .map(|block| normalize_whitespace(&block.text().collect::<String>()))
+        .filter(|block| !block.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+/// Length of the text in all `<p>` elements below `element`.
+fn paragraph_text_len(element: &ElementRef) -> usize {
+    let Ok(paragraphs) = Selector::parse("p") else {
+        return 0;
+    };
+    element
+        .select(&paragraphs)
+        .map(|p| p.text().map(str::len).sum::<usize>())
+        .sum()
+}
+
+/// Length of the text in the `<p>` elements that are direct children of `element`.
+fn direct_paragraph_text_len(element: &ElementRef) -> usize {
+    element
+        .children()
+        .filter_map(ElementRef::wrap)
+        .filter(|child| child.value().name() == "p")
+        .map(|p| p.text().map(str::len).sum::<usize>())
+        .sum()
+}
+
+/// Collapses runs of whitespace into single spaces and trims the ends.
+fn normalize_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Reduces an HTML fragment, such as an item description, to its text with whitespace collapsed.
+pub fn strip_tags(html: &str) -> String {
+    let fragment = Html::parse_fragment(html);
+    normalize_whitespace(&fragment.root_element().text().collect::<Vec<_>>().join(" "))
+}
+
+/// Resolves a possibly relative URL against a base URL.
+pub fn resolve_url(base_url: &str, url: &str) -> Option<String> {
+    match Url::parse(url) {
+        Ok(url) => Some(url.to_string()),
+        Err(url::ParseError::RelativeUrlWithoutBase) => Url::parse(base_url)
+            .and_then(|base| base.join(url))
+            .map(|url| url.to_string())
+            .ok(),
+        Err(_) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_discover_feed_url() {
+        let html = r#"<!DOCTYPE html><html><head>
+<link rel="stylesheet" href="/style.css">
+<link rel="alternate" type="application/atom+xml" href="/atom.xml">
+<link rel="alternate" type="application/rss+xml" title="RSS" href="/feed.xml">
+</head><body></body></html>"#;
+        assert_eq!(
+            discover_feed_url(html, "https://example.com/blog/").as_deref(),
+            Some("https://example.com/feed.xml")
+        );
+
+        let html = r#"<html><head><link rel="alternate" type="application/rss+xml" href="https://feeds.example.com/rss"></head></html>"#;
+        assert_eq!(
+            discover_feed_url(html, "https://example.com/").as_deref(),
+            Some("https://feeds.example.com/rss")
+        );
+
+        let html = r#"<html><head><link rel="alternate" hreflang="de" href="/de/"></head></html>"#;
+        assert_eq!(discover_feed_url(html, "https://example.com/"), None);
+    }
+
+    #[test]
+    fn test_page_metadata() {
+        let html = r#"<html><head>
+<title> Plain   title </title>
+<meta name="description" content="Plain description">
+<meta property="og:title" content="Open Graph title">
+<meta property="og:image" content="/images/card.png">
+</head><body></body></html>"#;
+        assert_eq!(
+            page_metadata(html, "https://example.com/post/"),
+            PageMetadata {
+                title: Some("Open Graph title".to_string()),
+                description: Some("Plain description".to_string()),
+                image_url: Some("https://example.com/images/card.png".to_string()),
+            }
+        );
+
+        let html = r#"<html><head>
+<meta name="twitter:image" content="https://cdn.example.com/card.jpg">
+</head></html>"#;
+        assert_eq!(
+            page_metadata(html, "https://example.com/")
+                .image_url
+                .as_deref(),
+            Some("https://cdn.example.com/card.jpg")
+        );
+
+        let html = "<html><head><title>Plain   title</title></head></html>";
+        assert_eq!(
+            page_metadata(html, "https://example.com/").title.as_deref(),
+            Some("Plain title")
+        );
+        assert_eq!(
+            page_metadata("", "https://example.com/"),
+            PageMetadata::default()
+        );
+    }
+
+    #[test]
+    fn test_strip_tags() {
+        assert_eq!(
+            strip_tags("<p>Hello <b>world</b></p>\n<p>Caf&eacute;</p>"),
+            "Hello world Café"
+        );
+        assert_eq!(strip_tags("No markup"), "No markup");
+    }
+
+    #[test]
+    fn test_extract_article_text() {
+        let paragraph =
+            "This is a sentence of the article that is long enough to count. ".repeat(4);
+        let html = format!(
+            r#"<html><body>
+<nav><ul><li>Home</li><li>About</li></ul></nav>
+<div class="sidebar"><p>Subscribe!</p></div>
+<div class="content">
+  <h1>The headline</h1>
+  <p>{paragraph}</p>
+  <p>  Second   paragraph.  {paragraph}</p>
+  <script>var tracking = true;</script>
+</div>
+<footer><p>Copyright</p></footer>
+</body></html>"#
+        );
+        let text = extract_article_text(&html).unwrap();
+        assert!(text.starts_with("The headline\n\nThis is a sentence"));
+        assert!(text.contains("\n\nSecond paragraph. This is"));
+        assert!(!text.contains("Subscribe"));
+        assert!(!text.contains("Copyright"));
+        assert!(!text.contains("tracking"));
+
+        let html = format!(
+            "<html><body><div><p>Teaser</p></div><article><p>{paragraph}</p><ul><li><p>Item</p></li></ul></article></body></html>"
+        );
+        let text = extract_article_text(&html).unwrap();
+        assert!(!text.contains("Teaser"));
+        assert_eq!(text.matches("Item").count(), 1);
+
+        assert_eq!(
+            extract_article_text("<html><body><p>Too short</p></body></html>"),
+            None
+        );
+    }
+}
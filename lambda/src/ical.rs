@@ -0,0 +1,383 @@
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Duration, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use rss::{Channel, Guid, Item};
+
+/// An event (`VEVENT`) of an iCalendar document.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Event {
+    /// The unique identifier of the event (`UID`).
+    pub uid: String,
+    /// The title of the event (`SUMMARY`).
+    pub summary: Option<String>,
+    /// When the event starts (`DTSTART`).
+    pub start: EventStart,
+    /// Where the event takes place (`LOCATION`).
+    pub location: Option<String>,
+    /// The details of the event (`DESCRIPTION`).
+    pub description: Option<String>,
+    /// The web page of the event (`URL`).
+    pub url: Option<String>,
+}
+
+/// The start of an event, which is either a point in time or a whole day.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EventStart {
+    /// A start time, in the time zone the calendar gives for it (UTC for floating times).
+    DateTime(DateTime<Tz>),
+    /// A start date, for all day events.
+    Date(NaiveDate),
+}
+
+impl EventStart {
+    /// Returns the start as a UTC time. All day events start at midnight UTC.
+    pub fn to_utc(&self) -> DateTime<Utc> {
+        match self {
+            Self::DateTime(start) => start.with_timezone(&Utc),
+            Self::Date(date) => date.and_time(Default::default()).and_utc(),
+        }
+    }
+
+    /// Formats the start for a post, e.g. `Sat 23 Nov 2024, 19:00 CET`.
+    pub fn display(&self) -> String {
+        match self {
+            Self::DateTime(start) => start.format("%a %-d %b %Y, %H:%M %Z").to_string(),
+            Self::Date(date) => date.format("%a %-d %b %Y").to_string(),
+        }
+    }
+}
+
+/// Parses the events of an iCalendar (`.ics`) document.
+///
+/// Only the first occurrence of a recurring event is returned, as recurrence rules aren't expanded. Events without a
+/// `UID` or a valid `DTSTART` are skipped.
+///
+/// # Arguments
+///
+/// * `content` - The raw bytes of the document.
+///
+/// # Returns
+///
+/// A Result containing the events, or an error if the document is not an iCalendar document.
+pub fn parse_events(content: &[u8]) -> Result<Vec<Event>> {
+    let content = String::from_utf8_lossy(content);
+    let lines = unfold_lines(&content);
+    if !lines
+        .first()
+        .is_some_and(|l| l.eq_ignore_ascii_case("BEGIN:VCALENDAR"))
+    {
+        return Err(anyhow!("Not an iCalendar document"));
+    }
+
+    let mut events = Vec::new();
+    let mut properties: Option<Vec<Property>> = None;
+    // Components nested in an event, such as alarms, have properties of their own that must not be mixed in
+    let mut nested = 0;
+
+    for line in &lines {
+        let Some(property) = Property::parse(line) else {
+            continue;
+        };
+        match (
+            property.name.as_str(),
+            property.value.to_uppercase().as_str(),
+        ) {
+            ("BEGIN", "VEVENT") if properties.is_none() => properties = Some(Vec::new()),
+            ("BEGIN", _) if properties.is_some() => nested += 1,
+            ("END", "VEVENT") if nested == 0 => {
+                if let Some(event) = properties.take().and_then(|p| Event::from_properties(&p)) {
+                    events.push(event);
+                }
+            }
+            ("END", _) if properties.is_some() => nested -= 1,
+            _ if nested == 0 => {
+                if let Some(properties) = properties.as_mut() {
+                    properties.push(property);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(events)
+}
+
+/// Builds a feed from the events of a calendar that start within `horizon` from now.
+///
+/// Every event becomes an item whose title is the event's and whose description gives its start time, location and
+/// details. The items are dated `now` rather than by their start, as that is when they are announced: dating them in
+/// the future would hold back the feed's high-water mark. Events that are rescheduled get a new guid, and so are
+/// announced again.
+///
+/// # Arguments
+///
+/// * `content` - The raw bytes of the calendar.
+/// * `fallback_link` - The link of events without a `URL`, e.g. the web site of the venue.
+/// * `now` - The current time.
+/// * `horizon` - How far ahead events are announced.
+///
+/// # Returns
+///
+/// A Result containing a Channel with one Item per upcoming event, or an error if the document is not an iCalendar
+/// document.
+pub fn ical_channel(
+    content: &[u8],
+    fallback_link: &str,
+    now: DateTime<Utc>,
+    horizon: Duration,
+) -> Result<Channel> {
+    let mut events: Vec<Event> = parse_events(content)?
+        .into_iter()
+        .filter(|e| e.start.to_utc() >= now && e.start.to_utc() <= now + horizon)
+        .collect();
+    events.sort_by_key(|e| e.start.to_utc());
+
+    let items: Vec<Item> = events
+        .into_iter()
+        .map(|event| {
+            let mut details = vec![event.start.display()];
+            details.extend(event.location.clone());
+            details.extend(event.description.clone());
+
+            let mut item = Item::default();
+            item.set_guid(Guid {
+                value: format!("{}#{}", event.uid, event.start.to_utc().timestamp()),
+                permalink: false,
+            });
+            item.set_link(event.url.unwrap_or_else(|| fallback_link.to_string()));
+            item.set_title(event.summary);
+            item.set_description(details.join("\n"));
+            item.set_pub_date(now.to_rfc2822());
+            item
+        })
+        .collect();
+
+    let mut channel = Channel::default();
+    channel.set_link(fallback_link);
+    channel.set_items(items);
+    Ok(channel)
+}
+
+impl Event {
+    fn from_properties(properties: &[Property]) -> Option<Self> {
+        let text = |name: &str| {
+            properties
+                .iter()
+                .find(|p| p.name == name)
+                .map(|p| unescape_text(&p.value))
+                .filter(|v| !v.is_empty())
+        };
+        let start = properties.iter().find(|p| p.name == "DTSTART")?;
+
+        Some(Self {
+            uid: text("UID")?,
+            summary: text("SUMMARY"),
+            start: parse_start(start)?,
+            location: text("LOCATION"),
+            description: text("DESCRIPTION"),
+            url: text("URL"),
+        })
+    }
+}
+
+/// A content line of an iCalendar document, e.g. `DTSTART;TZID=Europe/Berlin:20241123T190000`.
+#[derive(Debug)]
+struct Property {
+    name: String,
+    params: Vec<(String, String)>,
+    value: String,
+}
+
+impl Property {
+    fn parse(line: &str) -> Option<Self> {
+        // The value starts at the first colon outside of a quoted parameter value
+        let mut in_quotes = false;
+        let colon = line.char_indices().find_map(|(i, c)| match c {
+            '"' => {
+                in_quotes = !in_quotes;
+                None
+            }
+            ':' if !in_quotes => Some(i),
+            _ => None,
+        })?;
+
+        let (head, value) = (&line[..colon], &line[colon + 1..]);
+        let mut parts = head.split(';');
+        let name = parts.next()?.trim().to_uppercase();
+        let params = parts
+            .filter_map(|param| {
+                let (key, value) = param.split_once('=')?;
+                Some((
+                    key.trim().to_uppercase(),
+                    value.trim_matches('"').to_string(),
+                ))
+            })
+            .collect();
+
+        Some(Self {
+            name,
+            params,
+            value: value.to_string(),
+        })
+    }
+
+    fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Joins folded lines (continuation lines start with a space or tab) and drops empty ones.
+fn unfold_lines(content: &str) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for line in content.lines() {
+        match line.strip_prefix([' ', '\t']) {
+            Some(continuation) if !lines.is_empty() => {
+                if let Some(last) = lines.last_mut() {
+                    last.push_str(continuation);
+                }
+            }
+            _ if line.trim().is_empty() => {}
+            _ => lines.push(line.to_string()),
+        }
+    }
+    lines
+}
+
+/// Reverts the escaping of TEXT values (`\n`, `\,`, `\;` and `\\`).
+fn unescape_text(value: &str) -> String {
+    let mut text = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('n') | Some('N') => text.push('\n'),
+                Some(other) => text.push(other),
+                None => {}
+            },
+            _ => text.push(c),
+        }
+    }
+    text.trim().to_string()
+}
+
+fn parse_start(property: &Property) -> Option<EventStart> {
+    let value = property.value.trim();
+    if property.param("VALUE") == Some("DATE") || value.len() == 8 {
+        return NaiveDate::parse_from_str(value, "%Y%m%d")
+            .ok()
+            .map(EventStart::Date);
+    }
+
+    let (local, is_utc) = match value.strip_suffix('Z') {
+        Some(local) => (local, true),
+        None => (value, false),
+    };
+    let local = NaiveDateTime::parse_from_str(local, "%Y%m%dT%H%M%S").ok()?;
+    let tz = match property.param("TZID") {
+        Some(tzid) if !is_utc => tzid.parse::<Tz>().ok()?,
+        _ => Tz::UTC,
+    };
+    // Times in a daylight saving gap don't exist and are skipped along with their event
+    tz.from_local_datetime(&local)
+        .earliest()
+        .map(EventStart::DateTime)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CALENDAR: &str = "BEGIN:VCALENDAR\r
+VERSION:2.0\r
+BEGIN:VEVENT\r
+UID:meetup-1@example.com\r
+DTSTART;TZID=Europe/Berlin:20241123T190000\r
+SUMMARY:Rust Meetup\\, November\r
+LOCATION:Café Example\\, Berlin\r
+DESCRIPTION:Talks and pizza.\\nBring a\r
+  friend!\r
+URL:https://example.com/meetups/1\r
+BEGIN:VALARM\r
+DESCRIPTION:Reminder\r
+END:VALARM\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+UID:festival@example.com\r
+DTSTART;VALUE=DATE:20241130\r
+SUMMARY:Festival\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+UID:past@example.com\r
+DTSTART:20241101T120000Z\r
+SUMMARY:Already happened\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+DTSTART:20241125T120000Z\r
+SUMMARY:No UID\r
+END:VEVENT\r
+END:VCALENDAR\r
+";
+
+    #[test]
+    fn test_parse_events() {
+        let events = parse_events(CALENDAR.as_bytes()).unwrap();
+        assert_eq!(events.len(), 3);
+
+        assert_eq!(events[0].uid, "meetup-1@example.com");
+        assert_eq!(events[0].summary.as_deref(), Some("Rust Meetup, November"));
+        assert_eq!(events[0].location.as_deref(), Some("Café Example, Berlin"));
+        assert_eq!(
+            events[0].description.as_deref(),
+            Some("Talks and pizza.\nBring a friend!")
+        );
+        assert_eq!(events[0].start.display(), "Sat 23 Nov 2024, 19:00 CET");
+        assert_eq!(
+            events[0].start.to_utc(),
+            Utc.with_ymd_and_hms(2024, 11, 23, 18, 0, 0).unwrap()
+        );
+
+        assert_eq!(events[1].start.display(), "Sat 30 Nov 2024");
+        assert_eq!(events[2].start.display(), "Fri 1 Nov 2024, 12:00 UTC");
+
+        assert!(parse_events(b"<html></html>").is_err());
+    }
+
+    #[test]
+    fn test_ical_channel() {
+        let now = Utc.with_ymd_and_hms(2024, 11, 20, 9, 0, 0).unwrap();
+        let channel = ical_channel(
+            CALENDAR.as_bytes(),
+            "https://example.com/",
+            now,
+            Duration::days(7),
+        )
+        .unwrap();
+
+        let items = channel.items();
+        assert_eq!(items.len(), 1);
+        assert_eq!(
+            items[0].guid().map(|g| g.value()),
+            Some("meetup-1@example.com#1732384800")
+        );
+        assert_eq!(items[0].title(), Some("Rust Meetup, November"));
+        assert_eq!(items[0].link(), Some("https://example.com/meetups/1"));
+        assert_eq!(
+            items[0].description(),
+            Some("Sat 23 Nov 2024, 19:00 CET\nCafé Example, Berlin\nTalks and pizza.\nBring a friend!")
+        );
+        assert_eq!(items[0].pub_date(), Some(now.to_rfc2822().as_str()));
+
+        let channel = ical_channel(
+            CALENDAR.as_bytes(),
+            "https://example.com/",
+            now,
+            Duration::days(14),
+        )
+        .unwrap();
+        assert_eq!(channel.items().len(), 2);
+        assert_eq!(channel.items()[1].link(), Some("https://example.com/"));
+    }
+}
@@ -0,0 +1,238 @@
+use anyhow::{anyhow, Context, Result};
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageReader};
+use std::io::Cursor;
+
+/// Images are scaled down to fit within this many pixels on their longest side before anything else is tried.
+/// Bluesky doesn't display them any larger.
+const MAX_DIMENSION: u32 = 2000;
+/// JPEG qualities tried in turn at each size, best first.
+const JPEG_QUALITIES: [u8; 4] = [85, 75, 65, 50];
+/// Images aren't scaled below this many pixels on their longest side; a smaller one would be useless.
+const MIN_DIMENSION: u32 = 200;
+
+/// Makes an image small enough to upload as a Bluesky blob.
+///
+/// Images already within the limit are returned as they are. Larger ones are decoded (JPEG, PNG, WebP or GIF),
+/// scaled down to at most 2000 pixels on their longest side and re-encoded as JPEG, at decreasing qualities and then
+/// halving the size until the result fits.
+///
+/// # Arguments
+///
+/// * `bytes` - The encoded image.
+/// * `max_bytes` - The size the result must not exceed.
+///
+/// # Returns
+///
+/// A Result containing the image, possibly re-encoded, or an error if it can't be decoded or doesn't get under the
+/// limit without being scaled down to almost nothing.
+pub fn fit_image(bytes: &[u8], max_bytes: usize) -> Result<Vec<u8>> {
+    if bytes.len() <= max_bytes {
+        return Ok(bytes.to_vec());
+    }
+
+    let image = ImageReader::new(Cursor::new(bytes))
+        .with_guessed_format()
+        .context("Failed to read image")?
+        .decode()
+        .context("Failed to decode image")?;
+    // JPEG has no alpha channel, and transparent areas are usually meant to show a light background
+    let image = DynamicImage::ImageRgb8(flatten_alpha(&image));
+
+    let mut dimension = image.width().max(image.height()).min(MAX_DIMENSION);
+    while dimension >= MIN_DIMENSION {
+        let resized = image.resize(dimension, dimension, FilterType::Lanczos3);
+        for quality in JPEG_QUALITIES {
+            let mut encoded = Vec::new();
+            resized
+                .write_with_encoder(JpegEncoder::new_with_quality(&mut encoded, quality))
+                .context("Failed to encode image")?;
+            if encoded.len() <= max_bytes {
+                return Ok(encoded);
+            }
+        }
+        dimension /= 2;
+    }
+
+    Err(anyhow!(
+        "Image can't be compressed below {} bytes",
+        max_bytes
+    ))
+}
+
+/// Reads the width and height of an encoded image from its header, without decoding it.
+///
+/// # Returns
+///
+/// The dimensions in pixels, or None if the format isn't known or the header can't be read.
+pub fn image_dimensions(bytes: &[u8]) -> Option<(u32, u32)> {
+    ImageReader::new(Cursor::new(bytes))
+        .with_guessed_format()
+        .ok()?
+        .into_dimensions()
+        .ok()
+        .filter(|(width, height)| *width > 0 && *height > 0)
+}
+
+/// Reads the width and height of an MP4 video from the header (`tkhd` box) of its first track that has any, as it's
+/// displayed, so a video recorded sideways and rotated by its track matrix has them swapped.
+///
+/// # Returns
+///
+/// The dimensions in pixels, or None if the file has no video track or its boxes can't be read.
+pub fn video_dimensions(bytes: &[u8]) -> Option<(u32, u32)> {
+    let moov = mp4_boxes(bytes).find(|(kind, _)| kind == b"moov")?.1;
+    mp4_boxes(moov)
+        .filter(|(kind, _)| kind == b"trak")
+        .filter_map(|(_, trak)| mp4_boxes(trak).find(|(kind, _)| kind == b"tkhd"))
+        .find_map(|(_, tkhd)| track_dimensions(tkhd))
+}
+
+/// Iterates over the boxes of an MP4 file, or the content of a box, as their types and contents.
+fn mp4_boxes(bytes: &[u8]) -> impl Iterator<Item = ([u8; 4], &[u8])> {
+    let mut rest = bytes;
+    std::iter::from_fn(move || {
+        let size = u32::from_be_bytes(rest.get(0..4)?.try_into().ok()?);
+        let kind: [u8; 4] = rest.get(4..8)?.try_into().ok()?;
+        let (header, size) = match size {
+            0 => (8, rest.len()),
+            1 => (
+                16,
+                usize::try_from(u64::from_be_bytes(rest.get(8..16)?.try_into().ok()?)).ok()?,
+            ),
+            size => (8, usize::try_from(size).ok()?),
+        };
+        let content = rest.get(header..size)?;
+        rest = &rest[size..];
+        Some((kind, content))
+    })
+}
+
+/// Reads the dimensions from the content of a track header, None for tracks without any, such as audio.
+fn track_dimensions(tkhd: &[u8]) -> Option<(u32, u32)> {
+    // The times and duration before the matrix are 32-bit in version 0 and 64-bit in version 1
+    let matrix = match *tkhd.first()? {
+        0 => 40,
+        1 => 52,
+        _ => return None,
+    };
+    let read = |at: usize| Some(u32::from_be_bytes(tkhd.get(at..at + 4)?.try_into().ok()?));
+    let (a, b) = (read(matrix)?, read(matrix + 4)?);
+    // The width and height are 16.16 fixed point numbers
+    let (width, height) = (read(matrix + 36)? >> 16, read(matrix + 40)? >> 16);
+    if width == 0 || height == 0 {
+        return None;
+    }
+    // A matrix that rotates by 90 or 270 degrees has a = 0
+    Some(if a == 0 && b != 0 {
+        (height, width)
+    } else {
+        (width, height)
+    })
+}
+
+/// Converts an image to RGB, blending transparent pixels onto white.
+fn flatten_alpha(image: &DynamicImage) -> image::RgbImage {
+    let rgba = image.to_rgba8();
+    image::RgbImage::from_fn(rgba.width(), rgba.height(), |x, y| {
+        let [r, g, b, a] = rgba.get_pixel(x, y).0;
+        let blend =
+            |c: u8| ((u16::from(c) * u16::from(a) + 255 * (255 - u16::from(a))) / 255) as u8;
+        image::Rgb([blend(r), blend(g), blend(b)])
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{ImageFormat, Rgba, RgbaImage};
+
+    #[test]
+    fn test_fit_image() {
+        // Noise barely compresses, so this PNG is far over the limit
+        let mut seed: u32 = 1;
+        let noise = RgbaImage::from_fn(3000, 1500, |_, _| {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            let [r, g, b, _] = seed.to_le_bytes();
+            Rgba([r, g, b, 255])
+        });
+        let mut png = Vec::new();
+        noise
+            .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+            .unwrap();
+        assert!(png.len() > 1_000_000);
+
+        let fitted = fit_image(&png, 1_000_000).unwrap();
+        assert!(fitted.len() <= 1_000_000, "{}", fitted.len());
+        let decoded = image::load_from_memory(&fitted).unwrap();
+        assert_eq!(image::guess_format(&fitted).unwrap(), ImageFormat::Jpeg);
+        assert!(decoded.width() <= MAX_DIMENSION);
+        assert_eq!(decoded.width(), decoded.height() * 2);
+
+        assert_eq!(fit_image(&fitted, 1_000_000).unwrap(), fitted);
+        assert!(fit_image(b"not an image", 4).is_err());
+    }
+
+    #[test]
+    fn test_image_dimensions() {
+        let mut png = Vec::new();
+        RgbaImage::from_pixel(300, 200, Rgba([0, 0, 0, 255]))
+            .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+            .unwrap();
+        assert_eq!(image_dimensions(&png), Some((300, 200)));
+        assert_eq!(image_dimensions(b"not an image"), None);
+    }
+
+    fn mp4_box(kind: &[u8; 4], content: &[u8]) -> Vec<u8> {
+        let mut mp4_box = ((content.len() + 8) as u32).to_be_bytes().to_vec();
+        mp4_box.extend_from_slice(kind);
+        mp4_box.extend_from_slice(content);
+        mp4_box
+    }
+
+    fn tkhd(width: u32, height: u32, matrix: [u32; 2]) -> Vec<u8> {
+        // Version 0, no flags, then times, track ID, reserved, duration, reserved, layer, group, volume, reserved
+        let mut tkhd = vec![0; 40];
+        tkhd.extend(matrix.iter().flat_map(|n| n.to_be_bytes()));
+        tkhd.extend([0; 28]);
+        tkhd.extend((width << 16).to_be_bytes());
+        tkhd.extend((height << 16).to_be_bytes());
+        mp4_box(b"tkhd", &tkhd)
+    }
+
+    fn mp4(tracks: &[Vec<u8>]) -> Vec<u8> {
+        let traks: Vec<u8> = tracks
+            .iter()
+            .flat_map(|tkhd| mp4_box(b"trak", tkhd))
+            .collect();
+        let mut mp4 = mp4_box(b"ftyp", b"isom\0\0\0\0");
+        mp4.extend(mp4_box(b"moov", &traks));
+        mp4
+    }
+
+    #[test]
+    fn test_video_dimensions() {
+        // The audio track comes first, without dimensions
+        let audio = tkhd(0, 0, [0x0001_0000, 0]);
+        let landscape = mp4(&[audio.clone(), tkhd(1920, 1080, [0x0001_0000, 0])]);
+        assert_eq!(video_dimensions(&landscape), Some((1920, 1080)));
+
+        // Recorded sideways and rotated by 90 degrees
+        let rotated = mp4(&[tkhd(1920, 1080, [0, 0x0001_0000])]);
+        assert_eq!(video_dimensions(&rotated), Some((1080, 1920)));
+
+        assert_eq!(video_dimensions(&mp4(&[audio])), None);
+        assert_eq!(video_dimensions(b"not a video"), None);
+        // A box that claims to be longer than the file
+        assert_eq!(video_dimensions(&landscape[..landscape.len() - 1]), None);
+    }
+
+    #[test]
+    fn test_flatten_alpha() {
+        let image = DynamicImage::ImageRgba8(RgbaImage::from_pixel(1, 1, Rgba([0, 0, 0, 0])));
+        assert_eq!(flatten_alpha(&image).get_pixel(0, 0).0, [255, 255, 255]);
+        let image = DynamicImage::ImageRgba8(RgbaImage::from_pixel(1, 1, Rgba([10, 20, 30, 255])));
+        assert_eq!(flatten_alpha(&image).get_pixel(0, 0).0, [10, 20, 30]);
+    }
+}
@@ -0,0 +1,183 @@
+use crate::feed::parse_date;
+use crate::html::resolve_url;
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, FixedOffset};
+use rss::{Channel, Guid, Item};
+use serde_json::Value;
+use serde_json_path::JsonPath;
+use std::collections::HashMap;
+
+/// Unix timestamps above this are taken to be in milliseconds rather than seconds.
+const MAX_TIMESTAMP_SECS: i64 = 100_000_000_000;
+
+/// Builds a feed from the response of a JSON API, such as a headless CMS.
+///
+/// The selectors are JSONPath expressions, keyed by field:
+///
+/// * `item` (required) - Matches one value per article in the response, e.g. `$.data.posts[*]`.
+/// * `link` (required) - Within an item, the article URL, e.g. `$.url`.
+/// * `id` - Within an item, a stable identifier. Defaults to the link. As ids are usually only unique within one
+///   API, they are scoped to the API URL.
+/// * `title` - Within an item, the title.
+/// * `date` - Within an item, the publication date, as a date string or a Unix timestamp in seconds or
+///   milliseconds. Undated items are only processed when ACCEPT_UNDATED_ITEMS is set.
+/// * `description` - Within an item, the body or a teaser.
+///
+/// # Arguments
+///
+/// * `content` - The raw bytes of the response.
+/// * `api_url` - The URL of the API, used to resolve relative links.
+/// * `selectors` - The JSONPath expressions of the fields.
+///
+/// # Returns
+///
+/// A Result containing a Channel with one Item per article that has a link, or an error if the response is not
+/// JSON or an expression is invalid.
+pub fn json_channel(
+    content: &[u8],
+    api_url: &str,
+    selectors: &HashMap<String, String>,
+) -> Result<Channel> {
+    let path = |field: &str| -> Result<Option<JsonPath>> {
+        selectors
+            .get(field)
+            .map(|p| {
+                JsonPath::parse(p).map_err(|e| anyhow!("Invalid {} JSONPath {:?}: {}", field, p, e))
+            })
+            .transpose()
+    };
+
+    let item_path = path("item")?.ok_or_else(|| anyhow!("The item JSONPath is required"))?;
+    let link_path = path("link")?.ok_or_else(|| anyhow!("The link JSONPath is required"))?;
+    let id_path = path("id")?;
+    let title_path = path("title")?;
+    let date_path = path("date")?;
+    let description_path = path("description")?;
+
+    let document: Value =
+        serde_json::from_slice(content).context("Failed to parse JSON API response")?;
+
+    let items: Vec<Item> = item_path
+        .query(&document)
+        .all()
+        .into_iter()
+        .filter_map(|value| {
+            let text = |path: &JsonPath| path.query(value).first().and_then(scalar_text);
+            let field = |path: &Option<JsonPath>| path.as_ref().and_then(text);
+
+            let link = text(&link_path).and_then(|l| resolve_url(api_url, &l))?;
+            let guid = match field(&id_path) {
+                Some(id) => Guid {
+                    value: format!("{}#{}", api_url, id),
+                    permalink: false,
+                },
+                None => Guid {
+                    value: link.clone(),
+                    permalink: true,
+                },
+            };
+            let pub_date = date_path
+                .as_ref()
+                .and_then(|p| p.query(value).first())
+                .and_then(date_value);
+
+            let mut item = Item::default();
+            item.set_guid(guid);
+            item.set_link(link);
+            item.set_title(field(&title_path));
+            item.set_description(field(&description_path));
+            item.set_pub_date(pub_date.map(|d| d.to_rfc2822()));
+            Some(item)
+        })
+        .collect();
+
+    let mut channel = Channel::default();
+    channel.set_link(api_url);
+    channel.set_items(items);
+    Ok(channel)
+}
+
+/// Returns the text of a string or number value, trimmed, or None for anything else or an empty string.
+fn scalar_text(value: &Value) -> Option<String> {
+    let text = match value {
+        Value::String(s) => s.trim().to_string(),
+        Value::Number(n) => n.to_string(),
+        _ => return None,
+    };
+    (!text.is_empty()).then_some(text)
+}
+
+/// Reads a date from a date string or a Unix timestamp.
+fn date_value(value: &Value) -> Option<DateTime<FixedOffset>> {
+    match value {
+        Value::String(s) => parse_date(s),
+        Value::Number(n) => {
+            let timestamp = n.as_i64()?;
+            let date = if timestamp > MAX_TIMESTAMP_SECS {
+                DateTime::from_timestamp_millis(timestamp)
+            } else {
+                DateTime::from_timestamp(timestamp, 0)
+            };
+            date.map(|d| d.fixed_offset())
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_channel() {
+        let content = r#"{"data": {"posts": [
+            {"id": 42, "title": " First post ", "path": "/posts/first", "published": "2024-11-20T10:00:00Z", "excerpt": "Teaser"},
+            {"id": 43, "title": "Second post", "path": "https://example.com/posts/second", "published": 1732096800},
+            {"id": 44, "title": "No link"}
+        ]}}"#;
+        let selectors: HashMap<String, String> = [
+            ("item", "$.data.posts[*]"),
+            ("id", "$.id"),
+            ("link", "$.path"),
+            ("title", "$.title"),
+            ("date", "$.published"),
+            ("description", "$.excerpt"),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+
+        let channel = json_channel(
+            content.as_bytes(),
+            "https://example.com/api/posts",
+            &selectors,
+        )
+        .unwrap();
+        let items = channel.items();
+        assert_eq!(items.len(), 2);
+
+        assert_eq!(
+            items[0].guid().map(|g| g.value()),
+            Some("https://example.com/api/posts#42")
+        );
+        assert_eq!(items[0].link(), Some("https://example.com/posts/first"));
+        assert_eq!(items[0].title(), Some("First post"));
+        assert_eq!(items[0].description(), Some("Teaser"));
+        assert_eq!(
+            items[0].pub_date().and_then(parse_date),
+            parse_date("2024-11-20T10:00:00Z")
+        );
+
+        assert_eq!(items[1].description(), None);
+        assert_eq!(
+            items[1].pub_date().and_then(parse_date),
+            parse_date("2024-11-20T10:00:00Z")
+        );
+
+        let mut selectors = selectors;
+        selectors.remove("link");
+        assert!(json_channel(content.as_bytes(), "https://example.com/", &selectors).is_err());
+        selectors.insert("link".to_string(), "$[".to_string());
+        assert!(json_channel(content.as_bytes(), "https://example.com/", &selectors).is_err());
+    }
+}
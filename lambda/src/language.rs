@@ -0,0 +1,123 @@
+/// ISO 639-3 codes of the languages whatlang detects, with their ISO 639-1 equivalents (as used in RSS `<language>`
+/// and BCP 47 language tags).
+const ISO_639_1: [(&str, &str); 70] = [
+    ("afr", "af"),
+    ("aka", "ak"),
+    ("amh", "am"),
+    ("ara", "ar"),
+    ("aze", "az"),
+    ("bel", "be"),
+    ("ben", "bn"),
+    ("bul", "bg"),
+    ("cat", "ca"),
+    ("ces", "cs"),
+    ("cmn", "zh"),
+    ("cym", "cy"),
+    ("dan", "da"),
+    ("deu", "de"),
+    ("ell", "el"),
+    ("eng", "en"),
+    ("epo", "eo"),
+    ("est", "et"),
+    ("fin", "fi"),
+    ("fra", "fr"),
+    ("guj", "gu"),
+    ("heb", "he"),
+    ("hin", "hi"),
+    ("hrv", "hr"),
+    ("hun", "hu"),
+    ("hye", "hy"),
+    ("ind", "id"),
+    ("ita", "it"),
+    ("jav", "jv"),
+    ("jpn", "ja"),
+    ("kan", "kn"),
+    ("kat", "ka"),
+    ("khm", "km"),
+    ("kor", "ko"),
+    ("lat", "la"),
+    ("lav", "lv"),
+    ("lit", "lt"),
+    ("mal", "ml"),
+    ("mar", "mr"),
+    ("mkd", "mk"),
+    ("mya", "my"),
+    ("nep", "ne"),
+    ("nld", "nl"),
+    ("nob", "nb"),
+    ("ori", "or"),
+    ("pan", "pa"),
+    ("pes", "fa"),
+    ("pol", "pl"),
+    ("por", "pt"),
+    ("ron", "ro"),
+    ("rus", "ru"),
+    ("sin", "si"),
+    ("slk", "sk"),
+    ("slv", "sl"),
+    ("sna", "sn"),
+    ("spa", "es"),
+    ("srp", "sr"),
+    ("swe", "sv"),
+    ("tam", "ta"),
+    ("tel", "te"),
+    ("tgl", "tl"),
+    ("tha", "th"),
+    ("tuk", "tk"),
+    ("tur", "tr"),
+    ("ukr", "uk"),
+    ("urd", "ur"),
+    ("uzb", "uz"),
+    ("vie", "vi"),
+    ("yid", "yi"),
+    ("zul", "zu"),
+];
+
+/// Detects the language of a text.
+///
+/// # Arguments
+///
+/// * `text` - Plain text, e.g. the title and description of an item.
+///
+/// # Returns
+///
+/// The ISO 639-1 code of the language, or None if the text is too short or ambiguous to tell reliably.
+pub fn detect_language(text: &str) -> Option<&'static str> {
+    let info = whatlang::detect(text).filter(|info| info.is_reliable())?;
+    let code = info.lang().code();
+    ISO_639_1
+        .iter()
+        .find(|(iso_639_3, _)| *iso_639_3 == code)
+        .map(|(_, iso_639_1)| *iso_639_1)
+}
+
+/// Reduces a language tag such as `en-US` (or `en_us`) to its lowercase primary language, `en`.
+pub fn primary_language(tag: &str) -> Option<String> {
+    tag.trim()
+        .split(['-', '_'])
+        .next()
+        .map(str::to_lowercase)
+        .filter(|language| !language.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_language() {
+        assert_eq!(
+            detect_language("The government announced on Tuesday that it would increase funding for public schools across the country."),
+            Some("en")
+        );
+        assert_eq!(
+            detect_language("Die Regierung hat am Dienstag angekündigt, dass sie die Mittel für öffentliche Schulen im ganzen Land erhöhen wird."),
+            Some("de")
+        );
+        assert_eq!(detect_language("ok"), None);
+
+        assert_eq!(primary_language("en-US").as_deref(), Some("en"));
+        assert_eq!(primary_language(" pt_BR ").as_deref(), Some("pt"));
+        assert_eq!(primary_language(""), None);
+    }
+}
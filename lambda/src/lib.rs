@@ -1,3 +1,37 @@
+pub mod alt_text;
+pub mod analytics;
+pub mod bluesky;
+pub mod content_warnings;
+pub mod crosspost;
+pub mod digest;
+pub mod env;
+pub mod feed;
+pub mod feed_generator;
+pub mod filters;
+pub mod github;
+pub mod html;
+pub mod http;
+pub mod ical;
+pub mod image_utils;
+pub mod json_api;
+pub mod language;
+pub mod lint;
+pub mod mastodon;
+pub mod metrics;
 pub mod models;
+pub mod nostr;
+pub mod oauth;
+pub mod openai;
+pub mod opml;
+pub mod poster;
+pub mod preprocess;
 pub mod repository;
+pub mod retry;
+pub mod robots;
+pub mod schedule;
+pub mod scrape;
+pub mod similarity;
+pub mod sitemap;
+pub mod summarize;
 pub mod text_utils;
+pub mod url_utils;
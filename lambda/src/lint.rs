@@ -0,0 +1,290 @@
+use crate::feed::{self, parse_date};
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use serde::Serialize;
+use std::collections::HashMap;
+use url::Url;
+
+/// Descriptions longer than this are flagged, as they are usually full articles that bloat the ExecutionItems and the
+/// summarization prompt.
+pub const MAX_DESCRIPTION_CHARS: usize = 10_000;
+
+/// How serious a lint finding is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// The feed or item can't be processed by the bridge.
+    Error,
+    /// The feed or item is processed, but possibly not as intended.
+    Warning,
+}
+
+/// A single problem found in a feed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LintIssue {
+    pub severity: Severity,
+    /// Stable identifier of the check, e.g. `missing_guid`.
+    pub code: &'static str,
+    /// Zero based position of the item the issue was found in, or None for issues with the feed as a whole.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub item: Option<usize>,
+    pub message: String,
+}
+
+/// The result of linting a feed.
+#[derive(Debug, Clone, Serialize)]
+pub struct LintReport {
+    pub feed_url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    pub item_count: usize,
+    pub issues: Vec<LintIssue>,
+}
+
+impl LintReport {
+    /// Returns true if any issue prevents the feed or one of its items from being processed.
+    pub fn has_errors(&self) -> bool {
+        self.issues.iter().any(|i| i.severity == Severity::Error)
+    }
+}
+
+/// Checks a feed document for problems that affect how the bridge processes it.
+///
+/// The checks cover the encoding, whether the document parses at all, and per item: missing or duplicate guids,
+/// missing or unparseable dates, missing or relative links, missing content and oversized descriptions.
+///
+/// # Arguments
+///
+/// * `feed_url` - The URL the feed was fetched from.
+/// * `content` - The raw bytes of the feed document.
+///
+/// # Returns
+///
+/// The LintReport for the feed.
+pub fn lint_feed(feed_url: &str, content: &[u8]) -> LintReport {
+    let mut report = LintReport {
+        feed_url: feed_url.to_string(),
+        title: None,
+        item_count: 0,
+        issues: Vec::new(),
+    };
+    let mut issue = |severity, code, item, message: String| {
+        report.issues.push(LintIssue {
+            severity,
+            code,
+            item,
+            message,
+        })
+    };
+
+    let encoding = declared_encoding(content);
+    let declares_utf8 = encoding
+        .as_deref()
+        .is_none_or(|e| e.eq_ignore_ascii_case("utf-8"));
+    if declares_utf8 {
+        if let Err(e) = std::str::from_utf8(content) {
+            issue(
+                Severity::Error,
+                "invalid_encoding",
+                None,
+                format!(
+                    "The document is declared (or defaults to) UTF-8 but is not valid UTF-8: {}",
+                    e
+                ),
+            );
+        }
+    }
+
+    let channel = match feed::read_channel(content) {
+        Ok(channel) => channel,
+        Err(e) => {
+            issue(
+                Severity::Error,
+                "unparseable",
+                None,
+                format!("The document is not a valid RSS feed: {:#}", e),
+            );
+            return report;
+        }
+    };
+
+    let base_url = feed::base_url(&channel, feed_url);
+    let atom_prefix = feed::atom_prefix(&channel);
+
+    if channel.items().is_empty() {
+        issue(
+            Severity::Warning,
+            "no_items",
+            None,
+            "The feed has no items".to_string(),
+        );
+    }
+
+    let mut guids: HashMap<&str, usize> = HashMap::new();
+    for (index, item) in channel.items().iter().enumerate() {
+        let item_index = Some(index);
+
+        match non_empty(item.guid().map(|g| g.value())) {
+            Some(guid) => {
+                if let Some(first) = guids.insert(guid, index) {
+                    issue(
+                        Severity::Warning,
+                        "duplicate_guid",
+                        item_index,
+                        format!(
+                            "The guid {:?} is also used by item {}, only one of them will be posted",
+                            guid, first
+                        ),
+                    );
+                }
+            }
+            None if feed::item_identity(item).is_some() => issue(
+                Severity::Warning,
+                "missing_guid",
+                item_index,
+                "The item has no guid, its identity is derived from its link or content and changes if they are edited"
+                    .to_string(),
+            ),
+            None => {}
+        }
+
+        if non_empty(item.title()).is_none() && non_empty(item.description()).is_none() {
+            issue(
+                Severity::Error,
+                "missing_content",
+                item_index,
+                "The item has neither a title nor a description".to_string(),
+            );
+        }
+
+        match non_empty(item.pub_date()) {
+            Some(pub_date) if parse_date(pub_date).is_none() => issue(
+                Severity::Error,
+                "invalid_date",
+                item_index,
+                format!(
+                    "The publication date {:?} is neither RFC 2822, RFC 3339 nor YYYY-MM-DD",
+                    pub_date
+                ),
+            ),
+            _ if feed::item_pub_date(item, &atom_prefix).is_none() => issue(
+                Severity::Warning,
+                "missing_date",
+                item_index,
+                "The item has no publication date and is skipped unless ACCEPT_UNDATED_ITEMS is set"
+                    .to_string(),
+            ),
+            _ => {}
+        }
+
+        match non_empty(item.link()) {
+            None => issue(
+                Severity::Warning,
+                "missing_link",
+                item_index,
+                "The item has no link, its post won't have a link card".to_string(),
+            ),
+            Some(link) => match Url::parse(link) {
+                Ok(_) => {}
+                Err(url::ParseError::RelativeUrlWithoutBase) => issue(
+                    Severity::Warning,
+                    "relative_link",
+                    item_index,
+                    format!(
+                        "The link {:?} is relative and is resolved to {:?}",
+                        link,
+                        feed::item_link(item, &base_url).unwrap_or_default()
+                    ),
+                ),
+                Err(e) => issue(
+                    Severity::Error,
+                    "invalid_link",
+                    item_index,
+                    format!("The link {:?} is not a valid URL: {}", link, e),
+                ),
+            },
+        }
+
+        if let Some(description) = item.description() {
+            let length = description.chars().count();
+            if length > MAX_DESCRIPTION_CHARS {
+                issue(
+                    Severity::Warning,
+                    "oversized_description",
+                    item_index,
+                    format!(
+                        "The description is {} characters long, more than {}",
+                        length, MAX_DESCRIPTION_CHARS
+                    ),
+                );
+            }
+        }
+    }
+
+    report.title = Some(channel.title().trim().to_string()).filter(|t| !t.is_empty());
+    report.item_count = channel.items().len();
+    report
+}
+
+fn non_empty(value: Option<&str>) -> Option<&str> {
+    value.map(str::trim).filter(|v| !v.is_empty())
+}
+
+/// Returns the encoding named in the XML declaration, if there is one.
+fn declared_encoding(content: &[u8]) -> Option<String> {
+    let mut reader = Reader::from_reader(content);
+    let mut buf = Vec::new();
+    loop {
+        match reader.read_event_into(&mut buf).ok()? {
+            Event::Decl(decl) => {
+                let encoding = decl.encoding()?.ok()?;
+                return Some(String::from_utf8_lossy(&encoding).into_owned());
+            }
+            Event::Start(_) | Event::Empty(_) | Event::Eof => return None,
+            _ => {}
+        }
+        buf.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lint_feed() {
+        let content = format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<rss version="2.0"><channel><title>Example</title><link>https://example.com/</link>
+  <item><title>Fine</title><guid>1</guid><link>https://example.com/1</link><pubDate>Mon, 07 Oct 2024 10:00:00 GMT</pubDate></item>
+  <item><title>Duplicate</title><guid>1</guid><link>/2</link><pubDate>yesterday</pubDate></item>
+  <item><description>{}</description></item>
+</channel></rss>"#,
+            "x".repeat(MAX_DESCRIPTION_CHARS + 1)
+        );
+        let report = lint_feed("https://example.com/feed.xml", content.as_bytes());
+        assert_eq!(report.title.as_deref(), Some("Example"));
+        assert_eq!(report.item_count, 3);
+
+        let codes: Vec<(Option<usize>, &str)> =
+            report.issues.iter().map(|i| (i.item, i.code)).collect();
+        assert_eq!(
+            codes,
+            vec![
+                (Some(1), "duplicate_guid"),
+                (Some(1), "invalid_date"),
+                (Some(1), "relative_link"),
+                (Some(2), "missing_guid"),
+                (Some(2), "missing_date"),
+                (Some(2), "missing_link"),
+                (Some(2), "oversized_description"),
+            ]
+        );
+        assert!(report.has_errors());
+
+        let report = lint_feed("https://example.com/feed.xml", b"<html>\xff</html>");
+        let codes: Vec<&str> = report.issues.iter().map(|i| i.code).collect();
+        assert_eq!(codes, vec!["invalid_encoding", "unparseable"]);
+        assert_eq!(report.item_count, 0);
+    }
+}
@@ -0,0 +1,265 @@
+use crate::crosspost::{content_labels, item_text, TextSettings};
+use crate::http::USER_AGENT;
+use crate::models::ExecutionItem;
+use crate::poster::{PostResult, Poster};
+use crate::retry::backoff_delay;
+use crate::text_utils::sha256_hex;
+use anyhow::{anyhow, Context, Result};
+use aws_sdk_secretsmanager::Client as SecretsManagerClient;
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// The most characters a Mastodon instance accepts in a status, unless it's configured otherwise.
+pub const DEFAULT_MAX_STATUS_CHARS: usize = 500;
+/// Mastodon counts every link as this many characters, however long it is.
+pub const URL_CHARS: usize = 23;
+/// How many times a failed request is retried.
+const MAX_RETRIES: u32 = 3;
+/// Delay ceiling for the first retry.
+const RETRY_BASE_DELAY: Duration = Duration::from_secs(1);
+
+/// A new status, as posted to `/api/v1/statuses`.
+#[derive(Debug, Default, Serialize)]
+pub struct NewStatus {
+    pub status: String,
+    /// ISO 639 code of the language of the status.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+    /// A content warning, which hides the text behind it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub spoiler_text: Option<String>,
+    pub sensitive: bool,
+    /// `public`, `unlisted`, `private` or `direct`. The account's default when not set.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub visibility: Option<String>,
+}
+
+/// A posted status, as far as the bridge cares.
+#[derive(Debug, Deserialize)]
+pub struct Status {
+    pub id: String,
+    /// The web address of the status.
+    #[serde(default)]
+    pub url: Option<String>,
+}
+
+/// A client for the REST API of a Mastodon account.
+pub struct MastodonClient {
+    client: reqwest::Client,
+    instance_url: String,
+    access_token: String,
+}
+
+impl MastodonClient {
+    /// Creates a client.
+    ///
+    /// # Arguments
+    ///
+    /// * `instance_url` - The URL of the account's instance, e.g. `https://mastodon.social`.
+    /// * `access_token` - An access token of an application with the `write:statuses` scope.
+    pub fn new(instance_url: &str, access_token: &str) -> Result<Self> {
+        let client = reqwest::Client::builder().user_agent(USER_AGENT).build()?;
+        Ok(Self {
+            client,
+            instance_url: instance_url.trim_end_matches('/').to_string(),
+            access_token: access_token.to_string(),
+        })
+    }
+
+    /// Creates a client for the account whose credentials are stored in a Secrets Manager secret.
+    ///
+    /// # Arguments
+    ///
+    /// * `secrets_client` - The client to read the secret with.
+    /// * `secret_name` - Name or ARN of the secret, holding `{"instance_url": "...", "access_token": "..."}`.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the client, or an error if the secret can't be read.
+    pub async fn from_secret(
+        secrets_client: &SecretsManagerClient,
+        secret_name: &str,
+    ) -> Result<Self> {
+        let secret = secrets_client
+            .get_secret_value()
+            .secret_id(secret_name)
+            .send()
+            .await
+            .context("Failed to retrieve secret")?;
+
+        let secret_string = secret.secret_string().context("Secret string is empty")?;
+        let credentials: serde_json::Value =
+            serde_json::from_str(secret_string).context("Failed to parse secret JSON")?;
+
+        let instance_url = credentials["instance_url"]
+            .as_str()
+            .context("Instance URL not found in secret")?;
+        let access_token = credentials["access_token"]
+            .as_str()
+            .context("Access token not found in secret")?;
+        Self::new(instance_url, access_token)
+    }
+
+    /// Posts a status.
+    ///
+    /// Rate limited (429) and failed (5xx) requests are retried. The idempotency key makes the instance return the
+    /// status posted already instead of posting it again, should an earlier attempt have gone through after all.
+    ///
+    /// # Arguments
+    ///
+    /// * `status` - The status to post.
+    /// * `idempotency_key` - A key unique to the item, such as a hash of its guid.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the posted status, or an error if the instance refused it.
+    pub async fn post_status(&self, status: &NewStatus, idempotency_key: &str) -> Result<Status> {
+        let url = format!("{}/api/v1/statuses", self.instance_url);
+        let mut attempt = 0;
+        loop {
+            let response = self
+                .client
+                .post(&url)
+                .bearer_auth(&self.access_token)
+                .header("Idempotency-Key", idempotency_key)
+                .json(status)
+                .send()
+                .await
+                .with_context(|| format!("Failed to call {}", url))?;
+
+            let code = response.status();
+            if code.is_success() {
+                return response.json().await.context("Invalid status in response");
+            }
+            let retryable = code == StatusCode::TOO_MANY_REQUESTS || code.is_server_error();
+            if !retryable || attempt >= MAX_RETRIES {
+                let body = response.text().await.unwrap_or_default();
+                return Err(anyhow!("{} responded with {}: {}", url, code, body));
+            }
+
+            let delay = backoff_delay(RETRY_BASE_DELAY, attempt);
+            tracing::warn!(
+                "Mastodon request failed ({}), retrying in {:?} (attempt {} of {})",
+                code,
+                delay,
+                attempt + 1,
+                MAX_RETRIES
+            );
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+}
+
+/// Posts items to a Mastodon account, laid out like their Bluesky posts.
+pub struct MastodonPoster {
+    pub client: MastodonClient,
+    /// How statuses are written. Mastodon makes its own link card from the link in the text.
+    pub text: TextSettings,
+    /// The status length limit of the instance.
+    pub max_status_chars: usize,
+    /// Visibility of the statuses, or None for the account's default.
+    pub visibility: Option<String>,
+}
+
+/// A status of an item, ready to be posted.
+#[derive(Debug, Serialize)]
+pub struct StatusDraft {
+    pub status: NewStatus,
+    /// Derived from the guid of the item, so the status is only posted once however often it's sent.
+    pub idempotency_key: String,
+}
+
+impl Poster for MastodonPoster {
+    type Draft = StatusDraft;
+
+    fn network(&self) -> &'static str {
+        "Mastodon"
+    }
+
+    async fn prepare(&self, item: &ExecutionItem) -> Result<StatusDraft> {
+        let link = item.link.as_deref().context("Link not found in item")?;
+        let text = item_text(
+            item,
+            &self.text,
+            grapheme_budget(self.max_status_chars, link),
+        )?;
+
+        // Mastodon has no self-labels, so the posts of labelled feeds go behind a content warning naming them
+        let labels = content_labels(item);
+        Ok(StatusDraft {
+            status: NewStatus {
+                status: text.text,
+                language: text.language,
+                spoiler_text: (!labels.is_empty()).then(|| labels.join(", ")),
+                sensitive: !labels.is_empty(),
+                visibility: self.visibility.clone(),
+            },
+            idempotency_key: sha256_hex(item.guid.as_bytes()),
+        })
+    }
+
+    async fn publish(&self, draft: StatusDraft) -> Result<PostResult> {
+        let status = self
+            .client
+            .post_status(&draft.status, &draft.idempotency_key)
+            .await?;
+        Ok(PostResult {
+            id: Some(status.id),
+            url: status.url,
+            ..PostResult::default()
+        })
+    }
+}
+
+/// Counts the characters of a status the way Mastodon does: graphemes, with every link counting as [`URL_CHARS`].
+pub fn status_length(text: &str) -> usize {
+    text.split_inclusive(char::is_whitespace)
+        .map(|word| {
+            let trimmed = word.trim_end();
+            if trimmed.starts_with("https://") || trimmed.starts_with("http://") {
+                URL_CHARS + (word.len() - trimmed.len())
+            } else {
+                word.graphemes(true).count()
+            }
+        })
+        .sum()
+}
+
+/// Works out the grapheme limit that keeps a status carrying `link` within `max_chars` as Mastodon counts them.
+///
+/// Text is laid out by counting graphemes, which would count the link at its full length rather than as
+/// [`URL_CHARS`].
+pub fn grapheme_budget(max_chars: usize, link: &str) -> usize {
+    (max_chars + link.graphemes(true).count()).saturating_sub(URL_CHARS)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status_length() {
+        assert_eq!(status_length("Hello world"), 11);
+        assert_eq!(
+            status_length("Read this: https://example.com/a/very/long/path/to/an/article"),
+            11 + URL_CHARS
+        );
+        assert_eq!(status_length("https://a.co\n\n#rust"), URL_CHARS + 2 + 5);
+        assert_eq!(status_length("Café 👩‍👩‍👧"), 6);
+    }
+
+    #[test]
+    fn test_grapheme_budget() {
+        let link = "https://example.com/a/very/long/path/to/an/article";
+        let budget = grapheme_budget(DEFAULT_MAX_STATUS_CHARS, link);
+        let text = format!("{}\n\n{}", "a".repeat(budget - link.len() - 2), link);
+        assert_eq!(text.graphemes(true).count(), budget);
+        assert_eq!(status_length(&text), DEFAULT_MAX_STATUS_CHARS);
+
+        // Short links count as more than they are
+        assert_eq!(grapheme_budget(500, "https://a.co"), 489);
+    }
+}
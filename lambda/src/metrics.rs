@@ -0,0 +1,101 @@
+use serde_json::{json, Map, Value};
+
+/// The CloudWatch namespace the bridge's metrics are published in.
+pub const METRICS_NAMESPACE: &str = "RssBlueskyBridge";
+
+/// A value of a metric, with its CloudWatch unit, e.g. `Count`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Metric<'a> {
+    pub name: &'a str,
+    pub value: f64,
+    pub unit: &'a str,
+}
+
+/// Writes a record in the CloudWatch embedded metric format (EMF). Logged as a line of its own, it makes CloudWatch
+/// publish its metrics, without the lambda calling PutMetricData.
+///
+/// # Arguments
+///
+/// * `dimensions` - The dimensions of the metrics, e.g. `[("Feed", "https://example.com/feed.xml")]`.
+/// * `properties` - Further values the record carries for searching the logs, which aren't dimensions.
+/// * `metrics` - The metrics.
+/// * `timestamp_millis` - When the values were measured, as a Unix timestamp in milliseconds.
+///
+/// # Returns
+///
+/// The record, to be logged as a single line of JSON.
+pub fn emf_record(
+    dimensions: &[(&str, &str)],
+    properties: &[(&str, &str)],
+    metrics: &[Metric],
+    timestamp_millis: i64,
+) -> Value {
+    let mut record = Map::new();
+    record.insert(
+        "_aws".to_string(),
+        json!({
+            "Timestamp": timestamp_millis,
+            "CloudWatchMetrics": [{
+                "Namespace": METRICS_NAMESPACE,
+                "Dimensions": [dimensions.iter().map(|(name, _)| *name).collect::<Vec<_>>()],
+                "Metrics": metrics
+                    .iter()
+                    .map(|metric| json!({"Name": metric.name, "Unit": metric.unit}))
+                    .collect::<Vec<_>>(),
+            }],
+        }),
+    );
+    for (name, value) in dimensions.iter().chain(properties) {
+        record.insert(name.to_string(), json!(value));
+    }
+    for metric in metrics {
+        record.insert(metric.name.to_string(), json!(metric.value));
+    }
+    Value::Object(record)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_emf_record() {
+        let record = emf_record(
+            &[("Feed", "https://example.com/feed.xml")],
+            &[("Model", "anthropic.claude-3-haiku-20240307-v1:0")],
+            &[
+                Metric {
+                    name: "InputTokens",
+                    value: 120.0,
+                    unit: "Count",
+                },
+                Metric {
+                    name: "OutputTokens",
+                    value: 40.0,
+                    unit: "Count",
+                },
+            ],
+            1_700_000_000_000,
+        );
+        assert_eq!(
+            record,
+            json!({
+                "_aws": {
+                    "Timestamp": 1_700_000_000_000i64,
+                    "CloudWatchMetrics": [{
+                        "Namespace": "RssBlueskyBridge",
+                        "Dimensions": [["Feed"]],
+                        "Metrics": [
+                            {"Name": "InputTokens", "Unit": "Count"},
+                            {"Name": "OutputTokens", "Unit": "Count"}
+                        ]
+                    }]
+                },
+                "Feed": "https://example.com/feed.xml",
+                "Model": "anthropic.claude-3-haiku-20240307-v1:0",
+                "InputTokens": 120.0,
+                "OutputTokens": 40.0
+            })
+        );
+    }
+}
@@ -1,6 +1,7 @@
 use anyhow::{anyhow, Result};
 use chrono::Utc;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
 #[derive(Deserialize, Serialize, Clone, Debug)]
 pub struct ItemIdentifier {
@@ -12,7 +13,7 @@ pub struct ItemIdentifier {
 ///
 /// This struct contains all the fields associated with an execution item,
 /// including metadata and content-related information. An execution item is one item that we got from the RSS feed that we want to process further via other lambdas, such as summarize the description and post to bluesky. We temporarily store this in ddb so that, we don't have to pass huge data as input/output in state functions.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct ExecutionItem {
     /// Each step function execution has a unique execution id. This becomes the PK of the record
     pub execution_id: String,
@@ -32,6 +33,64 @@ pub struct ExecutionItem {
     pub _type: Option<String>,
     /// Publication date of the RSS item.
     pub pub_date: Option<String>,
+    /// URL of the feed this item was fetched from. Lets a single execution serve several feeds.
+    pub feed_url: Option<String>,
+    /// Values of the `<category>` elements of the RSS item.
+    #[serde(default)]
+    pub categories: Vec<String>,
+    /// URL of the item's main image, from Media RSS, an image enclosure or the podcast artwork.
+    pub image_url: Option<String>,
+    /// Podcast episode subtitle (`itunes:subtitle`).
+    pub subtitle: Option<String>,
+    /// Podcast episode number (`itunes:episode`).
+    pub episode: Option<String>,
+    /// Podcast season number (`itunes:season`).
+    pub season: Option<String>,
+    /// Name of the writer of the item, from `dc:creator`, `<author>` or an Atom author/contributor.
+    pub author: Option<String>,
+    /// Title of the feed the item came from, for attributing posts when several feeds are bridged.
+    pub feed_title: Option<String>,
+    /// URL of the website of the feed the item came from.
+    pub site_url: Option<String>,
+    /// Main text of the linked article, extracted from its web page when article extraction is enabled.
+    pub article_text: Option<String>,
+    /// URLs of the images attached to the RSS item, collected only for feeds that post images. When set, the post
+    /// shows the images instead of a link card.
+    #[serde(default)]
+    pub image_urls: Vec<String>,
+    /// URL of the item's MP4 video enclosure, if it's small enough to post. When set, the post shows the video instead
+    /// of the images or the link card.
+    pub video_url: Option<String>,
+    /// Whether the post has the link in its text rather than a link card, copied from its feed's FeedConfig.
+    #[serde(default)]
+    pub text_only: bool,
+    /// Emoji or label the post starts with, copied from its feed's FeedConfig.
+    pub prefix: Option<String>,
+    /// Hashtags added to every post from the item's feed, copied from its FeedConfig.
+    #[serde(default)]
+    pub hashtags: Vec<String>,
+    /// Language of the posts from the item's feed, copied from its FeedConfig.
+    pub language: Option<String>,
+    /// Self-labels of the posts from the item's feed, copied from its FeedConfig.
+    #[serde(default)]
+    pub labels: Vec<String>,
+    /// Secret with the credentials of the Bluesky account the item's feed posts to, copied from its FeedConfig.
+    pub bluesky_secret: Option<String>,
+    /// Who may reply to the posts from the item's feed, copied from its FeedConfig.
+    pub reply_control: Option<String>,
+    /// Settings the summary of the item is written with, copied from its feed's FeedConfig.
+    pub ai_max_tokens: Option<u32>,
+    pub ai_temperature: Option<f32>,
+    pub ai_top_p: Option<f32>,
+    /// Hash of the title and description as the feed gave them, to recognise a posted item whose content changed.
+    pub content_hash: Option<String>,
+    /// The at:// URI of the post of an earlier version of the item. Set by check-dynamodb when an item that was
+    /// posted already is posted again because its content changed.
+    pub update_of_uri: Option<String>,
+    /// The CID of the post of the earlier version of the item.
+    pub update_of_cid: Option<String>,
+    /// Guid of a recently posted item about the same story, whose post this item's post quotes.
+    pub quote_guid: Option<String>,
 }
 
 impl ExecutionItem {
@@ -72,6 +131,7 @@ impl ExecutionItem {
                 ttl: Some(ttl.timestamp()),
                 _type: Some("ExecutionItem".to_string()),
                 pub_date,
+                ..Default::default()
             })
         }
     }
@@ -79,11 +139,30 @@ impl ExecutionItem {
 
 /// Represents a RSS item that we have already published to bluesky stored in the DynamoDB table for deduping.
 ///
-/// This struct contains just the guid field necessary to identify whether an RSS item has already been published or not. We specifically don't want to include all metadata here to save cost. This has an infinite TTL compared to the extremely short TTL of ExecutionItem.
+/// This struct contains the guid field necessary to identify whether an RSS item has already been published or not, and where the post is, for deleting or editing it later. We specifically don't want to include all metadata here to save cost. This has an infinite TTL compared to the extremely short TTL of ExecutionItem.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RecordItem {
     /// Globally unique identifier for the record item.
     pub guid: String,
+    /// The at:// URI of the Bluesky post. Not set for items recorded before it was stored.
+    pub uri: Option<String>,
+    /// The CID of the Bluesky post, which together with the URI makes a strong reference to it.
+    pub cid: Option<String>,
+    /// Unix timestamp of when the item was posted.
+    pub posted_at: Option<i64>,
+    /// Unix timestamp of when the post was deleted from Bluesky. The RecordItem is kept, so the item isn't posted
+    /// again.
+    pub deleted_at: Option<i64>,
+    /// Hash of the title and description of the posted version of the item. Not set for items recorded before it was
+    /// stored, which are never taken for updated.
+    pub content_hash: Option<String>,
+    /// The name of the template variant the post was laid out with, for posts made during a template experiment.
+    pub template_variant: Option<String>,
+    /// Execution id of the execution posting the item, set while it's claimed. Recording the post finalizes the
+    /// RecordItem, which drops the claim.
+    pub claimed_by: Option<String>,
+    /// Unix timestamp of when the item was claimed.
+    pub claimed_at: Option<i64>,
     /// Type identifier for the item, always set to "RecordItem".
     pub _type: Option<String>,
 }
@@ -102,8 +181,318 @@ impl RecordItem {
         } else {
             Ok(Self {
                 guid,
+                uri: None,
+                cid: None,
+                posted_at: None,
+                deleted_at: None,
+                content_hash: None,
+                template_variant: None,
+                claimed_by: None,
+                claimed_at: None,
                 _type: Some("RecordItem".to_string()),
             })
         }
     }
 }
+
+/// How long a claim on an item holds. An execution that failed to post a claimed item leaves the claim behind, which
+/// a later execution takes over once it's this old.
+pub const CLAIM_TIMEOUT_SECS: i64 = 3600;
+
+/// An item that was recently posted, kept for a few days to recognise the same story coming in through another feed.
+///
+/// RecordItems only dedupe by guid, which differs between feeds syndicating the same story. All RecentPost items
+/// share the same PK, and the SK starts with the time of posting, so the posts of the last hours can be queried as a
+/// key range.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RecentPost {
+    /// Unix timestamp of when the item was posted.
+    pub posted_at: i64,
+    /// Identity of the item, as in its RecordItem.
+    pub guid: String,
+    /// Title of the item.
+    pub title: Option<String>,
+    /// Link of the item.
+    pub link: Option<String>,
+    /// URL of the feed the item came from.
+    pub feed_url: Option<String>,
+    /// Secret of the Bluesky account the item was posted to, None for that of the deployment.
+    pub account: Option<String>,
+    /// Time-to-live value for DynamoDB, in Unix timestamp format.
+    pub ttl: Option<i64>,
+}
+
+/// An item waiting to be posted, because it was fetched outside the posting window or its account had reached its
+/// daily post cap.
+///
+/// Its ExecutionItem stays where it is, under the execution that fetched it, with its TTL extended for as long as it's
+/// held. All HeldItems share the same PK, with the
+/// guid as the SK, so an item is only held once.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HeldItem {
+    /// Execution id of the ExecutionItem.
+    pub execution_id: String,
+    /// Globally unique identifier of the item.
+    pub guid: String,
+    /// Secret of the Bluesky account the item goes to, None for that of the deployment.
+    pub account: Option<String>,
+    /// Unix timestamp of when the item was first held. Held items are posted oldest first.
+    pub held_at: i64,
+}
+
+/// How a post was doing at one point in time, as sampled by collect-engagement.
+///
+/// The samples of a post make up the time series of its engagement. All EngagementSample items share the same PK,
+/// and the SK starts with the time of sampling, so the samples of the last days can be queried as a key range.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EngagementSample {
+    /// Unix timestamp of when the post was sampled.
+    pub sampled_at: i64,
+    /// Identity of the posted item, as in its RecordItem.
+    pub guid: String,
+    /// The at:// URI of the post.
+    pub uri: String,
+    /// URL of the feed the item came from. None for items posted in a digest, which aren't tied to a feed.
+    pub feed_url: Option<String>,
+    /// Secret of the Bluesky account the item was posted to, None for that of the deployment.
+    pub account: Option<String>,
+    /// The template variant the post was laid out with, during a template experiment.
+    pub template_variant: Option<String>,
+    /// Unix timestamp of when the item was posted.
+    pub posted_at: i64,
+    pub likes: i64,
+    pub reposts: i64,
+    pub quotes: i64,
+    pub replies: i64,
+    /// Time-to-live value for DynamoDB, in Unix timestamp format.
+    pub ttl: Option<i64>,
+}
+
+impl EngagementSample {
+    /// The likes, reposts, quotes and replies of the post together.
+    pub fn engagement(&self) -> i64 {
+        self.likes + self.reposts + self.quotes + self.replies
+    }
+}
+
+/// A blob uploaded for an image, cached so the next post showing the image, such as a site logo used as the thumbnail
+/// of every link card, doesn't fetch and upload it again.
+///
+/// Blobs belong to the account that uploaded them, so they are cached per account. All CachedBlob items share the
+/// same PK, and the SK is the DID of the account followed by the hash of the image URL.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CachedBlob {
+    /// DID of the account the blob was uploaded to.
+    pub did: String,
+    /// SHA-256 of the image URL, in hex.
+    pub url_hash: String,
+    /// The blob reference, as JSON.
+    pub blob: String,
+    /// Dimensions of the image, for the aspect ratio of embedded images.
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    /// The alt text written for the image, when it was posted as an embedded image.
+    pub alt: Option<String>,
+    /// Time-to-live value for DynamoDB, in Unix timestamp format. Cached blobs past it aren't used, even before
+    /// DynamoDB gets to deleting them.
+    pub ttl: Option<i64>,
+}
+
+/// A summary a model wrote, cached so items with the same description, such as those of a re-run or retried
+/// execution or one posted by several feeds, don't have Bedrock summarize it again.
+///
+/// All CachedSummary items share the same PK, and the SK is the hash of the prompt, which has the description in it
+/// along with the length and language the summary is asked for, together with the models and settings it's written
+/// with, so summaries aren't reused once those change.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CachedSummary {
+    /// SHA-256 of the prompt, the models and their settings, in hex, see
+    /// [`summary_cache_key`](crate::summarize::summary_cache_key).
+    pub prompt_hash: String,
+    pub summary: String,
+    /// Time-to-live value for DynamoDB, in Unix timestamp format. Cached summaries past it aren't used, even before
+    /// DynamoDB gets to deleting them.
+    pub ttl: Option<i64>,
+}
+
+/// Per feed bookkeeping stored in the DynamoDB table.
+///
+/// Unlike ExecutionItem this outlives a single execution, so get_rss_items can remember what it saw on the previous
+/// cron tick. There is one FeedState per feed URL.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FeedState {
+    /// URL of the feed as configured. This becomes the PK of the record.
+    pub feed_url: String,
+    /// SHA-256 of the feed body from the last successful fetch.
+    pub content_hash: Option<String>,
+    /// URL the feed document is fetched from instead of the configured one: the feed linked from an HTML page the
+    /// feed was configured with, or the location a feed has permanently moved to.
+    pub resolved_url: Option<String>,
+    /// Unix timestamp of the newest publication date among the items posted from this feed. Items older than this
+    /// are not considered again, regardless of MAX_AGE_HOURS.
+    pub high_water_mark: Option<i64>,
+    /// Unix timestamp of the last fetch of a feed with its own polling interval.
+    pub last_polled: Option<i64>,
+    /// Type identifier for the item, always set to "FeedState".
+    pub _type: Option<String>,
+}
+
+/// Represents a feed registered in the DynamoDB table.
+///
+/// Feeds can be configured through the FEED_URL / FEED_URLS environment variables or stored as FeedConfig items
+/// (e.g. via the import-opml binary). get_rss_items processes both. All FeedConfig items share the same PK so they
+/// can be listed with a single query, and the feed URL is the SK.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FeedConfig {
+    /// URL of the feed.
+    pub feed_url: String,
+    /// Human readable title of the feed.
+    pub title: Option<String>,
+    /// URL of the website the feed belongs to.
+    pub site_url: Option<String>,
+    /// Only items with at least one of these categories are processed. Empty means all categories.
+    #[serde(default)]
+    pub include_categories: Vec<String>,
+    /// Items with any of these categories are skipped.
+    #[serde(default)]
+    pub exclude_categories: Vec<String>,
+    /// Source specific clean-up of the feed's items, e.g. `reddit`. When not set it's detected from the feed URL;
+    /// `none` turns detection off.
+    pub preprocessor: Option<String>,
+    /// How often the feed is fetched, in minutes. Runs in between skip it. When not set the feed is fetched on
+    /// every run of the schedule.
+    pub poll_interval_minutes: Option<u32>,
+    /// Name or ARN of a Secrets Manager secret with the credentials the feed requires, as JSON: either
+    /// `{"username": "...", "password": "..."}` for basic auth or `{"token": "..."}` for a bearer token.
+    pub credentials_secret: Option<String>,
+    /// What the feed URL points to: `rss` (the default) for an RSS feed, `scrape` for a web page listing articles,
+    /// `json` for a JSON API, `sitemap` for a sitemap or `ical` for an iCalendar (`.ics`) calendar.
+    pub feed_type: Option<String>,
+    /// How to find the fields of the items in sources that aren't RSS feeds, e.g. CSS selectors for `scrape` feeds or
+    /// JSONPath expressions for `json` feeds.
+    #[serde(default)]
+    pub selectors: HashMap<String, String>,
+    /// Whether posts show the images attached to the items (Media RSS images or an image enclosure) instead of a link
+    /// card. Suits photo feeds, where the image is the content. Items without images still get a link card.
+    #[serde(default)]
+    pub embed_images: bool,
+    /// Whether posts have the link in their text rather than a link card, for a more compact look or links that
+    /// make poor cards. Images and videos are still shown for feeds that post them.
+    #[serde(default)]
+    pub text_only: bool,
+    /// Emoji or short label the posts from this feed start with, e.g. `📰` or `[Lobsters]`, telling sources apart on
+    /// an account that posts several. Placed by the `{prefix}` field of the post template.
+    pub prefix: Option<String>,
+    /// Hashtags added to every post from this feed, with or without the `#`, e.g. `["rust", "programming"]`. They
+    /// replace the POST_HASHTAGS of the deployment.
+    #[serde(default)]
+    pub hashtags: Vec<String>,
+    /// Language of the posts from this feed as a BCP 47 tag, e.g. `en` or `pt-BR`, for feeds whose language
+    /// detection gets wrong. When not set it's detected from the title and summary of each post.
+    pub language: Option<String>,
+    /// Self-labels the posts from this feed carry, e.g. `["graphic-media"]`, so Bluesky hides or blurs them for
+    /// readers who asked for that. Bluesky knows `sexual`, `nudity`, `porn` and `graphic-media`.
+    #[serde(default)]
+    pub labels: Vec<String>,
+    /// Name or ARN of a Secrets Manager secret with the `{"username": "...", "password": "..."}` of the Bluesky
+    /// account this feed posts to, so one deployment can post different feeds to different accounts. When not set
+    /// the feed posts to the deployment's account.
+    pub bluesky_secret: Option<String>,
+    /// Who may reply to the posts from this feed: `everyone`, `nobody`, `followers`, `following` (accounts the
+    /// posting account follows) or `mentioned` (accounts mentioned in the post). When not set the REPLY_CONTROL of
+    /// the deployment applies.
+    pub reply_control: Option<String>,
+    /// Whether the best-performing posts from this feed are reposted at peak hours, overriding REPROMOTE_POSTS.
+    pub repromote: Option<bool>,
+    /// The most tokens the summaries of this feed's items are written with, overriding AI_MAX_TOKENS.
+    pub ai_max_tokens: Option<u32>,
+    /// The temperature the summaries of this feed's items are written with, between 0 and 1, overriding
+    /// AI_TEMPERATURE. Higher values vary the wording more, lower ones keep it to the text.
+    pub ai_temperature: Option<f32>,
+    /// The top_p the summaries of this feed's items are written with, between 0 and 1, overriding AI_TOP_P.
+    pub ai_top_p: Option<f32>,
+    /// Type identifier for the item, always set to "FeedConfig".
+    pub _type: Option<String>,
+}
+
+/// The kinds of sources a FeedConfig can point to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FeedType {
+    /// An RSS 2.0 or RSS 1.0 (RDF) feed, or an HTML page advertising one.
+    Rss,
+    /// A web page listing articles, turned into a feed with the FeedConfig's CSS selectors.
+    Scrape,
+    /// A JSON API, turned into a feed with the FeedConfig's JSONPath expressions.
+    Json,
+    /// A sitemap or sitemap index, with an item for every recently modified page.
+    Sitemap,
+    /// An iCalendar (`.ics`) calendar, with an item for every upcoming event.
+    Ical,
+}
+
+/// Marks the posts of items that changed since they were first posted.
+pub const UPDATE_PREFIX: &str = "Updated: ";
+
+/// What to do when a feed re-emits an item that was posted already, with a changed title or description.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum UpdateMode {
+    /// Skip it, like any other item that was posted already.
+    #[default]
+    Ignore,
+    /// Post it again, marked as updated.
+    Post,
+    /// Post it again, marked as updated, as a reply to the earlier post.
+    Reply,
+}
+
+impl UpdateMode {
+    /// Parses an update mode: `ignore`, `post` or `reply`, in any case.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the UpdateMode, or an error if the mode is unknown.
+    pub fn parse(mode: &str) -> Result<Self> {
+        match mode.trim().to_lowercase().as_str() {
+            "" | "ignore" => Ok(UpdateMode::Ignore),
+            "post" => Ok(UpdateMode::Post),
+            "reply" => Ok(UpdateMode::Reply),
+            other => Err(anyhow!("Unknown update mode {:?}", other)),
+        }
+    }
+}
+
+impl FeedConfig {
+    /// Returns the FeedType of the feed, or an error if the configured feed_type is unknown.
+    pub fn feed_type(&self) -> Result<FeedType> {
+        match self
+            .feed_type
+            .as_deref()
+            .map(|t| t.trim().to_lowercase())
+            .as_deref()
+        {
+            None | Some("") | Some("rss") => Ok(FeedType::Rss),
+            Some("scrape") => Ok(FeedType::Scrape),
+            Some("json") => Ok(FeedType::Json),
+            Some("sitemap") => Ok(FeedType::Sitemap),
+            Some("ical") => Ok(FeedType::Ical),
+            Some(other) => Err(anyhow!("Unknown feed type {:?}", other)),
+        }
+    }
+
+    /// Creates a new FeedConfig for the given feed URL with no optional settings.
+    ///
+    /// # Returns
+    ///
+    /// A new instance of FeedConfig.
+    pub fn new(feed_url: String) -> Result<Self> {
+        if feed_url.trim().is_empty() {
+            Err(anyhow!("Feed URL cannot be empty"))
+        } else {
+            Ok(Self {
+                feed_url: feed_url.trim().to_string(),
+                _type: Some("FeedConfig".to_string()),
+                ..Default::default()
+            })
+        }
+    }
+}
@@ -0,0 +1,265 @@
+use crate::crosspost::{content_labels, item_text, TextSettings};
+use crate::models::ExecutionItem;
+use crate::poster::{PostResult, Poster};
+use anyhow::{anyhow, Context, Result};
+use futures::future::join_all;
+use futures::{SinkExt, StreamExt};
+use k256::schnorr::SigningKey;
+use rand::RngCore;
+use serde::Serialize;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::time::Duration;
+use tokio_tungstenite::tungstenite::Message;
+
+/// The kind of short text notes (NIP-01).
+pub const KIND_TEXT_NOTE: u32 = 1;
+/// Notes have no length limit, but clients fold long ones, so they are kept to this many graphemes by default.
+pub const DEFAULT_MAX_NOTE_GRAPHEMES: usize = 1000;
+/// How long a relay gets to take an event, connecting included.
+const RELAY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The key a Nostr account signs its events with.
+pub struct NostrKeys {
+    signing_key: SigningKey,
+}
+
+impl NostrKeys {
+    /// Reads a secret key, given as an `nsec1...` string (NIP-19) or as 64 hex digits.
+    pub fn parse(secret_key: &str) -> Result<Self> {
+        let secret_key = secret_key.trim();
+        let bytes = if secret_key.starts_with("nsec1") {
+            let (hrp, bytes) = bech32::decode(secret_key).context("Invalid nsec key")?;
+            if hrp.as_str() != "nsec" {
+                return Err(anyhow!("Invalid nsec key"));
+            }
+            bytes
+        } else {
+            hex::decode(secret_key).context("Invalid secret key, expected nsec or hex")?
+        };
+        let signing_key =
+            SigningKey::from_bytes(&bytes).map_err(|_| anyhow!("Invalid secret key"))?;
+        Ok(Self { signing_key })
+    }
+
+    /// Returns the public key of the account, as hex.
+    pub fn public_key(&self) -> String {
+        hex::encode(self.signing_key.verifying_key().to_bytes())
+    }
+}
+
+/// A signed Nostr event.
+#[derive(Debug, Clone, Serialize)]
+pub struct Event {
+    pub id: String,
+    pub pubkey: String,
+    pub created_at: i64,
+    pub kind: u32,
+    pub tags: Vec<Vec<String>>,
+    pub content: String,
+    pub sig: String,
+}
+
+impl Event {
+    /// Creates and signs a text note.
+    ///
+    /// # Arguments
+    ///
+    /// * `keys` - The key of the account posting the note.
+    /// * `content` - The text of the note.
+    /// * `tags` - The tags of the note, such as `["t", "rust"]` for a hashtag.
+    /// * `created_at` - Unix time the note is dated.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the event, or an error if it can't be signed.
+    pub fn text_note(
+        keys: &NostrKeys,
+        content: String,
+        tags: Vec<Vec<String>>,
+        created_at: i64,
+    ) -> Result<Self> {
+        let pubkey = keys.public_key();
+        // The id is the hash of the event serialized as NIP-01 prescribes, which is serde_json's compact output
+        let serialized = json!([0, pubkey, created_at, KIND_TEXT_NOTE, tags, content]).to_string();
+        let id = Sha256::digest(serialized.as_bytes());
+
+        let mut aux_rand = [0u8; 32];
+        rand::thread_rng().fill_bytes(&mut aux_rand);
+        let sig = keys
+            .signing_key
+            .sign_raw(&id, &aux_rand)
+            .map_err(|_| anyhow!("Failed to sign event"))?;
+
+        Ok(Self {
+            id: hex::encode(id),
+            pubkey,
+            created_at,
+            kind: KIND_TEXT_NOTE,
+            tags,
+            content,
+            sig: hex::encode(sig.to_bytes()),
+        })
+    }
+}
+
+/// Sends an event to a relay and waits for it to accept it (NIP-20).
+///
+/// # Arguments
+///
+/// * `relay` - The WebSocket URL of the relay, e.g. `wss://relay.damus.io`.
+/// * `event` - The event to publish.
+///
+/// # Returns
+///
+/// A Result indicating success, or an error if the relay can't be reached, rejects the event or doesn't answer in
+/// time.
+pub async fn publish(relay: &str, event: &Event) -> Result<()> {
+    tokio::time::timeout(RELAY_TIMEOUT, async {
+        let (mut socket, _) = tokio_tungstenite::connect_async(relay)
+            .await
+            .context("Failed to connect")?;
+        socket
+            .send(Message::Text(json!(["EVENT", event]).to_string()))
+            .await
+            .context("Failed to send event")?;
+
+        while let Some(message) = socket.next().await {
+            let Message::Text(text) = message.context("Failed to read from relay")? else {
+                continue;
+            };
+            let Ok(reply) = serde_json::from_str::<serde_json::Value>(&text) else {
+                continue;
+            };
+            if reply[0] == "OK" && reply[1] == event.id.as_str() {
+                let _ = socket.close(None).await;
+                return match reply[2].as_bool() {
+                    Some(true) => Ok(()),
+                    _ => Err(anyhow!(
+                        "Event rejected: {}",
+                        reply[3].as_str().unwrap_or_default()
+                    )),
+                };
+            }
+        }
+        Err(anyhow!("Relay closed the connection without an answer"))
+    })
+    .await
+    .map_err(|_| anyhow!("Relay did not answer in time"))?
+    .with_context(|| format!("Failed to publish to {}", relay))
+}
+
+/// Posts items to a Nostr account as text notes, laid out like their Bluesky posts, published to several relays.
+pub struct NostrPoster {
+    pub keys: NostrKeys,
+    /// The WebSocket URLs of the relays notes are published to.
+    pub relays: Vec<String>,
+    /// How notes are written. They carry the link in their text.
+    pub text: TextSettings,
+    pub max_note_graphemes: usize,
+}
+
+impl Poster for NostrPoster {
+    type Draft = Event;
+
+    fn network(&self) -> &'static str {
+        "Nostr"
+    }
+
+    async fn prepare(&self, item: &ExecutionItem) -> Result<Event> {
+        let text = item_text(item, &self.text, self.max_note_graphemes)?;
+
+        // Hashtags are tagged for relay searches (NIP-24), the link as a reference, and the labels of the feed as a
+        // content warning (NIP-36)
+        let mut tags: Vec<Vec<String>> = text
+            .hashtags
+            .iter()
+            .map(|tag| vec!["t".to_string(), tag.trim_start_matches('#').to_lowercase()])
+            .collect();
+        if let Some(link) = &item.link {
+            tags.push(vec!["r".to_string(), link.clone()]);
+        }
+        let labels = content_labels(item);
+        if !labels.is_empty() {
+            tags.push(vec!["content-warning".to_string(), labels.join(", ")]);
+        }
+
+        Event::text_note(&self.keys, text.text, tags, chrono::Utc::now().timestamp())
+    }
+
+    /// Publishes the note to all relays at once. It fails only when none of them takes it.
+    async fn publish(&self, note: Event) -> Result<PostResult> {
+        let results = join_all(self.relays.iter().map(|relay| publish(relay, &note))).await;
+        let mut accepted = Vec::new();
+        for (relay, result) in self.relays.iter().zip(results) {
+            match result {
+                Ok(()) => accepted.push(relay.as_str()),
+                Err(e) => tracing::warn!("Relay {} did not take the note: {:?}", relay, e),
+            }
+        }
+        if accepted.is_empty() {
+            return Err(anyhow!("No relay took note {}", note.id));
+        }
+        tracing::info!("Relays {:?} took note {}", accepted, note.id);
+
+        Ok(PostResult {
+            id: Some(note.id),
+            ..PostResult::default()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use k256::schnorr::{Signature, VerifyingKey};
+
+    /// The secret key of BIP-340 test vector 0.
+    const SECRET_KEY: &str = "0000000000000000000000000000000000000000000000000000000000000003";
+
+    #[test]
+    fn test_parse_keys() {
+        let keys = NostrKeys::parse(SECRET_KEY).unwrap();
+        assert_eq!(
+            keys.public_key(),
+            "f9308a019258c31049344f85f89d5229b531c845836f99b08601f113bce036f9"
+        );
+
+        let nsec = bech32::encode::<bech32::Bech32>(
+            bech32::Hrp::parse("nsec").unwrap(),
+            &hex::decode(SECRET_KEY).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            NostrKeys::parse(&nsec).unwrap().public_key(),
+            keys.public_key()
+        );
+
+        assert!(NostrKeys::parse("not a key").is_err());
+        assert!(NostrKeys::parse(&"00".repeat(32)).is_err());
+    }
+
+    #[test]
+    fn test_text_note() {
+        let keys = NostrKeys::parse(SECRET_KEY).unwrap();
+        let event = Event::text_note(
+            &keys,
+            "Hello \"world\"\nhttps://example.com".to_string(),
+            vec![vec!["t".to_string(), "rust".to_string()]],
+            1_700_000_000,
+        )
+        .unwrap();
+        assert_eq!(event.kind, 1);
+
+        let serialized = format!(
+            r#"[0,"{}",1700000000,1,[["t","rust"]],"Hello \"world\"\nhttps://example.com"]"#,
+            keys.public_key()
+        );
+        let id = Sha256::digest(serialized.as_bytes());
+        assert_eq!(event.id, hex::encode(id));
+
+        let verifying_key = VerifyingKey::from_bytes(&hex::decode(&event.pubkey).unwrap()).unwrap();
+        let signature = Signature::try_from(hex::decode(&event.sig).unwrap().as_slice()).unwrap();
+        assert!(verifying_key.verify_raw(&id, &signature).is_ok());
+    }
+}
@@ -0,0 +1,526 @@
+use crate::http::USER_AGENT;
+use anyhow::{anyhow, Context, Result};
+use atrium_api::xrpc::http::{HeaderMap, HeaderValue, Request, Response, StatusCode};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use openssl::bn::{BigNum, BigNumContext};
+use openssl::ec::{EcGroup, EcKey};
+use openssl::ecdsa::EcdsaSig;
+use openssl::nid::Nid;
+use openssl::pkey::Private;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::sync::Mutex;
+
+/// The scope the bridge asks for: the account's identity, plus the access app passwords have.
+pub const SCOPE: &str = "atproto transition:generic";
+/// Access tokens with less time than this left are refreshed first, so they don't expire in the middle of a run.
+const MIN_ACCESS_TOKEN_SECS: i64 = 300;
+
+/// The P-256 key the DPoP proofs of an OAuth session are signed with (RFC 9449).
+///
+/// Bluesky binds the tokens it issues to this key, so it's stored along with them.
+pub struct DpopKey {
+    key: EcKey<Private>,
+    jwk: serde_json::Value,
+}
+
+impl DpopKey {
+    /// Generates a new key, for a new session.
+    pub fn generate() -> Result<Self> {
+        let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1)?;
+        Self::new(EcKey::generate(&group)?)
+    }
+
+    /// Reads a key stored with [`DpopKey::to_pem`].
+    pub fn from_pem(pem: &str) -> Result<Self> {
+        let key = EcKey::private_key_from_pem(pem.as_bytes()).context("Invalid DPoP key")?;
+        Self::new(key)
+    }
+
+    /// Writes the key as PEM.
+    pub fn to_pem(&self) -> Result<String> {
+        Ok(String::from_utf8(self.key.private_key_to_pem()?)?)
+    }
+
+    fn new(key: EcKey<Private>) -> Result<Self> {
+        let mut x = BigNum::new()?;
+        let mut y = BigNum::new()?;
+        let mut ctx = BigNumContext::new()?;
+        key.public_key()
+            .affine_coordinates(key.group(), &mut x, &mut y, &mut ctx)?;
+        let jwk = json!({
+            "kty": "EC",
+            "crv": "P-256",
+            "x": URL_SAFE_NO_PAD.encode(x.to_vec_padded(32)?),
+            "y": URL_SAFE_NO_PAD.encode(y.to_vec_padded(32)?),
+        });
+        Ok(Self { key, jwk })
+    }
+
+    /// Signs the DPoP proof of a request.
+    ///
+    /// # Arguments
+    ///
+    /// * `method` - The HTTP method of the request.
+    /// * `url` - The URL of the request. Its query and fragment are left out of the proof.
+    /// * `nonce` - The last nonce the server sent, if it sent one.
+    /// * `access_token` - The access token the request carries, for requests to the PDS.
+    /// * `now` - The current Unix time.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the proof, a JWT for the `DPoP` header.
+    pub fn proof(
+        &self,
+        method: &str,
+        url: &str,
+        nonce: Option<&str>,
+        access_token: Option<&str>,
+        now: i64,
+    ) -> Result<String> {
+        let mut htu = url::Url::parse(url).with_context(|| format!("Invalid URL {}", url))?;
+        htu.set_query(None);
+        htu.set_fragment(None);
+
+        let mut jti = [0u8; 16];
+        rand::thread_rng().fill_bytes(&mut jti);
+
+        let header = json!({"typ": "dpop+jwt", "alg": "ES256", "jwk": self.jwk});
+        let mut claims = json!({
+            "jti": hex::encode(jti),
+            "htm": method,
+            "htu": htu.as_str(),
+            "iat": now,
+        });
+        if let Some(nonce) = nonce {
+            claims["nonce"] = json!(nonce);
+        }
+        if let Some(access_token) = access_token {
+            claims["ath"] = json!(URL_SAFE_NO_PAD.encode(Sha256::digest(access_token)));
+        }
+
+        let signing_input = format!(
+            "{}.{}",
+            URL_SAFE_NO_PAD.encode(header.to_string()),
+            URL_SAFE_NO_PAD.encode(claims.to_string())
+        );
+        // JWS wants the raw R and S values, where OpenSSL works with DER encoded signatures
+        let signature = EcdsaSig::sign(&Sha256::digest(&signing_input), &self.key)?;
+        let mut raw = signature.r().to_vec_padded(32)?;
+        raw.extend(signature.s().to_vec_padded(32)?);
+        Ok(format!("{}.{}", signing_input, URL_SAFE_NO_PAD.encode(raw)))
+    }
+}
+
+/// An OAuth session of a Bluesky account, stored in the account's Secrets Manager secret as
+/// `{"oauth": {...}}` in place of the username and password of an app password.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OAuthSession {
+    /// The URL of the client metadata the session was authorized for.
+    pub client_id: String,
+    /// The token endpoint of the account's authorization server.
+    pub token_endpoint: String,
+    /// The URL of the account's PDS.
+    pub pds_url: String,
+    /// The DID of the account.
+    pub did: String,
+    /// The refresh token. It can only be used once, so the session has to be stored again after every refresh.
+    pub refresh_token: String,
+    /// The current access token, if it's still known.
+    #[serde(default)]
+    pub access_token: Option<String>,
+    /// Unix time the access token expires at.
+    #[serde(default)]
+    pub expires_at: Option<i64>,
+    /// The DPoP key the tokens are bound to, as PEM.
+    pub dpop_key: String,
+}
+
+/// What a token endpoint returns.
+#[derive(Debug, Deserialize)]
+pub struct TokenResponse {
+    pub access_token: String,
+    #[serde(default)]
+    pub refresh_token: Option<String>,
+    #[serde(default)]
+    pub expires_in: Option<i64>,
+    /// The DID of the account the tokens are for.
+    pub sub: String,
+}
+
+impl OAuthSession {
+    /// Checks whether the access token is good for the rest of a run, so it can be used without a refresh.
+    pub fn has_fresh_access_token(&self, now: i64) -> bool {
+        self.access_token.is_some()
+            && self
+                .expires_at
+                .is_some_and(|expires_at| expires_at - now > MIN_ACCESS_TOKEN_SECS)
+    }
+
+    /// Gets new tokens with the refresh token.
+    ///
+    /// The old refresh token is spent once this returns, successful or not, so the session must be stored again
+    /// right away.
+    ///
+    /// # Arguments
+    ///
+    /// * `client` - The HTTP client to call the token endpoint with.
+    /// * `now` - The current Unix time.
+    ///
+    /// # Returns
+    ///
+    /// A Result indicating success, or an error if the authorization server refused the refresh, e.g. because the
+    /// session was revoked or expired.
+    pub async fn refresh(&mut self, client: &reqwest::Client, now: i64) -> Result<()> {
+        let key = DpopKey::from_pem(&self.dpop_key)?;
+        let response = token_request(
+            client,
+            &self.token_endpoint,
+            &[
+                ("grant_type", "refresh_token"),
+                ("refresh_token", &self.refresh_token),
+                ("client_id", &self.client_id),
+            ],
+            &key,
+        )
+        .await
+        .context("Failed to refresh the OAuth session")?;
+        if response.sub != self.did {
+            return Err(anyhow!(
+                "The refreshed tokens are for {} rather than {}",
+                response.sub,
+                self.did
+            ));
+        }
+
+        self.access_token = Some(response.access_token);
+        self.expires_at = response.expires_in.map(|expires_in| now + expires_in);
+        if let Some(refresh_token) = response.refresh_token {
+            self.refresh_token = refresh_token;
+        }
+        Ok(())
+    }
+}
+
+/// Builds an HTTP client for talking to authorization servers.
+pub fn oauth_client() -> Result<reqwest::Client> {
+    Ok(reqwest::Client::builder().user_agent(USER_AGENT).build()?)
+}
+
+/// Posts a form to an endpoint of an authorization server, such as its token or pushed authorization request
+/// endpoint.
+///
+/// Authorization servers want a fresh nonce in the DPoP proof, which they hand out in the error response to a
+/// proof without one, so the request is sent again once with the nonce.
+///
+/// # Returns
+///
+/// A Result containing the JSON response, or an error if the server refused the request.
+pub async fn post_form(
+    client: &reqwest::Client,
+    url: &str,
+    form: &[(&str, &str)],
+    key: &DpopKey,
+) -> Result<serde_json::Value> {
+    let mut nonce: Option<String> = None;
+    loop {
+        let now = chrono::Utc::now().timestamp();
+        let proof = key.proof("POST", url, nonce.as_deref(), None, now)?;
+        let response = client
+            .post(url)
+            .header("DPoP", proof)
+            .form(form)
+            .send()
+            .await
+            .with_context(|| format!("Failed to call {}", url))?;
+
+        let status = response.status();
+        let new_nonce = response
+            .headers()
+            .get("dpop-nonce")
+            .and_then(|value| value.to_str().ok())
+            .map(String::from);
+        let body: serde_json::Value = response
+            .json()
+            .await
+            .with_context(|| format!("Invalid response from {}", url))?;
+        if status.is_success() {
+            return Ok(body);
+        }
+
+        if body["error"] == "use_dpop_nonce" && nonce.is_none() && new_nonce.is_some() {
+            nonce = new_nonce;
+            continue;
+        }
+        return Err(anyhow!(
+            "{} responded with {}: {} {}",
+            url,
+            status,
+            body["error"].as_str().unwrap_or_default(),
+            body["error_description"].as_str().unwrap_or_default()
+        ));
+    }
+}
+
+/// Calls a token endpoint.
+pub async fn token_request(
+    client: &reqwest::Client,
+    token_endpoint: &str,
+    form: &[(&str, &str)],
+    key: &DpopKey,
+) -> Result<TokenResponse> {
+    let body = post_form(client, token_endpoint, form, key).await?;
+    serde_json::from_value(body).context("Invalid token response")
+}
+
+/// Returns the PKCE code challenge (S256) of a code verifier.
+pub fn pkce_challenge(verifier: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(verifier))
+}
+
+/// Generates a random URL-safe string, for PKCE code verifiers and OAuth states.
+pub fn random_token() -> String {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+/// Signs the requests of an OAuth session to its PDS.
+///
+/// The agent sends the access token as a bearer token, as it does for app passwords. This turns it into a DPoP
+/// token with a proof, and keeps track of the nonce the PDS wants in the proofs.
+pub struct DpopAuth {
+    key: DpopKey,
+    nonce: Mutex<Option<String>>,
+}
+
+impl DpopAuth {
+    /// Creates the signer for a session's key.
+    pub fn new(key: DpopKey) -> Self {
+        Self {
+            key,
+            nonce: Mutex::new(None),
+        }
+    }
+
+    /// Replaces the bearer token of a request with a DPoP token and its proof. Requests without a token are left
+    /// alone.
+    pub fn authorize(&self, request: &mut Request<Vec<u8>>) -> Result<()> {
+        let Some(token) = request
+            .headers()
+            .get("authorization")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .map(String::from)
+        else {
+            return Ok(());
+        };
+
+        let nonce = self
+            .nonce
+            .lock()
+            .map_err(|_| anyhow!("Poisoned lock"))?
+            .clone();
+        let proof = self.key.proof(
+            request.method().as_str(),
+            &request.uri().to_string(),
+            nonce.as_deref(),
+            Some(&token),
+            chrono::Utc::now().timestamp(),
+        )?;
+        let headers = request.headers_mut();
+        headers.insert(
+            "authorization",
+            HeaderValue::from_str(&format!("DPoP {}", token))?,
+        );
+        headers.insert("dpop", HeaderValue::from_str(&proof)?);
+        Ok(())
+    }
+
+    /// Takes note of the nonce in a response.
+    ///
+    /// # Returns
+    ///
+    /// True if the response asks for the request to be sent again with the new nonce.
+    pub fn update_nonce(&self, response: &Response<Vec<u8>>) -> bool {
+        let Some(nonce) = response
+            .headers()
+            .get("dpop-nonce")
+            .and_then(|value| value.to_str().ok())
+        else {
+            return false;
+        };
+        let Ok(mut current) = self.nonce.lock() else {
+            return false;
+        };
+        let changed = current.as_deref() != Some(nonce);
+        *current = Some(nonce.to_string());
+        changed && wants_nonce(response.status(), response.headers())
+    }
+}
+
+/// Checks whether a PDS refused a request for want of a (current) DPoP nonce.
+fn wants_nonce(status: StatusCode, headers: &HeaderMap) -> bool {
+    status == StatusCode::UNAUTHORIZED
+        && headers
+            .get("www-authenticate")
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.contains("use_dpop_nonce"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use openssl::ec::EcPoint;
+
+    fn decode_json(part: &str) -> serde_json::Value {
+        serde_json::from_slice(&URL_SAFE_NO_PAD.decode(part).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_proof() {
+        let key = DpopKey::generate().unwrap();
+        let proof = key
+            .proof(
+                "POST",
+                "https://pds.example.com/xrpc/com.atproto.repo.createRecord?x=1#y",
+                Some("nonce-1"),
+                Some("token"),
+                1_000,
+            )
+            .unwrap();
+        let parts: Vec<&str> = proof.split('.').collect();
+        assert_eq!(parts.len(), 3);
+
+        let header = decode_json(parts[0]);
+        assert_eq!(header["typ"], "dpop+jwt");
+        assert_eq!(header["alg"], "ES256");
+        assert_eq!(header["jwk"]["crv"], "P-256");
+
+        let claims = decode_json(parts[1]);
+        assert_eq!(claims["htm"], "POST");
+        assert_eq!(
+            claims["htu"],
+            "https://pds.example.com/xrpc/com.atproto.repo.createRecord"
+        );
+        assert_eq!(claims["iat"], 1_000);
+        assert_eq!(claims["nonce"], "nonce-1");
+        assert_eq!(
+            claims["ath"],
+            URL_SAFE_NO_PAD.encode(Sha256::digest("token"))
+        );
+
+        // The signature verifies against the public key in the header
+        let signature = URL_SAFE_NO_PAD.decode(parts[2]).unwrap();
+        assert_eq!(signature.len(), 64);
+        let signature = EcdsaSig::from_private_components(
+            BigNum::from_slice(&signature[..32]).unwrap(),
+            BigNum::from_slice(&signature[32..]).unwrap(),
+        )
+        .unwrap();
+        let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+        let coordinate = |name: &str| {
+            BigNum::from_slice(
+                &URL_SAFE_NO_PAD
+                    .decode(header["jwk"][name].as_str().unwrap())
+                    .unwrap(),
+            )
+            .unwrap()
+        };
+        let mut point = EcPoint::new(&group).unwrap();
+        point
+            .set_affine_coordinates_gfp(
+                &group,
+                &coordinate("x"),
+                &coordinate("y"),
+                &mut BigNumContext::new().unwrap(),
+            )
+            .unwrap();
+        let public_key = EcKey::from_public_key(&group, &point).unwrap();
+        let digest = Sha256::digest(format!("{}.{}", parts[0], parts[1]));
+        assert!(signature.verify(&digest, &public_key).unwrap());
+    }
+
+    #[test]
+    fn test_key_pem() {
+        let key = DpopKey::generate().unwrap();
+        let restored = DpopKey::from_pem(&key.to_pem().unwrap()).unwrap();
+        assert_eq!(restored.jwk, key.jwk);
+        assert!(DpopKey::from_pem("not a key").is_err());
+    }
+
+    #[test]
+    fn test_pkce_challenge() {
+        // The example of RFC 7636, appendix B
+        assert_eq!(
+            pkce_challenge("dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk"),
+            "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM"
+        );
+    }
+
+    #[test]
+    fn test_authorize() {
+        let auth = DpopAuth::new(DpopKey::generate().unwrap());
+        let mut request = Request::builder()
+            .method("POST")
+            .uri("https://pds.example.com/xrpc/com.atproto.repo.createRecord")
+            .header("authorization", "Bearer token")
+            .body(Vec::new())
+            .unwrap();
+        auth.authorize(&mut request).unwrap();
+        assert_eq!(request.headers()["authorization"], "DPoP token");
+        assert!(request.headers().contains_key("dpop"));
+
+        let mut anonymous = Request::builder()
+            .uri("https://pds.example.com/xrpc/com.atproto.identity.resolveHandle")
+            .body(Vec::new())
+            .unwrap();
+        auth.authorize(&mut anonymous).unwrap();
+        assert!(!anonymous.headers().contains_key("dpop"));
+    }
+
+    #[test]
+    fn test_update_nonce() {
+        let auth = DpopAuth::new(DpopKey::generate().unwrap());
+        let response = |status: u16, www_authenticate: Option<&str>| {
+            let mut builder = Response::builder()
+                .status(status)
+                .header("dpop-nonce", "nonce-1");
+            if let Some(value) = www_authenticate {
+                builder = builder.header("www-authenticate", value);
+            }
+            builder.body(Vec::new()).unwrap()
+        };
+
+        // A new nonce on a successful response is only remembered
+        assert!(!auth.update_nonce(&response(200, None)));
+        assert_eq!(auth.nonce.lock().unwrap().as_deref(), Some("nonce-1"));
+
+        // A refusal for the nonce the request already had is not retried
+        let refusal = response(401, Some(r#"DPoP error="use_dpop_nonce""#));
+        assert!(!auth.update_nonce(&refusal));
+        *auth.nonce.lock().unwrap() = None;
+        assert!(auth.update_nonce(&refusal));
+    }
+
+    #[test]
+    fn test_has_fresh_access_token() {
+        let mut session = OAuthSession {
+            client_id: "https://example.com/client-metadata.json".to_string(),
+            token_endpoint: "https://bsky.social/oauth/token".to_string(),
+            pds_url: "https://pds.example.com".to_string(),
+            did: "did:plc:abc".to_string(),
+            refresh_token: "refresh".to_string(),
+            access_token: None,
+            expires_at: Some(10_000),
+            dpop_key: String::new(),
+        };
+        assert!(!session.has_fresh_access_token(1_000));
+        session.access_token = Some("access".to_string());
+        assert!(session.has_fresh_access_token(1_000));
+        assert!(!session.has_fresh_access_token(9_900));
+    }
+}
@@ -0,0 +1,222 @@
+use crate::http::USER_AGENT;
+use crate::retry::backoff_delay;
+use crate::summarize::{Completion, InferenceParams, TokenUsage};
+use anyhow::{anyhow, Context, Result};
+use aws_sdk_secretsmanager::Client as SecretsManagerClient;
+use reqwest::StatusCode;
+use serde_json::{json, Value};
+use std::time::Duration;
+
+/// The API summaries are written with unless OPENAI_BASE_URL names another OpenAI-compatible one.
+pub const DEFAULT_OPENAI_BASE_URL: &str = "https://api.openai.com/v1";
+/// How many times a failed request is retried.
+const MAX_RETRIES: u32 = 3;
+/// Delay ceiling for the first retry.
+const RETRY_BASE_DELAY: Duration = Duration::from_secs(1);
+/// Models can take a while to write, but a request that hangs shouldn't use up the lambda's time.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// A client for the chat completions of an OpenAI-compatible API, such as OpenAI's own, Azure OpenAI, OpenRouter or
+/// a self-hosted server.
+pub struct OpenAiClient {
+    client: reqwest::Client,
+    base_url: String,
+    api_key: String,
+    model: String,
+}
+
+impl OpenAiClient {
+    /// Creates a client.
+    ///
+    /// # Arguments
+    ///
+    /// * `base_url` - The URL the API's paths start with, e.g. `https://api.openai.com/v1`.
+    /// * `api_key` - The key the requests are authorized with.
+    /// * `model` - The model that writes the completions, e.g. `gpt-4o-mini`.
+    pub fn new(base_url: &str, api_key: &str, model: &str) -> Result<Self> {
+        let client = reqwest::Client::builder()
+            .user_agent(USER_AGENT)
+            .timeout(REQUEST_TIMEOUT)
+            .build()?;
+        Ok(Self {
+            client,
+            base_url: base_url.trim_end_matches('/').to_string(),
+            api_key: api_key.to_string(),
+            model: model.to_string(),
+        })
+    }
+
+    /// Creates a client with the API key stored in a Secrets Manager secret.
+    ///
+    /// # Arguments
+    ///
+    /// * `secrets_client` - The client to read the secret with.
+    /// * `secret_name` - Name or ARN of the secret, holding `{"api_key": "..."}`.
+    /// * `base_url` - The URL the API's paths start with.
+    /// * `model` - The model that writes the completions.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the client, or an error if the secret can't be read.
+    pub async fn from_secret(
+        secrets_client: &SecretsManagerClient,
+        secret_name: &str,
+        base_url: &str,
+        model: &str,
+    ) -> Result<Self> {
+        let secret = secrets_client
+            .get_secret_value()
+            .secret_id(secret_name)
+            .send()
+            .await
+            .context("Failed to retrieve secret")?;
+
+        let secret_string = secret.secret_string().context("Secret string is empty")?;
+        let credentials: Value =
+            serde_json::from_str(secret_string).context("Failed to parse secret JSON")?;
+
+        let api_key = credentials["api_key"]
+            .as_str()
+            .context("API key not found in secret")?;
+        Self::new(base_url, api_key, model)
+    }
+
+    /// Asks the model to complete a prompt.
+    ///
+    /// Rate limited (429) and failed (5xx) requests are retried.
+    ///
+    /// # Arguments
+    ///
+    /// * `prompt` - The prompt, sent as the user's message.
+    /// * `params` - The settings the model generates with.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the completion, or an error if the API refused the request.
+    pub async fn complete(&self, prompt: &str, params: &InferenceParams) -> Result<Completion> {
+        let url = format!("{}/chat/completions", self.base_url);
+        let body = chat_request(&self.model, prompt, params);
+        let mut attempt = 0;
+        loop {
+            let response = self
+                .client
+                .post(&url)
+                .bearer_auth(&self.api_key)
+                .json(&body)
+                .send()
+                .await
+                .with_context(|| format!("Failed to call {}", url))?;
+
+            let code = response.status();
+            if code.is_success() {
+                let response: Value = response
+                    .json()
+                    .await
+                    .context("Invalid chat completion in response")?;
+                return Ok(Completion {
+                    text: parse_chat_response(&response),
+                    usage: parse_chat_usage(&response),
+                });
+            }
+            let retryable = code == StatusCode::TOO_MANY_REQUESTS || code.is_server_error();
+            if !retryable || attempt >= MAX_RETRIES {
+                let body = response.text().await.unwrap_or_default();
+                return Err(anyhow!("{} responded with {}: {}", url, code, body));
+            }
+
+            let delay = backoff_delay(RETRY_BASE_DELAY, attempt);
+            tracing::warn!(
+                "Chat completion request failed ({}), retrying in {:?} (attempt {} of {})",
+                code,
+                delay,
+                attempt + 1,
+                MAX_RETRIES
+            );
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+}
+
+/// Builds the body of a chat completion request with a single user message.
+pub fn chat_request(model: &str, prompt: &str, params: &InferenceParams) -> Value {
+    json!({
+        "model": model,
+        "messages": [{"role": "user", "content": prompt}],
+        "max_tokens": params.max_tokens,
+        "temperature": params.temperature,
+        "top_p": params.top_p,
+    })
+}
+
+/// Reads the text of the first choice of a chat completion.
+///
+/// # Returns
+///
+/// The text, trimmed, or None if the completion has none.
+pub fn parse_chat_response(response: &Value) -> Option<String> {
+    let text = response["choices"][0]["message"]["content"]
+        .as_str()?
+        .trim();
+    (!text.is_empty()).then(|| text.to_string())
+}
+
+/// Reads the tokens a chat completion used.
+///
+/// # Returns
+///
+/// The usage, or None if the response doesn't tell.
+pub fn parse_chat_usage(response: &Value) -> Option<TokenUsage> {
+    Some(TokenUsage {
+        input_tokens: response["usage"]["prompt_tokens"].as_u64()?,
+        output_tokens: response["usage"]["completion_tokens"].as_u64()?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chat_request() {
+        let request = chat_request("gpt-4o-mini", "Summarize", &InferenceParams::default());
+        assert_eq!(request["model"], "gpt-4o-mini");
+        assert_eq!(
+            request["messages"],
+            json!([{"role": "user", "content": "Summarize"}])
+        );
+        assert_eq!(request["max_tokens"], 300);
+    }
+
+    #[test]
+    fn test_parse_chat_response() {
+        let response = json!({
+            "choices": [{"index": 0, "message": {"role": "assistant", "content": " A summary. "}}]
+        });
+        assert_eq!(
+            parse_chat_response(&response).as_deref(),
+            Some("A summary.")
+        );
+        assert_eq!(parse_chat_response(&json!({"choices": []})), None);
+        assert_eq!(
+            parse_chat_response(&json!({"choices": [{"message": {"content": null}}]})),
+            None
+        );
+    }
+
+    #[test]
+    fn test_parse_chat_usage() {
+        let response = json!({
+            "choices": [],
+            "usage": {"prompt_tokens": 120, "completion_tokens": 40, "total_tokens": 160}
+        });
+        assert_eq!(
+            parse_chat_usage(&response),
+            Some(TokenUsage {
+                input_tokens: 120,
+                output_tokens: 40,
+            })
+        );
+        assert_eq!(parse_chat_usage(&json!({"choices": []})), None);
+    }
+}
@@ -0,0 +1,96 @@
+use crate::models::FeedConfig;
+use anyhow::{Context, Result};
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+
+/// Parses an OPML document, as exported by most RSS readers, into FeedConfigs.
+///
+/// Every `outline` element with an `xmlUrl` attribute is treated as a feed, regardless of how deeply it is nested
+/// in folders. Feeds listed more than once are only returned once.
+///
+/// # Arguments
+///
+/// * `content` - The raw bytes of the OPML document.
+///
+/// # Returns
+///
+/// A Result containing the FeedConfigs, or an error if the document is not valid XML.
+pub fn parse_opml(content: &[u8]) -> Result<Vec<FeedConfig>> {
+    let mut reader = Reader::from_reader(content);
+    let mut buf = Vec::new();
+    let mut feed_configs: Vec<FeedConfig> = Vec::new();
+
+    loop {
+        match reader
+            .read_event_into(&mut buf)
+            .context("Failed to parse OPML document")?
+        {
+            Event::Start(element) | Event::Empty(element)
+                if element.local_name().as_ref() == b"outline" =>
+            {
+                let Some(feed_url) = attribute(&reader, &element, b"xmlUrl") else {
+                    continue;
+                };
+                if feed_configs.iter().any(|f| f.feed_url == feed_url) {
+                    continue;
+                }
+
+                let mut feed_config = FeedConfig::new(feed_url)?;
+                feed_config.title = attribute(&reader, &element, b"title")
+                    .or_else(|| attribute(&reader, &element, b"text"));
+                feed_config.site_url = attribute(&reader, &element, b"htmlUrl");
+                feed_configs.push(feed_config);
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+
+    Ok(feed_configs)
+}
+
+fn attribute(reader: &Reader<&[u8]>, element: &BytesStart, name: &[u8]) -> Option<String> {
+    element
+        .attributes()
+        .flatten()
+        .find(|attr| attr.key.as_ref() == name)
+        .and_then(|attr| attr.decode_and_unescape_value(reader.decoder()).ok())
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_opml() {
+        let content = r#"<?xml version="1.0" encoding="UTF-8"?>
+<opml version="2.0">
+  <head><title>Subscriptions</title></head>
+  <body>
+    <outline text="Tech" title="Tech">
+      <outline type="rss" text="Example &amp; Co" xmlUrl="https://example.com/feed.xml" htmlUrl="https://example.com/" />
+      <outline type="rss" title="Other" text="Other text" xmlUrl="https://other.example.com/rss" />
+    </outline>
+    <outline type="rss" text="Duplicate" xmlUrl="https://example.com/feed.xml" />
+    <outline text="Not a feed" />
+  </body>
+</opml>"#;
+
+        let feed_configs = parse_opml(content.as_bytes()).unwrap();
+        assert_eq!(feed_configs.len(), 2);
+
+        assert_eq!(feed_configs[0].feed_url, "https://example.com/feed.xml");
+        assert_eq!(feed_configs[0].title.as_deref(), Some("Example & Co"));
+        assert_eq!(
+            feed_configs[0].site_url.as_deref(),
+            Some("https://example.com/")
+        );
+
+        assert_eq!(feed_configs[1].feed_url, "https://other.example.com/rss");
+        assert_eq!(feed_configs[1].title.as_deref(), Some("Other"));
+        assert_eq!(feed_configs[1].site_url, None);
+    }
+}
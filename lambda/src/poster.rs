@@ -0,0 +1,167 @@
+use crate::models::ExecutionItem;
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::future::Future;
+
+/// Where a post ended up. The fields a network has no use for stay None.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PostResult {
+    /// The at:// URI of a Bluesky post.
+    pub uri: Option<String>,
+    /// The CID of a Bluesky post.
+    pub cid: Option<String>,
+    /// The id of the post on its network, e.g. of a Mastodon status or a Nostr note.
+    pub id: Option<String>,
+    /// The web address of the post.
+    pub url: Option<String>,
+    /// The name of the template variant the post was laid out with, when templates are A/B tested.
+    pub template_variant: Option<String>,
+    /// True if the post was only written and logged, not made.
+    pub dry_run: bool,
+}
+
+/// A network items can be posted to.
+///
+/// Posting is split in two: [`Poster::prepare`] writes the post, uploading whatever it embeds, and
+/// [`Poster::publish`] makes it. A dry run stops after the first, so it tries out everything but the post itself.
+pub trait Poster {
+    /// A post that is written but not made yet. It's logged as JSON in dry runs.
+    type Draft: Serialize + Send;
+
+    /// The name of the network, for logs and errors.
+    fn network(&self) -> &'static str;
+
+    /// Writes the post of an item.
+    fn prepare(&self, item: &ExecutionItem) -> impl Future<Output = Result<Self::Draft>> + Send;
+
+    /// Makes a written post.
+    fn publish(&self, draft: Self::Draft) -> impl Future<Output = Result<PostResult>> + Send;
+
+    /// Posts an item.
+    ///
+    /// # Arguments
+    ///
+    /// * `item` - The item to post.
+    /// * `dry_run` - Logs the post instead of making it.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing where the post ended up (nothing for a dry run), or an error if it couldn't be written
+    /// or made.
+    fn post(
+        &self,
+        item: &ExecutionItem,
+        dry_run: bool,
+    ) -> impl Future<Output = Result<PostResult>> + Send
+    where
+        Self: Sync,
+    {
+        async move {
+            let network = self.network();
+            let draft = self.prepare(item).await.with_context(|| {
+                format!("Failed to write the {} post of {:?}", network, item.guid)
+            })?;
+
+            if dry_run {
+                let draft = serde_json::to_string(&draft).context("Failed to serialize post")?;
+                tracing::info!("Dry run, not posting to {}: {}", network, draft);
+                return Ok(PostResult {
+                    dry_run: true,
+                    ..PostResult::default()
+                });
+            }
+
+            let result = self
+                .publish(draft)
+                .await
+                .with_context(|| format!("Failed to post {:?} to {}", item.guid, network))?;
+            tracing::info!("Posted {:?} to {}: {:?}", item.guid, network, result);
+            Ok(result)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::anyhow;
+    use std::sync::Mutex;
+
+    /// Posts the titles of items, remembering what it posted.
+    #[derive(Default)]
+    struct MockPoster {
+        posted: Mutex<Vec<String>>,
+        fail: bool,
+    }
+
+    impl Poster for MockPoster {
+        type Draft = String;
+
+        fn network(&self) -> &'static str {
+            "Mock"
+        }
+
+        async fn prepare(&self, item: &ExecutionItem) -> Result<String> {
+            item.title.clone().context("Title not found in item")
+        }
+
+        async fn publish(&self, draft: String) -> Result<PostResult> {
+            if self.fail {
+                return Err(anyhow!("Service unavailable"));
+            }
+            let mut posted = self.posted.lock().unwrap();
+            posted.push(draft);
+            Ok(PostResult {
+                id: Some(posted.len().to_string()),
+                ..PostResult::default()
+            })
+        }
+    }
+
+    fn item(title: Option<&str>) -> ExecutionItem {
+        ExecutionItem::new(
+            "execution".to_string(),
+            "guid".to_string(),
+            title.map(String::from),
+            None,
+            None,
+            None,
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_post() {
+        let poster = MockPoster::default();
+        let result = poster.post(&item(Some("Hello")), false).await.unwrap();
+        assert_eq!(result.id.as_deref(), Some("1"));
+        assert!(!result.dry_run);
+        assert_eq!(*poster.posted.lock().unwrap(), vec!["Hello"]);
+
+        // A dry run writes the post but doesn't make it
+        let result = poster.post(&item(Some("World")), true).await.unwrap();
+        assert_eq!(
+            result,
+            PostResult {
+                dry_run: true,
+                ..PostResult::default()
+            }
+        );
+        assert_eq!(poster.posted.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_post_errors() {
+        let poster = MockPoster::default();
+        let error = poster.post(&item(None), true).await.unwrap_err();
+        assert!(error.to_string().contains("Failed to write the Mock post"));
+
+        let poster = MockPoster {
+            fail: true,
+            ..MockPoster::default()
+        };
+        let error = poster.post(&item(Some("Hello")), false).await.unwrap_err();
+        assert_eq!(error.to_string(), "Failed to post \"guid\" to Mock");
+        assert_eq!(error.root_cause().to_string(), "Service unavailable");
+    }
+}
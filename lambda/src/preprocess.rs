@@ -0,0 +1,213 @@
+use crate::github::is_releases_feed;
+use crate::html::block_text;
+use scraper::{Html, Selector};
+use url::Url;
+
+/// Source specific clean-up applied to the items of a feed before they are filtered and stored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Preprocessor {
+    /// Reddit feeds: strips the "submitted by", `[link]` and `[comments]` markup and links to the submitted URL.
+    Reddit,
+    /// GitHub releases feeds: reduces the release notes to plain text, so posts can quote them. Releases without
+    /// notes fall back to the title.
+    GithubReleases,
+}
+
+/// The parts of an item a Preprocessor rewrites.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ItemContent {
+    pub description: Option<String>,
+    pub link: Option<String>,
+}
+
+impl Preprocessor {
+    /// Picks the Preprocessor for a feed.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The preprocessor configured on the feed, if any. `none` disables detection.
+    /// * `feed_url` - The URL of the feed, used to detect the source when no preprocessor is configured.
+    ///
+    /// # Returns
+    ///
+    /// The Preprocessor to apply, or None if the feed's items are used as they are.
+    pub fn for_feed(name: Option<&str>, feed_url: &str) -> Option<Self> {
+        match name.map(|n| n.trim().to_lowercase()).as_deref() {
+            Some("reddit") => Some(Self::Reddit),
+            Some("github") => Some(Self::GithubReleases),
+            Some("none") => None,
+            Some(other) => {
+                tracing::warn!(
+                    "Unknown preprocessor {:?} for feed {}, ignoring it",
+                    other,
+                    feed_url
+                );
+                None
+            }
+            None if is_releases_feed(feed_url) => Some(Self::GithubReleases),
+            None => {
+                let host = Url::parse(feed_url).ok()?.host_str()?.to_lowercase();
+                is_reddit_host(&host).then_some(Self::Reddit)
+            }
+        }
+    }
+
+    /// Rewrites the description and link of an item.
+    ///
+    /// # Arguments
+    ///
+    /// * `title` - The title of the item.
+    /// * `description` - The description of the item, usually HTML.
+    /// * `link` - The link of the item.
+    ///
+    /// # Returns
+    ///
+    /// The new description and link.
+    pub fn apply(
+        &self,
+        title: Option<&str>,
+        description: Option<&str>,
+        link: Option<&str>,
+    ) -> ItemContent {
+        match self {
+            Self::Reddit => reddit(title, description, link),
+            Self::GithubReleases => ItemContent {
+                description: description
+                    .map(|notes| block_text(&Html::parse_fragment(notes).root_element()))
+                    .filter(|notes| !notes.is_empty())
+                    .or_else(|| title.map(String::from)),
+                link: link.map(String::from),
+            },
+        }
+    }
+}
+
+/// Reddit descriptions are an HTML table holding the thumbnail, the self text of the post (in `<div class="md">`),
+/// "submitted by /u/..." and the `[link]` and `[comments]` anchors. Only the self text is kept; link posts have none,
+/// so they fall back to the title. The `[link]` anchor points to the submitted URL, or back to the post for self
+/// posts.
+fn reddit(title: Option<&str>, description: Option<&str>, link: Option<&str>) -> ItemContent {
+    let Some(description) = description else {
+        return ItemContent {
+            description: title.map(String::from),
+            link: link.map(String::from),
+        };
+    };
+
+    let fragment = Html::parse_fragment(description);
+
+    let text = Selector::parse("div.md")
+        .ok()
+        .and_then(|selector| {
+            let text = fragment
+                .select(&selector)
+                .map(|md| block_text(&md))
+                .collect::<Vec<_>>()
+                .join("\n\n");
+            (!text.is_empty()).then_some(text)
+        })
+        .or_else(|| title.map(String::from));
+
+    let outbound_link = Selector::parse("a[href]").ok().and_then(|selector| {
+        fragment
+            .select(&selector)
+            .find(|a| a.text().collect::<String>().trim() == "[link]")
+            .and_then(|a| a.value().attr("href"))
+            .map(str::trim)
+            .filter(|href| {
+                Url::parse(href)
+                    .ok()
+                    .and_then(|url| url.host_str().map(str::to_lowercase))
+                    .is_some_and(|host| !is_reddit_host(&host))
+            })
+            .map(String::from)
+    });
+
+    ItemContent {
+        description: text,
+        link: outbound_link.or_else(|| link.map(String::from)),
+    }
+}
+
+/// Hosts serving Reddit pages. Media hosts such as i.redd.it are deliberately not included, as an image or video is
+/// the submitted content.
+fn is_reddit_host(host: &str) -> bool {
+    host == "reddit.com" || host.ends_with(".reddit.com")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_github_releases() {
+        assert_eq!(
+            Preprocessor::for_feed(None, "https://github.com/rust-lang/rust/releases.atom"),
+            Some(Preprocessor::GithubReleases)
+        );
+
+        let link = "https://github.com/rust-lang/rust/releases/tag/1.83.0";
+        assert_eq!(
+            Preprocessor::GithubReleases.apply(
+                Some("Rust 1.83.0"),
+                Some("<h2>Language</h2>\n<ul>\n<li>Stabilize <code>&amp;mut</code> in const</li>\n</ul>"),
+                Some(link)
+            ),
+            ItemContent {
+                description: Some("Language\n\nStabilize &mut in const".to_string()),
+                link: Some(link.to_string()),
+            }
+        );
+        assert_eq!(
+            Preprocessor::GithubReleases
+                .apply(Some("Rust 1.83.0"), Some(""), Some(link))
+                .description
+                .as_deref(),
+            Some("Rust 1.83.0")
+        );
+    }
+
+    #[test]
+    fn test_reddit() {
+        assert_eq!(
+            Preprocessor::for_feed(None, "https://www.reddit.com/r/rust/.rss"),
+            Some(Preprocessor::Reddit)
+        );
+        assert_eq!(
+            Preprocessor::for_feed(Some("none"), "https://www.reddit.com/r/rust/.rss"),
+            None
+        );
+        assert_eq!(
+            Preprocessor::for_feed(Some("Reddit"), "https://example.com/feed"),
+            Some(Preprocessor::Reddit)
+        );
+        assert_eq!(
+            Preprocessor::for_feed(None, "https://example.com/feed"),
+            None
+        );
+
+        let comments = "https://www.reddit.com/r/rust/comments/abc/post/";
+        let link_post = format!(
+            r#"<table> <tr><td> <a href="{comments}"> <img src="https://b.thumbs.redditmedia.com/x.jpg" alt="Post" /> </a> </td><td> &#32; submitted by &#32; <a href="https://www.reddit.com/user/ferris"> /u/ferris </a> <br/> <span><a href="https://blog.example.com/article">[link]</a></span> &#32; <span><a href="{comments}">[comments]</a></span> </td></tr></table>"#
+        );
+        assert_eq!(
+            Preprocessor::Reddit.apply(Some("Post"), Some(&link_post), Some(comments)),
+            ItemContent {
+                description: Some("Post".to_string()),
+                link: Some("https://blog.example.com/article".to_string()),
+            }
+        );
+
+        let self_post = format!(
+            r#"<!-- SC_OFF --><div class="md"><p>First <a href="https://example.com">paragraph</a>.</p>
+<p>Second   paragraph.</p></div><!-- SC_ON --> &#32; submitted by &#32; <a href="https://www.reddit.com/user/ferris"> /u/ferris </a> <br/> <span><a href="{comments}">[link]</a></span> &#32; <span><a href="{comments}">[comments]</a></span>"#
+        );
+        assert_eq!(
+            Preprocessor::Reddit.apply(Some("Post"), Some(&self_post), Some(comments)),
+            ItemContent {
+                description: Some("First paragraph.\n\nSecond paragraph.".to_string()),
+                link: Some(comments.to_string()),
+            }
+        );
+    }
+}
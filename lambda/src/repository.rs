@@ -1,9 +1,30 @@
-use crate::models::{ExecutionItem, RecordItem};
+use crate::models::{
+    CachedBlob, CachedSummary, EngagementSample, ExecutionItem, FeedConfig, FeedState, HeldItem,
+    RecentPost, RecordItem, CLAIM_TIMEOUT_SECS,
+};
 use anyhow::{Context, Result};
 use aws_sdk_dynamodb::types::{AttributeValue, DeleteRequest, PutRequest, WriteRequest};
 use aws_sdk_dynamodb::Client;
 use std::collections::HashMap;
 
+/// PK shared by all FeedConfig items.
+const FEED_CONFIG_PK: &str = "FeedConfig";
+/// PK shared by all RecentPost items.
+const RECENT_POST_PK: &str = "RecentPost";
+/// PK shared by all HeldItem items.
+const HELD_ITEM_PK: &str = "HeldItem";
+/// PK shared by all EngagementSample items.
+const ENGAGEMENT_SAMPLE_PK: &str = "EngagementSample";
+/// PK shared by all CachedBlob items.
+const CACHED_BLOB_PK: &str = "CachedBlob";
+/// PK shared by all CachedSummary items.
+const CACHED_SUMMARY_PK: &str = "CachedSummary";
+/// How long the ExecutionItem of an item lasts once it's held. Holding it again, or copying it into the execution that
+/// posts it, starts the time over, so it only expires for items held this long without a run seeing them.
+const HELD_EXECUTION_ITEM_TTL_DAYS: i64 = 7;
+/// PK and SK of the single item recording state across executions.
+const RUN_STATE_KEY: &str = "RunState";
+
 /// Repository for interacting with DynamoDB.
 pub struct DynamoRepository {
     client: Client,
@@ -35,38 +56,10 @@ impl DynamoRepository {
     ///
     /// A Result indicating success or failure.
     pub async fn create_execution_item(&self, item: &ExecutionItem) -> Result<()> {
-        let mut request = self
-            .client
+        self.client
             .put_item()
             .table_name(&self.table_name)
-            .item("PK", AttributeValue::S(item.execution_id.clone()))
-            .item("SK", AttributeValue::S(item.guid.to_string()));
-
-        if let Some(title) = &item.title {
-            request = request.item("title", AttributeValue::S(title.clone()));
-        }
-
-        if let Some(description) = &item.description {
-            request = request.item("description", AttributeValue::S(description.clone()))
-        }
-
-        if let Some(link) = &item.link {
-            request = request.item("link", AttributeValue::S(link.clone()))
-        }
-
-        if let Some(ttl) = &item.ttl {
-            request = request.item("ttl", AttributeValue::N(ttl.to_string()))
-        }
-
-        if let Some(_type) = &item._type {
-            request = request.item("_TYPE", AttributeValue::S("ExecutionItem".to_string()))
-        }
-
-        if let Some(pub_date) = &item.pub_date {
-            request = request.item("pub_date", AttributeValue::S(pub_date.clone()));
-        }
-
-        request
+            .set_item(Some(execution_item_attributes(item)))
             .send()
             .await
             .context("Failed to create execution item")?;
@@ -87,41 +80,8 @@ impl DynamoRepository {
             let mut write_requests = Vec::new();
 
             for item in chunk {
-                let mut put_request_builder = PutRequest::builder()
-                    .item("PK", AttributeValue::S(item.execution_id.clone()))
-                    .item("SK", AttributeValue::S(item.guid.to_string()));
-
-                if let Some(title) = &item.title {
-                    put_request_builder =
-                        put_request_builder.item("title", AttributeValue::S(title.clone()));
-                }
-
-                if let Some(description) = &item.description {
-                    put_request_builder = put_request_builder
-                        .item("description", AttributeValue::S(description.clone()));
-                }
-
-                if let Some(link) = &item.link {
-                    put_request_builder =
-                        put_request_builder.item("link", AttributeValue::S(link.clone()));
-                }
-
-                if let Some(ttl) = &item.ttl {
-                    put_request_builder =
-                        put_request_builder.item("ttl", AttributeValue::N(ttl.to_string()));
-                }
-
-                if let Some(_type) = &item._type {
-                    put_request_builder = put_request_builder
-                        .item("_TYPE", AttributeValue::S("ExecutionItem".to_string()));
-                }
-
-                if let Some(pub_date) = &item.pub_date {
-                    put_request_builder =
-                        put_request_builder.item("pub_date", AttributeValue::S(pub_date.clone()));
-                }
-
-                let put_request = put_request_builder
+                let put_request = PutRequest::builder()
+                    .set_item(Some(execution_item_attributes(item)))
                     .build()
                     .context("Unable to create put_request")?;
 
@@ -184,6 +144,105 @@ impl DynamoRepository {
         Ok(())
     }
 
+    /// Replaces the description of an ExecutionItem, e.g. with one taken from the page it links to.
+    ///
+    /// # Arguments
+    ///
+    /// * `execution_id` - The execution ID of the item to update.
+    /// * `guid` - The GUID of the item to update.
+    /// * `description` - The new description.
+    ///
+    /// # Returns
+    ///
+    /// A Result indicating success or failure.
+    pub async fn update_execution_item_description(
+        &self,
+        execution_id: &str,
+        guid: &str,
+        description: &str,
+    ) -> Result<()> {
+        self.client
+            .update_item()
+            .table_name(&self.table_name)
+            .key("PK", AttributeValue::S(execution_id.to_string()))
+            .key("SK", AttributeValue::S(guid.to_string()))
+            .update_expression("SET description = :description")
+            .expression_attribute_values(":description", AttributeValue::S(description.to_string()))
+            .send()
+            .await
+            .context("Failed to update execution item description")?;
+
+        Ok(())
+    }
+
+    /// Stores the text extracted from the article an ExecutionItem links to.
+    ///
+    /// # Arguments
+    ///
+    /// * `execution_id` - The execution ID of the item to update.
+    /// * `guid` - The GUID of the item to update.
+    /// * `article_text` - The extracted article text.
+    ///
+    /// # Returns
+    ///
+    /// A Result indicating success or failure.
+    pub async fn update_execution_item_article_text(
+        &self,
+        execution_id: &str,
+        guid: &str,
+        article_text: &str,
+    ) -> Result<()> {
+        self.client
+            .update_item()
+            .table_name(&self.table_name)
+            .key("PK", AttributeValue::S(execution_id.to_string()))
+            .key("SK", AttributeValue::S(guid.to_string()))
+            .update_expression("SET article_text = :article_text")
+            .expression_attribute_values(
+                ":article_text",
+                AttributeValue::S(article_text.to_string()),
+            )
+            .send()
+            .await
+            .context("Failed to update execution item article text")?;
+
+        Ok(())
+    }
+
+    /// Marks an ExecutionItem as an update of an item that was posted already.
+    ///
+    /// # Arguments
+    ///
+    /// * `execution_id` - The execution ID of the item to update.
+    /// * `guid` - The GUID of the item to update.
+    /// * `uri` - The at:// URI of the post of the earlier version of the item.
+    /// * `cid` - The CID of that post.
+    ///
+    /// # Returns
+    ///
+    /// A Result indicating success or failure.
+    pub async fn update_execution_item_update_of(
+        &self,
+        execution_id: &str,
+        guid: &str,
+        uri: &str,
+        cid: &str,
+    ) -> Result<()> {
+        self.client
+            .update_item()
+            .table_name(&self.table_name)
+            .key("PK", AttributeValue::S(execution_id.to_string()))
+            .key("SK", AttributeValue::S(guid.to_string()))
+            .update_expression("SET update_of_uri = :uri, update_of_cid = :cid")
+            .expression_attribute_values(":uri", AttributeValue::S(uri.to_string()))
+            .expression_attribute_values(":cid", AttributeValue::S(cid.to_string()))
+            .send()
+            .await
+            .context("Failed to mark execution item as an update")?;
+
+        Ok(())
+    }
+
     /// Retrieves an ExecutionItem from DynamoDB.
     ///
     /// # Arguments
@@ -210,38 +269,7 @@ impl DynamoRepository {
             .context("Failed to get execution item")?;
 
         if let Some(item) = result.item {
-            Ok(ExecutionItem {
-                execution_id: execution_id.to_string(),
-                guid: guid.to_string(),
-                title: item
-                    .get("title")
-                    .and_then(|av| av.as_s().ok())
-                    .map(String::from),
-                description: item
-                    .get("description")
-                    .and_then(|av| av.as_s().ok())
-                    .map(String::from),
-                link: item
-                    .get("link")
-                    .and_then(|av| av.as_s().ok())
-                    .map(String::from),
-                summary: item
-                    .get("summary")
-                    .and_then(|av| av.as_s().ok())
-                    .map(String::from),
-                ttl: item
-                    .get("ttl")
-                    .and_then(|av| av.as_n().ok())
-                    .and_then(|n| n.parse().ok()),
-                _type: item
-                    .get("_TYPE")
-                    .and_then(|av| av.as_s().ok())
-                    .map(String::from),
-                pub_date: item
-                    .get("pub_date")
-                    .and_then(|av| av.as_s().ok())
-                    .map(String::from),
-            })
+            Ok(execution_item_from_attributes(execution_id, guid, &item))
         } else {
             Err(anyhow::anyhow!("Execution item not found"))
         }
@@ -351,12 +379,42 @@ impl DynamoRepository {
     ///
     /// A Result indicating success or failure.
     pub async fn create_record_item(&self, item: &RecordItem) -> Result<()> {
+        let mut attributes = HashMap::new();
+        attributes.insert("PK".to_string(), AttributeValue::S(item.guid.to_string()));
+        attributes.insert("SK".to_string(), AttributeValue::S("A".to_string()));
+        attributes.insert(
+            "_TYPE".to_string(),
+            AttributeValue::S("RecordItem".to_string()),
+        );
+        if let Some(uri) = &item.uri {
+            attributes.insert("uri".to_string(), AttributeValue::S(uri.clone()));
+        }
+        if let Some(cid) = &item.cid {
+            attributes.insert("cid".to_string(), AttributeValue::S(cid.clone()));
+        }
+        if let Some(content_hash) = &item.content_hash {
+            attributes.insert(
+                "content_hash".to_string(),
+                AttributeValue::S(content_hash.clone()),
+            );
+        }
+        if let Some(template_variant) = &item.template_variant {
+            attributes.insert(
+                "template_variant".to_string(),
+                AttributeValue::S(template_variant.clone()),
+            );
+        }
+        if let Some(posted_at) = item.posted_at {
+            attributes.insert(
+                "posted_at".to_string(),
+                AttributeValue::N(posted_at.to_string()),
+            );
+        }
+
         self.client
             .put_item()
             .table_name(&self.table_name)
-            .item("PK", AttributeValue::S(item.guid.to_string()))
-            .item("SK", AttributeValue::S("A".to_string()))
-            .item("_TYPE", AttributeValue::S("RecordItem".to_string()))
+            .set_item(Some(attributes))
             .send()
             .await
             .context("Failed to create record item")?;
@@ -384,21 +442,89 @@ impl DynamoRepository {
             .await
             .context("Failed to get record item")?;
 
-        if let Some(item) = result.item {
-            Ok(RecordItem {
-                guid: item
-                    .get("PK")
-                    .and_then(|av| av.as_s().ok())
-                    .map(String::from)
-                    .context("Missing or invalid guid")?,
-                _type: item
-                    .get("_TYPE")
-                    .and_then(|av| av.as_s().ok())
-                    .map(String::from),
-            })
-        } else {
-            Err(anyhow::anyhow!("Record item not found"))
+        result
+            .item
+            .as_ref()
+            .and_then(record_item_from_attributes)
+            .ok_or_else(|| anyhow::anyhow!("Record item not found"))
+    }
+
+    /// Lists the RecordItems of the items posted within a time range.
+    ///
+    /// RecordItems are keyed by guid, so this scans the table. It's meant for occasional clean-ups, not for the
+    /// regular runs.
+    ///
+    /// # Arguments
+    ///
+    /// * `from` - Unix timestamp of the start of the range, inclusive.
+    /// * `to` - Unix timestamp of the end of the range, inclusive.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the RecordItems, in no particular order, or an error if the scan failed.
+    pub async fn list_record_items_posted_between(
+        &self,
+        from: i64,
+        to: i64,
+    ) -> Result<Vec<RecordItem>> {
+        let mut record_items = Vec::new();
+        let mut last_evaluated_key = None;
+
+        loop {
+            let mut scan = self
+                .client
+                .scan()
+                .table_name(&self.table_name)
+                .filter_expression("#type = :type AND posted_at BETWEEN :from AND :to")
+                .expression_attribute_names("#type", "_TYPE")
+                .expression_attribute_values(":type", AttributeValue::S("RecordItem".to_string()))
+                .expression_attribute_values(":from", AttributeValue::N(from.to_string()))
+                .expression_attribute_values(":to", AttributeValue::N(to.to_string()));
+
+            if let Some(key) = last_evaluated_key {
+                scan = scan.set_exclusive_start_key(Some(key));
+            }
+
+            let result = scan.send().await.context("Failed to scan record items")?;
+
+            if let Some(items) = result.items {
+                record_items.extend(items.iter().filter_map(record_item_from_attributes));
+            }
+
+            last_evaluated_key = result.last_evaluated_key;
+
+            if last_evaluated_key.is_none() {
+                break;
+            }
         }
+
+        Ok(record_items)
+    }
+
+    /// Records that the post of a RecordItem was deleted from Bluesky.
+    ///
+    /// # Arguments
+    ///
+    /// * `guid` - The GUID of the RecordItem.
+    /// * `deleted_at` - Unix timestamp of the deletion.
+    ///
+    /// # Returns
+    ///
+    /// A Result indicating success or failure. It fails if there is no RecordItem for the guid.
+    pub async fn mark_record_item_deleted(&self, guid: &str, deleted_at: i64) -> Result<()> {
+        self.client
+            .update_item()
+            .table_name(&self.table_name)
+            .key("PK", AttributeValue::S(guid.to_string()))
+            .key("SK", AttributeValue::S("A".to_string()))
+            .update_expression("SET deleted_at = :deleted_at")
+            .condition_expression("attribute_exists(PK)")
+            .expression_attribute_values(":deleted_at", AttributeValue::N(deleted_at.to_string()))
+            .send()
+            .await
+            .context("Failed to mark record item as deleted")?;
+
+        Ok(())
     }
 
     /// Checks if a RecordItem exists in DynamoDB.
@@ -409,8 +535,8 @@ impl DynamoRepository {
     ///
     /// # Returns
     ///
-    /// A Result containing a boolean: true if the item exists, false if it doesn't.
-    /// Returns an error if the operation failed.
+    /// A Result containing a boolean: true if the item exists, false if it doesn't. The claim on an item that
+    /// isn't posted yet doesn't count. Returns an error if the operation failed.
     pub async fn record_item_exists(&self, guid: &str) -> Result<bool> {
         let result = self
             .client
@@ -418,11 +544,1437 @@ impl DynamoRepository {
             .table_name(&self.table_name)
             .key("PK", AttributeValue::S(guid.to_string()))
             .key("SK", AttributeValue::S("A".to_string()))
-            .projection_expression("PK") // We only need to retrieve the PK attribute
+            .projection_expression("PK, claimed_by, posted_at")
             .send()
             .await
             .context("Failed to check record item existence")?;
 
-        Ok(result.item.is_some())
+        Ok(result
+            .item
+            .is_some_and(|item| !item.contains_key("claimed_by") || item.contains_key("posted_at")))
+    }
+
+    /// Claims an item for posting, so that an overlapping execution doesn't post it too.
+    ///
+    /// A new item is claimed by creating its RecordItem, which is only done if there is none yet. An item posted
+    /// again as an update is claimed on its RecordItem, as long as that still records the posted version the update
+    /// was made against. Either way a claim by another execution stands until it's [`CLAIM_TIMEOUT_SECS`] old.
+    ///
+    /// # Arguments
+    ///
+    /// * `guid` - The GUID of the item.
+    /// * `execution_id` - The execution claiming it. Its own claim can be claimed again, say when a step is retried.
+    /// * `claimed_at` - Unix timestamp of the claim.
+    /// * `posted_hash` - The content hash of the posted version of an updated item, None for a new item.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing true if the item was claimed, false if another execution has it or posted it already, or
+    /// an error if the operation failed.
+    pub async fn claim_record_item(
+        &self,
+        guid: &str,
+        execution_id: &str,
+        claimed_at: i64,
+        posted_hash: Option<&str>,
+    ) -> Result<bool> {
+        let claimable =
+            "attribute_not_exists(claimed_by) OR claimed_by = :execution_id OR claimed_at < :stale";
+        let mut request = self
+            .client
+            .update_item()
+            .table_name(&self.table_name)
+            .key("PK", AttributeValue::S(guid.to_string()))
+            .key("SK", AttributeValue::S("A".to_string()))
+            .update_expression(
+                "SET claimed_by = :execution_id, claimed_at = :claimed_at, #type = :type",
+            )
+            .expression_attribute_names("#type", "_TYPE")
+            .expression_attribute_values(
+                ":execution_id",
+                AttributeValue::S(execution_id.to_string()),
+            )
+            .expression_attribute_values(":claimed_at", AttributeValue::N(claimed_at.to_string()))
+            .expression_attribute_values(
+                ":stale",
+                AttributeValue::N((claimed_at - CLAIM_TIMEOUT_SECS).to_string()),
+            )
+            .expression_attribute_values(":type", AttributeValue::S("RecordItem".to_string()));
+        request = match posted_hash {
+            Some(posted_hash) => request
+                .condition_expression(format!("content_hash = :posted_hash AND ({})", claimable))
+                .expression_attribute_values(":posted_hash", AttributeValue::S(posted_hash.to_string())),
+            // A claim without a posting time is one on a new item, which an execution failed to post
+            None => request.condition_expression(format!(
+                "attribute_not_exists(PK) OR (attribute_exists(claimed_by) AND attribute_not_exists(posted_at) AND ({}))",
+                claimable
+            )),
+        };
+
+        match request.send().await {
+            Ok(_) => Ok(true),
+            Err(e)
+                if e.as_service_error()
+                    .is_some_and(|e| e.is_conditional_check_failed_exception()) =>
+            {
+                Ok(false)
+            }
+            Err(e) => Err(e).context("Failed to claim record item"),
+        }
+    }
+
+    /// Gives up an execution's claim on an item it didn't post, such as in a dry run. The RecordItem of a new item
+    /// is deleted, and that of an update goes back to what it was.
+    ///
+    /// # Arguments
+    ///
+    /// * `guid` - The GUID of the item.
+    /// * `execution_id` - The execution whose claim is given up. Claims of other executions are left alone.
+    ///
+    /// # Returns
+    ///
+    /// A Result indicating success or failure.
+    pub async fn release_record_claim(&self, guid: &str, execution_id: &str) -> Result<()> {
+        let deleted = self
+            .client
+            .delete_item()
+            .table_name(&self.table_name)
+            .key("PK", AttributeValue::S(guid.to_string()))
+            .key("SK", AttributeValue::S("A".to_string()))
+            .condition_expression("claimed_by = :execution_id AND attribute_not_exists(posted_at)")
+            .expression_attribute_values(
+                ":execution_id",
+                AttributeValue::S(execution_id.to_string()),
+            )
+            .send()
+            .await;
+        match deleted {
+            Ok(_) => return Ok(()),
+            // Not a new item's claim by this execution, so it may still be that of an update
+            Err(e)
+                if e.as_service_error()
+                    .is_some_and(|e| e.is_conditional_check_failed_exception()) => {}
+            Err(e) => return Err(e).context("Failed to release record claim"),
+        }
+
+        let updated = self
+            .client
+            .update_item()
+            .table_name(&self.table_name)
+            .key("PK", AttributeValue::S(guid.to_string()))
+            .key("SK", AttributeValue::S("A".to_string()))
+            .update_expression("REMOVE claimed_by, claimed_at")
+            .condition_expression("claimed_by = :execution_id")
+            .expression_attribute_values(
+                ":execution_id",
+                AttributeValue::S(execution_id.to_string()),
+            )
+            .send()
+            .await;
+        match updated {
+            Ok(_) => Ok(()),
+            Err(e)
+                if e.as_service_error()
+                    .is_some_and(|e| e.is_conditional_check_failed_exception()) =>
+            {
+                Ok(())
+            }
+            Err(e) => Err(e).context("Failed to release record claim"),
+        }
+    }
+
+    /// Creates a RecentPost in DynamoDB.
+    ///
+    /// # Arguments
+    ///
+    /// * `item` - The RecentPost to create.
+    ///
+    /// # Returns
+    ///
+    /// A Result indicating success or failure.
+    pub async fn create_recent_post(&self, item: &RecentPost) -> Result<()> {
+        let mut attributes = HashMap::new();
+        attributes.insert(
+            "PK".to_string(),
+            AttributeValue::S(RECENT_POST_PK.to_string()),
+        );
+        attributes.insert(
+            "SK".to_string(),
+            AttributeValue::S(recent_post_sk(item.posted_at, &item.guid)),
+        );
+        attributes.insert(
+            "_TYPE".to_string(),
+            AttributeValue::S("RecentPost".to_string()),
+        );
+        attributes.insert("guid".to_string(), AttributeValue::S(item.guid.clone()));
+        attributes.insert(
+            "posted_at".to_string(),
+            AttributeValue::N(item.posted_at.to_string()),
+        );
+        for (name, value) in [
+            ("title", &item.title),
+            ("link", &item.link),
+            ("feed_url", &item.feed_url),
+            ("account", &item.account),
+        ] {
+            if let Some(value) = value {
+                attributes.insert(name.to_string(), AttributeValue::S(value.clone()));
+            }
+        }
+        if let Some(ttl) = item.ttl {
+            // Unlike the other items, recent posts are expired by DynamoDB through the table's TTL attribute
+            attributes.insert("TTL".to_string(), AttributeValue::N(ttl.to_string()));
+        }
+
+        self.client
+            .put_item()
+            .table_name(&self.table_name)
+            .set_item(Some(attributes))
+            .send()
+            .await
+            .context("Failed to create recent post")?;
+
+        Ok(())
+    }
+
+    /// Lists the RecentPosts posted at or after the given time.
+    ///
+    /// # Arguments
+    ///
+    /// * `since` - Unix timestamp of the oldest post to return.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the RecentPosts, oldest first, or an error if the query failed.
+    pub async fn list_recent_posts(&self, since: i64) -> Result<Vec<RecentPost>> {
+        let mut recent_posts = Vec::new();
+        let mut last_evaluated_key = None;
+
+        loop {
+            let mut query = self
+                .client
+                .query()
+                .table_name(&self.table_name)
+                .key_condition_expression("PK = :pk_val AND SK >= :since")
+                .expression_attribute_values(
+                    ":pk_val",
+                    AttributeValue::S(RECENT_POST_PK.to_string()),
+                )
+                .expression_attribute_values(
+                    ":since",
+                    AttributeValue::S(recent_post_sk(since, "")),
+                );
+
+            if let Some(key) = last_evaluated_key {
+                query = query.set_exclusive_start_key(Some(key));
+            }
+
+            let result = query.send().await.context("Failed to query recent posts")?;
+
+            if let Some(items) = result.items {
+                recent_posts.extend(items.iter().filter_map(recent_post_from_attributes));
+            }
+
+            last_evaluated_key = result.last_evaluated_key;
+
+            if last_evaluated_key.is_none() {
+                break;
+            }
+        }
+
+        Ok(recent_posts)
+    }
+
+    /// Creates or replaces multiple HeldItems in DynamoDB using BatchWriteItem.
+    ///
+    /// The ExecutionItems of the held items would otherwise expire a day after they were fetched, so their TTL is
+    /// moved to [`HELD_EXECUTION_ITEM_TTL_DAYS`] from now.
+    ///
+    /// # Arguments
+    ///
+    /// * `items` - A slice of HeldItems to store.
+    ///
+    /// # Returns
+    ///
+    /// A Result indicating success or failure.
+    pub async fn hold_items(&self, items: &[HeldItem]) -> Result<()> {
+        for chunk in items.chunks(25) {
+            let mut write_requests = Vec::new();
+
+            for item in chunk {
+                let put_request = PutRequest::builder()
+                    .set_item(Some(held_item_attributes(item)))
+                    .build()
+                    .context("Unable to create put_request")?;
+
+                let write_request = WriteRequest::builder().put_request(put_request).build();
+                write_requests.push(write_request);
+            }
+
+            let mut request_items = HashMap::new();
+            request_items.insert(self.table_name.clone(), write_requests);
+
+            let result = self
+                .client
+                .batch_write_item()
+                .set_request_items(Some(request_items))
+                .send()
+                .await
+                .context("Failed to hold items")?;
+
+            if let Some(unprocessed_items) = result.unprocessed_items() {
+                if !unprocessed_items.is_empty() {
+                    return Err(anyhow::anyhow!(
+                        "Some items were not held: {:?}",
+                        unprocessed_items
+                    ));
+                }
+            }
+        }
+
+        let ttl =
+            (chrono::Utc::now() + chrono::Duration::days(HELD_EXECUTION_ITEM_TTL_DAYS)).timestamp();
+        for item in items {
+            self.extend_execution_item_ttl(&item.execution_id, &item.guid, ttl)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Moves the TTL of an ExecutionItem, if it still exists.
+    async fn extend_execution_item_ttl(
+        &self,
+        execution_id: &str,
+        guid: &str,
+        ttl: i64,
+    ) -> Result<()> {
+        let result = self
+            .client
+            .update_item()
+            .table_name(&self.table_name)
+            .key("PK", AttributeValue::S(execution_id.to_string()))
+            .key("SK", AttributeValue::S(guid.to_string()))
+            .update_expression("SET TTL = :ttl")
+            .condition_expression("attribute_exists(PK)")
+            .expression_attribute_values(":ttl", AttributeValue::N(ttl.to_string()))
+            .send()
+            .await;
+
+        match result {
+            Ok(_) => Ok(()),
+            // Gone already, which the runs that post it find out about
+            Err(e)
+                if e.as_service_error()
+                    .is_some_and(|e| e.is_conditional_check_failed_exception()) =>
+            {
+                Ok(())
+            }
+            Err(e) => Err(e).context("Failed to extend the TTL of the held item"),
+        }
+    }
+
+    /// Lists the HeldItems.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the HeldItems, oldest first, or an error if the query failed.
+    pub async fn list_held_items(&self) -> Result<Vec<HeldItem>> {
+        let mut held_items = Vec::new();
+        let mut last_evaluated_key = None;
+
+        loop {
+            let mut query = self
+                .client
+                .query()
+                .table_name(&self.table_name)
+                .key_condition_expression("PK = :pk_val")
+                .expression_attribute_values(
+                    ":pk_val",
+                    AttributeValue::S(HELD_ITEM_PK.to_string()),
+                );
+
+            if let Some(key) = last_evaluated_key {
+                query = query.set_exclusive_start_key(Some(key));
+            }
+
+            let result = query.send().await.context("Failed to query held items")?;
+
+            if let Some(items) = result.items {
+                held_items.extend(items.iter().filter_map(held_item_from_attributes));
+            }
+
+            last_evaluated_key = result.last_evaluated_key;
+
+            if last_evaluated_key.is_none() {
+                break;
+            }
+        }
+
+        // Stable sort, so items held together keep the order they would have been posted in
+        held_items.sort_by_key(|item| item.held_at);
+        Ok(held_items)
+    }
+
+    /// Releases a held item, once it's posted or needs no posting anymore.
+    ///
+    /// # Arguments
+    ///
+    /// * `guid` - The GUID of the item.
+    ///
+    /// # Returns
+    ///
+    /// A Result indicating success or failure. Releasing an item that isn't held succeeds too.
+    pub async fn release_held_item(&self, guid: &str) -> Result<()> {
+        self.client
+            .delete_item()
+            .table_name(&self.table_name)
+            .key("PK", AttributeValue::S(HELD_ITEM_PK.to_string()))
+            .key("SK", AttributeValue::S(guid.to_string()))
+            .send()
+            .await
+            .context("Failed to release held item")?;
+
+        Ok(())
+    }
+
+    /// Stores multiple EngagementSamples in DynamoDB using BatchWriteItem.
+    ///
+    /// # Arguments
+    ///
+    /// * `samples` - A slice of EngagementSamples to store.
+    ///
+    /// # Returns
+    ///
+    /// A Result indicating success or failure.
+    pub async fn create_engagement_samples(&self, samples: &[EngagementSample]) -> Result<()> {
+        for chunk in samples.chunks(25) {
+            let mut write_requests = Vec::new();
+
+            for sample in chunk {
+                let put_request = PutRequest::builder()
+                    .set_item(Some(engagement_sample_attributes(sample)))
+                    .build()
+                    .context("Unable to create put_request")?;
+
+                let write_request = WriteRequest::builder().put_request(put_request).build();
+                write_requests.push(write_request);
+            }
+
+            let mut request_items = HashMap::new();
+            request_items.insert(self.table_name.clone(), write_requests);
+
+            let result = self
+                .client
+                .batch_write_item()
+                .set_request_items(Some(request_items))
+                .send()
+                .await
+                .context("Failed to store engagement samples")?;
+
+            if let Some(unprocessed_items) = result.unprocessed_items() {
+                if !unprocessed_items.is_empty() {
+                    return Err(anyhow::anyhow!(
+                        "Some engagement samples were not stored: {:?}",
+                        unprocessed_items
+                    ));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Lists the EngagementSamples taken at or after the given time.
+    ///
+    /// # Arguments
+    ///
+    /// * `since` - Unix timestamp of the oldest sample to return.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the EngagementSamples, oldest first, or an error if the query failed.
+    pub async fn list_engagement_samples(&self, since: i64) -> Result<Vec<EngagementSample>> {
+        let mut samples = Vec::new();
+        let mut last_evaluated_key = None;
+
+        loop {
+            let mut query = self
+                .client
+                .query()
+                .table_name(&self.table_name)
+                .key_condition_expression("PK = :pk_val AND SK >= :since")
+                .expression_attribute_values(
+                    ":pk_val",
+                    AttributeValue::S(ENGAGEMENT_SAMPLE_PK.to_string()),
+                )
+                .expression_attribute_values(
+                    ":since",
+                    AttributeValue::S(recent_post_sk(since, "")),
+                );
+
+            if let Some(key) = last_evaluated_key {
+                query = query.set_exclusive_start_key(Some(key));
+            }
+
+            let result = query
+                .send()
+                .await
+                .context("Failed to query engagement samples")?;
+
+            if let Some(items) = result.items {
+                samples.extend(items.iter().filter_map(engagement_sample_from_attributes));
+            }
+
+            last_evaluated_key = result.last_evaluated_key;
+
+            if last_evaluated_key.is_none() {
+                break;
+            }
+        }
+
+        Ok(samples)
+    }
+
+    /// Retrieves a cached blob from DynamoDB.
+    ///
+    /// # Arguments
+    ///
+    /// * `did` - The DID of the account the blob was uploaded to.
+    /// * `url_hash` - The SHA-256 of the image URL, in hex.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the CachedBlob, None if the image isn't cached or its entry expired, or an error if the
+    /// operation failed.
+    pub async fn get_cached_blob(&self, did: &str, url_hash: &str) -> Result<Option<CachedBlob>> {
+        let result = self
+            .client
+            .get_item()
+            .table_name(&self.table_name)
+            .key("PK", AttributeValue::S(CACHED_BLOB_PK.to_string()))
+            .key("SK", AttributeValue::S(format!("{}#{}", did, url_hash)))
+            .send()
+            .await
+            .context("Failed to get cached blob")?;
+
+        let Some(item) = result.item else {
+            return Ok(None);
+        };
+        let get_s = |name: &str| {
+            item.get(name)
+                .and_then(|av| av.as_s().ok())
+                .map(String::from)
+        };
+        let ttl: Option<i64> = get_n(&item, "TTL");
+        if ttl.is_some_and(|ttl| ttl <= chrono::Utc::now().timestamp()) {
+            return Ok(None);
+        }
+        Ok(get_s("blob").map(|blob| CachedBlob {
+            did: did.to_string(),
+            url_hash: url_hash.to_string(),
+            blob,
+            width: get_n(&item, "width"),
+            height: get_n(&item, "height"),
+            alt: get_s("alt"),
+            ttl,
+        }))
+    }
+
+    /// Creates or replaces a cached blob in DynamoDB.
+    ///
+    /// # Arguments
+    ///
+    /// * `item` - The CachedBlob to store.
+    ///
+    /// # Returns
+    ///
+    /// A Result indicating success or failure.
+    pub async fn put_cached_blob(&self, item: &CachedBlob) -> Result<()> {
+        let mut attributes = HashMap::from([
+            (
+                "PK".to_string(),
+                AttributeValue::S(CACHED_BLOB_PK.to_string()),
+            ),
+            (
+                "SK".to_string(),
+                AttributeValue::S(format!("{}#{}", item.did, item.url_hash)),
+            ),
+            (
+                "_TYPE".to_string(),
+                AttributeValue::S("CachedBlob".to_string()),
+            ),
+            ("blob".to_string(), AttributeValue::S(item.blob.clone())),
+        ]);
+        for (name, value) in [("width", item.width), ("height", item.height)] {
+            if let Some(value) = value {
+                attributes.insert(name.to_string(), AttributeValue::N(value.to_string()));
+            }
+        }
+        if let Some(alt) = &item.alt {
+            attributes.insert("alt".to_string(), AttributeValue::S(alt.clone()));
+        }
+        if let Some(ttl) = item.ttl {
+            attributes.insert("TTL".to_string(), AttributeValue::N(ttl.to_string()));
+        }
+
+        self.client
+            .put_item()
+            .table_name(&self.table_name)
+            .set_item(Some(attributes))
+            .send()
+            .await
+            .context("Failed to cache blob")?;
+
+        Ok(())
+    }
+
+    /// Retrieves a cached summary from DynamoDB.
+    ///
+    /// # Arguments
+    ///
+    /// * `prompt_hash` - The SHA-256 of the prompt, in hex.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the CachedSummary, None if the prompt isn't cached or its entry expired, or an error if
+    /// the operation failed.
+    pub async fn get_cached_summary(&self, prompt_hash: &str) -> Result<Option<CachedSummary>> {
+        let result = self
+            .client
+            .get_item()
+            .table_name(&self.table_name)
+            .key("PK", AttributeValue::S(CACHED_SUMMARY_PK.to_string()))
+            .key("SK", AttributeValue::S(prompt_hash.to_string()))
+            .send()
+            .await
+            .context("Failed to get cached summary")?;
+
+        let Some(item) = result.item else {
+            return Ok(None);
+        };
+        let ttl: Option<i64> = get_n(&item, "TTL");
+        if ttl.is_some_and(|ttl| ttl <= chrono::Utc::now().timestamp()) {
+            return Ok(None);
+        }
+        Ok(item
+            .get("summary")
+            .and_then(|av| av.as_s().ok())
+            .map(|summary| CachedSummary {
+                prompt_hash: prompt_hash.to_string(),
+                summary: summary.clone(),
+                ttl,
+            }))
+    }
+
+    /// Creates or replaces a cached summary in DynamoDB.
+    ///
+    /// # Arguments
+    ///
+    /// * `item` - The CachedSummary to store.
+    ///
+    /// # Returns
+    ///
+    /// A Result indicating success or failure.
+    pub async fn put_cached_summary(&self, item: &CachedSummary) -> Result<()> {
+        let mut attributes = HashMap::from([
+            (
+                "PK".to_string(),
+                AttributeValue::S(CACHED_SUMMARY_PK.to_string()),
+            ),
+            (
+                "SK".to_string(),
+                AttributeValue::S(item.prompt_hash.clone()),
+            ),
+            (
+                "_TYPE".to_string(),
+                AttributeValue::S("CachedSummary".to_string()),
+            ),
+            (
+                "summary".to_string(),
+                AttributeValue::S(item.summary.clone()),
+            ),
+        ]);
+        if let Some(ttl) = item.ttl {
+            attributes.insert("TTL".to_string(), AttributeValue::N(ttl.to_string()));
+        }
+
+        self.client
+            .put_item()
+            .table_name(&self.table_name)
+            .set_item(Some(attributes))
+            .send()
+            .await
+            .context("Failed to cache summary")?;
+
+        Ok(())
+    }
+
+    /// Retrieves the FeedState of a feed from DynamoDB.
+    ///
+    /// # Arguments
+    ///
+    /// * `feed_url` - The URL of the feed.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the FeedState if one has been stored, None if the feed hasn't been seen before, or an
+    /// error if the operation failed.
+    pub async fn get_feed_state(&self, feed_url: &str) -> Result<Option<FeedState>> {
+        let result = self
+            .client
+            .get_item()
+            .table_name(&self.table_name)
+            .key("PK", AttributeValue::S(feed_url.to_string()))
+            .key("SK", AttributeValue::S("FeedState".to_string()))
+            .send()
+            .await
+            .context("Failed to get feed state")?;
+
+        Ok(result.item.map(|item| {
+            let get_s = |name: &str| {
+                item.get(name)
+                    .and_then(|av| av.as_s().ok())
+                    .map(String::from)
+            };
+            FeedState {
+                feed_url: feed_url.to_string(),
+                content_hash: get_s("content_hash"),
+                resolved_url: get_s("resolved_url"),
+                high_water_mark: get_n(&item, "high_water_mark"),
+                last_polled: get_n(&item, "last_polled"),
+                _type: get_s("_TYPE"),
+            }
+        }))
+    }
+
+    /// Stores the content hash of the last successful fetch of a feed, creating the FeedState if needed.
+    ///
+    /// # Arguments
+    ///
+    /// * `feed_url` - The URL of the feed.
+    /// * `content_hash` - The SHA-256 of the fetched feed body.
+    ///
+    /// # Returns
+    ///
+    /// A Result indicating success or failure.
+    pub async fn update_feed_content_hash(&self, feed_url: &str, content_hash: &str) -> Result<()> {
+        self.set_feed_state_attribute(
+            feed_url,
+            "content_hash",
+            AttributeValue::S(content_hash.to_string()),
+        )
+        .await
+        .context("Failed to update feed content hash")
+    }
+
+    /// Stores the feed URL discovered from the HTML page a feed was configured with, creating the FeedState if
+    /// needed.
+    ///
+    /// # Arguments
+    ///
+    /// * `feed_url` - The URL of the feed as configured.
+    /// * `resolved_url` - The URL of the actual feed document.
+    ///
+    /// # Returns
+    ///
+    /// A Result indicating success or failure.
+    pub async fn update_feed_resolved_url(&self, feed_url: &str, resolved_url: &str) -> Result<()> {
+        self.set_feed_state_attribute(
+            feed_url,
+            "resolved_url",
+            AttributeValue::S(resolved_url.to_string()),
+        )
+        .await
+        .context("Failed to update feed resolved url")
+    }
+
+    /// Stores when a feed with its own polling interval was last fetched, creating the FeedState if needed.
+    ///
+    /// # Arguments
+    ///
+    /// * `feed_url` - The URL of the feed as configured.
+    /// * `polled_at` - Unix timestamp of the fetch.
+    ///
+    /// # Returns
+    ///
+    /// A Result indicating success or failure.
+    pub async fn update_feed_last_polled(&self, feed_url: &str, polled_at: i64) -> Result<()> {
+        self.set_feed_state_attribute(
+            feed_url,
+            "last_polled",
+            AttributeValue::N(polled_at.to_string()),
+        )
+        .await
+        .context("Failed to update feed last polled time")
+    }
+
+    /// Moves the high-water mark of a feed forward to the given publication date, creating the FeedState if needed.
+    ///
+    /// The update is conditional, so a mark that is already newer (e.g. because items were posted out of order) is
+    /// left alone.
+    ///
+    /// # Arguments
+    ///
+    /// * `feed_url` - The URL of the feed as configured.
+    /// * `pub_date` - Unix timestamp of the publication date of an item that was posted.
+    ///
+    /// # Returns
+    ///
+    /// A Result indicating success or failure.
+    pub async fn advance_feed_high_water_mark(&self, feed_url: &str, pub_date: i64) -> Result<()> {
+        let result = self
+            .client
+            .update_item()
+            .table_name(&self.table_name)
+            .key("PK", AttributeValue::S(feed_url.to_string()))
+            .key("SK", AttributeValue::S("FeedState".to_string()))
+            .update_expression("SET high_water_mark = :value, #type = :type")
+            .condition_expression(
+                "attribute_not_exists(high_water_mark) OR high_water_mark < :value",
+            )
+            .expression_attribute_names("#type", "_TYPE")
+            .expression_attribute_values(":value", AttributeValue::N(pub_date.to_string()))
+            .expression_attribute_values(":type", AttributeValue::S("FeedState".to_string()))
+            .send()
+            .await;
+
+        match result {
+            Ok(_) => Ok(()),
+            Err(e)
+                if e.as_service_error()
+                    .is_some_and(|e| e.is_conditional_check_failed_exception()) =>
+            {
+                Ok(())
+            }
+            Err(e) => Err(e).context("Failed to advance feed high-water mark"),
+        }
+    }
+
+    /// Retrieves when the last execution that processed all of its items without errors started.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the Unix timestamp, None if no execution has succeeded yet, or an error if the operation
+    /// failed.
+    pub async fn get_last_successful_run(&self) -> Result<Option<i64>> {
+        let result = self
+            .client
+            .get_item()
+            .table_name(&self.table_name)
+            .key("PK", AttributeValue::S(RUN_STATE_KEY.to_string()))
+            .key("SK", AttributeValue::S(RUN_STATE_KEY.to_string()))
+            .send()
+            .await
+            .context("Failed to get run state")?;
+
+        Ok(result
+            .item
+            .and_then(|item| get_n(&item, "last_successful_run")))
+    }
+
+    /// Records the start of a successful execution. Executions that finish out of order never move the time back.
+    ///
+    /// # Arguments
+    ///
+    /// * `started_at` - Unix timestamp of when the execution started fetching feeds.
+    ///
+    /// # Returns
+    ///
+    /// A Result indicating success or failure.
+    pub async fn advance_last_successful_run(&self, started_at: i64) -> Result<()> {
+        let result = self
+            .client
+            .update_item()
+            .table_name(&self.table_name)
+            .key("PK", AttributeValue::S(RUN_STATE_KEY.to_string()))
+            .key("SK", AttributeValue::S(RUN_STATE_KEY.to_string()))
+            .update_expression("SET last_successful_run = :value, #type = :type")
+            .condition_expression(
+                "attribute_not_exists(last_successful_run) OR last_successful_run < :value",
+            )
+            .expression_attribute_names("#type", "_TYPE")
+            .expression_attribute_values(":value", AttributeValue::N(started_at.to_string()))
+            .expression_attribute_values(":type", AttributeValue::S(RUN_STATE_KEY.to_string()))
+            .send()
+            .await;
+
+        match result {
+            Ok(_) => Ok(()),
+            Err(e)
+                if e.as_service_error()
+                    .is_some_and(|e| e.is_conditional_check_failed_exception()) =>
+            {
+                Ok(())
+            }
+            Err(e) => Err(e).context("Failed to record successful run"),
+        }
+    }
+
+    /// Sets a single attribute on the FeedState of a feed, creating the FeedState if needed.
+    async fn set_feed_state_attribute(
+        &self,
+        feed_url: &str,
+        name: &str,
+        value: AttributeValue,
+    ) -> Result<()> {
+        self.client
+            .update_item()
+            .table_name(&self.table_name)
+            .key("PK", AttributeValue::S(feed_url.to_string()))
+            .key("SK", AttributeValue::S("FeedState".to_string()))
+            .update_expression("SET #name = :value, #type = :type")
+            .expression_attribute_names("#name", name)
+            .expression_attribute_names("#type", "_TYPE")
+            .expression_attribute_values(":value", value)
+            .expression_attribute_values(":type", AttributeValue::S("FeedState".to_string()))
+            .send()
+            .await?;
+
+        Ok(())
+    }
+
+    /// Creates or replaces multiple FeedConfigs in DynamoDB using BatchWriteItem.
+    ///
+    /// # Arguments
+    ///
+    /// * `items` - A slice of FeedConfigs to store.
+    ///
+    /// # Returns
+    ///
+    /// A Result indicating success or failure.
+    pub async fn create_feed_configs(&self, items: &[FeedConfig]) -> Result<()> {
+        for chunk in items.chunks(25) {
+            let mut write_requests = Vec::new();
+
+            for item in chunk {
+                let put_request = PutRequest::builder()
+                    .set_item(Some(feed_config_attributes(item)))
+                    .build()
+                    .context("Unable to create put_request")?;
+
+                let write_request = WriteRequest::builder().put_request(put_request).build();
+                write_requests.push(write_request);
+            }
+
+            let mut request_items = HashMap::new();
+            request_items.insert(self.table_name.clone(), write_requests);
+
+            let result = self
+                .client
+                .batch_write_item()
+                .set_request_items(Some(request_items))
+                .send()
+                .await
+                .context("Failed to batch write feed configs")?;
+
+            if let Some(unprocessed_items) = result.unprocessed_items() {
+                if !unprocessed_items.is_empty() {
+                    return Err(anyhow::anyhow!(
+                        "Some feed configs were not processed: {:?}",
+                        unprocessed_items
+                    ));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Lists all FeedConfigs stored in DynamoDB.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the FeedConfigs, or an error if the operation failed.
+    pub async fn list_feed_configs(&self) -> Result<Vec<FeedConfig>> {
+        let mut feed_configs = Vec::new();
+        let mut last_evaluated_key = None;
+
+        loop {
+            let mut query = self
+                .client
+                .query()
+                .table_name(&self.table_name)
+                .key_condition_expression("PK = :pk_val")
+                .expression_attribute_values(
+                    ":pk_val",
+                    AttributeValue::S(FEED_CONFIG_PK.to_string()),
+                );
+
+            if let Some(key) = last_evaluated_key {
+                query = query.set_exclusive_start_key(Some(key));
+            }
+
+            let result = query.send().await.context("Failed to query feed configs")?;
+
+            if let Some(items) = result.items {
+                feed_configs.extend(items.iter().filter_map(feed_config_from_attributes));
+            }
+
+            last_evaluated_key = result.last_evaluated_key;
+
+            if last_evaluated_key.is_none() {
+                break;
+            }
+        }
+
+        Ok(feed_configs)
+    }
+}
+
+/// Converts an ExecutionItem into its DynamoDB attribute map, skipping fields that are not set.
+fn execution_item_attributes(item: &ExecutionItem) -> HashMap<String, AttributeValue> {
+    let mut attributes = HashMap::new();
+    attributes.insert(
+        "PK".to_string(),
+        AttributeValue::S(item.execution_id.clone()),
+    );
+    attributes.insert("SK".to_string(), AttributeValue::S(item.guid.to_string()));
+
+    let mut put_s = |name: &str, value: &Option<String>| {
+        if let Some(value) = value {
+            attributes.insert(name.to_string(), AttributeValue::S(value.clone()));
+        }
+    };
+    put_s("title", &item.title);
+    put_s("description", &item.description);
+    put_s("link", &item.link);
+    put_s("pub_date", &item.pub_date);
+    put_s("feed_url", &item.feed_url);
+    put_s("image_url", &item.image_url);
+    put_s("subtitle", &item.subtitle);
+    put_s("episode", &item.episode);
+    put_s("season", &item.season);
+    put_s("author", &item.author);
+    put_s("feed_title", &item.feed_title);
+    put_s("site_url", &item.site_url);
+    put_s("article_text", &item.article_text);
+    put_s("language", &item.language);
+    put_s("bluesky_secret", &item.bluesky_secret);
+    put_s("reply_control", &item.reply_control);
+    put_s("video_url", &item.video_url);
+    put_s("prefix", &item.prefix);
+    put_s("content_hash", &item.content_hash);
+    put_s("update_of_uri", &item.update_of_uri);
+    put_s("update_of_cid", &item.update_of_cid);
+    put_s("quote_guid", &item.quote_guid);
+    put_string_list(&mut attributes, "categories", &item.categories);
+    put_string_list(&mut attributes, "image_urls", &item.image_urls);
+    put_string_list(&mut attributes, "hashtags", &item.hashtags);
+    put_string_list(&mut attributes, "labels", &item.labels);
+    if item.text_only {
+        attributes.insert("text_only".to_string(), AttributeValue::Bool(true));
+    }
+    put_inference_params(
+        &mut attributes,
+        item.ai_max_tokens,
+        item.ai_temperature,
+        item.ai_top_p,
+    );
+
+    if let Some(ttl) = &item.ttl {
+        attributes.insert("TTL".to_string(), AttributeValue::N(ttl.to_string()));
+    }
+
+    if item._type.is_some() {
+        attributes.insert(
+            "_TYPE".to_string(),
+            AttributeValue::S("ExecutionItem".to_string()),
+        );
+    }
+
+    attributes
+}
+
+/// Builds an ExecutionItem from a DynamoDB attribute map.
+fn execution_item_from_attributes(
+    execution_id: &str,
+    guid: &str,
+    item: &HashMap<String, AttributeValue>,
+) -> ExecutionItem {
+    let get_s = |name: &str| {
+        item.get(name)
+            .and_then(|av| av.as_s().ok())
+            .map(String::from)
+    };
+
+    ExecutionItem {
+        execution_id: execution_id.to_string(),
+        guid: guid.to_string(),
+        title: get_s("title"),
+        description: get_s("description"),
+        link: get_s("link"),
+        summary: get_s("summary"),
+        // Items written before the attribute matched the table's TTL attribute have a lowercase one
+        ttl: get_n(item, "TTL").or_else(|| get_n(item, "ttl")),
+        _type: get_s("_TYPE"),
+        pub_date: get_s("pub_date"),
+        feed_url: get_s("feed_url"),
+        categories: get_string_list(item, "categories"),
+        image_url: get_s("image_url"),
+        subtitle: get_s("subtitle"),
+        episode: get_s("episode"),
+        season: get_s("season"),
+        author: get_s("author"),
+        feed_title: get_s("feed_title"),
+        site_url: get_s("site_url"),
+        article_text: get_s("article_text"),
+        image_urls: get_string_list(item, "image_urls"),
+        video_url: get_s("video_url"),
+        text_only: get_bool(item, "text_only"),
+        prefix: get_s("prefix"),
+        hashtags: get_string_list(item, "hashtags"),
+        labels: get_string_list(item, "labels"),
+        language: get_s("language"),
+        bluesky_secret: get_s("bluesky_secret"),
+        reply_control: get_s("reply_control"),
+        ai_max_tokens: get_n(item, "ai_max_tokens"),
+        ai_temperature: get_n(item, "ai_temperature"),
+        ai_top_p: get_n(item, "ai_top_p"),
+        content_hash: get_s("content_hash"),
+        update_of_uri: get_s("update_of_uri"),
+        update_of_cid: get_s("update_of_cid"),
+        quote_guid: get_s("quote_guid"),
+    }
+}
+
+/// Converts a FeedConfig into its DynamoDB attribute map, skipping fields that are not set.
+fn feed_config_attributes(item: &FeedConfig) -> HashMap<String, AttributeValue> {
+    let mut attributes = HashMap::new();
+    attributes.insert(
+        "PK".to_string(),
+        AttributeValue::S(FEED_CONFIG_PK.to_string()),
+    );
+    attributes.insert("SK".to_string(), AttributeValue::S(item.feed_url.clone()));
+    attributes.insert(
+        "_TYPE".to_string(),
+        AttributeValue::S("FeedConfig".to_string()),
+    );
+
+    let mut put_s = |name: &str, value: &Option<String>| {
+        if let Some(value) = value {
+            attributes.insert(name.to_string(), AttributeValue::S(value.clone()));
+        }
+    };
+    put_s("title", &item.title);
+    put_s("site_url", &item.site_url);
+    put_s("preprocessor", &item.preprocessor);
+    put_s("credentials_secret", &item.credentials_secret);
+    put_s("feed_type", &item.feed_type);
+    put_s("language", &item.language);
+    put_s("bluesky_secret", &item.bluesky_secret);
+    put_s("reply_control", &item.reply_control);
+    put_s("prefix", &item.prefix);
+    if !item.selectors.is_empty() {
+        attributes.insert(
+            "selectors".to_string(),
+            AttributeValue::M(
+                item.selectors
+                    .iter()
+                    .map(|(k, v)| (k.clone(), AttributeValue::S(v.clone())))
+                    .collect(),
+            ),
+        );
+    }
+    if let Some(poll_interval_minutes) = item.poll_interval_minutes {
+        attributes.insert(
+            "poll_interval_minutes".to_string(),
+            AttributeValue::N(poll_interval_minutes.to_string()),
+        );
+    }
+    put_string_list(
+        &mut attributes,
+        "include_categories",
+        &item.include_categories,
+    );
+    put_string_list(
+        &mut attributes,
+        "exclude_categories",
+        &item.exclude_categories,
+    );
+    put_string_list(&mut attributes, "hashtags", &item.hashtags);
+    put_string_list(&mut attributes, "labels", &item.labels);
+    if item.embed_images {
+        attributes.insert("embed_images".to_string(), AttributeValue::Bool(true));
+    }
+    if item.text_only {
+        attributes.insert("text_only".to_string(), AttributeValue::Bool(true));
+    }
+    if let Some(repromote) = item.repromote {
+        attributes.insert("repromote".to_string(), AttributeValue::Bool(repromote));
+    }
+    put_inference_params(
+        &mut attributes,
+        item.ai_max_tokens,
+        item.ai_temperature,
+        item.ai_top_p,
+    );
+
+    attributes
+}
+
+/// Builds a FeedConfig from a DynamoDB attribute map. Returns None if the feed URL is missing.
+fn feed_config_from_attributes(item: &HashMap<String, AttributeValue>) -> Option<FeedConfig> {
+    let get_s = |name: &str| {
+        item.get(name)
+            .and_then(|av| av.as_s().ok())
+            .map(String::from)
+    };
+
+    Some(FeedConfig {
+        feed_url: get_s("SK")?,
+        title: get_s("title"),
+        site_url: get_s("site_url"),
+        include_categories: get_string_list(item, "include_categories"),
+        exclude_categories: get_string_list(item, "exclude_categories"),
+        preprocessor: get_s("preprocessor"),
+        credentials_secret: get_s("credentials_secret"),
+        feed_type: get_s("feed_type"),
+        selectors: item
+            .get("selectors")
+            .and_then(|av| av.as_m().ok())
+            .map(|selectors| {
+                selectors
+                    .iter()
+                    .filter_map(|(k, v)| Some((k.clone(), v.as_s().ok()?.clone())))
+                    .collect()
+            })
+            .unwrap_or_default(),
+        poll_interval_minutes: get_n(item, "poll_interval_minutes").filter(|m| *m > 0),
+        embed_images: get_bool(item, "embed_images"),
+        text_only: get_bool(item, "text_only"),
+        prefix: get_s("prefix"),
+        hashtags: get_string_list(item, "hashtags"),
+        labels: get_string_list(item, "labels"),
+        language: get_s("language"),
+        bluesky_secret: get_s("bluesky_secret"),
+        reply_control: get_s("reply_control"),
+        repromote: item
+            .get("repromote")
+            .and_then(|av| av.as_bool().ok())
+            .copied(),
+        ai_max_tokens: get_n(item, "ai_max_tokens"),
+        ai_temperature: get_n(item, "ai_temperature"),
+        ai_top_p: get_n(item, "ai_top_p"),
+        _type: get_s("_TYPE"),
+    })
+}
+
+/// Adds the inference settings of a feed, as far as they're set.
+fn put_inference_params(
+    attributes: &mut HashMap<String, AttributeValue>,
+    max_tokens: Option<u32>,
+    temperature: Option<f32>,
+    top_p: Option<f32>,
+) {
+    for (name, value) in [
+        ("ai_max_tokens", max_tokens.map(|n| n.to_string())),
+        ("ai_temperature", temperature.map(|n| n.to_string())),
+        ("ai_top_p", top_p.map(|n| n.to_string())),
+    ] {
+        if let Some(value) = value {
+            attributes.insert(name.to_string(), AttributeValue::N(value));
+        }
+    }
+}
+
+/// Builds the SK of a RecentPost, or of an EngagementSample from the time it was sampled. The timestamp is zero padded
+/// so that the SKs sort chronologically.
+fn recent_post_sk(posted_at: i64, guid: &str) -> String {
+    format!("{:012}#{}", posted_at.max(0), guid)
+}
+
+/// Converts a HeldItem into its DynamoDB attribute map.
+fn held_item_attributes(item: &HeldItem) -> HashMap<String, AttributeValue> {
+    let mut attributes = HashMap::from([
+        (
+            "PK".to_string(),
+            AttributeValue::S(HELD_ITEM_PK.to_string()),
+        ),
+        ("SK".to_string(), AttributeValue::S(item.guid.clone())),
+        (
+            "_TYPE".to_string(),
+            AttributeValue::S("HeldItem".to_string()),
+        ),
+        (
+            "execution_id".to_string(),
+            AttributeValue::S(item.execution_id.clone()),
+        ),
+        (
+            "held_at".to_string(),
+            AttributeValue::N(item.held_at.to_string()),
+        ),
+    ]);
+    if let Some(account) = &item.account {
+        attributes.insert("account".to_string(), AttributeValue::S(account.clone()));
+    }
+    attributes
+}
+
+/// Builds a HeldItem from a DynamoDB attribute map. Returns None if the guid or execution id is missing.
+fn held_item_from_attributes(item: &HashMap<String, AttributeValue>) -> Option<HeldItem> {
+    let get_s = |name: &str| {
+        item.get(name)
+            .and_then(|av| av.as_s().ok())
+            .map(String::from)
+    };
+
+    Some(HeldItem {
+        execution_id: get_s("execution_id")?,
+        guid: get_s("SK")?,
+        account: get_s("account"),
+        held_at: get_n(item, "held_at").unwrap_or_default(),
+    })
+}
+
+/// Converts an EngagementSample into its DynamoDB attribute map.
+fn engagement_sample_attributes(sample: &EngagementSample) -> HashMap<String, AttributeValue> {
+    let mut attributes = HashMap::from([
+        (
+            "PK".to_string(),
+            AttributeValue::S(ENGAGEMENT_SAMPLE_PK.to_string()),
+        ),
+        (
+            "SK".to_string(),
+            AttributeValue::S(recent_post_sk(sample.sampled_at, &sample.guid)),
+        ),
+        (
+            "_TYPE".to_string(),
+            AttributeValue::S("EngagementSample".to_string()),
+        ),
+        ("guid".to_string(), AttributeValue::S(sample.guid.clone())),
+        ("uri".to_string(), AttributeValue::S(sample.uri.clone())),
+    ]);
+    for (name, value) in [
+        ("feed_url", &sample.feed_url),
+        ("account", &sample.account),
+        ("template_variant", &sample.template_variant),
+    ] {
+        if let Some(value) = value {
+            attributes.insert(name.to_string(), AttributeValue::S(value.clone()));
+        }
+    }
+    for (name, value) in [
+        ("sampled_at", sample.sampled_at),
+        ("posted_at", sample.posted_at),
+        ("likes", sample.likes),
+        ("reposts", sample.reposts),
+        ("quotes", sample.quotes),
+        ("replies", sample.replies),
+    ] {
+        attributes.insert(name.to_string(), AttributeValue::N(value.to_string()));
+    }
+    if let Some(ttl) = sample.ttl {
+        attributes.insert("TTL".to_string(), AttributeValue::N(ttl.to_string()));
+    }
+    attributes
+}
+
+/// Builds an EngagementSample from a DynamoDB attribute map. Returns None if the guid, URI or sampling time is
+/// missing.
+fn engagement_sample_from_attributes(
+    item: &HashMap<String, AttributeValue>,
+) -> Option<EngagementSample> {
+    let get_s = |name: &str| {
+        item.get(name)
+            .and_then(|av| av.as_s().ok())
+            .map(String::from)
+    };
+
+    Some(EngagementSample {
+        sampled_at: get_n(item, "sampled_at")?,
+        guid: get_s("guid")?,
+        uri: get_s("uri")?,
+        feed_url: get_s("feed_url"),
+        account: get_s("account"),
+        template_variant: get_s("template_variant"),
+        posted_at: get_n(item, "posted_at").unwrap_or_default(),
+        likes: get_n(item, "likes").unwrap_or_default(),
+        reposts: get_n(item, "reposts").unwrap_or_default(),
+        quotes: get_n(item, "quotes").unwrap_or_default(),
+        replies: get_n(item, "replies").unwrap_or_default(),
+        ttl: get_n(item, "TTL"),
+    })
+}
+
+/// Reads a RecordItem from its DynamoDB attribute map. Returns None if the guid is missing.
+fn record_item_from_attributes(item: &HashMap<String, AttributeValue>) -> Option<RecordItem> {
+    let get_s = |name: &str| item.get(name).and_then(|av| av.as_s().ok()).cloned();
+    let get_n = |name: &str| {
+        item.get(name)
+            .and_then(|av| av.as_n().ok())
+            .and_then(|n| n.parse().ok())
+    };
+    Some(RecordItem {
+        guid: get_s("PK")?,
+        uri: get_s("uri"),
+        cid: get_s("cid"),
+        posted_at: get_n("posted_at"),
+        deleted_at: get_n("deleted_at"),
+        content_hash: get_s("content_hash"),
+        template_variant: get_s("template_variant"),
+        claimed_by: get_s("claimed_by"),
+        claimed_at: get_n("claimed_at"),
+        _type: get_s("_TYPE"),
+    })
+}
+
+/// Builds a RecentPost from a DynamoDB attribute map. Returns None if the guid or posting time is missing.
+fn recent_post_from_attributes(item: &HashMap<String, AttributeValue>) -> Option<RecentPost> {
+    let get_s = |name: &str| {
+        item.get(name)
+            .and_then(|av| av.as_s().ok())
+            .map(String::from)
+    };
+
+    Some(RecentPost {
+        posted_at: get_n(item, "posted_at")?,
+        guid: get_s("guid")?,
+        title: get_s("title"),
+        link: get_s("link"),
+        feed_url: get_s("feed_url"),
+        account: get_s("account"),
+        ttl: get_n(item, "TTL"),
+    })
+}
+
+/// Reads a number attribute. Returns None if it's missing or not a valid number of the requested type.
+fn get_n<T: std::str::FromStr>(item: &HashMap<String, AttributeValue>, name: &str) -> Option<T> {
+    item.get(name)
+        .and_then(|av| av.as_n().ok())
+        .and_then(|n| n.parse().ok())
+}
+
+/// Reads a boolean attribute, which counts as false when it's missing.
+fn get_bool(item: &HashMap<String, AttributeValue>, name: &str) -> bool {
+    item.get(name)
+        .and_then(|av| av.as_bool().ok())
+        .copied()
+        .unwrap_or(false)
+}
+
+/// Stores a list of strings as a DynamoDB list attribute. Empty lists are not stored.
+fn put_string_list(
+    attributes: &mut HashMap<String, AttributeValue>,
+    name: &str,
+    values: &[String],
+) {
+    if !values.is_empty() {
+        attributes.insert(
+            name.to_string(),
+            AttributeValue::L(values.iter().cloned().map(AttributeValue::S).collect()),
+        );
+    }
+}
+
+/// Reads a list of strings from a DynamoDB list or string set attribute. Missing attributes yield an empty list.
+fn get_string_list(item: &HashMap<String, AttributeValue>, name: &str) -> Vec<String> {
+    match item.get(name) {
+        Some(AttributeValue::L(values)) => values
+            .iter()
+            .filter_map(|av| av.as_s().ok())
+            .cloned()
+            .collect(),
+        Some(AttributeValue::Ss(values)) => values.clone(),
+        _ => Vec::new(),
     }
 }
@@ -0,0 +1,46 @@
+use rand::Rng;
+use std::time::Duration;
+
+/// Upper bound for a single backoff delay.
+pub const MAX_BACKOFF_DELAY: Duration = Duration::from_secs(20);
+
+/// Computes how long to wait before the next retry, using exponential backoff with full jitter.
+///
+/// The delay is a random duration between zero and `base_delay * 2^attempt`, capped at [`MAX_BACKOFF_DELAY`].
+/// The jitter keeps concurrent executions that failed together from retrying in lockstep.
+///
+/// # Arguments
+///
+/// * `base_delay` - The delay ceiling for the first retry.
+/// * `attempt` - The number of the retry being scheduled, starting at 0.
+pub fn backoff_delay(base_delay: Duration, attempt: u32) -> Duration {
+    let ceiling = base_delay
+        .saturating_mul(2u32.saturating_pow(attempt))
+        .min(MAX_BACKOFF_DELAY);
+    let millis = ceiling.as_millis() as u64;
+    if millis == 0 {
+        return Duration::ZERO;
+    }
+    Duration::from_millis(rand::thread_rng().gen_range(0..=millis))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff_delay() {
+        let base = Duration::from_millis(100);
+        for attempt in 0..5 {
+            let ceiling = Duration::from_millis(100 * 2u64.pow(attempt));
+            for _ in 0..20 {
+                assert!(backoff_delay(base, attempt) <= ceiling);
+            }
+        }
+
+        for _ in 0..20 {
+            assert!(backoff_delay(base, 30) <= MAX_BACKOFF_DELAY);
+        }
+        assert_eq!(backoff_delay(Duration::ZERO, 3), Duration::ZERO);
+    }
+}
@@ -0,0 +1,207 @@
+use std::time::Duration;
+
+/// The longest crawl delay honoured. Sites asking for more would otherwise keep a lambda waiting until it times out.
+pub const MAX_CRAWL_DELAY: Duration = Duration::from_secs(60);
+
+/// The rules of a robots.txt file (RFC 9309) that apply to one user agent.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Robots {
+    rules: Vec<Rule>,
+    crawl_delay: Option<Duration>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Rule {
+    allow: bool,
+    pattern: String,
+}
+
+impl Robots {
+    /// Rules that allow everything, used when a site has no robots.txt.
+    pub fn allow_all() -> Self {
+        Self::default()
+    }
+
+    /// Rules that disallow everything, used when a site's robots.txt can't be retrieved.
+    pub fn disallow_all() -> Self {
+        Self {
+            rules: vec![Rule {
+                allow: false,
+                pattern: "/".to_string(),
+            }],
+            crawl_delay: None,
+        }
+    }
+
+    /// Parses a robots.txt file.
+    ///
+    /// The groups naming `user_agent` apply. When there is none, the `*` groups apply. Unknown lines are ignored.
+    ///
+    /// # Arguments
+    ///
+    /// * `content` - The content of the robots.txt file.
+    /// * `user_agent` - The product token of the crawler, e.g. `rss-bluesky-bridge`.
+    ///
+    /// # Returns
+    ///
+    /// The rules for the user agent.
+    pub fn parse(content: &str, user_agent: &str) -> Self {
+        let user_agent = user_agent.to_lowercase();
+        let mut groups: Vec<(Vec<String>, Robots)> = Vec::new();
+        let mut in_agent_lines = false;
+
+        for line in content.lines() {
+            let line = line.split('#').next().unwrap_or_default();
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+            let key = key.trim().to_lowercase();
+            let value = value.trim();
+
+            if key == "user-agent" {
+                // Consecutive user-agent lines share one group
+                if !in_agent_lines {
+                    groups.push((Vec::new(), Robots::default()));
+                }
+                if let Some((agents, _)) = groups.last_mut() {
+                    agents.push(value.to_lowercase());
+                }
+                in_agent_lines = true;
+                continue;
+            }
+            in_agent_lines = false;
+
+            let Some((_, robots)) = groups.last_mut() else {
+                continue;
+            };
+            match key.as_str() {
+                "allow" | "disallow" if !value.is_empty() => robots.rules.push(Rule {
+                    allow: key == "allow",
+                    pattern: value.to_string(),
+                }),
+                "crawl-delay" => {
+                    // Delays too large for a Duration are capped like any other long delay
+                    robots.crawl_delay = value
+                        .parse::<f64>()
+                        .ok()
+                        .filter(|secs| !secs.is_nan() && *secs >= 0.0)
+                        .map(|secs| {
+                            Duration::try_from_secs_f64(secs)
+                                .unwrap_or(MAX_CRAWL_DELAY)
+                                .min(MAX_CRAWL_DELAY)
+                        })
+                }
+                _ => {}
+            }
+        }
+
+        let select = |matches: &dyn Fn(&str) -> bool| {
+            groups
+                .iter()
+                .filter(|(agents, _)| agents.iter().any(|a| matches(a)))
+                .fold(None, |merged: Option<Robots>, (_, robots)| {
+                    let mut merged = merged.unwrap_or_default();
+                    merged.rules.extend(robots.rules.iter().cloned());
+                    merged.crawl_delay = merged.crawl_delay.or(robots.crawl_delay);
+                    Some(merged)
+                })
+        };
+
+        select(&|agent| agent == user_agent)
+            .or_else(|| select(&|agent| agent == "*"))
+            .unwrap_or_default()
+    }
+
+    /// Checks whether a path (including its query string) may be fetched.
+    ///
+    /// The rule with the longest matching pattern decides; on a tie Allow wins. Paths no rule matches are allowed.
+    pub fn is_allowed(&self, path: &str) -> bool {
+        self.rules
+            .iter()
+            .filter(|rule| pattern_matches(&rule.pattern, path))
+            .max_by_key(|rule| (rule.pattern.len(), rule.allow))
+            .is_none_or(|rule| rule.allow)
+    }
+
+    /// The Crawl-delay requested for the user agent, if any.
+    pub fn crawl_delay(&self) -> Option<Duration> {
+        self.crawl_delay
+    }
+}
+
+/// Matches a path against a robots.txt pattern, where `*` matches any sequence of characters and a trailing `$`
+/// anchors the pattern at the end of the path.
+fn pattern_matches(pattern: &str, path: &str) -> bool {
+    let (pattern, anchored) = match pattern.strip_suffix('$') {
+        Some(pattern) => (pattern, true),
+        None => (pattern, false),
+    };
+
+    let mut parts = pattern.split('*');
+    let Some(mut rest) = path.strip_prefix(parts.next().unwrap_or_default()) else {
+        return false;
+    };
+
+    let parts: Vec<&str> = parts.collect();
+    for (index, part) in parts.iter().enumerate() {
+        if anchored && index == parts.len() - 1 {
+            return rest.ends_with(part);
+        }
+        match rest.find(part) {
+            Some(position) => rest = &rest[position + part.len()..],
+            None => return false,
+        }
+    }
+
+    !anchored || rest.is_empty()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_robots() {
+        let content = "
+# Comment
+User-agent: *
+Disallow: /private
+Allow: /private/open
+Disallow: /*.pdf$
+
+User-agent: other-bot
+User-agent: rss-bluesky-bridge
+Disallow: /feeds-only
+Crawl-delay: 2.5
+";
+        let robots = Robots::parse(content, "rss-bluesky-bridge");
+        assert!(!robots.is_allowed("/feeds-only/page"));
+        assert!(robots.is_allowed("/private"));
+        assert_eq!(robots.crawl_delay(), Some(Duration::from_millis(2500)));
+
+        let robots = Robots::parse(content, "some-crawler");
+        assert!(robots.is_allowed("/"));
+        assert!(!robots.is_allowed("/private/page"));
+        assert!(robots.is_allowed("/private/open/page"));
+        assert!(!robots.is_allowed("/docs/file.pdf"));
+        assert!(robots.is_allowed("/docs/file.pdf?download=1"));
+        assert_eq!(robots.crawl_delay(), None);
+
+        // Delays past the cap, or too large for a Duration, wait MAX_CRAWL_DELAY
+        for delay in ["86400", "1e30", "inf"] {
+            let content = format!("User-agent: *\nCrawl-delay: {}\n", delay);
+            assert_eq!(
+                Robots::parse(&content, "x").crawl_delay(),
+                Some(MAX_CRAWL_DELAY)
+            );
+        }
+        assert_eq!(
+            Robots::parse("User-agent: *\nCrawl-delay: -1\n", "x").crawl_delay(),
+            None
+        );
+
+        assert!(Robots::parse("User-agent: *\nDisallow:\n", "x").is_allowed("/"));
+        assert!(Robots::allow_all().is_allowed("/anything"));
+        assert!(!Robots::disallow_all().is_allowed("/anything"));
+    }
+}
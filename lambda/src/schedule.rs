@@ -0,0 +1,189 @@
+use crate::models::HeldItem;
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Duration, NaiveTime, Utc};
+use chrono_tz::Tz;
+use std::collections::HashMap;
+
+/// The hours of the day items are posted in, such as 08:00-22:00 in the timezone of the readers.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PostingWindow {
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+    pub timezone: Tz,
+}
+
+impl PostingWindow {
+    /// Parses a window.
+    ///
+    /// # Arguments
+    ///
+    /// * `window` - The start and end of the window as `HH:MM-HH:MM`. A window ending before it starts, such as
+    ///   `18:00-02:00`, spans midnight.
+    /// * `timezone` - The IANA name of the timezone the times are in, e.g. `Europe/Berlin`.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the window, or an error if the times or the timezone aren't valid.
+    pub fn parse(window: &str, timezone: &str) -> Result<Self> {
+        let (start, end) = window.split_once('-').with_context(|| {
+            format!("Invalid posting window {:?}, expected HH:MM-HH:MM", window)
+        })?;
+        let parse_time = |time: &str| {
+            NaiveTime::parse_from_str(time.trim(), "%H:%M")
+                .with_context(|| format!("Invalid time {:?} in posting window", time.trim()))
+        };
+        let (start, end) = (parse_time(start)?, parse_time(end)?);
+        if start == end {
+            return Err(anyhow!("Posting window {:?} is empty", window));
+        }
+        let timezone = timezone
+            .trim()
+            .parse::<Tz>()
+            .map_err(|_| anyhow!("Unknown timezone {:?}", timezone))?;
+        Ok(Self {
+            start,
+            end,
+            timezone,
+        })
+    }
+
+    /// Tells whether posts may go out at the given time. The window includes its start but not its end.
+    pub fn contains(&self, at: DateTime<Utc>) -> bool {
+        let time = at.with_timezone(&self.timezone).time();
+        if self.start < self.end {
+            self.start <= time && time < self.end
+        } else {
+            time >= self.start || time < self.end
+        }
+    }
+}
+
+/// Returns the Unix timestamp of the midnight the day of `at` started with in `timezone`.
+pub fn start_of_day(at: DateTime<Utc>, timezone: Tz) -> i64 {
+    at.with_timezone(&timezone)
+        .date_naive()
+        .and_time(NaiveTime::MIN)
+        .and_local_timezone(timezone)
+        .earliest()
+        // Where clocks skip midnight for summer time, the day starts an hour later
+        .or_else(|| {
+            (at.with_timezone(&timezone)
+                .date_naive()
+                .and_time(NaiveTime::MIN)
+                + Duration::hours(1))
+            .and_local_timezone(timezone)
+            .earliest()
+        })
+        .map_or(at.timestamp(), |midnight| midnight.timestamp())
+}
+
+/// Splits items into those that can be posted today and those over the daily post cap of their account.
+///
+/// # Arguments
+///
+/// * `items` - The items, in the order they are posted in.
+/// * `posted_today` - How many posts each account made today, by account.
+/// * `max_posts_per_day` - The most posts an account makes a day.
+/// * `counts` - Tells whether an item counts towards the cap. Those that don't are always posted.
+///
+/// # Returns
+///
+/// The items to post today and those left for a later day, both in their original order.
+pub fn apply_daily_cap(
+    items: Vec<HeldItem>,
+    posted_today: &HashMap<Option<String>, usize>,
+    max_posts_per_day: usize,
+    counts: impl Fn(&HeldItem) -> bool,
+) -> (Vec<HeldItem>, Vec<HeldItem>) {
+    let mut posts = posted_today.clone();
+    items.into_iter().partition(|item| {
+        if !counts(item) {
+            return true;
+        }
+        let posted = posts.entry(item.account.clone()).or_default();
+        *posted += 1;
+        *posted <= max_posts_per_day
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_posting_window() {
+        let window = PostingWindow::parse("08:00-22:00", "Europe/Berlin").unwrap();
+        // 06:30 UTC is 08:30 in Berlin in summer, and 07:30 in winter
+        assert!(window.contains(Utc.with_ymd_and_hms(2024, 7, 1, 6, 30, 0).unwrap()));
+        assert!(!window.contains(Utc.with_ymd_and_hms(2024, 1, 1, 6, 30, 0).unwrap()));
+        assert!(!window.contains(Utc.with_ymd_and_hms(2024, 7, 1, 20, 0, 0).unwrap()));
+
+        let window = PostingWindow::parse("18:00 - 02:00", "UTC").unwrap();
+        assert!(window.contains(Utc.with_ymd_and_hms(2024, 7, 1, 23, 0, 0).unwrap()));
+        assert!(window.contains(Utc.with_ymd_and_hms(2024, 7, 1, 1, 59, 0).unwrap()));
+        assert!(!window.contains(Utc.with_ymd_and_hms(2024, 7, 1, 2, 0, 0).unwrap()));
+        assert!(!window.contains(Utc.with_ymd_and_hms(2024, 7, 1, 12, 0, 0).unwrap()));
+
+        assert!(PostingWindow::parse("08:00", "UTC").is_err());
+        assert!(PostingWindow::parse("08:00-25:00", "UTC").is_err());
+        assert!(PostingWindow::parse("08:00-08:00", "UTC").is_err());
+        assert!(PostingWindow::parse("08:00-22:00", "Mars/Olympus").is_err());
+    }
+
+    #[test]
+    fn test_start_of_day() {
+        let at = Utc.with_ymd_and_hms(2024, 7, 1, 21, 30, 0).unwrap();
+        assert_eq!(
+            start_of_day(at, Tz::UTC),
+            Utc.with_ymd_and_hms(2024, 7, 1, 0, 0, 0)
+                .unwrap()
+                .timestamp()
+        );
+        // It's already July 2nd in Tokyo
+        assert_eq!(
+            start_of_day(at, Tz::Asia__Tokyo),
+            Utc.with_ymd_and_hms(2024, 7, 1, 15, 0, 0)
+                .unwrap()
+                .timestamp()
+        );
+        // Clocks in Santiago went from midnight to 01:00 on September 8th 2024
+        assert_eq!(
+            start_of_day(
+                Utc.with_ymd_and_hms(2024, 9, 8, 12, 0, 0).unwrap(),
+                Tz::America__Santiago
+            ),
+            Utc.with_ymd_and_hms(2024, 9, 8, 4, 0, 0)
+                .unwrap()
+                .timestamp()
+        );
+    }
+
+    #[test]
+    fn test_apply_daily_cap() {
+        let item = |guid: &str, account: Option<&str>| HeldItem {
+            execution_id: "execution".to_string(),
+            guid: guid.to_string(),
+            account: account.map(String::from),
+            held_at: 0,
+        };
+        let items = vec![
+            item("a", None),
+            item("b", Some("other")),
+            item("c", None),
+            item("d", None),
+            item("e", Some("other")),
+        ];
+        let posted_today = HashMap::from([(None, 1)]);
+
+        let (posts, held) = apply_daily_cap(items.clone(), &posted_today, 2, |_| true);
+        let guids = |items: &[HeldItem]| items.iter().map(|i| i.guid.clone()).collect::<Vec<_>>();
+        assert_eq!(guids(&posts), vec!["a", "b", "e"]);
+        assert_eq!(guids(&held), vec!["c", "d"]);
+
+        // Items that don't count go out either way, and leave room for the others
+        let (posts, held) = apply_daily_cap(items, &posted_today, 2, |i| i.guid != "a");
+        assert_eq!(guids(&posts), vec!["a", "b", "c", "e"]);
+        assert_eq!(guids(&held), vec!["d"]);
+    }
+}
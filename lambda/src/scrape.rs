@@ -0,0 +1,176 @@
+use crate::feed::parse_date;
+use crate::html::{block_text, resolve_url};
+use anyhow::{anyhow, Result};
+use rss::{Channel, Guid, Item};
+use scraper::{ElementRef, Html, Selector};
+use std::collections::HashMap;
+
+/// Builds a feed from a web page that lists articles but doesn't publish one itself.
+///
+/// The selectors are CSS selectors, keyed by field:
+///
+/// * `item` (required) - Matches one element per article.
+/// * `link` - Within an item, the element whose `href` is the article URL. Defaults to the first `a[href]`.
+/// * `title` - Within an item, the element holding the title. Defaults to the text of the link.
+/// * `date` - Within an item, the element holding the publication date, from its `datetime` or `content`
+///   attribute or else its text. Undated items are only processed when ACCEPT_UNDATED_ITEMS is set.
+/// * `description` - Within an item, the element holding a teaser.
+///
+/// # Arguments
+///
+/// * `html` - The HTML of the page.
+/// * `page_url` - The URL of the page, used to resolve relative links.
+/// * `selectors` - The CSS selectors of the fields.
+///
+/// # Returns
+///
+/// A Result containing a Channel with one Item per article that has a link, or an error if a selector is invalid.
+pub fn scrape_channel(
+    html: &str,
+    page_url: &str,
+    selectors: &HashMap<String, String>,
+) -> Result<Channel> {
+    let selector = |field: &str| -> Result<Option<Selector>> {
+        selectors
+            .get(field)
+            .map(|s| {
+                Selector::parse(s).map_err(|e| anyhow!("Invalid {} selector {:?}: {}", field, s, e))
+            })
+            .transpose()
+    };
+
+    let item_selector =
+        selector("item")?.ok_or_else(|| anyhow!("The item selector is required"))?;
+    let link_selector = match selector("link")? {
+        Some(selector) => selector,
+        None => Selector::parse("a[href]").map_err(|e| anyhow!("{}", e))?,
+    };
+    let title_selector = selector("title")?;
+    let date_selector = selector("date")?;
+    let description_selector = selector("description")?;
+
+    let document = Html::parse_document(html);
+    let items: Vec<Item> = document
+        .select(&item_selector)
+        .filter_map(|element| {
+            let link_element = if link_selector.matches(&element) {
+                element
+            } else {
+                element.select(&link_selector).next()?
+            };
+            let link = resolve_url(page_url, link_element.value().attr("href")?.trim())?;
+
+            let title = match &title_selector {
+                Some(selector) => element.select(selector).next().map(|e| text(&e)),
+                None => Some(text(&link_element)),
+            }
+            .filter(|t| !t.is_empty());
+
+            let pub_date = date_selector.as_ref().and_then(|selector| {
+                let date_element = element.select(selector).next()?;
+                let value = date_element
+                    .value()
+                    .attr("datetime")
+                    .or(date_element.value().attr("content"))
+                    .map(String::from)
+                    .unwrap_or_else(|| text(&date_element));
+                parse_date(&value)
+            });
+
+            let description = description_selector.as_ref().and_then(|selector| {
+                let description = element.select(selector).next()?;
+                Some(block_text(&description))
+                    .filter(|d| !d.is_empty())
+                    .or_else(|| Some(text(&description)))
+                    .filter(|d| !d.is_empty())
+            });
+
+            let mut item = Item::default();
+            item.set_guid(Guid {
+                value: link.clone(),
+                permalink: true,
+            });
+            item.set_link(link);
+            item.set_title(title);
+            item.set_description(description);
+            item.set_pub_date(pub_date.map(|d| d.to_rfc2822()));
+            Some(item)
+        })
+        .collect();
+
+    let mut channel = Channel::default();
+    if let Some(title) = Selector::parse("title")
+        .ok()
+        .and_then(|selector| document.select(&selector).next().map(|e| text(&e)))
+    {
+        channel.set_title(title);
+    }
+    channel.set_link(page_url);
+    channel.set_items(items);
+    Ok(channel)
+}
+
+/// The text of an element with whitespace collapsed.
+fn text(element: &ElementRef) -> String {
+    element
+        .text()
+        .flat_map(str::split_whitespace)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scrape_channel() {
+        let html = r#"<html><head><title>Example News</title></head><body>
+<ul class="posts">
+  <li class="post">
+    <a class="headline" href="/2024/first">First <em>post</em></a>
+    <time datetime="2024-11-20T10:00:00Z">Nov 20</time>
+    <p class="teaser">The   first teaser.</p>
+  </li>
+  <li class="post"><a class="headline" href="https://other.example.com/second">Second post</a><span class="date">2024-11-19</span></li>
+  <li class="post">No link here</li>
+</ul></body></html>"#;
+
+        let selectors: HashMap<String, String> = [
+            ("item", "li.post"),
+            ("link", "a.headline"),
+            ("date", "time, .date"),
+            ("description", ".teaser"),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+
+        let channel = scrape_channel(html, "https://example.com/news/", &selectors).unwrap();
+        assert_eq!(channel.title(), "Example News");
+        assert_eq!(channel.link(), "https://example.com/news/");
+
+        let items = channel.items();
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0].title(), Some("First post"));
+        assert_eq!(items[0].link(), Some("https://example.com/2024/first"));
+        assert_eq!(
+            items[0].guid().map(|g| g.value()),
+            Some("https://example.com/2024/first")
+        );
+        assert_eq!(
+            items[0].pub_date().and_then(parse_date),
+            parse_date("2024-11-20T10:00:00Z")
+        );
+        assert_eq!(items[0].description(), Some("The first teaser."));
+
+        assert_eq!(items[1].title(), Some("Second post"));
+        assert_eq!(
+            items[1].pub_date().and_then(parse_date),
+            parse_date("2024-11-19")
+        );
+        assert_eq!(items[1].description(), None);
+
+        assert!(scrape_channel(html, "https://example.com/", &HashMap::new()).is_err());
+    }
+}
@@ -0,0 +1,439 @@
+use crate::models::{ExecutionItem, RecentPost};
+use crate::url_utils::dedupe_key;
+use std::collections::{HashMap, HashSet};
+
+/// Length of the character shingles titles are compared by.
+const SHINGLE_CHARS: usize = 3;
+
+/// Measures how similar two titles are, from 0.0 (nothing in common) to 1.0 (the same words).
+///
+/// Titles are compared case insensitively and without punctuation, as the Jaccard similarity of their character
+/// trigrams ("shingles"). This tolerates the small edits syndicated copies of a story tend to have, such as a
+/// changed word, different quotes or a source suffix, while unrelated titles score close to 0.
+///
+/// # Arguments
+///
+/// * `a` - The first title.
+/// * `b` - The second title.
+///
+/// # Returns
+///
+/// The similarity of the titles. Titles without any letters or digits have a similarity of 0.0.
+pub fn title_similarity(a: &str, b: &str) -> f64 {
+    let (a, b) = (normalize(a), normalize(b));
+    if a.is_empty() || b.is_empty() {
+        return 0.0;
+    }
+    if a == b {
+        return 1.0;
+    }
+
+    let (a, b) = (shingles(&a), shingles(&b));
+    let intersection = a.intersection(&b).count();
+    let union = a.union(&b).count();
+    intersection as f64 / union as f64
+}
+
+/// Drops items whose guid was already seen earlier in the run, keeping the first occurrence.
+///
+/// ExecutionItems are keyed by execution id and guid, so a repeated guid (within one feed, or across feeds that
+/// syndicate the same content) would overwrite its twin in DynamoDB (or fail the batch write outright) while the map
+/// state still processed it twice.
+///
+/// # Arguments
+///
+/// * `execution_items` - The items of the run, in the order they were fetched in.
+///
+/// # Returns
+///
+/// The items with distinct guids, in their original order.
+pub fn dedupe_by_guid(execution_items: Vec<ExecutionItem>) -> Vec<ExecutionItem> {
+    let mut seen: HashMap<String, Option<String>> = HashMap::new();
+    execution_items
+        .into_iter()
+        .filter(|item| match seen.get(&item.guid) {
+            Some(first_feed_url) => {
+                tracing::warn!(
+                    "Dropping item {:?} from feed {:?} as its guid {} was already seen in feed {:?}",
+                    item.title,
+                    item.feed_url,
+                    item.guid,
+                    first_feed_url
+                );
+                false
+            }
+            None => {
+                seen.insert(item.guid.clone(), item.feed_url.clone());
+                true
+            }
+        })
+        .collect()
+}
+
+/// Drops items that tell the same story as an item posted recently, or as an item earlier in the run, from
+/// another feed.
+///
+/// Items are the same story when their links normalize to the same URL, or when they come from different feeds and
+/// their titles are at least `title_similarity_threshold` similar. Titles within a feed aren't compared, as feeds
+/// often reuse titles such as "Weekly update" for different items. A recent post of the item itself isn't a
+/// duplicate, as whether it's posted again is up to check-dynamodb.
+///
+/// # Arguments
+///
+/// * `execution_items` - The items of the run, in the order they are posted in.
+/// * `recent_posts` - The items posted recently, with links as dedupe keys.
+/// * `title_similarity_threshold` - How similar titles have to be for the same story, see [`title_similarity`].
+///   0.0 turns the title comparison off.
+///
+/// # Returns
+///
+/// The items that aren't duplicates, in their original order.
+pub fn suppress_near_duplicates(
+    execution_items: Vec<ExecutionItem>,
+    recent_posts: &[RecentPost],
+    title_similarity_threshold: f64,
+) -> Vec<ExecutionItem> {
+    // Everything that's been kept so far, with links as dedupe keys
+    let mut seen = recent_posts.to_vec();
+
+    execution_items
+        .into_iter()
+        .filter(|item| {
+            let link = item.link.as_deref().map(dedupe_key);
+            let duplicate = seen.iter().find(|seen| {
+                if seen.guid == item.guid {
+                    return false;
+                }
+                if link.is_some() && seen.link == link {
+                    return true;
+                }
+                match (&item.title, &seen.title) {
+                    (Some(title), Some(seen_title))
+                        if title_similarity_threshold > 0.0 && seen.feed_url != item.feed_url =>
+                    {
+                        title_similarity(title, seen_title) >= title_similarity_threshold
+                    }
+                    _ => false,
+                }
+            });
+
+            match duplicate {
+                Some(seen) => {
+                    tracing::info!(
+                        "Skipping item {:?} from feed {:?} as it looks like {:?} from feed {:?}",
+                        item.title,
+                        item.feed_url,
+                        seen.title,
+                        seen.feed_url
+                    );
+                    false
+                }
+                None => {
+                    seen.push(RecentPost {
+                        guid: item.guid.clone(),
+                        link,
+                        title: item.title.clone(),
+                        feed_url: item.feed_url.clone(),
+                        ..Default::default()
+                    });
+                    true
+                }
+            }
+        })
+        .collect()
+}
+
+/// Points items at the recent post whose title is most like theirs, as earlier coverage of the same story for their
+/// posts to quote.
+///
+/// Only titles are compared, as items linking to the same page as a recent post were suppressed as duplicates
+/// already, and only with the posts of other feeds, as feeds often reuse titles such as "Weekly update" for
+/// unrelated items.
+///
+/// # Arguments
+///
+/// * `execution_items` - The items of the run.
+/// * `recent_posts` - The items posted recently.
+/// * `title_similarity_threshold` - How similar titles have to be for the same story, see [`title_similarity`].
+pub fn link_related_posts(
+    execution_items: &mut [ExecutionItem],
+    recent_posts: &[RecentPost],
+    title_similarity_threshold: f64,
+) {
+    for item in execution_items.iter_mut() {
+        let Some(title) = &item.title else {
+            continue;
+        };
+        let related = recent_posts
+            .iter()
+            .filter(|post| post.guid != item.guid && post.feed_url != item.feed_url)
+            .filter_map(|post| Some((post, title_similarity(title, post.title.as_deref()?))))
+            .filter(|(_, similarity)| *similarity >= title_similarity_threshold)
+            .max_by(|(_, a), (_, b)| a.total_cmp(b));
+
+        if let Some((post, similarity)) = related {
+            tracing::info!(
+                "Item {:?} follows up on {:?} (title similarity {:.2}), quoting it",
+                title,
+                post.title,
+                similarity
+            );
+            item.quote_guid = Some(post.guid.clone());
+        }
+    }
+}
+
+/// Lowercases a title and reduces it to its words, separated by single spaces.
+fn normalize(title: &str) -> String {
+    title
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn shingles(text: &str) -> HashSet<Vec<char>> {
+    let chars: Vec<char> = text.chars().collect();
+    if chars.len() <= SHINGLE_CHARS {
+        return HashSet::from([chars]);
+    }
+    chars.windows(SHINGLE_CHARS).map(<[char]>::to_vec).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_title_similarity() {
+        assert_eq!(
+            title_similarity("Rust 1.83 released!", "rust 1.83: RELEASED"),
+            1.0
+        );
+
+        let syndicated = title_similarity(
+            "Central bank raises interest rates to fight inflation",
+            "Central bank raises interest rates to fight inflation - Reuters",
+        );
+        assert!(syndicated > 0.8, "{}", syndicated);
+
+        let edited = title_similarity(
+            "Central bank raises interest rates to fight inflation",
+            "Central bank hikes interest rates to fight inflation",
+        );
+        assert!(edited > 0.75, "{}", edited);
+
+        let unrelated = title_similarity(
+            "Central bank raises interest rates to fight inflation",
+            "Local team wins the championship after extra time",
+        );
+        assert!(unrelated < 0.2, "{}", unrelated);
+
+        assert_eq!(title_similarity("", "Anything"), 0.0);
+        assert_eq!(title_similarity("Go", "Go"), 1.0);
+    }
+
+    fn item(guid: &str, title: &str, link: &str, feed_url: &str) -> ExecutionItem {
+        ExecutionItem {
+            guid: guid.to_string(),
+            title: Some(title.to_string()),
+            link: Some(link.to_string()),
+            feed_url: Some(feed_url.to_string()),
+            ..Default::default()
+        }
+    }
+
+    fn post(guid: &str, title: &str, link: &str, feed_url: &str) -> RecentPost {
+        RecentPost {
+            guid: guid.to_string(),
+            title: Some(title.to_string()),
+            link: Some(dedupe_key(link)),
+            feed_url: Some(feed_url.to_string()),
+            ..Default::default()
+        }
+    }
+
+    fn guids(items: &[ExecutionItem]) -> Vec<&str> {
+        items.iter().map(|item| item.guid.as_str()).collect()
+    }
+
+    #[test]
+    fn test_dedupe_by_guid() {
+        let items = vec![
+            item(
+                "a",
+                "First",
+                "https://a.example/1",
+                "https://a.example/feed",
+            ),
+            item(
+                "b",
+                "Second",
+                "https://a.example/2",
+                "https://a.example/feed",
+            ),
+            item(
+                "a",
+                "Syndicated first",
+                "https://b.example/1",
+                "https://b.example/feed",
+            ),
+        ];
+        let items = dedupe_by_guid(items);
+        assert_eq!(guids(&items), ["a", "b"]);
+        // The first occurrence wins
+        assert_eq!(items[0].title.as_deref(), Some("First"));
+        assert_eq!(items[0].feed_url.as_deref(), Some("https://a.example/feed"));
+    }
+
+    #[test]
+    fn test_suppress_near_duplicates_same_guid() {
+        // A recent post of the item itself is left to check-dynamodb
+        let items = vec![item(
+            "a",
+            "Rust 1.83 released",
+            "https://a.example/rust",
+            "https://a.example/feed",
+        )];
+        let recent = [post(
+            "a",
+            "Rust 1.83 released",
+            "https://a.example/rust",
+            "https://a.example/feed",
+        )];
+        assert_eq!(guids(&suppress_near_duplicates(items, &recent, 0.8)), ["a"]);
+    }
+
+    #[test]
+    fn test_suppress_near_duplicates_link() {
+        let items = vec![
+            item(
+                "a",
+                "Rust 1.83 released",
+                "https://news.example/rust/",
+                "https://a.example/feed",
+            ),
+            item(
+                "b",
+                "Something else entirely",
+                "http://news.example/rust",
+                "https://b.example/feed",
+            ),
+            item(
+                "c",
+                "Unrelated",
+                "https://news.example/other",
+                "https://a.example/feed",
+            ),
+        ];
+        let recent = [post(
+            "d",
+            "Yet another title",
+            "https://news.example/other",
+            "https://c.example/feed",
+        )];
+        // The second links to the same page as the first, however unlike their titles, and the third to a recent post
+        assert_eq!(guids(&suppress_near_duplicates(items, &recent, 0.8)), ["a"]);
+    }
+
+    #[test]
+    fn test_suppress_near_duplicates_title_across_feeds() {
+        let items = vec![
+            item(
+                "a",
+                "Central bank raises interest rates",
+                "https://a.example/1",
+                "https://a.example/feed",
+            ),
+            item(
+                "b",
+                "Central bank raises interest rates - Reuters",
+                "https://b.example/1",
+                "https://b.example/feed",
+            ),
+            item(
+                "c",
+                "Weekly update",
+                "https://c.example/2",
+                "https://c.example/feed",
+            ),
+        ];
+        let recent = [post(
+            "d",
+            "Weekly update",
+            "https://c.example/1",
+            "https://c.example/feed",
+        )];
+        // Titles within a feed aren't compared, so the third isn't a duplicate of the recent post from its own feed
+        assert_eq!(
+            guids(&suppress_near_duplicates(items, &recent, 0.8)),
+            ["a", "c"]
+        );
+    }
+
+    #[test]
+    fn test_suppress_near_duplicates_threshold_off() {
+        let items = vec![
+            item(
+                "a",
+                "Central bank raises interest rates",
+                "https://a.example/1",
+                "https://a.example/feed",
+            ),
+            item(
+                "b",
+                "Central bank raises interest rates",
+                "https://b.example/1",
+                "https://b.example/feed",
+            ),
+        ];
+        assert_eq!(
+            guids(&suppress_near_duplicates(items, &[], 0.0)),
+            ["a", "b"]
+        );
+    }
+
+    #[test]
+    fn test_link_related_posts() {
+        let mut items = vec![
+            item(
+                "a",
+                "Central bank raises interest rates again",
+                "https://a.example/2",
+                "https://a.example/feed",
+            ),
+            item(
+                "b",
+                "Weekly update",
+                "https://b.example/2",
+                "https://b.example/feed",
+            ),
+            item(
+                "c",
+                "Local team wins the championship",
+                "https://c.example/1",
+                "https://c.example/feed",
+            ),
+        ];
+        let recent = [
+            post(
+                "d",
+                "Central bank raises interest rates",
+                "https://d.example/1",
+                "https://d.example/feed",
+            ),
+            post(
+                "e",
+                "Weekly update",
+                "https://b.example/1",
+                "https://b.example/feed",
+            ),
+        ];
+        link_related_posts(&mut items, &recent, 0.5);
+        assert_eq!(items[0].quote_guid.as_deref(), Some("d"));
+        // The previous item of a recurring title in the same feed isn't the same story
+        assert_eq!(items[1].quote_guid, None);
+        assert_eq!(items[2].quote_guid, None);
+    }
+}
@@ -0,0 +1,198 @@
+use crate::feed::parse_date;
+use crate::html::PageMetadata;
+use anyhow::{Context, Result};
+use chrono::{DateTime, FixedOffset};
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use rss::{Guid, Item};
+
+/// A page listed in a sitemap.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SitemapUrl {
+    /// The URL of the page (`<loc>`).
+    pub loc: String,
+    /// When the page was last modified (`<lastmod>`), if the sitemap says.
+    pub lastmod: Option<DateTime<FixedOffset>>,
+}
+
+/// The contents of a sitemap document (sitemaps.org protocol).
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Sitemap {
+    /// The pages of a `<urlset>`.
+    pub urls: Vec<SitemapUrl>,
+    /// The child sitemaps of a `<sitemapindex>`, along with their last modification.
+    pub sitemaps: Vec<SitemapUrl>,
+}
+
+/// Parses a sitemap or sitemap index document.
+///
+/// # Arguments
+///
+/// * `content` - The raw bytes of the sitemap.
+///
+/// # Returns
+///
+/// A Result containing the Sitemap, or an error if the document is not valid XML.
+pub fn parse_sitemap(content: &[u8]) -> Result<Sitemap> {
+    let mut reader = Reader::from_reader(content);
+    reader.config_mut().trim_text(true);
+    let mut buf = Vec::new();
+
+    let mut sitemap = Sitemap::default();
+    // The <url> or <sitemap> entry being read, the element within it whose text comes next, and how deeply nested
+    // that is. Only direct children count, so extensions such as <image:image><image:loc> are skipped.
+    let mut entry: Option<(bool, SitemapUrl)> = None;
+    let mut field: Option<Vec<u8>> = None;
+    let mut depth = 0;
+
+    loop {
+        match reader
+            .read_event_into(&mut buf)
+            .context("Failed to parse sitemap")?
+        {
+            Event::Start(element) => match element.local_name().as_ref() {
+                b"url" | b"sitemap" if entry.is_none() => {
+                    depth = 0;
+                    let is_index_entry = element.local_name().as_ref() == b"sitemap";
+                    entry = Some((
+                        is_index_entry,
+                        SitemapUrl {
+                            loc: String::new(),
+                            lastmod: None,
+                        },
+                    ));
+                }
+                name => {
+                    depth += 1;
+                    field = (depth == 1).then(|| name.to_vec());
+                }
+            },
+            Event::Text(text) => {
+                if let (Some((_, url)), Some(name)) = (entry.as_mut(), field.as_deref()) {
+                    let value = text.unescape().unwrap_or_default();
+                    match name {
+                        b"loc" => url.loc = value.trim().to_string(),
+                        b"lastmod" => url.lastmod = parse_date(&value),
+                        _ => {}
+                    }
+                }
+            }
+            Event::End(element) => match element.local_name().as_ref() {
+                b"url" | b"sitemap" if depth == 0 => {
+                    if let Some((is_index_entry, url)) = entry.take() {
+                        if !url.loc.is_empty() {
+                            if is_index_entry {
+                                sitemap.sitemaps.push(url);
+                            } else {
+                                sitemap.urls.push(url);
+                            }
+                        }
+                    }
+                }
+                _ => {
+                    depth -= 1;
+                    field = None;
+                }
+            },
+            Event::Eof => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+
+    Ok(sitemap)
+}
+
+/// Turns a page listed in a sitemap into a feed item, so it can go through the same pipeline as RSS items.
+///
+/// # Arguments
+///
+/// * `url` - The sitemap entry of the page.
+/// * `metadata` - What the page itself says about its title, description and image.
+///
+/// # Returns
+///
+/// An Item with the page URL as guid and link, and its last modification as publication date.
+pub fn page_item(url: &SitemapUrl, metadata: PageMetadata) -> Item {
+    let mut item = Item::default();
+    item.set_guid(Guid {
+        value: url.loc.clone(),
+        permalink: true,
+    });
+    item.set_link(url.loc.clone());
+    item.set_title(metadata.title);
+    item.set_description(metadata.description);
+    item.set_pub_date(url.lastmod.map(|d| d.to_rfc2822()));
+    item
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_sitemap() {
+        let content = r#"<?xml version="1.0" encoding="UTF-8"?>
+<urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9" xmlns:image="http://www.google.com/schemas/sitemap-image/1.1">
+  <url><loc>https://example.com/docs/intro?a=1&amp;b=2</loc><lastmod>2024-11-20</lastmod><changefreq>weekly</changefreq></url>
+  <url>
+    <loc> https://example.com/docs/setup </loc>
+    <lastmod>2024-11-19T08:00:00+00:00</lastmod>
+    <image:image><image:loc>https://example.com/setup.png</image:loc></image:image>
+  </url>
+  <url><loc>https://example.com/undated</loc></url>
+  <url><lastmod>2024-11-19</lastmod></url>
+</urlset>"#;
+        let sitemap = parse_sitemap(content.as_bytes()).unwrap();
+        assert!(sitemap.sitemaps.is_empty());
+        assert_eq!(
+            sitemap.urls,
+            vec![
+                SitemapUrl {
+                    loc: "https://example.com/docs/intro?a=1&b=2".to_string(),
+                    lastmod: parse_date("2024-11-20"),
+                },
+                SitemapUrl {
+                    loc: "https://example.com/docs/setup".to_string(),
+                    lastmod: parse_date("2024-11-19T08:00:00Z"),
+                },
+                SitemapUrl {
+                    loc: "https://example.com/undated".to_string(),
+                    lastmod: None,
+                },
+            ]
+        );
+
+        let content = r#"<sitemapindex xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
+  <sitemap><loc>https://example.com/sitemap-posts.xml</loc><lastmod>2024-11-20</lastmod></sitemap>
+</sitemapindex>"#;
+        let sitemap = parse_sitemap(content.as_bytes()).unwrap();
+        assert!(sitemap.urls.is_empty());
+        assert_eq!(sitemap.sitemaps.len(), 1);
+        assert_eq!(
+            sitemap.sitemaps[0].loc,
+            "https://example.com/sitemap-posts.xml"
+        );
+    }
+
+    #[test]
+    fn test_page_item() {
+        let url = SitemapUrl {
+            loc: "https://example.com/docs/intro".to_string(),
+            lastmod: parse_date("2024-11-20"),
+        };
+        let item = page_item(
+            &url,
+            PageMetadata {
+                title: Some("Introduction".to_string()),
+                description: Some("Getting started".to_string()),
+                image_url: None,
+            },
+        );
+        assert_eq!(item.guid().map(|g| g.value()), Some(url.loc.as_str()));
+        assert_eq!(item.link(), Some(url.loc.as_str()));
+        assert_eq!(item.title(), Some("Introduction"));
+        assert_eq!(item.description(), Some("Getting started"));
+        assert_eq!(item.pub_date().and_then(parse_date), url.lastmod);
+    }
+}
@@ -0,0 +1,635 @@
+use crate::html::strip_tags;
+use crate::text_utils::{sha256_hex, truncate_to_word};
+use anyhow::{anyhow, Result};
+use serde_json::{json, Value};
+use unicode_segmentation::UnicodeSegmentation;
+
+/// How much of the text of an item a single request summarizes unless AI_MAX_INPUT_GRAPHEMES says otherwise, a few
+/// thousand tokens, which is plenty for a post's worth of summary and keeps the cost of a request down.
+pub const DEFAULT_MAX_INPUT_GRAPHEMES: usize = 12_000;
+
+/// The most tokens a summary may be written with. Summaries fit a post, and every model family takes this many.
+pub const MAX_SUMMARY_TOKENS: u32 = 4096;
+
+/// The providers of the Bedrock models summaries can be written with, as they start model IDs.
+const PROVIDERS: [&str; 4] = ["anthropic", "amazon", "meta", "mistral"];
+
+/// The families of Bedrock models, each of which takes and gives its own request and response bodies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModelFamily {
+    /// Claude, with the Anthropic messages API.
+    Anthropic,
+    /// Amazon Titan Text.
+    Titan,
+    /// Meta Llama 3 and later.
+    Llama,
+    /// Mistral and Mixtral.
+    Mistral,
+    /// Amazon Nova.
+    Nova,
+}
+
+impl ModelFamily {
+    /// Tells the family of a model from its ID, e.g. `anthropic.claude-3-haiku-20240307-v1:0`. The ID may also be
+    /// that of a cross-region inference profile, such as `us.meta.llama3-2-3b-instruct-v1:0`, or the ARN of either.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the family, or an error if the model isn't a text model of a known family.
+    pub fn from_model_id(model_id: &str) -> Result<Self> {
+        let id = model_id.trim().rsplit('/').next().unwrap_or_default();
+        // Inference profiles put the region in front of the model, e.g. `us.` or `apac.`
+        let id = match id.split_once('.') {
+            Some((region, rest)) if !PROVIDERS.contains(&region) => rest,
+            _ => id,
+        };
+        match id.split_once('.') {
+            Some(("anthropic", _)) => Ok(Self::Anthropic),
+            Some(("meta", _)) => Ok(Self::Llama),
+            Some(("mistral", _)) => Ok(Self::Mistral),
+            Some(("amazon", model)) if model.starts_with("titan-text") => Ok(Self::Titan),
+            Some(("amazon", model)) if model.starts_with("nova") => Ok(Self::Nova),
+            _ => Err(anyhow!(
+                "Unsupported model {:?}: summaries are written with Claude, Titan Text, Llama, Mistral or Nova models",
+                model_id
+            )),
+        }
+    }
+}
+
+/// Computes the key a summary is cached under, a hash of its prompt, the models it's written with and their
+/// settings, so changing the models or settings writes new summaries rather than reusing those of the old ones.
+///
+/// # Arguments
+///
+/// * `prompt` - The prompt the summary is written for.
+/// * `models` - The models the summary is written with, in the order they're tried.
+/// * `params` - The settings the models write it with.
+pub fn summary_cache_key(prompt: &str, models: &[String], params: &InferenceParams) -> String {
+    let key = json!({
+        "prompt": prompt,
+        "models": models,
+        "max_tokens": params.max_tokens,
+        "temperature": params.temperature,
+        "top_p": params.top_p,
+    });
+    sha256_hex(key.to_string().as_bytes())
+}
+
+/// The settings a model generates a summary with.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct InferenceParams {
+    /// The most tokens the model writes.
+    pub max_tokens: u32,
+    pub temperature: f32,
+    pub top_p: f32,
+}
+
+impl InferenceParams {
+    /// Replaces the settings that are given, e.g. by a feed's FeedConfig.
+    pub fn with_overrides(
+        self,
+        max_tokens: Option<u32>,
+        temperature: Option<f32>,
+        top_p: Option<f32>,
+    ) -> Self {
+        Self {
+            max_tokens: max_tokens.unwrap_or(self.max_tokens),
+            temperature: temperature.unwrap_or(self.temperature),
+            top_p: top_p.unwrap_or(self.top_p),
+        }
+    }
+
+    /// Checks the settings are in the ranges every model family takes.
+    ///
+    /// # Returns
+    ///
+    /// A Result indicating whether the settings are valid, with the error naming the one that isn't.
+    pub fn validate(&self) -> Result<()> {
+        if !(1..=MAX_SUMMARY_TOKENS).contains(&self.max_tokens) {
+            return Err(anyhow!(
+                "max_tokens must be between 1 and {}, not {}",
+                MAX_SUMMARY_TOKENS,
+                self.max_tokens
+            ));
+        }
+        for (name, value) in [("temperature", self.temperature), ("top_p", self.top_p)] {
+            if !(0.0..=1.0).contains(&value) {
+                return Err(anyhow!("{} must be between 0 and 1, not {}", name, value));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Default for InferenceParams {
+    fn default() -> Self {
+        Self {
+            max_tokens: 300,
+            temperature: 0.0,
+            top_p: 0.0,
+        }
+    }
+}
+
+/// What a model wrote for a prompt.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Completion {
+    /// The text, trimmed, or None if the model wrote none.
+    pub text: Option<String>,
+    /// The tokens the request used, if the response tells.
+    pub usage: Option<TokenUsage>,
+}
+
+/// The tokens a request to a model used, which it's billed by.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TokenUsage {
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+}
+
+impl TokenUsage {
+    /// Estimates what the tokens cost.
+    ///
+    /// # Arguments
+    ///
+    /// * `input_price` - The price of 1000 input tokens.
+    /// * `output_price` - The price of 1000 output tokens.
+    pub fn cost(&self, input_price: f64, output_price: f64) -> f64 {
+        (self.input_tokens as f64 * input_price + self.output_tokens as f64 * output_price) / 1000.0
+    }
+}
+
+/// Picks the text an item is summarized from: the article extracted from its page when that says more than the
+/// description the feed gave, as feeds often only give a teaser, or else the description.
+///
+/// # Arguments
+///
+/// * `description` - The description of the item, which may have HTML in it.
+/// * `article_text` - The text of the article, if it was extracted.
+/// * `max_graphemes` - The most of the text the summary is written from. Longer texts are cut at a word.
+///
+/// # Returns
+///
+/// The text to summarize, trimmed.
+pub fn summary_source(
+    description: &str,
+    article_text: Option<&str>,
+    max_graphemes: usize,
+) -> String {
+    let description_length = strip_tags(description).graphemes(true).count();
+    let text = match article_text {
+        Some(article_text) if article_text.graphemes(true).count() > description_length => {
+            article_text
+        }
+        _ => description,
+    };
+    truncate_to_word(text, max_graphemes)
+}
+
+/// Splits a long text into chunks that are summarized one by one, for texts too long to summarize at once.
+///
+/// Chunks end at the end of a line, which is the end of a paragraph of an extracted article. Lines longer than a chunk
+/// are split between words.
+///
+/// # Arguments
+///
+/// * `text` - The text.
+/// * `max_graphemes` - The most graphemes in a chunk, unless a single word has more.
+///
+/// # Returns
+///
+/// The chunks, in order, none for a blank text.
+pub fn split_into_chunks(text: &str, max_graphemes: usize) -> Vec<String> {
+    // The pieces the chunks are made of, each with what separates it from the one before
+    let mut pieces = Vec::new();
+    for line in text.lines().map(str::trim).filter(|line| !line.is_empty()) {
+        if line.graphemes(true).count() > max_graphemes {
+            let mut words = line.split_whitespace();
+            pieces.extend(words.next().map(|word| (word, "\n")));
+            pieces.extend(words.map(|word| (word, " ")));
+        } else {
+            pieces.push((line, "\n"));
+        }
+    }
+
+    let mut chunks = Vec::new();
+    let mut chunk = String::new();
+    let mut chunk_length = 0;
+    for (piece, separator) in pieces {
+        let length = piece.graphemes(true).count();
+        if !chunk.is_empty() && chunk_length + 1 + length > max_graphemes {
+            chunks.push(std::mem::take(&mut chunk));
+            chunk_length = 0;
+        }
+        if !chunk.is_empty() {
+            chunk.push_str(separator);
+            chunk_length += 1;
+        }
+        chunk.push_str(piece);
+        chunk_length += length;
+    }
+    if !chunk.is_empty() {
+        chunks.push(chunk);
+    }
+    chunks
+}
+
+/// Writes the prompt asking for the summary of a chunk of a long text, see [`split_into_chunks`]. The summaries of
+/// the chunks are then summarized together with [`summary_prompt`].
+///
+/// # Arguments
+///
+/// * `chunk` - The chunk.
+/// * `part` - The number of the chunk, starting at 1.
+/// * `parts` - How many chunks there are.
+/// * `max_graphemes` - The length the summary of the chunk is asked to keep to.
+pub fn chunk_prompt(chunk: &str, part: usize, parts: usize, max_graphemes: usize) -> String {
+    format!(
+        "This is part {} of {} of a long text. Remove all html tags and summarize it in {} graphemes or less, in the language of the text, keeping the facts and conclusions a summary of the whole text would need:\n\n{}",
+        part, parts, max_graphemes, chunk
+    )
+}
+
+/// Writes the prompt asking for the summary of an item.
+///
+/// # Arguments
+///
+/// * `text` - The text to summarize, which may have HTML in it.
+/// * `max_graphemes` - The length the summary is asked to keep to.
+/// * `target_language` - BCP 47 tag of the language the summary is written in, whatever that of the text. None for
+///   the language of the text.
+pub fn summary_prompt(text: &str, max_graphemes: i64, target_language: Option<&str>) -> String {
+    let language = target_language
+        .map(|language| {
+            format!(
+                ", writing the summary in the language with the BCP 47 tag {} and translating the text if it's in another language",
+                language
+            )
+        })
+        .unwrap_or_default();
+    format!(
+        "Remove all html tags and summarize the following text in {} graphemes or less{}:\n\n{}",
+        max_graphemes, language, text
+    )
+}
+
+/// Writes the prompt asking to shorten a summary that came out longer than asked for.
+///
+/// # Arguments
+///
+/// * `summary` - The summary.
+/// * `max_graphemes` - The length the summary should keep to.
+pub fn shorten_prompt(summary: &str, max_graphemes: i64) -> String {
+    format!(
+        "The following summary is {} graphemes long. Shorten it to {} graphemes or less, keeping its language and its most important points, and reply with the shortened summary only:\n\n{}",
+        summary.graphemes(true).count(),
+        max_graphemes,
+        summary
+    )
+}
+
+/// Builds the body of a Bedrock request asking a model for a summary.
+///
+/// # Arguments
+///
+/// * `family` - The family of the model, which sets the format of the body.
+/// * `prompt` - The prompt, see [`summary_prompt`].
+/// * `params` - The settings the model generates with.
+///
+/// # Returns
+///
+/// The request body, to be serialized as JSON.
+pub fn summary_request(family: ModelFamily, prompt: &str, params: &InferenceParams) -> Value {
+    match family {
+        ModelFamily::Anthropic => json!({
+            "anthropic_version": "bedrock-2023-05-31",
+            "max_tokens": params.max_tokens,
+            "messages": [
+                {
+                    "role": "user",
+                    "content": [
+                        {
+                            "type": "text",
+                            "text": prompt
+                        }
+                    ]
+                }
+            ],
+            "temperature": params.temperature,
+            "top_p": params.top_p,
+        }),
+        ModelFamily::Titan => json!({
+            "inputText": prompt,
+            "textGenerationConfig": {
+                "maxTokenCount": params.max_tokens,
+                "temperature": params.temperature,
+                "topP": params.top_p,
+            },
+        }),
+        ModelFamily::Llama => json!({
+            "prompt": format!(
+                "<|begin_of_text|><|start_header_id|>user<|end_header_id|>\n\n{}<|eot_id|><|start_header_id|>assistant<|end_header_id|>\n\n",
+                prompt
+            ),
+            "max_gen_len": params.max_tokens,
+            "temperature": params.temperature,
+            "top_p": params.top_p,
+        }),
+        ModelFamily::Mistral => json!({
+            "prompt": format!("<s>[INST] {} [/INST]", prompt),
+            "max_tokens": params.max_tokens,
+            "temperature": params.temperature,
+            "top_p": params.top_p,
+        }),
+        ModelFamily::Nova => json!({
+            "messages": [
+                {
+                    "role": "user",
+                    "content": [{"text": prompt}]
+                }
+            ],
+            "inferenceConfig": {
+                "maxTokens": params.max_tokens,
+                "temperature": params.temperature,
+                "topP": params.top_p,
+            },
+        }),
+    }
+}
+
+/// Reads the summary from a Bedrock response to [`summary_request`].
+///
+/// # Returns
+///
+/// The summary, trimmed, or None if the response has no text.
+pub fn parse_summary(family: ModelFamily, response: &Value) -> Option<String> {
+    let text = match family {
+        ModelFamily::Anthropic => &response["content"][0]["text"],
+        ModelFamily::Titan => &response["results"][0]["outputText"],
+        ModelFamily::Llama => &response["generation"],
+        ModelFamily::Mistral => &response["outputs"][0]["text"],
+        ModelFamily::Nova => &response["output"]["message"]["content"][0]["text"],
+    };
+    let text = text.as_str()?.trim();
+    (!text.is_empty()).then(|| text.to_string())
+}
+
+/// Reads the tokens a request used from a Bedrock response to [`summary_request`].
+///
+/// # Returns
+///
+/// The usage, or None if the response doesn't tell, as those of Mistral models don't.
+pub fn parse_usage(family: ModelFamily, response: &Value) -> Option<TokenUsage> {
+    let (input_tokens, output_tokens) = match family {
+        ModelFamily::Anthropic => (
+            &response["usage"]["input_tokens"],
+            &response["usage"]["output_tokens"],
+        ),
+        ModelFamily::Titan => (
+            &response["inputTextTokenCount"],
+            &response["results"][0]["tokenCount"],
+        ),
+        ModelFamily::Llama => (
+            &response["prompt_token_count"],
+            &response["generation_token_count"],
+        ),
+        ModelFamily::Mistral => return None,
+        ModelFamily::Nova => (
+            &response["usage"]["inputTokens"],
+            &response["usage"]["outputTokens"],
+        ),
+    };
+    Some(TokenUsage {
+        input_tokens: input_tokens.as_u64()?,
+        output_tokens: output_tokens.as_u64()?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summary_cache_key() {
+        let models = ["anthropic.claude-3-haiku-20240307-v1:0".to_string()];
+        let params = InferenceParams::default();
+        let key = summary_cache_key("Summarize this", &models, &params);
+        assert_eq!(key, summary_cache_key("Summarize this", &models, &params));
+        assert_ne!(key, summary_cache_key("Summarize that", &models, &params));
+        assert_ne!(
+            key,
+            summary_cache_key(
+                "Summarize this",
+                &["amazon.nova-lite-v1:0".to_string()],
+                &params
+            )
+        );
+        assert_ne!(
+            key,
+            summary_cache_key(
+                "Summarize this",
+                &models,
+                &params.with_overrides(None, Some(0.9), None)
+            )
+        );
+    }
+
+    #[test]
+    fn test_model_family() {
+        for (model_id, family) in [
+            (
+                "anthropic.claude-3-haiku-20240307-v1:0",
+                ModelFamily::Anthropic,
+            ),
+            ("amazon.titan-text-express-v1", ModelFamily::Titan),
+            ("us.meta.llama3-2-3b-instruct-v1:0", ModelFamily::Llama),
+            ("mistral.mistral-7b-instruct-v0:2", ModelFamily::Mistral),
+            ("apac.amazon.nova-micro-v1:0", ModelFamily::Nova),
+            (
+                "arn:aws:bedrock:us-east-1::foundation-model/amazon.nova-lite-v1:0",
+                ModelFamily::Nova,
+            ),
+        ] {
+            assert_eq!(ModelFamily::from_model_id(model_id).unwrap(), family);
+        }
+        assert!(ModelFamily::from_model_id("amazon.titan-embed-text-v2:0").is_err());
+        assert!(ModelFamily::from_model_id("cohere.command-r-v1:0").is_err());
+        assert!(ModelFamily::from_model_id("").is_err());
+    }
+
+    #[test]
+    fn test_inference_params() {
+        let params = InferenceParams::default().with_overrides(Some(500), None, Some(0.9));
+        assert_eq!(
+            params,
+            InferenceParams {
+                max_tokens: 500,
+                temperature: 0.0,
+                top_p: 0.9,
+            }
+        );
+        assert!(params.validate().is_ok());
+        assert!(params
+            .with_overrides(Some(0), None, None)
+            .validate()
+            .is_err());
+        assert!(params
+            .with_overrides(Some(MAX_SUMMARY_TOKENS + 1), None, None)
+            .validate()
+            .is_err());
+        assert!(params
+            .with_overrides(None, Some(1.5), None)
+            .validate()
+            .is_err());
+        assert!(params
+            .with_overrides(None, None, Some(-0.1))
+            .validate()
+            .is_err());
+        assert!(params
+            .with_overrides(None, Some(f32::NAN), None)
+            .validate()
+            .is_err());
+    }
+
+    #[test]
+    fn test_summary_source() {
+        let teaser = "<p>The council <b>voted</b> on Tuesday.</p>";
+        let article =
+            "The council voted on Tuesday to close the bridge for repairs, starting in May.";
+        assert_eq!(summary_source(teaser, Some(article), 1000), article);
+        assert_eq!(summary_source(teaser, None, 1000), teaser);
+        // A full-text feed says more than what the extraction found
+        assert_eq!(summary_source(teaser, Some("The council"), 1000), teaser);
+
+        let capped = summary_source(teaser, Some(article), 20);
+        assert!(capped.graphemes(true).count() <= 20);
+        assert!(article.starts_with(capped.trim_end_matches('…')));
+    }
+
+    #[test]
+    fn test_split_into_chunks() {
+        let text = "First paragraph.\nSecond one.\n\nThird paragraph here.";
+        assert_eq!(
+            split_into_chunks(text, 30),
+            vec!["First paragraph.\nSecond one.", "Third paragraph here."]
+        );
+        assert_eq!(
+            split_into_chunks(text, 100),
+            vec![text.replace("\n\n", "\n")]
+        );
+
+        // A paragraph longer than a chunk is split between words
+        assert_eq!(
+            split_into_chunks("Short.\none two three four five", 10),
+            vec!["Short.\none", "two three", "four five"]
+        );
+        assert!(split_into_chunks(" \n ", 10).is_empty());
+    }
+
+    #[test]
+    fn test_summary_prompt() {
+        assert_eq!(
+            summary_prompt("<p>Text</p>", 280, None),
+            "Remove all html tags and summarize the following text in 280 graphemes or less:\n\n<p>Text</p>"
+        );
+        let prompt = summary_prompt("<p>Text</p>", 280, Some("en"));
+        assert!(prompt.contains("in the language with the BCP 47 tag en"));
+        assert!(prompt.ends_with(":\n\n<p>Text</p>"));
+    }
+
+    #[test]
+    fn test_shorten_prompt() {
+        assert_eq!(
+            shorten_prompt("Ünïcode summary", 10),
+            "The following summary is 15 graphemes long. Shorten it to 10 graphemes or less, keeping its language and its most important points, and reply with the shortened summary only:\n\nÜnïcode summary"
+        );
+    }
+
+    #[test]
+    fn test_summary_request() {
+        let params = InferenceParams::default();
+        let request = summary_request(ModelFamily::Anthropic, "Summarize", &params);
+        assert_eq!(request["messages"][0]["content"][0]["text"], "Summarize");
+        assert_eq!(request["max_tokens"], 300);
+
+        let request = summary_request(ModelFamily::Titan, "Summarize", &params);
+        assert_eq!(request["inputText"], "Summarize");
+        assert_eq!(request["textGenerationConfig"]["maxTokenCount"], 300);
+
+        let request = summary_request(ModelFamily::Mistral, "Summarize", &params);
+        assert_eq!(request["prompt"], "<s>[INST] Summarize [/INST]");
+
+        let request = summary_request(ModelFamily::Nova, "Summarize", &params);
+        assert_eq!(request["messages"][0]["content"][0]["text"], "Summarize");
+        assert_eq!(request["inferenceConfig"]["maxTokens"], 300);
+    }
+
+    #[test]
+    fn test_parse_summary() {
+        for (family, response) in [
+            (
+                ModelFamily::Anthropic,
+                json!({"content": [{"type": "text", "text": " A summary. "}]}),
+            ),
+            (
+                ModelFamily::Titan,
+                json!({"results": [{"outputText": "A summary."}]}),
+            ),
+            (ModelFamily::Llama, json!({"generation": "A summary."})),
+            (
+                ModelFamily::Mistral,
+                json!({"outputs": [{"text": "A summary."}]}),
+            ),
+            (
+                ModelFamily::Nova,
+                json!({"output": {"message": {"content": [{"text": "A summary."}]}}}),
+            ),
+        ] {
+            assert_eq!(
+                parse_summary(family, &response).as_deref(),
+                Some("A summary.")
+            );
+        }
+        assert_eq!(parse_summary(ModelFamily::Llama, &json!({})), None);
+        assert_eq!(
+            parse_summary(ModelFamily::Llama, &json!({"generation": " "})),
+            None
+        );
+    }
+
+    #[test]
+    fn test_parse_usage() {
+        let usage = TokenUsage {
+            input_tokens: 120,
+            output_tokens: 40,
+        };
+        for (family, response) in [
+            (
+                ModelFamily::Anthropic,
+                json!({"usage": {"input_tokens": 120, "output_tokens": 40}}),
+            ),
+            (
+                ModelFamily::Titan,
+                json!({"inputTextTokenCount": 120, "results": [{"tokenCount": 40}]}),
+            ),
+            (
+                ModelFamily::Llama,
+                json!({"prompt_token_count": 120, "generation_token_count": 40}),
+            ),
+            (
+                ModelFamily::Nova,
+                json!({"usage": {"inputTokens": 120, "outputTokens": 40}}),
+            ),
+        ] {
+            assert_eq!(parse_usage(family, &response), Some(usage));
+        }
+        assert_eq!(
+            parse_usage(ModelFamily::Mistral, &json!({"outputs": []})),
+            None
+        );
+        assert_eq!(parse_usage(ModelFamily::Anthropic, &json!({})), None);
+
+        assert!((usage.cost(0.25, 1.25) - 0.08).abs() < 1e-9);
+    }
+}
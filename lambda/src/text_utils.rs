@@ -1,5 +1,11 @@
+pub mod template;
+
+use sha2::{Digest, Sha256};
 use unicode_segmentation::UnicodeSegmentation;
 
+/// The most graphemes Bluesky accepts in the text of a post.
+pub const MAX_POST_GRAPHEMES: usize = 300;
+
 #[must_use]
 pub fn truncate_to_word(summary: &str, max_graphemes: usize) -> String {
     if max_graphemes == 0 || summary.trim().is_empty() {
@@ -20,7 +26,7 @@ pub fn truncate_to_word(summary: &str, max_graphemes: usize) -> String {
             }
         }
         // Remove trailing spaces
-        while graphemes.last().map_or(false, |&g| g == " ") {
+        while graphemes.last().is_some_and(|&g| g == " ") {
             graphemes.pop();
         }
 
@@ -40,6 +46,268 @@ pub fn truncate_to_word(summary: &str, max_graphemes: usize) -> String {
     }
 }
 
+/// Works out how much room the variable part of a post (a summary, release notes) has next to the parts that are
+/// always included whole.
+///
+/// # Arguments
+///
+/// * `max_graphemes` - The length limit of the post.
+/// * `fixed` - The fixed parts, such as a heading or link, along with the separators between them and the
+///   variable part.
+///
+/// # Returns
+///
+/// The number of graphemes left for the variable part, 0 if the fixed parts fill the post already.
+#[must_use]
+pub fn remaining_graphemes(max_graphemes: usize, fixed: &[&str]) -> usize {
+    let used: usize = fixed.iter().map(|part| part.graphemes(true).count()).sum();
+    max_graphemes.saturating_sub(used)
+}
+
+/// Decodes HTML entities such as `&amp;`, `&#8217;` or `&eacute;` into the characters they represent.
+///
+/// Feeds frequently escape characters in titles and descriptions, which would otherwise show up verbatim in posts.
+#[must_use]
+pub fn decode_html_entities(text: &str) -> String {
+    html_escape::decode_html_entities(text).into_owned()
+}
+
+/// Builds a short label for a podcast episode, e.g. "S2E5" or "Episode 5".
+///
+/// Returns None if there is no episode number.
+#[must_use]
+pub fn episode_label(season: Option<&str>, episode: Option<&str>) -> Option<String> {
+    let episode = episode.map(str::trim).filter(|e| !e.is_empty())?;
+    match season.map(str::trim).filter(|s| !s.is_empty()) {
+        Some(season) => Some(format!("S{}E{}", season, episode)),
+        None => Some(format!("Episode {}", episode)),
+    }
+}
+
+/// Bluesky ignores tags longer than this, not counting the `#`.
+const MAX_HASHTAG_CHARS: usize = 64;
+
+/// Turns a category, e.g. "Machine Learning" or "open-source", into a hashtag: "#MachineLearning", "#OpenSource".
+///
+/// Words are joined in camel case (capitalising only their first letter, so "iOS" stays as it is) and anything
+/// else than letters and digits is dropped.
+///
+/// Returns None if nothing usable is left, the category is only digits (which Bluesky doesn't treat as a tag) or
+/// the tag would be too long.
+#[must_use]
+pub fn hashtag(category: &str) -> Option<String> {
+    let words: Vec<&str> = category
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect();
+    let tag: String = if words.len() == 1 {
+        words[0].to_string()
+    } else {
+        words
+            .iter()
+            .map(|word| {
+                let mut chars = word.chars();
+                chars
+                    .next()
+                    .map(|first| first.to_uppercase().chain(chars).collect::<String>())
+                    .unwrap_or_default()
+            })
+            .collect()
+    };
+
+    let length = tag.chars().count();
+    if length == 0 || length > MAX_HASHTAG_CHARS || tag.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    Some(format!("#{}", tag))
+}
+
+/// Turns configured hashtags, which may be given with or without their `#`, into tags.
+#[must_use]
+pub fn static_hashtags(hashtags: &[String]) -> Vec<String> {
+    hashtags
+        .iter()
+        .filter_map(|tag| hashtag(tag.trim().trim_start_matches('#')))
+        .collect()
+}
+
+/// The most tags a post record holds.
+pub const MAX_RECORD_TAGS: usize = 8;
+
+/// Picks the tags of a post record, which Bluesky finds posts by like the hashtags in their text, without them taking
+/// up room in it.
+///
+/// # Arguments
+///
+/// * `candidates` - The hashtags, including their `#`, in order of preference. Anything else, such as mentions, is
+///   skipped.
+/// * `text` - The text of the post. Hashtags already in it aren't repeated.
+///
+/// # Returns
+///
+/// Up to [`MAX_RECORD_TAGS`] tags, without their `#`, each only once ignoring case.
+#[must_use]
+pub fn record_tags(candidates: &[String], text: &str) -> Vec<String> {
+    let in_text: Vec<String> = text
+        .split_whitespace()
+        .filter(|word| word.starts_with('#'))
+        .map(str::to_lowercase)
+        .collect();
+    let mut tags: Vec<String> = Vec::new();
+    for candidate in candidates.iter().filter(|tag| tag.starts_with('#')) {
+        let lowercase = candidate.to_lowercase();
+        if in_text.contains(&lowercase)
+            || tags.iter().any(|tag| tag.to_lowercase() == lowercase[1..])
+        {
+            continue;
+        }
+        tags.push(candidate[1..].to_string());
+        if tags.len() == MAX_RECORD_TAGS {
+            break;
+        }
+    }
+    tags
+}
+
+/// Appends hashtags to a post, on a line of their own, as long as they fit.
+///
+/// The text is never shortened for the tags: tags that don't fit within `max_graphemes` are left out, as are tags
+/// that repeat an earlier one ignoring case or already appear in the text.
+///
+/// # Arguments
+///
+/// * `text` - The text of the post.
+/// * `hashtags` - The hashtags, including their `#`, in order of preference. Mentions (`@handle`) can go among
+///   them too.
+/// * `max_graphemes` - The length limit of the post.
+///
+/// # Returns
+///
+/// The text with the tags that fit.
+#[must_use]
+pub fn append_hashtags(text: &str, hashtags: &[String], max_graphemes: usize) -> String {
+    let mut length = text.graphemes(true).count() + 2;
+    let mut included: Vec<&str> = Vec::new();
+    let text_lower = text.to_lowercase();
+    for hashtag in hashtags {
+        let lower = hashtag.to_lowercase();
+        let already_in_text = text_lower
+            .split(|c: char| c.is_whitespace())
+            .any(|word| word.trim_end_matches(|c: char| c.is_ascii_punctuation()) == lower);
+        if already_in_text || included.iter().any(|t| t.to_lowercase() == lower) {
+            continue;
+        }
+
+        let added = hashtag.graphemes(true).count() + usize::from(!included.is_empty());
+        if length + added > max_graphemes {
+            continue;
+        }
+        length += added;
+        included.push(hashtag);
+    }
+
+    if included.is_empty() {
+        text.to_string()
+    } else {
+        format!("{}\n\n{}", text, included.join(" "))
+    }
+}
+
+/// Splits a text that is too long for one post into the posts of a thread.
+///
+/// Posts end after a sentence where that doesn't leave them less than half full, and otherwise between words. Text
+/// that doesn't fit into `max_posts` posts is cut off at a word boundary and marked with an ellipsis.
+///
+/// # Arguments
+///
+/// * `text` - The text to split.
+/// * `first_max_graphemes` - The length limit of the first post, which usually shares its room with other text.
+/// * `max_graphemes` - The length limit of the other posts.
+/// * `max_posts` - The most posts to split the text into.
+///
+/// # Returns
+///
+/// The text of each post, in order. A text that fits into the first post is returned as it is.
+#[must_use]
+pub fn split_into_posts(
+    text: &str,
+    first_max_graphemes: usize,
+    max_graphemes: usize,
+    max_posts: usize,
+) -> Vec<String> {
+    let mut posts = Vec::new();
+    let mut rest = text.trim();
+    while !rest.is_empty() {
+        let limit = if posts.is_empty() {
+            first_max_graphemes
+        } else {
+            max_graphemes
+        };
+        if posts.len() + 1 >= max_posts || rest.graphemes(true).count() <= limit {
+            posts.push(truncate_to_word(rest, limit));
+            break;
+        }
+        let (post, remainder) = rest.split_at(split_point(rest, limit));
+        posts.push(post.trim_end().to_string());
+        rest = remainder.trim_start();
+    }
+    posts
+}
+
+/// Finds the byte index to end a post at, so that the post holds at most `max_graphemes` graphemes of `text`.
+fn split_point(text: &str, max_graphemes: usize) -> usize {
+    let end = text
+        .grapheme_indices(true)
+        .nth(max_graphemes)
+        .map_or(text.len(), |(index, _)| index);
+
+    let mut word_end = None;
+    let mut sentence_end = None;
+    for (index, _) in text
+        .char_indices()
+        .take_while(|(index, _)| *index <= end)
+        .filter(|(index, c)| *index > 0 && c.is_whitespace())
+    {
+        word_end = Some(index);
+        if text[..index].ends_with(['.', '!', '?', '…']) {
+            sentence_end = Some(index);
+        }
+    }
+    sentence_end
+        .filter(|index| *index >= end / 2)
+        .or(word_end)
+        .unwrap_or(end)
+}
+
+/// Links are shown with at most this many graphemes in the text of posts, their facets holding the full URL.
+pub const MAX_LINK_DISPLAY_GRAPHEMES: usize = 30;
+
+/// Writes the text a link is shown with in a post, the way the Bluesky app shows links: without its scheme, `www.`
+/// and trailing slash, and cut short with an ellipsis past MAX_LINK_DISPLAY_GRAPHEMES.
+pub fn link_display_text(url: &str) -> String {
+    let url = url.trim();
+    let text = url
+        .strip_prefix("https://")
+        .or_else(|| url.strip_prefix("http://"))
+        .unwrap_or(url);
+    let text = text.strip_prefix("www.").unwrap_or(text);
+    let text = text.strip_suffix('/').unwrap_or(text);
+    if text.graphemes(true).count() <= MAX_LINK_DISPLAY_GRAPHEMES {
+        return text.to_string();
+    }
+    let shortened: String = text
+        .graphemes(true)
+        .take(MAX_LINK_DISPLAY_GRAPHEMES - 1)
+        .collect();
+    format!("{}…", shortened)
+}
+
+/// Returns the lowercase hex encoded SHA-256 digest of `data`.
+#[must_use]
+pub fn sha256_hex(data: &[u8]) -> String {
+    hex::encode(Sha256::digest(data))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -114,4 +382,148 @@ mod tests {
         // String with tabs
         assert_eq!(truncate_to_word("Tab\tSeparated", 5), "Tab\t…");
     }
+
+    #[test]
+    fn test_decode_html_entities() {
+        assert_eq!(decode_html_entities("Plain text"), "Plain text");
+        assert_eq!(decode_html_entities("Q&amp;A"), "Q&A");
+        assert_eq!(decode_html_entities("It&#8217;s here"), "It’s here");
+        assert_eq!(
+            decode_html_entities("Caf&eacute; &#x2014; open"),
+            "Café — open"
+        );
+        assert_eq!(
+            decode_html_entities("&lt;b&gt;bold&lt;/b&gt;"),
+            "<b>bold</b>"
+        );
+        assert_eq!(decode_html_entities("AT&T"), "AT&T");
+    }
+
+    #[test]
+    fn test_hashtag() {
+        assert_eq!(hashtag("rust"), Some("#rust".to_string()));
+        assert_eq!(
+            hashtag("Machine Learning"),
+            Some("#MachineLearning".to_string())
+        );
+        assert_eq!(hashtag("open-source"), Some("#OpenSource".to_string()));
+        assert_eq!(hashtag("iOS dev"), Some("#IOSDev".to_string()));
+        assert_eq!(hashtag("C++"), Some("#C".to_string()));
+        assert_eq!(hashtag("Café"), Some("#Café".to_string()));
+        assert_eq!(hashtag("2024"), None);
+        assert_eq!(hashtag(" - "), None);
+        assert_eq!(hashtag(&"a".repeat(65)), None);
+
+        assert_eq!(
+            static_hashtags(&["#rust".to_string(), " news ".to_string(), "#".to_string()]),
+            vec!["#rust".to_string(), "#news".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_record_tags() {
+        let candidates: Vec<String> = ["#rust", "@jane.bsky.social", "#Release", "#RUST", "#news"]
+            .iter()
+            .map(|t| t.to_string())
+            .collect();
+        assert_eq!(
+            record_tags(&candidates, "Rust 2.0 released\n\n#release"),
+            vec!["rust", "news"]
+        );
+
+        let many: Vec<String> = (0..10).map(|i| format!("#tag{}", i)).collect();
+        assert_eq!(record_tags(&many, "").len(), MAX_RECORD_TAGS);
+    }
+
+    #[test]
+    fn test_append_hashtags() {
+        let tags: Vec<String> = ["#rust", "#Rust", "#programming", "#release"]
+            .iter()
+            .map(|t| t.to_string())
+            .collect();
+        assert_eq!(
+            append_hashtags("New version", &tags, 300),
+            "New version\n\n#rust #programming #release"
+        );
+        // "New version" + "\n\n" + "#rust #programming" is 33 graphemes
+        assert_eq!(
+            append_hashtags("New version", &tags, 33),
+            "New version\n\n#rust #programming"
+        );
+        assert_eq!(
+            append_hashtags("New #Rust version.", &tags[..2], 300),
+            "New #Rust version."
+        );
+        assert_eq!(append_hashtags("Full", &tags, 11), "Full\n\n#rust");
+        assert_eq!(append_hashtags("Full", &tags, 10), "Full");
+    }
+
+    #[test]
+    fn test_split_into_posts() {
+        assert_eq!(split_into_posts(" Short. ", 10, 20, 4), vec!["Short."]);
+
+        // Posts end after a sentence when that leaves them at least half full
+        assert_eq!(
+            split_into_posts("First one. Second sentence here. Third.", 20, 20, 4),
+            vec!["First one.", "Second sentence", "here. Third."]
+        );
+
+        // Otherwise between words, or inside a word that doesn't fit at all
+        assert_eq!(
+            split_into_posts("Hi. alpha beta gamma", 12, 12, 4),
+            vec!["Hi. alpha", "beta gamma"]
+        );
+        assert_eq!(
+            split_into_posts("abcdefghij", 4, 4, 4),
+            vec!["abcd", "efgh", "ij"]
+        );
+
+        // What doesn't fit into the last post is cut off
+        assert_eq!(
+            split_into_posts("one two three four five six", 8, 8, 2),
+            vec!["one two", "three…"]
+        );
+    }
+
+    #[test]
+    fn test_remaining_graphemes() {
+        assert_eq!(remaining_graphemes(300, &[]), 300);
+        assert_eq!(
+            remaining_graphemes(300, &["owner/repo v1 released", "\n\n"]),
+            276
+        );
+        // Emoji count as one grapheme each
+        assert_eq!(remaining_graphemes(10, &["👩‍💻 ", "🇧🇪"]), 7);
+        assert_eq!(remaining_graphemes(5, &["too long already"]), 0);
+    }
+
+    #[test]
+    fn test_link_display_text() {
+        assert_eq!(link_display_text("https://www.example.com/"), "example.com");
+        assert_eq!(
+            link_display_text("http://example.com/a?b=c"),
+            "example.com/a?b=c"
+        );
+        let text = link_display_text("https://example.com/articles/2024/a-very-long-slug");
+        assert_eq!(text, "example.com/articles/2024/a-v…");
+        assert_eq!(text.graphemes(true).count(), MAX_LINK_DISPLAY_GRAPHEMES);
+    }
+
+    #[test]
+    fn test_episode_label() {
+        assert_eq!(episode_label(None, None), None);
+        assert_eq!(episode_label(Some("2"), None), None);
+        assert_eq!(
+            episode_label(None, Some("5")),
+            Some("Episode 5".to_string())
+        );
+        assert_eq!(
+            episode_label(Some("2"), Some("5")),
+            Some("S2E5".to_string())
+        );
+        assert_eq!(
+            episode_label(Some(" "), Some("5")),
+            Some("Episode 5".to_string())
+        );
+    }
 }
@@ -0,0 +1,554 @@
+use super::truncate_to_word;
+use anyhow::{anyhow, Context, Result};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use unicode_segmentation::UnicodeSegmentation;
+
+/// A value that can be placed in a post template.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Field {
+    /// `{prefix}` - The emoji or short label of the item's feed, followed by a space, so it can be placed right
+    /// before another field.
+    Prefix,
+    /// `{title}` - The title of the item.
+    Title,
+    /// `{summary}` - The AI summary, or the description when there is none.
+    Summary,
+    /// `{source}` - The title of the feed the item came from.
+    Source,
+    /// `{author}` - The writer of the item.
+    Author,
+    /// `{episode}` - The podcast episode label, e.g. `S2E5`.
+    Episode,
+    /// `{link}` - The URL of the item.
+    Link,
+}
+
+impl Field {
+    /// Fields that are shortened when a post is too long, in the order they are shortened in. The others (the link
+    /// in particular) would be broken by truncation, so they are always kept whole.
+    const TRUNCATABLE: [Field; 4] = [Field::Summary, Field::Title, Field::Source, Field::Author];
+
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "prefix" => Some(Field::Prefix),
+            "title" => Some(Field::Title),
+            "summary" => Some(Field::Summary),
+            "source" => Some(Field::Source),
+            "author" => Some(Field::Author),
+            "episode" => Some(Field::Episode),
+            "link" => Some(Field::Link),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Literal(String),
+    Field(Field),
+}
+
+/// The layout of a post, such as `{source}: {title}\n\n{summary}\n{link}`.
+///
+/// Fields are written in braces; `{{` and `}}` stand for literal braces and `\n` for a line break, so templates can
+/// be given on a single line in an environment variable.
+///
+/// Text next to a field that has no value is left out up to the nearest line break, so `{source}: {title}` renders
+/// as just the title for items without a source, and a line like `by {author}` disappears entirely.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PostTemplate {
+    parts: Vec<Part>,
+}
+
+impl PostTemplate {
+    /// Returns the template posts use unless POST_TEMPLATE is set, e.g. "Ars Technica: S2E5: <summary>\n\nby
+    /// <author>". Podcast posts always lead with the episode number.
+    ///
+    /// # Arguments
+    ///
+    /// * `prefix_source` - Whether posts lead with the title of the feed.
+    /// * `credit_author` - Whether posts end with the writer.
+    pub fn default_text(prefix_source: bool, credit_author: bool) -> String {
+        let source = if prefix_source { "{source}: " } else { "" };
+        let author = if credit_author { "\n\nby {author}" } else { "" };
+        format!("{{prefix}}{}{{episode}}: {{summary}}{}", source, author)
+    }
+
+    /// Parses a template.
+    ///
+    /// # Arguments
+    ///
+    /// * `template` - The template text.
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the PostTemplate, or an error if the template has an unknown field or an unmatched
+    /// brace.
+    pub fn parse(template: &str) -> Result<Self> {
+        let template = template.replace("\\n", "\n");
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        let mut chars = template.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => name.push(c),
+                            None => return Err(anyhow!("Unclosed {{ in post template")),
+                        }
+                    }
+                    let field = Field::from_name(name.trim())
+                        .ok_or_else(|| anyhow!("Unknown field {{{}}} in post template", name))?;
+                    if !literal.is_empty() {
+                        parts.push(Part::Literal(std::mem::take(&mut literal)));
+                    }
+                    parts.push(Part::Field(field));
+                }
+                '}' => return Err(anyhow!("Unmatched }} in post template")),
+                c => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            parts.push(Part::Literal(literal));
+        }
+        Ok(Self { parts })
+    }
+
+    /// Checks whether the template places a field.
+    pub fn has_field(&self, field: Field) -> bool {
+        self.parts.contains(&Part::Field(field))
+    }
+
+    /// Returns the template with the link at the end, after a blank line, unless it places the link already. For
+    /// posts that carry the link in their text rather than in a link card.
+    pub fn with_link(&self) -> Self {
+        let mut template = self.clone();
+        if !self.has_field(Field::Link) {
+            template.parts.push(Part::Literal("\n\n".to_string()));
+            template.parts.push(Part::Field(Field::Link));
+        }
+        template
+    }
+
+    /// Returns the template with a content warning at the start, on a paragraph of its own. The warning counts
+    /// towards the length of the post like the rest of the literal text, and is never shortened.
+    pub fn with_warning(&self, warning: &str) -> Self {
+        let mut template = self.clone();
+        template
+            .parts
+            .insert(0, Part::Literal(format!("{}\n\n", warning)));
+        template
+    }
+
+    /// Fills in the template.
+    ///
+    /// When the result would be longer than `max_graphemes`, the summary is shortened first, at a word boundary
+    /// and marked with an ellipsis, then the title, source and author in that order. The other fields are only
+    /// shortened when the template and they alone are too long, as the text is then cut to fit as a whole.
+    ///
+    /// # Arguments
+    ///
+    /// * `values` - The values of the fields. Missing and blank values count as not set.
+    /// * `max_graphemes` - The length limit of the result.
+    ///
+    /// # Returns
+    ///
+    /// The text, trimmed, without runs of more than one blank line and never longer than `max_graphemes`.
+    pub fn render(&self, values: &HashMap<Field, String>, max_graphemes: usize) -> String {
+        let mut values: HashMap<Field, String> = values
+            .iter()
+            .map(|(field, value)| (*field, value.trim().to_string()))
+            .filter(|(_, value)| !value.is_empty())
+            .collect();
+
+        let mut text = self.fill(&values);
+        for field in Field::TRUNCATABLE {
+            let length = text.graphemes(true).count();
+            if length <= max_graphemes {
+                break;
+            }
+            let occurrences = self
+                .parts
+                .iter()
+                .filter(|part| **part == Part::Field(field))
+                .count();
+            let Some(value) = values.get(&field).filter(|_| occurrences > 0) else {
+                continue;
+            };
+
+            let excess = (length - max_graphemes).div_ceil(occurrences);
+            let budget = value.graphemes(true).count().saturating_sub(excess);
+            if budget == 0 {
+                values.remove(&field);
+            } else {
+                let truncated = truncate_to_word(value, budget);
+                values.insert(field, truncated);
+            }
+            text = self.fill(&values);
+        }
+
+        // A link or a long literal can be too long on its own, but a post that's too long is rejected outright
+        if text.graphemes(true).count() > max_graphemes {
+            text = truncate_to_word(&text, max_graphemes);
+        }
+        text
+    }
+
+    fn fill(&self, values: &HashMap<Field, String>) -> String {
+        let is_empty = |part: Option<&Part>| match part {
+            Some(Part::Field(field)) => !values.contains_key(field),
+            _ => false,
+        };
+
+        let mut text = String::new();
+        for (i, part) in self.parts.iter().enumerate() {
+            match part {
+                Part::Field(field) => {
+                    if let Some(value) = values.get(field) {
+                        text.push_str(value);
+                        if *field == Field::Prefix {
+                            text.push(' ');
+                        }
+                    }
+                }
+                Part::Literal(literal) => {
+                    let mut literal = literal.as_str();
+                    if i > 0 && is_empty(self.parts.get(i - 1)) {
+                        literal = literal.find('\n').map_or("", |index| &literal[index..]);
+                    }
+                    if is_empty(self.parts.get(i + 1)) {
+                        literal = literal.rfind('\n').map_or("", |index| &literal[..=index]);
+                    }
+                    text.push_str(literal);
+                }
+            }
+        }
+
+        let mut result = String::new();
+        let mut line_breaks = 0;
+        for c in text.trim().chars() {
+            if c == '\n' {
+                line_breaks += 1;
+                if line_breaks > 2 {
+                    continue;
+                }
+            } else {
+                line_breaks = 0;
+            }
+            result.push(c);
+        }
+        result
+    }
+}
+
+/// One of the post templates of an A/B experiment, which lays out a share of the posts set by its weight.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TemplateVariant {
+    /// The name posts laid out with the variant are recorded with, to compare their engagement.
+    pub name: String,
+    pub weight: u32,
+    pub template: PostTemplate,
+}
+
+#[derive(Deserialize)]
+struct VariantSpec {
+    name: String,
+    #[serde(default = "default_weight")]
+    weight: u32,
+    template: String,
+}
+
+fn default_weight() -> u32 {
+    1
+}
+
+/// Parses the variants of a template experiment.
+///
+/// # Arguments
+///
+/// * `json` - A JSON array of the variants, e.g. `[{"name": "short", "weight": 3, "template": "{title}"}, {"name":
+///   "long", "template": "{title}\n\n{summary}"}]`. Variants without a weight have a weight of 1.
+///
+/// # Returns
+///
+/// A Result containing the variants, or an error if there are fewer than two, their names aren't unique or one of
+/// their templates doesn't parse.
+pub fn parse_variants(json: &str) -> Result<Vec<TemplateVariant>> {
+    let specs: Vec<VariantSpec> = serde_json::from_str(json).context(
+        "Template variants must be a JSON array of {\"name\", \"weight\", \"template\"} objects",
+    )?;
+    if specs.len() < 2 {
+        return Err(anyhow!("A template experiment needs at least two variants"));
+    }
+    if specs.iter().all(|spec| spec.weight == 0) {
+        return Err(anyhow!(
+            "At least one template variant needs a weight above 0"
+        ));
+    }
+
+    let mut names = HashSet::new();
+    specs
+        .into_iter()
+        .map(|spec| {
+            let name = spec.name.trim().to_string();
+            if name.is_empty() || !names.insert(name.clone()) {
+                return Err(anyhow!(
+                    "Template variant names must be unique and not empty, got {:?}",
+                    spec.name
+                ));
+            }
+            let template = PostTemplate::parse(&spec.template)
+                .with_context(|| format!("Invalid template of variant {:?}", name))?;
+            Ok(TemplateVariant {
+                name,
+                weight: spec.weight,
+                template,
+            })
+        })
+        .collect()
+}
+
+/// Picks the variant that lays out the post of an item, at random but by weight.
+///
+/// The pick is made from a hash of `key`, so an item that's posted again after a failure keeps its variant.
+///
+/// # Returns
+///
+/// The variant, or None if there are none (or none with a weight).
+pub fn pick_variant<'a>(variants: &'a [TemplateVariant], key: &str) -> Option<&'a TemplateVariant> {
+    let total: u64 = variants
+        .iter()
+        .map(|variant| u64::from(variant.weight))
+        .sum();
+    if total == 0 {
+        return None;
+    }
+    let digest = Sha256::digest(key.as_bytes());
+    let mut bytes = [0; 8];
+    bytes.copy_from_slice(&digest[..8]);
+    let mut point = u64::from_be_bytes(bytes) % total;
+    variants.iter().find(|variant| {
+        if point < u64::from(variant.weight) {
+            return true;
+        }
+        point -= u64::from(variant.weight);
+        false
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values(values: &[(Field, &str)]) -> HashMap<Field, String> {
+        values.iter().map(|(f, v)| (*f, v.to_string())).collect()
+    }
+
+    #[test]
+    fn test_parse() {
+        let template = PostTemplate::parse("{source}: {title}\\n\\n{{{summary}}}").unwrap();
+        assert!(template.has_field(Field::Summary));
+        assert!(!template.has_field(Field::Link));
+        assert_eq!(
+            template.render(
+                &values(&[
+                    (Field::Source, "Blog"),
+                    (Field::Title, "Hello"),
+                    (Field::Summary, "World")
+                ]),
+                300
+            ),
+            "Blog: Hello\n\n{World}"
+        );
+
+        let with_link = template.with_link();
+        assert!(with_link.has_field(Field::Link));
+        assert_eq!(
+            with_link.render(
+                &values(&[
+                    (Field::Title, "Hello"),
+                    (Field::Link, "https://example.com")
+                ]),
+                300
+            ),
+            "Hello\n\nhttps://example.com"
+        );
+        assert_eq!(with_link.with_link(), with_link);
+
+        assert!(PostTemplate::parse("{unknown}").is_err());
+        assert!(PostTemplate::parse("{title").is_err());
+        assert!(PostTemplate::parse("title}").is_err());
+    }
+
+    #[test]
+    fn test_with_warning() {
+        let template = PostTemplate::parse("{source}: {title}")
+            .unwrap()
+            .with_warning("CW: death");
+        assert_eq!(
+            template.render(&values(&[(Field::Title, "A title")]), 300),
+            "CW: death\n\nA title"
+        );
+        // The title makes room for the warning
+        let text = template.render(&values(&[(Field::Title, &"word ".repeat(10))]), 25);
+        assert!(text.starts_with("CW: death\n\nword"));
+        assert!(text.graphemes(true).count() <= 25);
+    }
+
+    #[test]
+    fn test_render_missing_fields() {
+        let template =
+            PostTemplate::parse("{source}: {episode}: {summary}\n\nby {author}\n{link}").unwrap();
+        assert_eq!(
+            template.render(
+                &values(&[
+                    (Field::Summary, "Summary"),
+                    (Field::Link, "https://example.com")
+                ]),
+                300
+            ),
+            "Summary\n\nhttps://example.com"
+        );
+        assert_eq!(
+            template.render(
+                &values(&[
+                    (Field::Source, "Podcast"),
+                    (Field::Episode, "S2E5"),
+                    (Field::Summary, "Summary"),
+                    (Field::Author, " "),
+                ]),
+                300
+            ),
+            "Podcast: S2E5: Summary"
+        );
+        assert_eq!(template.render(&HashMap::new(), 300), "");
+    }
+
+    #[test]
+    fn test_render_prefix() {
+        let template = PostTemplate::parse("{prefix}{source}: {summary}").unwrap();
+        assert_eq!(
+            template.render(
+                &values(&[(Field::Prefix, "📰"), (Field::Summary, "Summary")]),
+                300
+            ),
+            "📰 Summary"
+        );
+        assert_eq!(
+            template.render(
+                &values(&[
+                    (Field::Prefix, "[Blog] "),
+                    (Field::Source, "Blog"),
+                    (Field::Summary, "Summary")
+                ]),
+                300
+            ),
+            "[Blog] Blog: Summary"
+        );
+        assert_eq!(
+            template.render(&values(&[(Field::Summary, "Summary")]), 300),
+            "Summary"
+        );
+    }
+
+    #[test]
+    fn test_render_budget() {
+        let template = PostTemplate::parse("{title}\n\n{summary}\n{link}").unwrap();
+        let link = "https://example.com/a-long-article-link";
+        let text = template.render(
+            &values(&[
+                (Field::Title, "A title"),
+                (Field::Summary, "one two three four five six seven"),
+                (Field::Link, link),
+            ]),
+            60,
+        );
+        assert_eq!(text, format!("A title\n\none two…\n{}", link));
+        assert!(text.graphemes(true).count() <= 60);
+
+        // With no room left for the summary, the title is shortened too
+        let text = template.render(
+            &values(&[
+                (Field::Title, "A rather long title"),
+                (Field::Summary, "Summary"),
+                (Field::Link, link),
+            ]),
+            50,
+        );
+        assert_eq!(text, format!("A rather…\n\n{}", link));
+
+        // What's left over once everything that can be shortened is gone is cut as a whole
+        let text = template.render(
+            &values(&[
+                (Field::Title, "Title"),
+                (Field::Summary, "Summary"),
+                (Field::Link, link),
+            ]),
+            20,
+        );
+        assert!(text.graphemes(true).count() <= 20);
+        assert!(text.ends_with('…'));
+    }
+
+    #[test]
+    fn test_parse_variants() {
+        let variants = parse_variants(
+            r#"[{"name": "short", "weight": 3, "template": "{title}"}, {"name": "long", "template": "{title}\\n\\n{summary}"}]"#,
+        )
+        .unwrap();
+        assert_eq!(variants.len(), 2);
+        assert_eq!(variants[0].weight, 3);
+        assert_eq!(variants[1].weight, 1);
+        assert!(variants[1].template.has_field(Field::Summary));
+
+        assert!(parse_variants(r#"[{"name": "a", "template": "{title}"}]"#).is_err());
+        assert!(parse_variants(
+            r#"[{"name": "a", "template": "{title}"}, {"name": "a", "template": "{summary}"}]"#
+        )
+        .is_err());
+        assert!(parse_variants(
+            r#"[{"name": "a", "template": "{title}"}, {"name": "b", "template": "{unknown}"}]"#
+        )
+        .is_err());
+        assert!(parse_variants("{title}").is_err());
+    }
+
+    #[test]
+    fn test_pick_variant() {
+        let variants = parse_variants(
+            r#"[{"name": "a", "weight": 3, "template": "{title}"}, {"name": "b", "template": "{summary}"}, {"name": "off", "weight": 0, "template": "{link}"}]"#,
+        )
+        .unwrap();
+        let picks: Vec<&str> = (0..400)
+            .map(|i| {
+                pick_variant(&variants, &format!("guid-{}", i))
+                    .unwrap()
+                    .name
+                    .as_str()
+            })
+            .collect();
+        let count = |name: &str| picks.iter().filter(|pick| **pick == name).count();
+        assert!((250..350).contains(&count("a")), "{}", count("a"));
+        assert_eq!(count("a") + count("b"), 400);
+
+        // An item keeps its variant
+        assert_eq!(
+            pick_variant(&variants, "guid-7"),
+            pick_variant(&variants, "guid-7")
+        );
+        assert_eq!(pick_variant(&[], "guid"), None);
+    }
+}
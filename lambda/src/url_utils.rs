@@ -0,0 +1,118 @@
+use url::Url;
+
+/// Query parameters that only track where a visitor came from, matched case insensitively. Parameters starting with
+/// `utm_` are removed too.
+const TRACKING_PARAMS: [&str; 16] = [
+    "fbclid",
+    "gclid",
+    "dclid",
+    "gbraid",
+    "wbraid",
+    "msclkid",
+    "twclid",
+    "yclid",
+    "igshid",
+    "mc_cid",
+    "mc_eid",
+    "_hsenc",
+    "_hsmi",
+    "mkt_tok",
+    "oly_anon_id",
+    "oly_enc_id",
+];
+
+/// Removes tracking parameters, such as `utm_source` or `fbclid`, from a URL.
+///
+/// This is the form of a link that is posted, so the link card points to the article rather than to a campaign.
+/// Anything that isn't an absolute URL is returned as it is.
+#[must_use]
+pub fn canonical_url(url: &str) -> String {
+    let url = url.trim();
+    let Ok(mut parsed) = Url::parse(url) else {
+        return url.to_string();
+    };
+    if parsed.query().is_none() {
+        return parsed.to_string();
+    }
+
+    let params: Vec<(String, String)> = parsed
+        .query_pairs()
+        .filter(|(key, _)| !is_tracking_param(key))
+        .map(|(key, value)| (key.into_owned(), value.into_owned()))
+        .collect();
+    if params.is_empty() {
+        parsed.set_query(None);
+    } else {
+        parsed.query_pairs_mut().clear().extend_pairs(params);
+    }
+    parsed.to_string()
+}
+
+/// Normalizes a URL for comparison, so the same article linked in slightly different ways is recognised as one.
+///
+/// On top of [`canonical_url`], http is treated as https and a trailing slash on the path (other than the root) is
+/// dropped. The result is only meant for dedupe keys; links that are posted keep their scheme and path as published.
+#[must_use]
+pub fn dedupe_key(url: &str) -> String {
+    let canonical = canonical_url(url);
+    let Ok(mut parsed) = Url::parse(&canonical) else {
+        return canonical;
+    };
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return canonical;
+    }
+
+    if parsed.scheme() == "http" {
+        parsed.set_scheme("https").ok();
+    }
+    let path = parsed.path().trim_end_matches('/').to_string();
+    parsed.set_path(&path);
+    parsed.to_string()
+}
+
+fn is_tracking_param(key: &str) -> bool {
+    let key = key.to_lowercase();
+    key.starts_with("utm_") || TRACKING_PARAMS.contains(&key.as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_canonical_url() {
+        assert_eq!(
+            canonical_url("https://example.com/post/?utm_source=rss&utm_medium=feed&id=5&FBCLID=x"),
+            "https://example.com/post/?id=5"
+        );
+        assert_eq!(
+            canonical_url(" https://Example.com/post?utm_campaign=launch#comments "),
+            "https://example.com/post#comments"
+        );
+        assert_eq!(
+            canonical_url("https://example.com/a b"),
+            "https://example.com/a%20b"
+        );
+        assert_eq!(
+            canonical_url("/relative?utm_source=x"),
+            "/relative?utm_source=x"
+        );
+    }
+
+    #[test]
+    fn test_dedupe_key() {
+        let key = dedupe_key("https://example.com/post");
+        assert_eq!(key, "https://example.com/post");
+        assert_eq!(dedupe_key("http://example.com/post/"), key);
+        assert_eq!(dedupe_key("https://example.com/post/?utm_source=bsky"), key);
+        assert_eq!(
+            dedupe_key("http://example.com/post/?page=2"),
+            "https://example.com/post?page=2"
+        );
+        assert_eq!(dedupe_key("http://example.com"), "https://example.com/");
+        assert_eq!(
+            dedupe_key("tag:example.com,2024:1"),
+            "tag:example.com,2024:1"
+        );
+    }
+}